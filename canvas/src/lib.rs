@@ -21,7 +21,7 @@ use pathfinder_content::color::ColorU;
 use pathfinder_content::dash::OutlineDash;
 use pathfinder_content::outline::{ArcDirection, Contour, Outline};
 use pathfinder_content::stroke::{LineCap, LineJoin as StrokeLineJoin};
-use pathfinder_content::stroke::{OutlineStrokeToFill, StrokeStyle};
+use pathfinder_content::stroke::{OutlineStrokeToFill, StrokeStyle, StrokeTransform};
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::vector::Vector2F;
 use pathfinder_geometry::rect::RectF;
@@ -273,10 +273,10 @@ impl CanvasRenderingContext2D {
         }
 
         let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+        stroke_to_fill.set_transform(&self.current_state.transform, StrokeTransform::Scaling);
         stroke_to_fill.offset();
         outline = stroke_to_fill.into_outline();
 
-        outline.transform(&self.current_state.transform);
         self.push_path(outline, paint_id);
     }
 
@@ -513,6 +513,14 @@ pub enum FillStyle {
 }
 
 impl FillStyle {
+    /// Parses a CSS color string, as assigned to `fillStyle` or `strokeStyle` in the HTML canvas
+    /// API. Returns `None` if the string isn't a valid color, in which case the HTML canvas API
+    /// leaves the style unchanged.
+    #[inline]
+    pub fn from_css(string: &str) -> Option<FillStyle> {
+        ColorU::from_css(string).map(FillStyle::Color)
+    }
+
     #[inline]
     fn to_paint(&self) -> Paint {
         match *self { FillStyle::Color(color) => Paint { color } }
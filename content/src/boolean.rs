@@ -0,0 +1,269 @@
+// pathfinder/content/src/boolean.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Boolean operations on the filled regions of outlines, for clipping and masking.
+
+use crate::fill::FillRule;
+use crate::outline::{Contour, Outline};
+use crate::tessellation::{self, Crossing};
+use pathfinder_geometry::vector::Vector2F;
+use std::collections::BTreeSet;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BooleanOp {
+    /// The region inside either outline.
+    Union,
+    /// The region inside both outlines.
+    Intersection,
+    /// The region inside the first outline but not the second.
+    Difference,
+    /// The region inside exactly one of the outlines.
+    Xor,
+}
+
+impl BooleanOp {
+    #[inline]
+    fn apply(self, inside_a: bool, inside_b: bool) -> bool {
+        match self {
+            BooleanOp::Union => inside_a || inside_b,
+            BooleanOp::Intersection => inside_a && inside_b,
+            BooleanOp::Difference => inside_a && !inside_b,
+            BooleanOp::Xor => inside_a != inside_b,
+        }
+    }
+}
+
+/// Combines the filled regions of two outlines.
+///
+/// Curves are flattened to within the tolerance first. The result is made of trapezoids with
+/// shared edges, so it fills without seams under the nonzero winding rule.
+pub struct OutlineBoolean<'a> {
+    a: &'a Outline,
+    b: &'a Outline,
+    op: BooleanOp,
+    fill_rules: (FillRule, FillRule),
+    tolerance: f32,
+    output: Outline,
+}
+
+impl<'a> OutlineBoolean<'a> {
+    #[inline]
+    pub fn new(a: &'a Outline, b: &'a Outline, op: BooleanOp, tolerance: f32)
+               -> OutlineBoolean<'a> {
+        OutlineBoolean {
+            a,
+            b,
+            op,
+            fill_rules: (FillRule::Winding, FillRule::Winding),
+            tolerance,
+            output: Outline::new(),
+        }
+    }
+
+    #[inline]
+    pub fn set_fill_rules(&mut self, a: FillRule, b: FillRule) {
+        self.fill_rules = (a, b);
+    }
+
+    pub fn compute(&mut self) {
+        let mut edges = vec![];
+        tessellation::push_outline_edges(self.a, self.tolerance, 0, &mut edges);
+        tessellation::push_outline_edges(self.b, self.tolerance, 1, &mut edges);
+
+        let (op, (fill_rule_a, fill_rule_b)) = (self.op, self.fill_rules);
+        let output = &mut self.output;
+        tessellation::sweep(edges, |y0, y1, crossings: &[Crossing]| {
+            let (mut winding_a, mut winding_b) = (0, 0);
+            let mut span_start = None;
+            for crossing in crossings {
+                let was_inside = op.apply(fill_rule_a.is_inside(winding_a),
+                                          fill_rule_b.is_inside(winding_b));
+                if crossing.source == 0 {
+                    winding_a += crossing.winding;
+                } else {
+                    winding_b += crossing.winding;
+                }
+                let is_inside = op.apply(fill_rule_a.is_inside(winding_a),
+                                         fill_rule_b.is_inside(winding_b));
+                match (was_inside, is_inside) {
+                    (false, true) => span_start = Some((crossing.x0, crossing.x1)),
+                    (true, false) => {
+                        if let Some((left_x0, left_x1)) = span_start.take() {
+                            push_trapezoid(output,
+                                           y0,
+                                           y1,
+                                           left_x0,
+                                           crossing.x0,
+                                           left_x1,
+                                           crossing.x1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    #[inline]
+    pub fn into_outline(self) -> Outline {
+        self.output
+    }
+}
+
+/// Splits a stack of outlines, listed from bottom to top, into the parts of each that no outline
+/// above it covers.
+///
+/// This takes a single sweep, however many outlines there are. As with `OutlineBoolean`, the parts
+/// are made of trapezoids, and the outlines are filled with the nonzero winding rule.
+pub fn visible_regions(outlines: &[&Outline], tolerance: f32) -> Vec<Outline> {
+    let mut edges = vec![];
+    for (outline_index, outline) in outlines.iter().enumerate() {
+        tessellation::push_outline_edges(outline, tolerance, outline_index as u32, &mut edges);
+    }
+
+    let mut regions = vec![Outline::new(); outlines.len()];
+    let mut windings = vec![0; outlines.len()];
+    let mut inside = BTreeSet::new();
+    tessellation::sweep(edges, |y0, y1, crossings: &[Crossing]| {
+        let mut span_start = None;
+        for crossing in crossings {
+            let old_top = inside.iter().next_back().cloned();
+            let source = crossing.source as usize;
+            windings[source] += crossing.winding;
+            if windings[source] == 0 {
+                inside.remove(&source);
+            } else {
+                inside.insert(source);
+            }
+            let new_top = inside.iter().next_back().cloned();
+            if new_top == old_top {
+                continue;
+            }
+
+            if let (Some(old_top), Some((left_x0, left_x1))) = (old_top, span_start.take()) {
+                push_trapezoid(&mut regions[old_top],
+                               y0,
+                               y1,
+                               left_x0,
+                               crossing.x0,
+                               left_x1,
+                               crossing.x1);
+            }
+            if new_top.is_some() {
+                span_start = Some((crossing.x0, crossing.x1));
+            }
+        }
+
+        // Every contour is closed, so this only matters for rounding.
+        for &source in &inside {
+            windings[source] = 0;
+        }
+        inside.clear();
+    });
+    regions
+}
+
+fn push_trapezoid(outline: &mut Outline,
+                  y0: f32,
+                  y1: f32,
+                  left_x0: f32,
+                  right_x0: f32,
+                  left_x1: f32,
+                  right_x1: f32) {
+    if right_x0 <= left_x0 && right_x1 <= left_x1 {
+        return;
+    }
+    let mut contour = Contour::new();
+    contour.push_endpoint(Vector2F::new(left_x0, y0));
+    contour.push_endpoint(Vector2F::new(right_x0, y0));
+    contour.push_endpoint(Vector2F::new(right_x1, y1));
+    contour.push_endpoint(Vector2F::new(left_x1, y1));
+    contour.close();
+    outline.push_contour(contour);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::boolean::{self, BooleanOp, OutlineBoolean};
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::vector::Vector2F;
+
+    fn square(origin: Vector2F, size: f32) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(origin);
+        contour.push_endpoint(origin + Vector2F::new(size, 0.0));
+        contour.push_endpoint(origin + Vector2F::new(size, size));
+        contour.push_endpoint(origin + Vector2F::new(0.0, size));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    // The trapezoids all wind the same way, so the signed areas of their contours add up.
+    fn area(outline: &Outline) -> f32 {
+        outline.contours().iter().map(|contour| {
+            let mut area = 0.0;
+            for point_index in 0..contour.len() {
+                let from = contour.position_of(point_index);
+                let to = contour.position_of((point_index + 1) % contour.len());
+                area += from.det(to) * 0.5;
+            }
+            area
+        }).sum()
+    }
+
+    #[test]
+    fn test_overlapping_squares() {
+        let a = square(Vector2F::new(0.0, 0.0), 10.0);
+        let b = square(Vector2F::new(5.0, 5.0), 10.0);
+        let expected_areas = [
+            (BooleanOp::Union, 175.0),
+            (BooleanOp::Intersection, 25.0),
+            (BooleanOp::Difference, 75.0),
+            (BooleanOp::Xor, 150.0),
+        ];
+        for &(op, expected_area) in &expected_areas {
+            let mut boolean = OutlineBoolean::new(&a, &b, op, 0.01);
+            boolean.compute();
+            let result = boolean.into_outline();
+            assert!(f32::abs(area(&result) - expected_area) < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_visible_regions() {
+        // Three squares, each overlapping the one below it, and a fourth apart from them.
+        let squares = [
+            square(Vector2F::new(0.0, 0.0), 10.0),
+            square(Vector2F::new(5.0, 5.0), 10.0),
+            square(Vector2F::new(7.0, 3.0), 4.0),
+            square(Vector2F::new(20.0, 0.0), 2.0),
+        ];
+        let outlines: Vec<_> = squares.iter().collect();
+        let regions = boolean::visible_regions(&outlines, 0.01);
+
+        let expected_areas = [100.0 - 25.0 - 6.0, 100.0 - 8.0, 16.0, 4.0];
+        assert_eq!(regions.len(), expected_areas.len());
+        for (region, &expected_area) in regions.iter().zip(expected_areas.iter()) {
+            assert!(f32::abs(area(region) - expected_area) < 0.01, "{}", area(region));
+        }
+
+        // The regions don't overlap each other.
+        let mut covered = Outline::new();
+        for region in &regions {
+            let mut boolean = OutlineBoolean::new(&covered, region, BooleanOp::Union, 0.01);
+            boolean.compute();
+            covered = boolean.into_outline();
+        }
+        let total_area: f32 = expected_areas.iter().sum();
+        assert!(f32::abs(area(&covered) - total_area) < 0.01);
+    }
+}
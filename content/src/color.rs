@@ -8,9 +8,25 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+//! Colors, including conversions between color spaces and parsing of CSS colors.
+
 use pathfinder_simd::default::F32x4;
 use std::fmt::{self, Debug, Formatter};
 
+// Converts linear Display P3 to linear sRGB. Rows are output components.
+const DISPLAY_P3_TO_SRGB: [[f32; 3]; 3] = [
+    [ 1.2249401, -0.2249404, 0.0000000],
+    [-0.0420569,  1.0420571, 0.0000000],
+    [-0.0196376, -0.0786361, 1.0982735],
+];
+
+// Converts linear sRGB to linear Display P3. Rows are output components.
+const SRGB_TO_DISPLAY_P3: [[f32; 3]; 3] = [
+    [0.8224621, 0.177538,  0.0000000],
+    [0.0331941, 0.9668058, 0.0000000],
+    [0.0170827, 0.0723974, 0.9105199],
+];
+
 // TODO(pcwalton): Maybe this should be a u32?
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct ColorU {
@@ -21,6 +37,11 @@ pub struct ColorU {
 }
 
 impl ColorU {
+    #[inline]
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> ColorU {
+        ColorU { r, g, b, a }
+    }
+
     #[inline]
     pub fn transparent_black() -> ColorU {
         ColorU::from_u32(0)
@@ -46,6 +67,49 @@ impl ColorU {
         }
     }
 
+    #[inline]
+    pub fn white() -> ColorU {
+        ColorU {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        }
+    }
+
+    /// Parses a CSS color: a hex color like `#rgb`, `#rgba`, `#rrggbb`, or `#rrggbbaa`; an
+    /// `rgb()`, `rgba()`, `hsl()`, or `hsla()` function; or a named color.
+    ///
+    /// Both the comma-separated and the space-separated function syntaxes are accepted. Returns
+    /// `None` if the string isn't a valid color. `currentcolor` and system colors aren't
+    /// supported, because they depend on context.
+    pub fn from_css(string: &str) -> Option<ColorU> {
+        let string = string.trim().to_ascii_lowercase();
+        if let Some(hex) = string.strip_prefix('#') {
+            return parse_hex_color(hex);
+        }
+
+        if let Some(open_paren_index) = string.find('(') {
+            if !string.ends_with(')') {
+                return None;
+            }
+            let function = string[..open_paren_index].trim();
+            let arguments: Vec<&str> = string[(open_paren_index + 1)..(string.len() - 1)]
+                .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+                .filter(|argument| !argument.is_empty())
+                .collect();
+            return match function {
+                "rgb" | "rgba" => parse_rgb_function(&arguments),
+                "hsl" | "hsla" => parse_hsl_function(&arguments),
+                _ => None,
+            };
+        }
+
+        NAMED_COLORS.binary_search_by(|&(name, _)| name.cmp(&string))
+                    .ok()
+                    .map(|index| ColorU::from_u32(NAMED_COLORS[index].1))
+    }
+
     #[inline]
     pub fn to_f32(&self) -> ColorF {
         let color = F32x4::new(self.r as f32, self.g as f32, self.b as f32, self.a as f32);
@@ -94,9 +158,102 @@ impl ColorF {
         ColorF(F32x4::splat(1.0))
     }
 
+    /// Creates a color from a hue in degrees, and saturation and lightness in [0, 1].
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> ColorF {
+        let chroma = saturation * f32::min(lightness, 1.0 - lightness);
+        let component = |n: f32| {
+            let k = (n + hue / 30.0).rem_euclid(12.0);
+            lightness - chroma * f32::min(k - 3.0, 9.0 - k).clamp(-1.0, 1.0)
+        };
+        ColorF::new(component(0.0), component(8.0), component(4.0), alpha)
+    }
+
+    /// Creates a color from a hue in degrees, and saturation and value in [0, 1].
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> ColorF {
+        let component = |n: f32| {
+            let k = (n + hue / 60.0).rem_euclid(6.0);
+            value - value * saturation * f32::min(k, 4.0 - k).clamp(0.0, 1.0)
+        };
+        ColorF::new(component(5.0), component(3.0), component(1.0), alpha)
+    }
+
+    /// Returns the hue in degrees, and the saturation and lightness in [0, 1], of this color.
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let (max, min) = (self.max_component(), self.min_component());
+        let (lightness, chroma) = ((max + min) * 0.5, max - min);
+        let saturation = if chroma <= 0.0 || lightness <= 0.0 || lightness >= 1.0 {
+            0.0
+        } else {
+            chroma / (1.0 - f32::abs(2.0 * lightness - 1.0))
+        };
+        (self.hue(max, chroma), saturation, lightness)
+    }
+
+    /// Returns the hue in degrees, and the saturation and value in [0, 1], of this color.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (max, min) = (self.max_component(), self.min_component());
+        let chroma = max - min;
+        let saturation = if max <= 0.0 { 0.0 } else { chroma / max };
+        (self.hue(max, chroma), saturation, max)
+    }
+
+    /// Multiplies the color components by alpha.
+    #[inline]
+    pub fn premultiply(&self) -> ColorF {
+        let color = self.0 * F32x4::splat(self.a());
+        ColorF::new(color[0], color[1], color[2], self.a())
+    }
+
+    /// Divides the color components by alpha. Fully transparent colors become transparent black.
+    #[inline]
+    pub fn unpremultiply(&self) -> ColorF {
+        if self.a() <= 0.0 {
+            return ColorF::transparent_black();
+        }
+        let color = self.0 * F32x4::splat(1.0 / self.a());
+        ColorF::new(color[0], color[1], color[2], self.a())
+    }
+
+    /// Applies the inverse of the sRGB transfer function to the color components, producing
+    /// linear light values. Alpha is unchanged.
+    #[inline]
+    pub fn srgb_to_linear(&self) -> ColorF {
+        self.map_rgb(srgb_to_linear)
+    }
+
+    /// Applies the sRGB transfer function to linear color components. Alpha is unchanged.
+    #[inline]
+    pub fn linear_to_srgb(&self) -> ColorF {
+        self.map_rgb(linear_to_srgb)
+    }
+
+    /// Returns the relative luminance of this sRGB color, from 0 for black to 1 for white.
+    ///
+    /// The components are converted to linear light and weighted as in Rec. 709. Alpha is
+    /// ignored.
+    #[inline]
+    pub fn luminance(&self) -> f32 {
+        let linear = self.srgb_to_linear();
+        linear.r() * 0.2126 + linear.g() * 0.7152 + linear.b() * 0.0722
+    }
+
+    /// Converts a Display P3 color to sRGB.
+    ///
+    /// Colors outside the sRGB gamut have components outside [0, 1].
+    pub fn display_p3_to_srgb(&self) -> ColorF {
+        self.srgb_to_linear().mul_rgb_matrix(&DISPLAY_P3_TO_SRGB).linear_to_srgb()
+    }
+
+    /// Converts an sRGB color to Display P3, which shares the sRGB transfer function.
+    pub fn srgb_to_display_p3(&self) -> ColorF {
+        self.srgb_to_linear().mul_rgb_matrix(&SRGB_TO_DISPLAY_P3).linear_to_srgb()
+    }
+
+    /// Converts to 8-bit components, clamping components outside [0, 1].
     #[inline]
     pub fn to_u8(&self) -> ColorU {
-        let color = (self.0 * F32x4::splat(255.0)).round().to_i32x4();
+        let color = self.0.clamp(F32x4::splat(0.0), F32x4::splat(1.0));
+        let color = (color * F32x4::splat(255.0)).round().to_i32x4();
         ColorU { r: color[0] as u8, g: color[1] as u8, b: color[2] as u8, a: color[3] as u8 }
     }
 
@@ -124,6 +281,41 @@ impl ColorF {
     pub fn a(&self) -> f32 {
         self.0[3]
     }
+
+    #[inline]
+    fn max_component(&self) -> f32 {
+        f32::max(f32::max(self.r(), self.g()), self.b())
+    }
+
+    #[inline]
+    fn min_component(&self) -> f32 {
+        f32::min(f32::min(self.r(), self.g()), self.b())
+    }
+
+    fn hue(&self, max: f32, chroma: f32) -> f32 {
+        if chroma <= 0.0 {
+            0.0
+        } else if max == self.r() {
+            60.0 * ((self.g() - self.b()) / chroma).rem_euclid(6.0)
+        } else if max == self.g() {
+            60.0 * ((self.b() - self.r()) / chroma + 2.0)
+        } else {
+            60.0 * ((self.r() - self.g()) / chroma + 4.0)
+        }
+    }
+
+    #[inline]
+    fn map_rgb<F>(&self, function: F) -> ColorF where F: Fn(f32) -> f32 {
+        ColorF::new(function(self.r()), function(self.g()), function(self.b()), self.a())
+    }
+
+    fn mul_rgb_matrix(&self, matrix: &[[f32; 3]; 3]) -> ColorF {
+        let row = |index: usize| {
+            let row: &[f32; 3] = &matrix[index];
+            row[0] * self.r() + row[1] * self.g() + row[2] * self.b()
+        };
+        ColorF::new(row(0), row(1), row(2), self.a())
+    }
 }
 
 impl Debug for ColorF {
@@ -138,3 +330,345 @@ impl Debug for ColorF {
         )
     }
 }
+
+// The transfer functions are extended to negative values by symmetry, so that out-of-gamut colors
+// survive a round trip.
+fn srgb_to_linear(value: f32) -> f32 {
+    let magnitude = f32::abs(value);
+    let linear = if magnitude <= 0.04045 {
+        magnitude / 12.92
+    } else {
+        f32::powf((magnitude + 0.055) / 1.055, 2.4)
+    };
+    linear.copysign(value)
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    let magnitude = f32::abs(value);
+    let encoded = if magnitude <= 0.0031308 {
+        magnitude * 12.92
+    } else {
+        1.055 * f32::powf(magnitude, 1.0 / 2.4) - 0.055
+    };
+    encoded.copysign(value)
+}
+
+fn parse_hex_color(digits: &str) -> Option<ColorU> {
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let expand = |nibble: u32| ((nibble & 0xf) * 0x11) as u8;
+    match digits.len() {
+        3 => Some(ColorU::new(expand(value >> 8), expand(value >> 4), expand(value), 255)),
+        4 => Some(ColorU::new(expand(value >> 12),
+                              expand(value >> 8),
+                              expand(value >> 4),
+                              expand(value))),
+        6 => Some(ColorU::from_u32((value << 8) | 0xff)),
+        8 => Some(ColorU::from_u32(value)),
+        _ => None,
+    }
+}
+
+fn parse_rgb_function(arguments: &[&str]) -> Option<ColorU> {
+    if arguments.len() != 3 && arguments.len() != 4 {
+        return None;
+    }
+    let mut components = [0.0; 3];
+    for (component, argument) in components.iter_mut().zip(arguments.iter()) {
+        *component = match parse_percentage(argument) {
+            Some(percentage) => percentage,
+            None => argument.parse::<f32>().ok()? / 255.0,
+        };
+    }
+    let alpha = parse_alpha(arguments.get(3))?;
+    Some(ColorF::new(components[0], components[1], components[2], alpha).to_u8())
+}
+
+fn parse_hsl_function(arguments: &[&str]) -> Option<ColorU> {
+    if arguments.len() != 3 && arguments.len() != 4 {
+        return None;
+    }
+    let hue = parse_angle(arguments[0])?;
+    let saturation = parse_percentage(arguments[1])?;
+    let lightness = parse_percentage(arguments[2])?;
+    let alpha = parse_alpha(arguments.get(3))?;
+    let (saturation, lightness) = (clamp_unit(saturation), clamp_unit(lightness));
+    Some(ColorF::from_hsl(hue, saturation, lightness, alpha).to_u8())
+}
+
+fn parse_alpha(argument: Option<&&str>) -> Option<f32> {
+    let alpha = match argument {
+        None => return Some(1.0),
+        Some(argument) => match parse_percentage(argument) {
+            Some(percentage) => percentage,
+            None => argument.parse().ok()?,
+        },
+    };
+    Some(clamp_unit(alpha))
+}
+
+// Returns the percentage as a fraction of 1.
+fn parse_percentage(argument: &str) -> Option<f32> {
+    let percentage = argument.strip_suffix('%')?;
+    percentage.parse::<f32>().ok().map(|percentage| percentage * 0.01)
+}
+
+// Returns the angle in degrees.
+fn parse_angle(argument: &str) -> Option<f32> {
+    for &(unit, degrees_per_unit) in &[
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / std::f32::consts::PI),
+        ("turn", 360.0),
+    ] {
+        if let Some(value) = argument.strip_suffix(unit) {
+            let value: f32 = value.parse().ok()?;
+            return Some(value * degrees_per_unit);
+        }
+    }
+    argument.parse().ok()
+}
+
+#[inline]
+fn clamp_unit(value: f32) -> f32 {
+    value.clamp(0.0, 1.0)
+}
+
+// Sorted by name, for binary search.
+static NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ffff),
+    ("antiquewhite", 0xfaebd7ff),
+    ("aqua", 0x00ffffff),
+    ("aquamarine", 0x7fffd4ff),
+    ("azure", 0xf0ffffff),
+    ("beige", 0xf5f5dcff),
+    ("bisque", 0xffe4c4ff),
+    ("black", 0x000000ff),
+    ("blanchedalmond", 0xffebcdff),
+    ("blue", 0x0000ffff),
+    ("blueviolet", 0x8a2be2ff),
+    ("brown", 0xa52a2aff),
+    ("burlywood", 0xdeb887ff),
+    ("cadetblue", 0x5f9ea0ff),
+    ("chartreuse", 0x7fff00ff),
+    ("chocolate", 0xd2691eff),
+    ("coral", 0xff7f50ff),
+    ("cornflowerblue", 0x6495edff),
+    ("cornsilk", 0xfff8dcff),
+    ("crimson", 0xdc143cff),
+    ("cyan", 0x00ffffff),
+    ("darkblue", 0x00008bff),
+    ("darkcyan", 0x008b8bff),
+    ("darkgoldenrod", 0xb8860bff),
+    ("darkgray", 0xa9a9a9ff),
+    ("darkgreen", 0x006400ff),
+    ("darkgrey", 0xa9a9a9ff),
+    ("darkkhaki", 0xbdb76bff),
+    ("darkmagenta", 0x8b008bff),
+    ("darkolivegreen", 0x556b2fff),
+    ("darkorange", 0xff8c00ff),
+    ("darkorchid", 0x9932ccff),
+    ("darkred", 0x8b0000ff),
+    ("darksalmon", 0xe9967aff),
+    ("darkseagreen", 0x8fbc8fff),
+    ("darkslateblue", 0x483d8bff),
+    ("darkslategray", 0x2f4f4fff),
+    ("darkslategrey", 0x2f4f4fff),
+    ("darkturquoise", 0x00ced1ff),
+    ("darkviolet", 0x9400d3ff),
+    ("deeppink", 0xff1493ff),
+    ("deepskyblue", 0x00bfffff),
+    ("dimgray", 0x696969ff),
+    ("dimgrey", 0x696969ff),
+    ("dodgerblue", 0x1e90ffff),
+    ("firebrick", 0xb22222ff),
+    ("floralwhite", 0xfffaf0ff),
+    ("forestgreen", 0x228b22ff),
+    ("fuchsia", 0xff00ffff),
+    ("gainsboro", 0xdcdcdcff),
+    ("ghostwhite", 0xf8f8ffff),
+    ("gold", 0xffd700ff),
+    ("goldenrod", 0xdaa520ff),
+    ("gray", 0x808080ff),
+    ("green", 0x008000ff),
+    ("greenyellow", 0xadff2fff),
+    ("grey", 0x808080ff),
+    ("honeydew", 0xf0fff0ff),
+    ("hotpink", 0xff69b4ff),
+    ("indianred", 0xcd5c5cff),
+    ("indigo", 0x4b0082ff),
+    ("ivory", 0xfffff0ff),
+    ("khaki", 0xf0e68cff),
+    ("lavender", 0xe6e6faff),
+    ("lavenderblush", 0xfff0f5ff),
+    ("lawngreen", 0x7cfc00ff),
+    ("lemonchiffon", 0xfffacdff),
+    ("lightblue", 0xadd8e6ff),
+    ("lightcoral", 0xf08080ff),
+    ("lightcyan", 0xe0ffffff),
+    ("lightgoldenrodyellow", 0xfafad2ff),
+    ("lightgray", 0xd3d3d3ff),
+    ("lightgreen", 0x90ee90ff),
+    ("lightgrey", 0xd3d3d3ff),
+    ("lightpink", 0xffb6c1ff),
+    ("lightsalmon", 0xffa07aff),
+    ("lightseagreen", 0x20b2aaff),
+    ("lightskyblue", 0x87cefaff),
+    ("lightslategray", 0x778899ff),
+    ("lightslategrey", 0x778899ff),
+    ("lightsteelblue", 0xb0c4deff),
+    ("lightyellow", 0xffffe0ff),
+    ("lime", 0x00ff00ff),
+    ("limegreen", 0x32cd32ff),
+    ("linen", 0xfaf0e6ff),
+    ("magenta", 0xff00ffff),
+    ("maroon", 0x800000ff),
+    ("mediumaquamarine", 0x66cdaaff),
+    ("mediumblue", 0x0000cdff),
+    ("mediumorchid", 0xba55d3ff),
+    ("mediumpurple", 0x9370dbff),
+    ("mediumseagreen", 0x3cb371ff),
+    ("mediumslateblue", 0x7b68eeff),
+    ("mediumspringgreen", 0x00fa9aff),
+    ("mediumturquoise", 0x48d1ccff),
+    ("mediumvioletred", 0xc71585ff),
+    ("midnightblue", 0x191970ff),
+    ("mintcream", 0xf5fffaff),
+    ("mistyrose", 0xffe4e1ff),
+    ("moccasin", 0xffe4b5ff),
+    ("navajowhite", 0xffdeadff),
+    ("navy", 0x000080ff),
+    ("oldlace", 0xfdf5e6ff),
+    ("olive", 0x808000ff),
+    ("olivedrab", 0x6b8e23ff),
+    ("orange", 0xffa500ff),
+    ("orangered", 0xff4500ff),
+    ("orchid", 0xda70d6ff),
+    ("palegoldenrod", 0xeee8aaff),
+    ("palegreen", 0x98fb98ff),
+    ("paleturquoise", 0xafeeeeff),
+    ("palevioletred", 0xdb7093ff),
+    ("papayawhip", 0xffefd5ff),
+    ("peachpuff", 0xffdab9ff),
+    ("peru", 0xcd853fff),
+    ("pink", 0xffc0cbff),
+    ("plum", 0xdda0ddff),
+    ("powderblue", 0xb0e0e6ff),
+    ("purple", 0x800080ff),
+    ("rebeccapurple", 0x663399ff),
+    ("red", 0xff0000ff),
+    ("rosybrown", 0xbc8f8fff),
+    ("royalblue", 0x4169e1ff),
+    ("saddlebrown", 0x8b4513ff),
+    ("salmon", 0xfa8072ff),
+    ("sandybrown", 0xf4a460ff),
+    ("seagreen", 0x2e8b57ff),
+    ("seashell", 0xfff5eeff),
+    ("sienna", 0xa0522dff),
+    ("silver", 0xc0c0c0ff),
+    ("skyblue", 0x87ceebff),
+    ("slateblue", 0x6a5acdff),
+    ("slategray", 0x708090ff),
+    ("slategrey", 0x708090ff),
+    ("snow", 0xfffafaff),
+    ("springgreen", 0x00ff7fff),
+    ("steelblue", 0x4682b4ff),
+    ("tan", 0xd2b48cff),
+    ("teal", 0x008080ff),
+    ("thistle", 0xd8bfd8ff),
+    ("tomato", 0xff6347ff),
+    ("transparent", 0x00000000),
+    ("turquoise", 0x40e0d0ff),
+    ("violet", 0xee82eeff),
+    ("wheat", 0xf5deb3ff),
+    ("white", 0xffffffff),
+    ("whitesmoke", 0xf5f5f5ff),
+    ("yellow", 0xffff00ff),
+    ("yellowgreen", 0x9acd32ff),
+];
+
+#[cfg(test)]
+mod test {
+    use crate::color::{ColorF, ColorU};
+
+    fn approx_eq(a: ColorF, b: ColorF) -> bool {
+        (0..4).all(|index| f32::abs(a.0[index] - b.0[index]) < 0.001)
+    }
+
+    #[test]
+    fn test_parse_css() {
+        let orange = ColorU::new(255, 165, 0, 255);
+        assert_eq!(ColorU::from_css("#ffa500"), Some(orange));
+        assert_eq!(ColorU::from_css("#FFA500"), Some(orange));
+        assert_eq!(ColorU::from_css("#f00"), Some(ColorU::new(255, 0, 0, 255)));
+        assert_eq!(ColorU::from_css("#ff000080"), Some(ColorU::new(255, 0, 0, 128)));
+        assert_eq!(ColorU::from_css("#f008"), Some(ColorU::new(255, 0, 0, 136)));
+        assert_eq!(ColorU::from_css("rgb(255, 165, 0)"), Some(orange));
+        assert_eq!(ColorU::from_css("rgb(100% 64.7% 0%)"), Some(orange));
+        assert_eq!(ColorU::from_css("rgba(255, 165, 0, 0.5)"), Some(ColorU::new(255, 165, 0, 128)));
+        assert_eq!(ColorU::from_css("rgb(255 165 0 / 50%)"), Some(ColorU::new(255, 165, 0, 128)));
+        assert_eq!(ColorU::from_css("hsl(38.8, 100%, 50%)"), Some(orange));
+        assert_eq!(ColorU::from_css("hsl(120deg 100% 25%)"), Some(ColorU::new(0, 128, 0, 255)));
+        assert_eq!(ColorU::from_css("hsla(0.5turn, 100%, 50%, 0)"),
+                   Some(ColorU::new(0, 255, 255, 0)));
+        assert_eq!(ColorU::from_css(" Orange "), Some(orange));
+        assert_eq!(ColorU::from_css("transparent"), Some(ColorU::transparent_black()));
+        assert_eq!(ColorU::from_css("rebeccapurple"), Some(ColorU::new(102, 51, 153, 255)));
+        assert_eq!(ColorU::from_css("#ffa50"), None);
+        assert_eq!(ColorU::from_css("#gggggg"), None);
+        assert_eq!(ColorU::from_css("rgb(1, 2)"), None);
+        assert_eq!(ColorU::from_css("hsl(0, 1, 2"), None);
+        assert_eq!(ColorU::from_css("notacolor"), None);
+    }
+
+    #[test]
+    fn test_hsl_hsv_round_trip() {
+        let color = ColorF::new(0.2, 0.6, 0.9, 0.5);
+        let (hue, saturation, lightness) = color.to_hsl();
+        assert!(approx_eq(ColorF::from_hsl(hue, saturation, lightness, color.a()), color));
+        let (hue, saturation, value) = color.to_hsv();
+        assert!(approx_eq(ColorF::from_hsv(hue, saturation, value, color.a()), color));
+        assert!(approx_eq(ColorF::from_hsv(240.0, 1.0, 0.5, 1.0), ColorF::new(0.0, 0.0, 0.5, 1.0)));
+    }
+
+    #[test]
+    fn test_premultiply() {
+        let color = ColorF::new(0.2, 0.6, 0.9, 0.5);
+        assert!(approx_eq(color.premultiply(), ColorF::new(0.1, 0.3, 0.45, 0.5)));
+        assert!(approx_eq(color.premultiply().unpremultiply(), color));
+        assert!(approx_eq(ColorF::new(1.0, 1.0, 1.0, 0.0).unpremultiply(),
+                          ColorF::transparent_black()));
+    }
+
+    #[test]
+    fn test_transfer_functions() {
+        let color = ColorF::new(0.0, 0.5, 1.0, 0.25);
+        assert!(approx_eq(color.srgb_to_linear(), ColorF::new(0.0, 0.2140, 1.0, 0.25)));
+        assert!(approx_eq(color.srgb_to_linear().linear_to_srgb(), color));
+    }
+
+    #[test]
+    fn test_luminance() {
+        assert_eq!(ColorF::white().luminance(), 1.0);
+        assert_eq!(ColorF::new(0.0, 0.0, 0.0, 0.5).luminance(), 0.0);
+        assert!(f32::abs(ColorF::new(0.0, 1.0, 0.0, 1.0).luminance() - 0.7152) < 0.0001);
+
+        // Mid-gray in sRGB is much darker than half in linear light.
+        assert!(f32::abs(ColorF::new(0.5, 0.5, 0.5, 1.0).luminance() - 0.2140) < 0.001);
+    }
+
+    #[test]
+    fn test_display_p3() {
+        // Display P3 and sRGB share a white point.
+        assert!(approx_eq(ColorF::white().display_p3_to_srgb(), ColorF::white()));
+        let color = ColorF::new(0.3, 0.7, 0.4, 1.0);
+        assert!(approx_eq(color.srgb_to_display_p3().display_p3_to_srgb(), color));
+
+        // Pure P3 red lies outside sRGB.
+        let red = ColorF::new(1.0, 0.0, 0.0, 1.0).display_p3_to_srgb();
+        assert!(red.r() > 1.0 && red.g() < 0.0 && red.b() < 0.0);
+        assert_eq!(red.to_u8(), ColorU::new(255, 0, 0, 255));
+    }
+}
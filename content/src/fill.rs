@@ -0,0 +1,36 @@
+// pathfinder/content/src/fill.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fill rules, which determine which regions of a path are inside it.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillRule {
+    /// A point is inside if the path winds around it a nonzero number of times.
+    Winding,
+    /// A point is inside if the path winds around it an odd number of times.
+    EvenOdd,
+}
+
+impl FillRule {
+    #[inline]
+    pub fn is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::Winding => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+impl Default for FillRule {
+    #[inline]
+    fn default() -> FillRule {
+        FillRule::Winding
+    }
+}
@@ -17,13 +17,18 @@ extern crate bitflags;
 #[macro_use]
 extern crate log;
 
+pub mod boolean;
 pub mod clip;
 pub mod color;
 pub mod dash;
+pub mod fill;
+pub mod marker;
 pub mod orientation;
 pub mod outline;
 pub mod segment;
 pub mod stroke;
+pub mod tessellation;
 pub mod transform;
+pub mod trim;
 
 mod dilation;
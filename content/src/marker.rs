@@ -0,0 +1,179 @@
+// pathfinder/content/src/marker.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Placement of markers, like SVG `marker-start`, `marker-mid`, and `marker-end`, on the vertices
+//! of a path.
+
+use crate::outline::{Contour, Outline};
+use crate::segment::{Segment, SegmentKind};
+use pathfinder_geometry::vector::Vector2F;
+use std::f32::consts::PI;
+
+/// A vertex of a path, along with the direction of the path there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarkerVertex {
+    pub position: Vector2F,
+    /// The angle of the path at this vertex, in radians, measured from the positive X axis toward
+    /// the positive Y axis.
+    ///
+    /// Where two segments meet, this bisects the incoming and outgoing directions, as SVG's
+    /// `orient="auto"` requires.
+    pub angle: f32,
+}
+
+impl MarkerVertex {
+    /// Returns every vertex of the outline, in order.
+    ///
+    /// The first vertex is the one that receives a start marker, and the last is the one that
+    /// receives an end marker. Closed contours end with a vertex at their start point, reached
+    /// by the closing segment.
+    pub fn from_outline(outline: &Outline) -> Vec<MarkerVertex> {
+        let mut vertices = vec![];
+        for contour in outline.contours() {
+            push_contour_vertices(contour, &mut vertices);
+        }
+        vertices
+    }
+}
+
+fn push_contour_vertices(contour: &Contour, vertices: &mut Vec<MarkerVertex>) {
+    let segments: Vec<Segment> = contour.iter().collect();
+    let (first_segment, last_segment) = match (segments.first(), segments.last()) {
+        (Some(first_segment), Some(last_segment)) => (first_segment, last_segment),
+        _ => {
+            if !contour.is_empty() {
+                vertices.push(MarkerVertex { position: contour.position_of(0), angle: 0.0 });
+            }
+            return;
+        }
+    };
+
+    let closing_direction = if contour.is_closed() {
+        Some(end_direction(last_segment))
+    } else {
+        None
+    };
+    vertices.push(MarkerVertex {
+        position: first_segment.baseline.from(),
+        angle: vertex_angle(closing_direction, Some(start_direction(first_segment))),
+    });
+
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let next_direction = match segments.get(segment_index + 1) {
+            Some(next_segment) => Some(start_direction(next_segment)),
+            None if contour.is_closed() => Some(start_direction(first_segment)),
+            None => None,
+        };
+        vertices.push(MarkerVertex {
+            position: segment.baseline.to(),
+            angle: vertex_angle(Some(end_direction(segment)), next_direction),
+        });
+    }
+}
+
+fn vertex_angle(incoming: Option<Vector2F>, outgoing: Option<Vector2F>) -> f32 {
+    match (incoming, outgoing) {
+        (None, None) => 0.0,
+        (Some(direction), None) | (None, Some(direction)) => angle_of(direction),
+        (Some(incoming), Some(outgoing)) => {
+            let (incoming_angle, mut outgoing_angle) = (angle_of(incoming), angle_of(outgoing));
+            if outgoing_angle - incoming_angle > PI {
+                outgoing_angle -= 2.0 * PI;
+            } else if incoming_angle - outgoing_angle > PI {
+                outgoing_angle += 2.0 * PI;
+            }
+            (incoming_angle + outgoing_angle) * 0.5
+        }
+    }
+}
+
+#[inline]
+fn angle_of(direction: Vector2F) -> f32 {
+    f32::atan2(direction.y(), direction.x())
+}
+
+// The direction of a segment at its start. If the first control point coincides with the start
+// point, the direction is toward the next distinct point, per the SVG specification.
+fn start_direction(segment: &Segment) -> Vector2F {
+    let from = segment.baseline.from();
+    let candidates = match segment.kind {
+        SegmentKind::None | SegmentKind::Line => [segment.baseline.to(); 3],
+        SegmentKind::Quadratic => [segment.ctrl.from(), segment.baseline.to(), from],
+        SegmentKind::Cubic => [segment.ctrl.from(), segment.ctrl.to(), segment.baseline.to()],
+    };
+    first_nonzero(&candidates, |point| point - from)
+}
+
+fn end_direction(segment: &Segment) -> Vector2F {
+    let to = segment.baseline.to();
+    let candidates = match segment.kind {
+        SegmentKind::None | SegmentKind::Line => [segment.baseline.from(); 3],
+        SegmentKind::Quadratic => [segment.ctrl.from(), segment.baseline.from(), to],
+        SegmentKind::Cubic => [segment.ctrl.to(), segment.ctrl.from(), segment.baseline.from()],
+    };
+    first_nonzero(&candidates, |point| to - point)
+}
+
+fn first_nonzero<F>(points: &[Vector2F; 3], direction: F) -> Vector2F
+                   where F: Fn(Vector2F) -> Vector2F {
+    points.iter()
+          .map(|&point| direction(point))
+          .find(|vector| !vector.is_zero())
+          .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::marker::MarkerVertex;
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::vector::Vector2F;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        f32::abs(a - b) < 0.0001
+    }
+
+    #[test]
+    fn test_open_polyline() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 10.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let vertices = MarkerVertex::from_outline(&outline);
+        assert_eq!(vertices.len(), 3);
+        assert!(approx_eq(vertices[0].angle, 0.0));
+        assert!(approx_eq(vertices[1].angle, FRAC_PI_4));
+        assert!(approx_eq(vertices[2].angle, FRAC_PI_2));
+        assert_eq!(vertices[2].position, Vector2F::new(10.0, 10.0));
+    }
+
+    #[test]
+    fn test_closed_square() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 10.0));
+        contour.push_endpoint(Vector2F::new(0.0, 10.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        // The closing segment heads up and the first one heads right, so both the first and the
+        // last vertex bisect those directions.
+        let vertices = MarkerVertex::from_outline(&outline);
+        assert_eq!(vertices.len(), 5);
+        assert!(approx_eq(vertices[0].angle, -FRAC_PI_4));
+        assert!(approx_eq(vertices[4].angle, -FRAC_PI_4));
+        assert_eq!(vertices[4].position, vertices[0].position);
+    }
+}
@@ -38,6 +38,16 @@ pub struct Contour {
     pub(crate) closed: bool,
 }
 
+/// A contour approximated by straight lines.
+///
+/// The last point of a closed polyline connects back to the first; that closing edge is not
+/// represented by a repeated point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Vector2F>,
+    pub closed: bool,
+}
+
 bitflags! {
     pub struct PointFlags: u8 {
         const CONTROL_POINT_0 = 0x01;
@@ -61,6 +71,15 @@ impl Outline {
         }
     }
 
+    /// Returns an outline of one closed contour around the rectangle, as `Contour::from_rect`
+    /// makes.
+    #[inline]
+    pub fn from_rect(rect: RectF) -> Outline {
+        let mut outline = Outline::new();
+        outline.push_contour(Contour::from_rect(rect));
+        outline
+    }
+
     #[inline]
     pub fn from_segments<I>(segments: I) -> Outline
     where
@@ -202,6 +221,17 @@ impl Outline {
             self.push_contour(ContourRectClipper::new(clip_rect, contour).clip());
         }
     }
+
+    /// Approximates each contour with a polyline whose points lie within `tolerance` of the
+    /// original curves.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        self.contours.iter().map(|contour| contour.flatten(tolerance)).collect()
+    }
+
+    /// Returns the total length of all contours, including the closing segments of closed ones.
+    pub fn length(&self) -> f32 {
+        self.contours.iter().map(|contour| contour.length()).sum()
+    }
 }
 
 impl Debug for Outline {
@@ -227,6 +257,18 @@ impl Contour {
         }
     }
 
+    /// Returns a closed contour around the rectangle, from its origin through its upper right,
+    /// lower right, and lower left corners.
+    pub fn from_rect(rect: RectF) -> Contour {
+        let mut contour = Contour::new();
+        contour.push_endpoint(rect.origin());
+        contour.push_endpoint(rect.upper_right());
+        contour.push_endpoint(rect.lower_right());
+        contour.push_endpoint(rect.lower_left());
+        contour.close();
+        contour
+    }
+
     // Replaces this contour with a new one, with arrays preallocated to match `self`.
     #[inline]
     pub(crate) fn take(&mut self) -> Contour {
@@ -280,9 +322,30 @@ impl Contour {
         self.points.last().cloned()
     }
 
-    #[inline]
-    pub(crate) fn position_of_last(&self, index: u32) -> Vector2F {
-        self.points[self.points.len() - index as usize]
+    /// Approximates this contour with a polyline whose points lie within `tolerance` of the
+    /// original curves.
+    pub fn flatten(&self, tolerance: f32) -> Polyline {
+        let mut points = vec![];
+        if let Some(&first) = self.points.first() {
+            points.push(first);
+        }
+        for segment in self.iter() {
+            segment.flatten(tolerance, &mut points);
+        }
+
+        // The closing edge is implicit.
+        if self.closed {
+            while points.len() > 1 && points.last() == points.first() {
+                points.pop();
+            }
+        }
+
+        Polyline { points, closed: self.closed }
+    }
+
+    /// Returns the length of this contour, including its closing segment if it's closed.
+    pub fn length(&self) -> f32 {
+        self.iter().map(|segment| segment.arc_length()).sum()
     }
 
     #[inline]
@@ -844,3 +907,141 @@ pub(crate) fn union_rect(bounds: &mut RectF, new_point: Vector2F, first: bool) {
         *bounds = bounds.union_point(new_point)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline, Polyline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+
+    const SAMPLE_COUNT: u32 = 1000;
+
+    fn distance_to_line_segment(point: Vector2F, from: Vector2F, to: Vector2F) -> f32 {
+        let vector = to - from;
+        let square_length = vector.square_length();
+        if square_length == 0.0 {
+            return (point - from).length();
+        }
+        let t = ((point - from).dot(vector) / square_length).clamp(0.0, 1.0);
+        (point - from.lerp(to, t)).length()
+    }
+
+    fn distance_to_polyline(point: Vector2F, polyline: &Polyline) -> f32 {
+        polyline.points.windows(2).map(|points| {
+            distance_to_line_segment(point, points[0], points[1])
+        }).fold(f32::INFINITY, f32::min)
+    }
+
+    // Checks that the polyline of the single-segment contour starts and ends where the curve does,
+    // and that the curve strays no further than `tolerance` from it.
+    fn assert_within_tolerance(contour: &Contour, tolerance: f32) -> usize {
+        let segment = contour.iter().next().unwrap();
+        let polyline = contour.flatten(tolerance);
+        assert!(!polyline.closed);
+        assert_eq!(polyline.points.first(), Some(&segment.baseline.from()));
+        assert_eq!(polyline.points.last(), Some(&segment.baseline.to()));
+
+        // Allow for rounding in the curve evaluation.
+        let slack = tolerance * 0.01;
+        for sample_index in 0..(SAMPLE_COUNT + 1) {
+            let point = segment.sample(sample_index as f32 / SAMPLE_COUNT as f32);
+            let distance = distance_to_polyline(point, &polyline);
+            assert!(distance <= tolerance + slack, "{:?} is {} away", point, distance);
+        }
+        polyline.points.len()
+    }
+
+    #[test]
+    fn test_flatten_quadratic() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_quadratic(Vector2F::new(50.0, 100.0), Vector2F::new(100.0, 0.0));
+
+        // A tighter tolerance takes more points.
+        let coarse_count = assert_within_tolerance(&contour, 1.0);
+        let fine_count = assert_within_tolerance(&contour, 0.01);
+        assert!(fine_count > coarse_count);
+        assert!(coarse_count > 2);
+    }
+
+    #[test]
+    fn test_flatten_cubic() {
+        // An S-curve, which is straight where its curvature changes sign.
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_cubic(Vector2F::new(100.0, 0.0),
+                           Vector2F::new(0.0, 100.0),
+                           Vector2F::new(100.0, 100.0));
+
+        let coarse_count = assert_within_tolerance(&contour, 1.0);
+        let fine_count = assert_within_tolerance(&contour, 0.01);
+        assert!(fine_count > coarse_count);
+        assert!(coarse_count > 2);
+    }
+
+    #[test]
+    fn test_flatten_lines() {
+        // Lines need no extra points.
+        let mut contour = Contour::new();
+        let points = [Vector2F::new(0.0, 0.0), Vector2F::new(10.0, 0.0), Vector2F::new(10.0, 5.0)];
+        for &point in &points {
+            contour.push_endpoint(point);
+        }
+        assert_eq!(contour.flatten(0.1), Polyline { points: points.to_vec(), closed: false });
+        assert_eq!(Contour::new().flatten(0.1), Polyline { points: vec![], closed: false });
+    }
+
+    #[test]
+    fn test_from_rect() {
+        let rect = RectF::new(Vector2F::new(1.0, 2.0), Vector2F::new(3.0, 4.0));
+        let contour = Contour::from_rect(rect);
+        let corners = vec![
+            Vector2F::new(1.0, 2.0),
+            Vector2F::new(4.0, 2.0),
+            Vector2F::new(4.0, 6.0),
+            Vector2F::new(1.0, 6.0),
+        ];
+        assert_eq!(contour.flatten(0.1), Polyline { points: corners, closed: true });
+        assert_eq!(contour.bounds(), rect);
+
+        let outline = Outline::from_rect(rect);
+        assert_eq!(outline.contours().len(), 1);
+        assert_eq!(outline.bounds(), rect);
+    }
+
+    #[test]
+    fn test_flatten_closed_contours() {
+        let square = [
+            Vector2F::new(0.0, 0.0),
+            Vector2F::new(10.0, 0.0),
+            Vector2F::new(10.0, 10.0),
+            Vector2F::new(0.0, 10.0),
+        ];
+        let mut implicit = Contour::new();
+        for &point in &square {
+            implicit.push_endpoint(point);
+        }
+        implicit.close();
+
+        // The closing edge isn't represented by a repeated point, even if the contour returns to
+        // its start explicitly before closing.
+        let mut open = Contour::new();
+        for &point in square.iter().chain(Some(&square[0])) {
+            open.push_endpoint(point);
+        }
+        let mut explicit = open.clone();
+        explicit.close();
+
+        let expected = Polyline { points: square.to_vec(), closed: true };
+        assert_eq!(implicit.flatten(0.1), expected);
+        assert_eq!(explicit.flatten(0.1), expected);
+        assert_eq!(open.flatten(0.1).points.len(), 5);
+        assert!(!open.flatten(0.1).closed);
+
+        // Outlines flatten each of their contours in order.
+        let mut outline = Outline::new();
+        outline.push_contour(open.clone());
+        outline.push_contour(implicit);
+        assert_eq!(outline.flatten(0.1), vec![open.flatten(0.1), expected]);
+    }
+}
@@ -18,6 +18,17 @@ use pathfinder_simd::default::F32x4;
 use std::f32::consts::SQRT_2;
 
 const MAX_NEWTON_ITERATIONS: u32 = 32;
+const MAX_FLATTENING_DEPTH: u32 = 16;
+const ARC_LENGTH_INTERVALS: u32 = 4;
+
+// Abscissae and weights of five-point Gauss-Legendre quadrature on [-1, 1].
+const GAUSS_LEGENDRE_COEFFICIENTS: [(f32, f32); 5] = [
+    (0.0, 0.568_888_9),
+    (-0.538_469_3, 0.478_628_67),
+    (0.538_469_3, 0.478_628_67),
+    (-0.906_179_9, 0.236_926_88),
+    (0.906_179_9, 0.236_926_88),
+];
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Segment {
@@ -214,6 +225,65 @@ impl Segment {
         }
     }
 
+    /// Returns the derivative of this segment at time `t`; that is, an unnormalized tangent
+    /// vector.
+    #[inline]
+    pub fn derivative(self, t: f32) -> Vector2F {
+        match self.kind {
+            SegmentKind::None => Vector2F::default(),
+            SegmentKind::Line => self.baseline.vector(),
+            SegmentKind::Quadratic => {
+                let (p0, p1, p2) = (self.baseline.from(), self.ctrl.from(), self.baseline.to());
+                (p1 - p0).scale(2.0 * (1.0 - t)) + (p2 - p1).scale(2.0 * t)
+            }
+            SegmentKind::Cubic => {
+                let (p0, p3) = (self.baseline.from(), self.baseline.to());
+                let (p1, p2) = (self.ctrl.from(), self.ctrl.to());
+                let s = 1.0 - t;
+                (p1 - p0).scale(3.0 * s * s) +
+                    (p2 - p1).scale(6.0 * s * t) +
+                    (p3 - p2).scale(3.0 * t * t)
+            }
+        }
+    }
+
+    /// Appends the endpoints of a polyline that approximates this segment to within `tolerance`
+    /// to `points`. The start point of the segment is not appended.
+    pub fn flatten(&self, tolerance: f32, points: &mut Vec<Vector2F>) {
+        match self.kind {
+            SegmentKind::None => {}
+            SegmentKind::Line => points.push(self.baseline.to()),
+            SegmentKind::Quadratic => {
+                // The second derivative of a quadratic is constant, so the distance of a chord
+                // spanning `1 / n` of it from the curve is at most `|p0 - 2 p1 + p2| / (4 n²)`.
+                let (p0, p1, p2) = (self.baseline.from(), self.ctrl.from(), self.baseline.to());
+                let deviation = (p0 - p1.scale(2.0) + p2).length();
+                let max_count = (1 << MAX_FLATTENING_DEPTH) as f32;
+                let count = f32::sqrt(deviation / (4.0 * tolerance)).ceil().max(1.0).min(max_count);
+                for index in 1..(count as u32) {
+                    let t = index as f32 / count;
+                    let s = 1.0 - t;
+                    points.push(p0.scale(s * s) + p1.scale(2.0 * s * t) + p2.scale(t * t));
+                }
+                points.push(p2);
+            }
+            SegmentKind::Cubic => flatten_cubic(self, tolerance, 0, points),
+        }
+
+        fn flatten_cubic(segment: &Segment,
+                         tolerance: f32,
+                         depth: u32,
+                         points: &mut Vec<Vector2F>) {
+            if depth >= MAX_FLATTENING_DEPTH || segment.as_cubic_segment().is_flat(tolerance) {
+                points.push(segment.baseline.to());
+                return;
+            }
+            let (before, after) = segment.as_cubic_segment().split(0.5);
+            flatten_cubic(&before, tolerance, depth + 1, points);
+            flatten_cubic(&after, tolerance, depth + 1, points);
+        }
+    }
+
     #[inline]
     pub fn transform(self, transform: &Transform2DF) -> Segment {
         Segment {
@@ -224,14 +294,63 @@ impl Segment {
         }
     }
 
+    #[inline]
     pub fn arc_length(&self) -> f32 {
-        // FIXME(pcwalton)
-        self.baseline.vector().length()
+        self.arc_length_between(0.0, 1.0)
+    }
+
+    /// Returns the length of this segment between times `t0` and `t1`.
+    pub fn arc_length_between(&self, t0: f32, t1: f32) -> f32 {
+        match self.kind {
+            SegmentKind::None => 0.0,
+            SegmentKind::Line => self.baseline.vector().length() * (t1 - t0),
+            SegmentKind::Quadratic | SegmentKind::Cubic => {
+                // Integrate the speed piecewise with Gauss-Legendre quadrature.
+                let step = (t1 - t0) / ARC_LENGTH_INTERVALS as f32;
+                let mut length = 0.0;
+                for interval_index in 0..ARC_LENGTH_INTERVALS {
+                    let start = t0 + step * interval_index as f32;
+                    for &(abscissa, weight) in &GAUSS_LEGENDRE_COEFFICIENTS {
+                        let t = start + step * (abscissa + 1.0) * 0.5;
+                        length += self.derivative(t).length() * weight * step * 0.5;
+                    }
+                }
+                length
+            }
+        }
     }
 
+    /// Returns the time at which the segment is `distance` long, clamped to the segment.
     pub fn time_for_distance(&self, distance: f32) -> f32 {
-        // FIXME(pcwalton)
-        distance / self.arc_length()
+        let length = self.arc_length();
+        if distance <= 0.0 {
+            return 0.0;
+        }
+        if distance >= length {
+            return 1.0;
+        }
+        if self.is_line() {
+            return distance / length;
+        }
+
+        // Newton's method, falling back to bisection whenever a step leaves the bracket.
+        let (mut low, mut high) = (0.0, 1.0);
+        let mut t = distance / length;
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let error = self.arc_length_between(0.0, t) - distance;
+            if f32::abs(error) < EPSILON {
+                break;
+            }
+            if error < 0.0 {
+                low = t;
+            } else {
+                high = t;
+            }
+            let speed = self.derivative(t).length();
+            let next_t = if speed > 0.0 { t - error / speed } else { -1.0 };
+            t = if next_t > low && next_t < high { next_t } else { (low + high) * 0.5 };
+        }
+        t
     }
 }
 
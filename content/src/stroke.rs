@@ -11,19 +11,34 @@
 //! Utilities for converting path strokes to fills.
 
 use crate::outline::{ArcDirection, Contour, Outline, PushSegmentFlags};
-use crate::segment::Segment;
+use crate::segment::{Segment, SegmentKind};
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::RectF;
-use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::transform2d::{Matrix2x2F, Transform2DF};
 use pathfinder_geometry::vector::Vector2F;
+use std::borrow::Cow;
 use std::f32;
 
 const TOLERANCE: f32 = 0.01;
 
+// The maximum number of times a segment is split in half while searching for a good offset.
+const MAX_SUBDIVISION_DEPTH: u32 = 12;
+
+// Sine of the largest angle between two tangents that is still considered smooth.
+const JOIN_EPSILON: f32 = 0.001;
+
+const CUSP_SAMPLE_COUNT: u32 = 32;
+const CUSP_REFINEMENT_ITERATIONS: u32 = 24;
+const CUSP_SPEED_EPSILON: f32 = 0.01;
+const CUSP_T_EPSILON: f32 = 0.001;
+const GOLDEN_RATIO_RECIP: f32 = 0.618_034;
+
 pub struct OutlineStrokeToFill<'a> {
     input: &'a Outline,
     output: Outline,
     style: StrokeStyle,
+    width_profile: Option<&'a WidthProfile>,
+    transform: Option<(Transform2DF, StrokeTransform)>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -47,38 +62,150 @@ pub enum LineJoin {
     Round,
 }
 
+/// Describes how a stroke interacts with the transform applied to its path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StrokeTransform {
+    /// The stroke is computed in the path's local space and is transformed along with it, so
+    /// non-uniform scales produce strokes of varying thickness. This is the SVG and canvas
+    /// default.
+    Scaling,
+    /// The stroke width is measured in the transformed (screen) space, as SVG's
+    /// `vector-effect="non-scaling-stroke"` requires.
+    NonScaling,
+}
+
+/// Per-point multipliers of the line width, for variable-width strokes such as those produced by
+/// pressure-sensitive brushes.
+///
+/// The profile holds one list of multipliers per contour, with one multiplier per endpoint (not
+/// per control point). Widths are linearly interpolated along each segment. Contours or endpoints
+/// without an entry use a multiplier of 1.0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WidthProfile {
+    contours: Vec<Vec<f32>>,
+}
+
+impl WidthProfile {
+    #[inline]
+    pub fn new() -> WidthProfile {
+        WidthProfile::default()
+    }
+
+    #[inline]
+    pub fn push_contour(&mut self, widths: Vec<f32>) {
+        self.contours.push(widths);
+    }
+
+    #[inline]
+    pub fn width_at(&self, contour_index: usize, endpoint_index: usize) -> f32 {
+        match self.contours.get(contour_index) {
+            Some(widths) => widths.get(endpoint_index).cloned().unwrap_or(1.0),
+            None => 1.0,
+        }
+    }
+
+    fn radii_for_contour(&self, contour_index: usize, contour: &Contour, radius: f32)
+                         -> Vec<f32> {
+        let endpoint_count = (0..contour.len()).filter(|&point_index| {
+            contour.point_is_endpoint(point_index)
+        }).count();
+        (0..endpoint_count).map(|endpoint_index| {
+            radius * self.width_at(contour_index, endpoint_index)
+        }).collect()
+    }
+}
+
 impl<'a> OutlineStrokeToFill<'a> {
     #[inline]
-    pub fn new(input: &Outline, style: StrokeStyle) -> OutlineStrokeToFill {
-        OutlineStrokeToFill { input, output: Outline::new(), style }
+    pub fn new(input: &'a Outline, style: StrokeStyle) -> OutlineStrokeToFill<'a> {
+        OutlineStrokeToFill {
+            input,
+            output: Outline::new(),
+            style,
+            width_profile: None,
+            transform: None,
+        }
+    }
+
+    /// Varies the stroke width along the path according to `profile`.
+    #[inline]
+    pub fn set_width_profile(&mut self, profile: &'a WidthProfile) {
+        self.width_profile = Some(profile);
+    }
+
+    /// Strokes the path as it appears under `transform`.
+    ///
+    /// When a transform is set, the resulting outline is already transformed and must not be
+    /// transformed again. Curve tolerances are chosen in the transformed space, so strokes stay
+    /// accurate under large or non-uniform scales.
+    #[inline]
+    pub fn set_transform(&mut self, transform: &Transform2DF, mode: StrokeTransform) {
+        self.transform = Some((*transform, mode));
     }
 
     pub fn offset(&mut self) {
+        let (input, tolerance) = match self.transform {
+            None => (Cow::Borrowed(self.input), TOLERANCE),
+            Some((_, StrokeTransform::Scaling)) => {
+                (Cow::Borrowed(self.input), TOLERANCE / self.max_scale_factor())
+            }
+            Some((ref transform, StrokeTransform::NonScaling)) => {
+                let mut input = (*self.input).clone();
+                input.transform(transform);
+                (Cow::Owned(input), TOLERANCE)
+            }
+        };
+
+        let radius = self.style.line_width * 0.5;
         let mut new_contours = vec![];
-        for input in &self.input.contours {
+        for (contour_index, input) in input.contours.iter().enumerate() {
             let closed = input.closed;
+            let radii = self.width_profile.map(|profile| {
+                profile.radii_for_contour(contour_index, input, radius)
+            });
+            let (first_radius, last_radius) = match radii {
+                Some(ref radii) if !radii.is_empty() => (radii[0], radii[radii.len() - 1]),
+                _ => (radius, radius),
+            };
+
+            // Zero-length subpaths are drawn as dots, as SVG and canvas require.
+            if input.is_degenerate(tolerance) {
+                if closed || input.len() > 1 {
+                    self.add_dot(&mut new_contours, input.position_of(0), first_radius);
+                }
+                continue;
+            }
+
             let mut stroker = ContourStrokeToFill::new(input,
                                                        Contour::new(),
-                                                       self.style.line_width * 0.5,
-                                                       self.style.line_join);
+                                                       radius,
+                                                       radii.as_ref().map(|radii| &radii[..]),
+                                                       self.style.line_join,
+                                                       tolerance);
 
             stroker.offset_forward();
             if closed {
-                self.push_stroked_contour(&mut new_contours, stroker, true);
+                self.push_stroked_contour(&mut new_contours, stroker, first_radius, true);
                 stroker = ContourStrokeToFill::new(input,
                                                    Contour::new(),
-                                                   self.style.line_width * 0.5,
-                                                   self.style.line_join);
+                                                   radius,
+                                                   radii.as_ref().map(|radii| &radii[..]),
+                                                   self.style.line_join,
+                                                   tolerance);
             } else {
-                self.add_cap(&mut stroker.output);
+                self.add_cap(&mut stroker, last_radius);
             }
 
             stroker.offset_backward();
             if !closed {
-                self.add_cap(&mut stroker.output);
+                self.add_cap(&mut stroker, first_radius);
             }
 
-            self.push_stroked_contour(&mut new_contours, stroker, closed);
+            self.push_stroked_contour(&mut new_contours, stroker, first_radius, closed);
+        }
+
+        if let Some((ref transform, StrokeTransform::Scaling)) = self.transform {
+            new_contours.iter_mut().for_each(|contour| contour.transform(transform));
         }
 
         let mut new_bounds = None;
@@ -93,80 +220,153 @@ impl<'a> OutlineStrokeToFill<'a> {
         self.output
     }
 
+    // Returns the largest factor by which the transform stretches any vector.
+    fn max_scale_factor(&self) -> f32 {
+        let matrix = match self.transform {
+            None => return 1.0,
+            Some((ref transform, _)) => transform.matrix,
+        };
+        let scale = largest_singular_value(&matrix);
+        if scale > f32::EPSILON { scale } else { 1.0 }
+    }
+
     fn push_stroked_contour(&mut self,
                             new_contours: &mut Vec<Contour>,
                             mut stroker: ContourStrokeToFill,
+                            radius: f32,
                             closed: bool) {
-        // Add join if necessary.
-        if closed && stroker.output.might_need_join(self.style.line_join) {
-            let (p1, p0) = (stroker.output.position_of(1), stroker.output.position_of(0));
-            let final_segment = LineSegment2F::new(p1, p0);
-            stroker.output.add_join(self.style.line_width * 0.5,
-                                    self.style.line_join,
-                                    stroker.input.position_of(0),
-                                    final_segment);
+        if stroker.output.is_empty() {
+            return;
+        }
+
+        // Add the join between the last segment and the first one, if necessary.
+        if closed {
+            if let (Some(first), Some(last)) = (stroker.first_tangent, stroker.last_tangent) {
+                let next_from = stroker.output.position_of(0);
+                stroker.output.add_join(radius,
+                                        self.style.line_join,
+                                        first.position,
+                                        last.tangent,
+                                        next_from,
+                                        first.tangent);
+            }
         }
 
         stroker.output.closed = true;
         new_contours.push(stroker.output);
     }
 
-    fn add_cap(&mut self, contour: &mut Contour) {
-        if self.style.line_cap == LineCap::Butt || contour.len() < 2 {
-            return
-        }
-
-        let width = self.style.line_width;
-        let (p0, p1) = (contour.position_of_last(2), contour.position_of_last(1));
-        let gradient = (p1 - p0).normalize();
+    // Adds a cap at the endpoint most recently reached by `stroker`.
+    //
+    // The cap already connects the two sides of the stroke, so the next offset segment must not
+    // be joined to the previous one.
+    fn add_cap(&mut self, stroker: &mut ContourStrokeToFill, radius: f32) {
+        let end = match stroker.last_tangent.take() {
+            None => return,
+            Some(end) => end,
+        };
 
+        // Offsetting by a positive distance places the stroke on the side of the path that
+        // `normal` points to, so the cap runs from that side around to the opposite one.
+        let normal = perpendicular(end.tangent);
+        let contour = &mut stroker.output;
         match self.style.line_cap {
-            LineCap::Butt => unreachable!(),
-
+            LineCap::Butt => {}
             LineCap::Square => {
-                let offset = gradient.scale(width * 0.5);
-
-                let p2 = p1 + offset;
-                let p3 = p2 + gradient.yx().scale_xy(Vector2F::new(-width, width));
-                let p4 = p3 - offset;
-
-                contour.push_endpoint(p2);
-                contour.push_endpoint(p3);
-                contour.push_endpoint(p4);
+                let extension = end.tangent.scale(radius);
+                contour.push_endpoint(end.position + normal.scale(radius) + extension);
+                contour.push_endpoint(end.position - normal.scale(radius) + extension);
+            }
+            LineCap::Round => {
+                let mut transform = Transform2DF::from_scale(Vector2F::splat(radius));
+                transform = transform.post_mul(&Transform2DF::from_translation(end.position));
+                let chord = LineSegment2F::new(normal, -normal);
+                contour.push_arc_from_unit_chord(&transform,
+                                                 chord,
+                                                 arc_direction_toward(normal, end.tangent));
             }
+        }
+    }
 
+    // Draws the cap shape for a zero-length subpath. Square caps are axis-aligned, because such a
+    // subpath has no direction.
+    fn add_dot(&mut self, new_contours: &mut Vec<Contour>, position: Vector2F, radius: f32) {
+        let mut contour = Contour::new();
+        match self.style.line_cap {
+            LineCap::Butt => return,
+            LineCap::Square => {
+                contour.push_endpoint(position + Vector2F::new(-radius, -radius));
+                contour.push_endpoint(position + Vector2F::new( radius, -radius));
+                contour.push_endpoint(position + Vector2F::new( radius,  radius));
+                contour.push_endpoint(position + Vector2F::new(-radius,  radius));
+            }
             LineCap::Round => {
-                let scale = Vector2F::splat(width * 0.5);
-                let offset = gradient.yx().scale_xy(Vector2F::new(-1.0, 1.0));
-                let mut transform = Transform2DF::from_scale(scale);
-                let translation = p1 + offset.scale(width * 0.5);
-                transform = transform.post_mul(&Transform2DF::from_translation(translation));
-                let chord = LineSegment2F::new(-offset, offset);
-                contour.push_arc_from_unit_chord(&transform, chord, ArcDirection::CW);
+                let mut transform = Transform2DF::from_scale(Vector2F::splat(radius));
+                transform = transform.post_mul(&Transform2DF::from_translation(position));
+                contour.push_ellipse(&transform);
             }
         }
+        contour.close();
+        new_contours.push(contour);
     }
 }
 
+// A point on the path, along with the unit tangent vector of the path there.
+#[derive(Clone, Copy, Debug)]
+struct PathTangent {
+    position: Vector2F,
+    tangent: Vector2F,
+}
+
 struct ContourStrokeToFill<'a> {
     input: &'a Contour,
     output: Contour,
     radius: f32,
+    radii: Option<&'a [f32]>,
     join: LineJoin,
+    tolerance: f32,
+    first_tangent: Option<PathTangent>,
+    last_tangent: Option<PathTangent>,
 }
 
 impl<'a> ContourStrokeToFill<'a> {
     #[inline]
-    fn new(input: &Contour, output: Contour, radius: f32, join: LineJoin) -> ContourStrokeToFill {
-        ContourStrokeToFill { input, output, radius, join }
+    fn new(input: &'a Contour,
+           output: Contour,
+           radius: f32,
+           radii: Option<&'a [f32]>,
+           join: LineJoin,
+           tolerance: f32)
+           -> ContourStrokeToFill<'a> {
+        ContourStrokeToFill {
+            input,
+            output,
+            radius,
+            radii,
+            join,
+            tolerance,
+            first_tangent: None,
+            last_tangent: None,
+        }
+    }
+
+    // Returns the radii at the start and end of the segment with the given index.
+    fn radii_of_segment(&self, segment_index: usize) -> (f32, f32) {
+        match self.radii {
+            None => (self.radius, self.radius),
+            Some(radii) => {
+                let radius_at = |index: usize| {
+                    radii.get(index % radii.len().max(1)).cloned().unwrap_or(self.radius)
+                };
+                (radius_at(segment_index), radius_at(segment_index + 1))
+            }
+        }
     }
 
     fn offset_forward(&mut self) {
         for (segment_index, segment) in self.input.iter().enumerate() {
-            // FIXME(pcwalton): We negate the radius here so that round end caps can be drawn
-            // clockwise. Of course, we should just implement anticlockwise arcs to begin with...
-            let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(-self.radius, join, &mut self.output);
+            let (radius_0, radius_1) = self.radii_of_segment(segment_index);
+            self.offset_segment(&segment, radius_0, radius_1, self.join);
         }
     }
 
@@ -175,39 +375,113 @@ impl<'a> ContourStrokeToFill<'a> {
             .input
             .iter()
             .map(|segment| segment.reversed())
+            .enumerate()
             .collect();
         segments.reverse();
-        for (segment_index, segment) in segments.iter().enumerate() {
-            // FIXME(pcwalton): We negate the radius here so that round end caps can be drawn
-            // clockwise. Of course, we should just implement anticlockwise arcs to begin with...
-            let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(-self.radius, join, &mut self.output);
+        for &(original_index, ref segment) in &segments {
+            let (radius_1, radius_0) = self.radii_of_segment(original_index);
+            self.offset_segment(segment, radius_0, radius_1, self.join);
         }
     }
+
+    // Offsets a single segment of the input, joining it to the previous one.
+    fn offset_segment(&mut self,
+                      segment: &Segment,
+                      start_distance: f32,
+                      end_distance: f32,
+                      join: LineJoin) {
+        // Zero-length segments have no direction, so they can't contribute anything but spikes.
+        if segment.is_degenerate(self.tolerance) {
+            return;
+        }
+
+        // The tangent flips direction at a cusp, so split there and join the halves with a round
+        // join, which is what sweeping the pen along the curve produces.
+        if let Some(t) = segment.cusp() {
+            let (before, after) = segment.split(t);
+            let mid_distance = start_distance + (end_distance - start_distance) * t;
+            self.offset_segment(&before, start_distance, mid_distance, join);
+            self.offset_segment(&after, mid_distance, end_distance, LineJoin::Round);
+            return;
+        }
+
+        let mut pieces = vec![];
+        if f32::abs(start_distance - end_distance) <= self.tolerance {
+            segment.offset(start_distance, self.tolerance, 0, &mut pieces);
+        } else {
+            segment.offset_tapered(start_distance, end_distance, self.tolerance, 0, &mut pieces);
+        }
+        if pieces.is_empty() {
+            return;
+        }
+
+        let (start_tangent, end_tangent) = segment.end_tangents();
+        let start = PathTangent { position: segment.baseline.from(), tangent: start_tangent };
+        match self.last_tangent {
+            None => self.first_tangent = Some(start),
+            Some(last) => {
+                self.output.add_join(start_distance,
+                                     join,
+                                     start.position,
+                                     last.tangent,
+                                     pieces[0].baseline.from(),
+                                     start.tangent);
+            }
+        }
+
+        // Adjacent pieces of the same segment meet, so only the first needs its start point.
+        for (piece_index, piece) in pieces.iter().enumerate() {
+            let mut flags = PushSegmentFlags::UPDATE_BOUNDS;
+            if piece_index == 0 {
+                flags.insert(PushSegmentFlags::INCLUDE_FROM_POINT);
+            }
+            self.output.push_segment(piece, flags);
+        }
+
+        self.last_tangent = Some(PathTangent {
+            position: segment.baseline.to(),
+            tangent: end_tangent,
+        });
+    }
 }
 
 trait Offset {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour);
-    fn add_to_contour(&self,
-                      distance: f32,
-                      join: LineJoin,
-                      join_point: Vector2F,
-                      contour: &mut Contour);
+    fn offset(&self, distance: f32, tolerance: f32, depth: u32, pieces: &mut Vec<Segment>);
+    fn offset_tapered(&self,
+                      start_distance: f32,
+                      end_distance: f32,
+                      tolerance: f32,
+                      depth: u32,
+                      pieces: &mut Vec<Segment>);
+    fn offset_as_line(&self, start_distance: f32, end_distance: f32) -> Segment;
+    fn is_degenerate(&self, tolerance: f32) -> bool;
+    fn cusp(&self) -> Option<f32>;
+    fn end_tangents(&self) -> (Vector2F, Vector2F);
     fn offset_once(&self, distance: f32) -> Self;
-    fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool;
+    fn error_is_within_tolerance(&self, other: &Segment, distance: f32, tolerance: f32) -> bool;
 }
 
 impl Offset for Segment {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour) {
-        let join_point = self.baseline.from();
-        if self.baseline.square_length() < TOLERANCE * TOLERANCE {
-            self.add_to_contour(distance, join, join_point, contour);
+    fn offset(&self, distance: f32, tolerance: f32, depth: u32, pieces: &mut Vec<Segment>) {
+        if self.is_degenerate(tolerance) {
+            return;
+        }
+
+        if self.is_line() {
+            pieces.push(Segment::line(self.baseline.offset(distance)));
             return;
         }
 
         let candidate = self.offset_once(distance);
-        if self.error_is_within_tolerance(&candidate, distance) {
-            candidate.add_to_contour(distance, join, join_point, contour);
+        if self.error_is_within_tolerance(&candidate, distance, tolerance) {
+            pieces.push(candidate);
+            return;
+        }
+
+        // If we still haven't converged, the curve is too tight for a curved approximation to
+        // make sense, so fall back to a line.
+        if depth >= MAX_SUBDIVISION_DEPTH {
+            pieces.push(self.offset_as_line(distance, distance));
             return;
         }
 
@@ -215,32 +489,131 @@ impl Offset for Segment {
         debug!("... PRE-SPLIT: {:?}", self);
         let (before, after) = self.split(0.5);
         debug!("... AFTER-SPLIT: {:?} {:?}", before, after);
-        before.offset(distance, join, contour);
-        after.offset(distance, join, contour);
-    }
-
-    fn add_to_contour(&self,
-                      distance: f32,
-                      join: LineJoin,
-                      join_point: Vector2F,
-                      contour: &mut Contour) {
-        // Add join if necessary.
-        if contour.might_need_join(join) {
-            let p3 = self.baseline.from();
-            let p4 = if self.is_line() {
-                self.baseline.to()
+        before.offset(distance, tolerance, depth + 1, pieces);
+        after.offset(distance, tolerance, depth + 1, pieces);
+    }
+
+    // Offsets a segment whose offset distance varies linearly from `start_distance` to
+    // `end_distance`. Curves are subdivided until they are flat and then offset as lines.
+    fn offset_tapered(&self,
+                      start_distance: f32,
+                      end_distance: f32,
+                      tolerance: f32,
+                      depth: u32,
+                      pieces: &mut Vec<Segment>) {
+        if self.is_degenerate(tolerance) {
+            return;
+        }
+
+        let is_flat = self.is_line() || self.to_cubic().as_cubic_segment().is_flat(tolerance);
+        if is_flat || depth >= MAX_SUBDIVISION_DEPTH {
+            pieces.push(self.offset_as_line(start_distance, end_distance));
+            return;
+        }
+
+        let mid_distance = (start_distance + end_distance) * 0.5;
+        let (before, after) = self.split(0.5);
+        before.offset_tapered(start_distance, mid_distance, tolerance, depth + 1, pieces);
+        after.offset_tapered(mid_distance, end_distance, tolerance, depth + 1, pieces);
+    }
+
+    // Offsets the endpoints along the curve's own normals, so that adjacent pieces of a
+    // subdivided curve meet exactly.
+    fn offset_as_line(&self, start_distance: f32, end_distance: f32) -> Segment {
+        let (start_tangent, end_tangent) = self.end_tangents();
+        let from = self.baseline.from() + perpendicular(start_tangent).scale(start_distance);
+        let to = self.baseline.to() + perpendicular(end_tangent).scale(end_distance);
+        Segment::line(LineSegment2F::new(from, to))
+    }
+
+    fn is_degenerate(&self, tolerance: f32) -> bool {
+        let from = self.baseline.from();
+        let square_tolerance = tolerance * tolerance;
+        let coincides = |point: Vector2F| (point - from).square_length() <= square_tolerance;
+        match self.kind {
+            SegmentKind::None => true,
+            SegmentKind::Line => coincides(self.baseline.to()),
+            SegmentKind::Quadratic => coincides(self.ctrl.from()) && coincides(self.baseline.to()),
+            SegmentKind::Cubic => {
+                coincides(self.ctrl.from()) && coincides(self.ctrl.to()) &&
+                    coincides(self.baseline.to())
+            }
+        }
+    }
+
+    // Returns the time of a cusp in the interior of this segment, if there is one.
+    //
+    // A cusp is a point at which the derivative vanishes and the tangent reverses direction. We
+    // find the minimum of the speed by sampling and then refine it with a golden-section search.
+    fn cusp(&self) -> Option<f32> {
+        if self.is_line() || self.is_none() {
+            return None;
+        }
+
+        let hull_length = if self.is_quadratic() {
+            (self.ctrl.from() - self.baseline.from()).length() +
+                (self.baseline.to() - self.ctrl.from()).length()
+        } else {
+            (self.ctrl.from() - self.baseline.from()).length() +
+                (self.ctrl.to() - self.ctrl.from()).length() +
+                (self.baseline.to() - self.ctrl.to()).length()
+        };
+        if hull_length <= f32::EPSILON {
+            return None;
+        }
+
+        let speed = |t: f32| self.derivative(t).length();
+        let mut best_index = 0;
+        for index in 1..(CUSP_SAMPLE_COUNT + 1) {
+            let t = index as f32 / CUSP_SAMPLE_COUNT as f32;
+            if speed(t) < speed(best_index as f32 / CUSP_SAMPLE_COUNT as f32) {
+                best_index = index;
+            }
+        }
+
+        let step = 1.0 / CUSP_SAMPLE_COUNT as f32;
+        let (mut lo, mut hi) = (f32::max(0.0, (best_index as f32 - 1.0) * step),
+                                f32::min(1.0, (best_index as f32 + 1.0) * step));
+        for _ in 0..CUSP_REFINEMENT_ITERATIONS {
+            let t0 = hi - (hi - lo) * GOLDEN_RATIO_RECIP;
+            let t1 = lo + (hi - lo) * GOLDEN_RATIO_RECIP;
+            if speed(t0) < speed(t1) {
+                hi = t1;
             } else {
-                // NB: If you change the representation of quadratic curves, you will need to
-                // change this.
-                self.ctrl.from()
-            };
+                lo = t0;
+            }
+        }
 
-            contour.add_join(distance, join, join_point, LineSegment2F::new(p4, p3));
+        let t = (lo + hi) * 0.5;
+        let is_interior = t > CUSP_T_EPSILON && t < 1.0 - CUSP_T_EPSILON;
+        if is_interior && speed(t) <= CUSP_SPEED_EPSILON * hull_length {
+            Some(t)
+        } else {
+            None
         }
+    }
 
-        // Push segment.
-        let flags = PushSegmentFlags::UPDATE_BOUNDS | PushSegmentFlags::INCLUDE_FROM_POINT;
-        contour.push_segment(self, flags);
+    // Returns the unit tangent vectors at the start and end of this segment.
+    //
+    // Unlike the derivative, this is well-defined when control points coincide with endpoints.
+    fn end_tangents(&self) -> (Vector2F, Vector2F) {
+        let (from, to) = (self.baseline.from(), self.baseline.to());
+        let (start_tangent, end_tangent) = if self.is_line() {
+            (to - from, to - from)
+        } else {
+            let cubic = self.to_cubic();
+            let (ctrl_0, ctrl_1) = (cubic.ctrl.from(), cubic.ctrl.to());
+            let start_tangent = [ctrl_0, ctrl_1, to].iter()
+                                                    .map(|&point| point - from)
+                                                    .find(|vector| !is_tiny(*vector))
+                                                    .unwrap_or(to - from);
+            let end_tangent = [ctrl_1, ctrl_0, from].iter()
+                                                    .map(|&point| to - point)
+                                                    .find(|vector| !is_tiny(*vector))
+                                                    .unwrap_or(to - from);
+            (start_tangent, end_tangent)
+        };
+        (normalize_or_zero(start_tangent), normalize_or_zero(end_tangent))
     }
 
     fn offset_once(&self, distance: f32) -> Segment {
@@ -251,6 +624,9 @@ impl Offset for Segment {
         if self.is_quadratic() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
             let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.baseline.to());
+            if is_tiny(segment_0.vector()) || is_tiny(segment_1.vector()) {
+                return self.offset_as_line(distance, distance);
+            }
             segment_0 = segment_0.offset(distance);
             segment_1 = segment_1.offset(distance);
             let ctrl = match segment_0.intersection_t(segment_1) {
@@ -263,9 +639,12 @@ impl Offset for Segment {
 
         debug_assert!(self.is_cubic());
 
-        if self.baseline.from() == self.ctrl.from() {
+        if is_tiny(self.ctrl.from() - self.baseline.from()) {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.to());
             let mut segment_1 = LineSegment2F::new(self.ctrl.to(), self.baseline.to());
+            if is_tiny(segment_0.vector()) || is_tiny(segment_1.vector()) {
+                return self.offset_as_line(distance, distance);
+            }
             segment_0 = segment_0.offset(distance);
             segment_1 = segment_1.offset(distance);
             let ctrl = match segment_0.intersection_t(segment_1) {
@@ -277,9 +656,12 @@ impl Offset for Segment {
             return Segment::cubic(baseline, ctrl);
         }
 
-        if self.ctrl.to() == self.baseline.to() {
+        if is_tiny(self.baseline.to() - self.ctrl.to()) {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
             let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.baseline.to());
+            if is_tiny(segment_1.vector()) {
+                return self.offset_as_line(distance, distance);
+            }
             segment_0 = segment_0.offset(distance);
             segment_1 = segment_1.offset(distance);
             let ctrl = match segment_0.intersection_t(segment_1) {
@@ -294,6 +676,19 @@ impl Offset for Segment {
         let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
         let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.ctrl.to());
         let mut segment_2 = LineSegment2F::new(self.ctrl.to(), self.baseline.to());
+        if is_tiny(segment_1.vector()) {
+            // The two control points coincide, so the middle leg has no direction. Join the outer
+            // legs directly instead.
+            segment_0 = segment_0.offset(distance);
+            segment_2 = segment_2.offset(distance);
+            let ctrl = match segment_0.intersection_t(segment_2) {
+                Some(t) => segment_0.sample(t),
+                None => segment_0.to().lerp(segment_2.from(), 0.5),
+            };
+            let baseline = LineSegment2F::new(segment_0.from(), segment_2.to());
+            return Segment::cubic(baseline, LineSegment2F::new(ctrl, ctrl));
+        }
+
         segment_0 = segment_0.offset(distance);
         segment_1 = segment_1.offset(distance);
         segment_2 = segment_2.offset(distance);
@@ -312,19 +707,18 @@ impl Offset for Segment {
         Segment::cubic(baseline, ctrl)
     }
 
-    fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool {
+    fn error_is_within_tolerance(&self, other: &Segment, distance: f32, tolerance: f32) -> bool {
         let (mut min, mut max) = (
-            f32::abs(distance) - TOLERANCE,
-            f32::abs(distance) + TOLERANCE,
+            f32::abs(distance) - tolerance,
+            f32::abs(distance) + tolerance,
         );
         min = if min <= 0.0 { 0.0 } else { min * min };
         max = if max <= 0.0 { 0.0 } else { max * max };
 
         for t_num in 0..(SAMPLE_COUNT + 1) {
             let t = t_num as f32 / SAMPLE_COUNT as f32;
-            // FIXME(pcwalton): Use signed distance!
             let (this_p, other_p) = (self.sample(t), other.sample(t));
-            let vector = this_p - other_p;
+            let vector = other_p - this_p;
             let square_distance = vector.square_length();
             debug!(
                 "this_p={:?} other_p={:?} vector={:?} sqdist={:?} min={:?} max={:?}",
@@ -333,6 +727,14 @@ impl Offset for Segment {
             if square_distance < min || square_distance > max {
                 return false;
             }
+
+            // The candidate must also lie on the correct side of the curve. Otherwise, it has
+            // folded over onto the other side, which shows up as a spike in the output.
+            let normal = perpendicular(self.derivative(t));
+            if f32::abs(distance) > tolerance && vector.dot(normal) * distance <= 0.0 &&
+                    !is_tiny(normal) {
+                return false;
+            }
         }
 
         return true;
@@ -342,32 +744,55 @@ impl Offset for Segment {
 }
 
 impl Contour {
-    fn might_need_join(&self, join: LineJoin) -> bool {
-        if self.len() < 2 {
-            false
-        } else {
-            match join {
-                LineJoin::Miter(_) | LineJoin::Round => true,
-                LineJoin::Bevel => false,
-            }
-        }
+    // Returns true if every point of this contour lies within `tolerance` of the first one.
+    fn is_degenerate(&self, tolerance: f32) -> bool {
+        let first = match self.points.first() {
+            None => return true,
+            Some(&first) => first,
+        };
+        self.points.iter().all(|&point| (point - first).square_length() <= tolerance * tolerance)
     }
 
+    // Connects the end of the previous offset segment to `next_from`, the start of the next one,
+    // around the point `join_point` on the original path. Tangents are unit vectors pointing in
+    // the direction of travel.
     fn add_join(&mut self,
                 distance: f32,
                 join: LineJoin,
                 join_point: Vector2F,
-                next_tangent: LineSegment2F) {
-        let (p0, p1) = (self.position_of_last(2), self.position_of_last(1));
-        let prev_tangent = LineSegment2F::new(p0, p1);
+                prev_tangent: Vector2F,
+                next_from: Vector2F,
+                next_tangent: Vector2F) {
+        let prev_to = match self.last_position() {
+            None => return,
+            Some(prev_to) => prev_to,
+        };
+
+        // Nothing to do if the path is smooth here.
+        let (turn, dot) = (prev_tangent.det(next_tangent), prev_tangent.dot(next_tangent));
+        if f32::abs(turn) <= JOIN_EPSILON && dot > 0.0 {
+            return;
+        }
+
+        // On the inside of a turn, the offset segments overlap. Pivoting around the original
+        // path point keeps the outline inside the stroke and the winding correct, whereas
+        // connecting the segments directly or mitering them produces spikes.
+        if turn * distance > 0.0 && f32::abs(turn) > JOIN_EPSILON {
+            self.push_endpoint(join_point);
+            return;
+        }
 
         match join {
             LineJoin::Bevel => {}
             LineJoin::Miter(miter_limit) => {
-                if let Some(prev_tangent_t) = prev_tangent.intersection_t(next_tangent) {
-                    let miter_endpoint = prev_tangent.sample(prev_tangent_t);
-                    let threshold = miter_limit * distance;
-                    if (miter_endpoint - join_point).square_length() <= threshold * threshold {
+                let prev_line = LineSegment2F::new(prev_to, prev_to + prev_tangent);
+                let next_line = LineSegment2F::new(next_from, next_from + next_tangent);
+                if let Some(prev_tangent_t) = prev_line.intersection_t(next_line) {
+                    let miter_endpoint = prev_line.sample(prev_tangent_t);
+                    let threshold = miter_limit * f32::abs(distance);
+                    if prev_tangent_t >= 0.0 &&
+                            (miter_endpoint - join_point).square_length() <=
+                            threshold * threshold {
                         self.push_endpoint(miter_endpoint);
                     }
                 }
@@ -376,15 +801,54 @@ impl Contour {
                 let scale = Vector2F::splat(distance.abs());
                 let mut transform = Transform2DF::from_scale(scale);
                 transform = transform.post_mul(&Transform2DF::from_translation(join_point));
-                let chord_from = (prev_tangent.to() - join_point).normalize();
-                let chord_to = (next_tangent.to() - join_point).normalize();
+                let chord_from = normalize_or_zero(prev_to - join_point);
+                let chord_to = normalize_or_zero(next_from - join_point);
+                if is_tiny(chord_from) || is_tiny(chord_to) {
+                    return;
+                }
                 let chord = LineSegment2F::new(chord_from, chord_to);
-                self.push_arc_from_unit_chord(&transform, chord, ArcDirection::CW);
+                let direction = arc_direction_toward(chord_from, prev_tangent);
+                self.push_arc_from_unit_chord(&transform, chord, direction);
             }
         }
     }
 }
 
+// Returns the unit normal that `LineSegment2F::offset()` moves along for a positive distance.
+#[inline]
+fn perpendicular(tangent: Vector2F) -> Vector2F {
+    normalize_or_zero(Vector2F::new(-tangent.y(), tangent.x()))
+}
+
+// Returns the direction of an arc that starts at the unit vector `start` and initially heads
+// toward `heading`.
+//
+// `ArcDirection::CW` sweeps from the positive X axis toward the positive Y axis.
+#[inline]
+fn arc_direction_toward(start: Vector2F, heading: Vector2F) -> ArcDirection {
+    if start.det(heading) >= 0.0 { ArcDirection::CW } else { ArcDirection::CCW }
+}
+
+#[inline]
+fn normalize_or_zero(vector: Vector2F) -> Vector2F {
+    if is_tiny(vector) { Vector2F::default() } else { vector.normalize() }
+}
+
+#[inline]
+fn is_tiny(vector: Vector2F) -> bool {
+    vector.square_length() <= f32::EPSILON * f32::EPSILON
+}
+
+// The largest singular value of a 2x2 matrix; i.e. the length of the longest vector the matrix
+// maps a unit vector to.
+fn largest_singular_value(matrix: &Matrix2x2F) -> f32 {
+    let (a, b, c, d) = (matrix.m11(), matrix.m12(), matrix.m21(), matrix.m22());
+    let sum_of_squares = a * a + b * b + c * c + d * d;
+    let det = matrix.det();
+    let discriminant = f32::max(sum_of_squares * sum_of_squares - 4.0 * det * det, 0.0);
+    f32::sqrt((sum_of_squares + f32::sqrt(discriminant)) * 0.5)
+}
+
 impl Default for StrokeStyle {
     #[inline]
     fn default() -> StrokeStyle {
@@ -405,3 +869,315 @@ impl Default for LineJoin {
     #[inline]
     fn default() -> LineJoin { LineJoin::Miter(10.0) }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline};
+    use crate::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle, StrokeTransform};
+    use crate::stroke::{WidthProfile, largest_singular_value};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::{Matrix2x2F, Transform2DF};
+    use pathfinder_geometry::vector::Vector2F;
+    use quickcheck;
+    use std::f32::consts::PI;
+
+    const FLATTENING_STEPS: u32 = 16;
+    const ENVELOPE_STEPS: u32 = 128;
+    const MAX_POLYLINE_POINTS: usize = 8;
+
+    fn stroke(outline: &Outline, line_width: f32, line_cap: LineCap, line_join: LineJoin)
+              -> Outline {
+        let style = StrokeStyle { line_width, line_cap, line_join };
+        let mut stroke_to_fill = OutlineStrokeToFill::new(outline, style);
+        stroke_to_fill.offset();
+        stroke_to_fill.into_outline()
+    }
+
+    fn outline_from_contour(contour: Contour) -> Outline {
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    // Approximates every contour of the outline with a polygon.
+    fn flatten(outline: &Outline) -> Vec<Vec<Vector2F>> {
+        outline.contours().iter().map(|contour| {
+            let mut polygon = vec![];
+            for segment in contour.iter() {
+                for step in 0..FLATTENING_STEPS {
+                    polygon.push(segment.sample(step as f32 / FLATTENING_STEPS as f32));
+                }
+            }
+            if let Some(last_position) = contour.last_position() {
+                polygon.push(last_position);
+            }
+            polygon
+        }).collect()
+    }
+
+    fn area(outline: &Outline) -> f32 {
+        flatten(outline).iter().map(|polygon| {
+            let mut area = 0.0;
+            for (index, &point) in polygon.iter().enumerate() {
+                area += point.det(polygon[(index + 1) % polygon.len()]);
+            }
+            area * 0.5
+        }).sum()
+    }
+
+    fn distance_to_line_segment(point: Vector2F, from: Vector2F, to: Vector2F) -> f32 {
+        let vector = to - from;
+        let square_length = vector.square_length();
+        if square_length == 0.0 {
+            return (point - from).length();
+        }
+        let t = ((point - from).dot(vector) / square_length).clamp(0.0, 1.0);
+        (point - from.lerp(to, t)).length()
+    }
+
+    // Checks that every point of the stroked outline lies within the stroke's envelope; that is,
+    // that there are no spikes.
+    fn is_within_envelope(input: &Outline, stroked: &Outline, radius: f32) -> bool {
+        let mut centerline = vec![];
+        for contour in input.contours() {
+            for segment in contour.iter() {
+                let steps = if segment.is_line() { 1 } else { ENVELOPE_STEPS };
+                for step in 0..(steps + 1) {
+                    centerline.push(segment.sample(step as f32 / steps as f32));
+                }
+            }
+        }
+
+        let slack = 0.1 + radius * 0.02;
+        flatten(stroked).iter().all(|polygon| {
+            polygon.iter().all(|&point| {
+                assert!(!point.x().is_nan() && !point.y().is_nan());
+                let distance = centerline.windows(2).map(|points| {
+                    distance_to_line_segment(point, points[0], points[1])
+                }).fold(f32::INFINITY, f32::min);
+                distance <= radius + slack
+            })
+        })
+    }
+
+    fn approx_eq_relative(a: f32, b: f32, epsilon: f32) -> bool {
+        f32::abs(a - b) <= epsilon * f32::max(f32::abs(a), f32::abs(b))
+    }
+
+    #[test]
+    fn test_line_area() {
+        quickcheck::quickcheck(prop_line_area as fn(f32, f32, f32, f32, f32, u8) -> bool);
+
+        fn prop_line_area(x: f32, y: f32, angle: f32, length: f32, width: f32, cap: u8) -> bool {
+            let (length, width) = (1.0 + f32::abs(length), 0.5 + f32::abs(width) * 0.2);
+            let direction = Vector2F::new(f32::cos(angle), f32::sin(angle));
+            let from = Vector2F::new(x, y);
+
+            let mut contour = Contour::new();
+            contour.push_endpoint(from);
+            contour.push_endpoint(from + direction.scale(length));
+            let input = outline_from_contour(contour);
+
+            let (cap, expected_area) = match cap % 3 {
+                0 => (LineCap::Butt, length * width),
+                1 => (LineCap::Square, (length + width) * width),
+                _ => (LineCap::Round, length * width + PI * width * width * 0.25),
+            };
+            let stroked = stroke(&input, width, cap, LineJoin::Miter(10.0));
+            approx_eq_relative(f32::abs(area(&stroked)), expected_area, 0.01)
+        }
+    }
+
+    #[test]
+    fn test_zero_length_subpaths() {
+        quickcheck::quickcheck(prop_zero_length_subpaths as fn(f32, f32, f32, bool) -> bool);
+
+        fn prop_zero_length_subpaths(x: f32, y: f32, width: f32, closed: bool) -> bool {
+            let (position, width) = (Vector2F::new(x, y), 0.5 + f32::abs(width) * 0.2);
+
+            let mut contour = Contour::new();
+            contour.push_endpoint(position);
+            contour.push_endpoint(position);
+            if closed {
+                contour.close();
+            }
+            let input = outline_from_contour(contour);
+
+            let radius = width * 0.5;
+            let butt = stroke(&input, width, LineCap::Butt, LineJoin::Bevel);
+            let square = stroke(&input, width, LineCap::Square, LineJoin::Bevel);
+            let round = stroke(&input, width, LineCap::Round, LineJoin::Round);
+            butt.contours().is_empty() &&
+                approx_eq_relative(f32::abs(area(&square)), width * width, 0.001) &&
+                approx_eq_relative(f32::abs(area(&round)), PI * radius * radius, 0.01) &&
+                (round.bounds().origin().lerp(round.bounds().lower_right(), 0.5) - position)
+                    .length() <= 0.001
+        }
+    }
+
+    #[test]
+    fn test_cubic_stroke_envelope() {
+        quickcheck::quickcheck(prop_cubic_stroke_envelope as fn(Vec<f32>, f32, bool) -> bool);
+
+        fn prop_cubic_stroke_envelope(coords: Vec<f32>, width: f32, round: bool) -> bool {
+            if coords.len() < 8 {
+                return true;
+            }
+            let point = |index: usize| Vector2F::new(coords[index * 2], coords[index * 2 + 1]);
+            let width = 0.5 + f32::abs(width) * 0.2;
+
+            let mut contour = Contour::new();
+            contour.push_endpoint(point(0));
+            contour.push_cubic(point(1), point(2), point(3));
+            let input = outline_from_contour(contour);
+
+            let (cap, join) = if round {
+                (LineCap::Round, LineJoin::Round)
+            } else {
+                (LineCap::Butt, LineJoin::Bevel)
+            };
+            let stroked = stroke(&input, width, cap, join);
+            is_within_envelope(&input, &stroked, width * 0.5)
+        }
+    }
+
+    #[test]
+    fn test_polyline_stroke_envelope() {
+        quickcheck::quickcheck(prop_polyline_stroke_envelope as
+                               fn(Vec<(i8, i8)>, f32, bool, bool) -> bool);
+
+        // Small integer coordinates make coincident points and collinear segments likely.
+        fn prop_polyline_stroke_envelope(points: Vec<(i8, i8)>, width: f32, round: bool,
+                                         closed: bool)
+                                         -> bool {
+            if points.is_empty() {
+                return true;
+            }
+            let width = 0.5 + f32::abs(width) * 0.05;
+
+            let mut contour = Contour::new();
+            for &(x, y) in points.iter().take(MAX_POLYLINE_POINTS) {
+                contour.push_endpoint(Vector2F::new((x % 8) as f32, (y % 8) as f32));
+            }
+            if closed {
+                contour.close();
+            }
+            let input = outline_from_contour(contour);
+
+            let (cap, join) = if round {
+                (LineCap::Round, LineJoin::Round)
+            } else {
+                (LineCap::Butt, LineJoin::Bevel)
+            };
+            let stroked = stroke(&input, width, cap, join);
+            is_within_envelope(&input, &stroked, width * 0.5)
+        }
+    }
+
+    #[test]
+    fn test_cusp() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_cubic(Vector2F::new(100.0, 100.0),
+                           Vector2F::new(0.0, 100.0),
+                           Vector2F::new(100.0, 0.0));
+        let input = outline_from_contour(contour);
+
+        for &join in &[LineJoin::Round, LineJoin::Bevel, LineJoin::Miter(4.0)] {
+            let stroked = stroke(&input, 10.0, LineCap::Butt, join);
+            assert!(is_within_envelope(&input, &stroked, 5.0 * 4.0));
+            assert!(is_within_envelope(&input, &stroke(&input, 10.0, LineCap::Round, join), 5.0));
+        }
+    }
+
+    #[test]
+    fn test_tight_inner_join() {
+        // A hairpin turn much tighter than the stroke width.
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(100.0, 0.0));
+        contour.push_endpoint(Vector2F::new(0.0, 1.0));
+        let input = outline_from_contour(contour);
+
+        let stroked = stroke(&input, 20.0, LineCap::Butt, LineJoin::Bevel);
+        assert!(is_within_envelope(&input, &stroked, 10.0));
+    }
+
+    fn assert_rect_approx_eq(rect: RectF, origin: Vector2F, lower_right: Vector2F) {
+        assert!((rect.origin() - origin).length() < 0.001, "{:?}", rect);
+        assert!((rect.lower_right() - lower_right).length() < 0.001, "{:?}", rect);
+    }
+
+    #[test]
+    fn test_tapered_width_profile() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(100.0, 0.0));
+        let input = outline_from_contour(contour);
+
+        // The width narrows from 10 to 2 along the line, so the stroke is a trapezoid.
+        let mut profile = WidthProfile::new();
+        profile.push_contour(vec![1.0, 0.2]);
+        let style = StrokeStyle { line_width: 10.0, ..StrokeStyle::default() };
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&input, style);
+        stroke_to_fill.set_width_profile(&profile);
+        stroke_to_fill.offset();
+        let stroked = stroke_to_fill.into_outline();
+
+        assert!(approx_eq_relative(f32::abs(area(&stroked)), 600.0, 0.001));
+        assert_rect_approx_eq(stroked.bounds(),
+                              Vector2F::new(0.0, -5.0),
+                              Vector2F::new(100.0, 5.0));
+        assert!(is_within_envelope(&input, &stroked, 5.0));
+        assert!(!is_within_envelope(&input, &stroked, 4.0));
+
+        // Contours and endpoints without an entry keep the full width.
+        assert_eq!(profile.width_at(0, 2), 1.0);
+        assert_eq!(profile.width_at(1, 0), 1.0);
+    }
+
+    #[test]
+    fn test_non_scaling_stroke() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(0.0, 10.0));
+        let input = outline_from_contour(contour);
+
+        // Stretching a vertical line sideways widens a scaling stroke, but not a non-scaling one.
+        let transform = Transform2DF::from_scale(Vector2F::new(4.0, 1.0));
+        let style = StrokeStyle { line_width: 2.0, ..StrokeStyle::default() };
+        let stroke_with = |mode| {
+            let mut stroke_to_fill = OutlineStrokeToFill::new(&input, style);
+            stroke_to_fill.set_transform(&transform, mode);
+            stroke_to_fill.offset();
+            stroke_to_fill.into_outline()
+        };
+
+        let scaling = stroke_with(StrokeTransform::Scaling);
+        assert_rect_approx_eq(scaling.bounds(), Vector2F::new(-4.0, 0.0), Vector2F::new(4.0, 10.0));
+        assert!(approx_eq_relative(f32::abs(area(&scaling)), 80.0, 0.001));
+
+        let non_scaling = stroke_with(StrokeTransform::NonScaling);
+        assert_rect_approx_eq(non_scaling.bounds(),
+                              Vector2F::new(-1.0, 0.0),
+                              Vector2F::new(1.0, 10.0));
+        assert!(approx_eq_relative(f32::abs(area(&non_scaling)), 20.0, 0.001));
+    }
+
+    #[test]
+    fn test_largest_singular_value() {
+        let scale = Matrix2x2F::from_scale(Vector2F::new(3.0, -2.0));
+        assert!(approx_eq_relative(largest_singular_value(&scale), 3.0, 0.0001));
+
+        // Rotation doesn't change how far the matrix stretches.
+        let rotated = Matrix2x2F::from_rotation(PI / 3.0).post_mul(&scale);
+        assert!(approx_eq_relative(largest_singular_value(&rotated), 3.0, 0.0001));
+
+        // A shear stretches by the golden ratio.
+        let shear = Matrix2x2F::row_major(1.0, 1.0, 0.0, 1.0);
+        let golden_ratio = (1.0 + f32::sqrt(5.0)) * 0.5;
+        assert!(approx_eq_relative(largest_singular_value(&shear), golden_ratio, 0.0001));
+
+        assert_eq!(largest_singular_value(&Matrix2x2F::from_scale(Vector2F::default())), 0.0);
+    }
+}
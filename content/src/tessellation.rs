@@ -0,0 +1,331 @@
+// pathfinder/content/src/tessellation.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversion of filled outlines to triangle meshes, for consumers that want geometry rather
+//! than pixels.
+
+use crate::fill::FillRule;
+use crate::outline::Outline;
+use pathfinder_geometry::vector::Vector2F;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// An indexed triangle mesh. Every three consecutive indices form a triangle.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TriangleMesh {
+    pub vertices: Vec<Vector2F>,
+    pub indices: Vec<u32>,
+}
+
+/// Converts the interior of an outline to triangles.
+///
+/// All contours are treated as closed, as they are when filling. The outline is flattened first,
+/// then cut into horizontal slabs at every vertex and edge crossing, and the inside spans of each
+/// slab become trapezoids. This handles self-intersecting and overlapping contours.
+pub struct OutlineTessellator<'a> {
+    input: &'a Outline,
+    fill_rule: FillRule,
+    tolerance: f32,
+    output: TriangleMesh,
+    vertex_indices: HashMap<(u32, u32), u32>,
+}
+
+impl<'a> OutlineTessellator<'a> {
+    #[inline]
+    pub fn new(input: &'a Outline, fill_rule: FillRule, tolerance: f32) -> OutlineTessellator<'a> {
+        OutlineTessellator {
+            input,
+            fill_rule,
+            tolerance,
+            output: TriangleMesh::default(),
+            vertex_indices: HashMap::new(),
+        }
+    }
+
+    pub fn tessellate(&mut self) {
+        let mut edges = vec![];
+        push_outline_edges(self.input, self.tolerance, 0, &mut edges);
+        sweep(edges, |y0, y1, crossings| self.tessellate_trapezoids(y0, y1, crossings));
+    }
+
+    #[inline]
+    pub fn into_mesh(self) -> TriangleMesh {
+        self.output
+    }
+
+    fn tessellate_trapezoids(&mut self, y0: f32, y1: f32, crossings: &[Crossing]) {
+        let mut winding = 0;
+        let mut span_start = None;
+        for crossing in crossings {
+            let was_inside = self.fill_rule.is_inside(winding);
+            winding += crossing.winding;
+            match (was_inside, self.fill_rule.is_inside(winding)) {
+                (false, true) => span_start = Some((crossing.x0, crossing.x1)),
+                (true, false) => {
+                    if let Some((left_x0, left_x1)) = span_start.take() {
+                        self.push_trapezoid(y0, y1, left_x0, crossing.x0, left_x1, crossing.x1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn push_trapezoid(&mut self,
+                      y0: f32,
+                      y1: f32,
+                      left_x0: f32,
+                      right_x0: f32,
+                      left_x1: f32,
+                      right_x1: f32) {
+        let upper_left = Vector2F::new(left_x0, y0);
+        let upper_right = Vector2F::new(right_x0, y0);
+        let lower_right = Vector2F::new(right_x1, y1);
+        let lower_left = Vector2F::new(left_x1, y1);
+        if right_x0 > left_x0 {
+            self.push_triangle(upper_left, upper_right, lower_right);
+        }
+        if right_x1 > left_x1 {
+            self.push_triangle(upper_left, lower_right, lower_left);
+        }
+    }
+
+    fn push_triangle(&mut self, a: Vector2F, b: Vector2F, c: Vector2F) {
+        for &vertex in &[a, b, c] {
+            let index = self.vertex_index(vertex);
+            self.output.indices.push(index);
+        }
+    }
+
+    // Vertices are shared between adjacent trapezoids, because edges are always evaluated the
+    // same way at a given Y coordinate.
+    fn vertex_index(&mut self, vertex: Vector2F) -> u32 {
+        let vertices = &mut self.output.vertices;
+        let key = (vertex.x().to_bits(), vertex.y().to_bits());
+        *self.vertex_indices.entry(key).or_insert_with(|| {
+            vertices.push(vertex);
+            (vertices.len() - 1) as u32
+        })
+    }
+}
+
+// Appends the non-horizontal edges of the flattened outline, treating every contour as closed.
+pub(crate) fn push_outline_edges(outline: &Outline,
+                                 tolerance: f32,
+                                 source: u32,
+                                 edges: &mut Vec<Edge>) {
+    for polyline in outline.flatten(tolerance) {
+        let points = &polyline.points;
+        for (point_index, &from) in points.iter().enumerate() {
+            let to = points[(point_index + 1) % points.len()];
+            if let Some(edge) = Edge::new(from, to, source) {
+                edges.push(edge);
+            }
+        }
+    }
+}
+
+// Cuts the plane into horizontal slabs at every vertex and edge crossing, and calls
+// `process_slab` with the top and bottom of each slab and the edges spanning it, sorted from left
+// to right. Within a slab, the left-to-right order of the edges is fixed.
+pub(crate) fn sweep<F>(mut edges: Vec<Edge>, mut process_slab: F)
+                       where F: FnMut(f32, f32, &[Crossing]) {
+    edges.sort_by(|a, b| compare_f32(a.from.y(), b.from.y()));
+
+    let mut ys: Vec<f32> = edges.iter().flat_map(|edge| vec![edge.from.y(), edge.to.y()])
+                                       .collect();
+    ys.sort_by(|a, b| compare_f32(*a, *b));
+    ys.dedup();
+
+    let (mut active_edges, mut next_edge_index) = (vec![], 0);
+    for slab in ys.windows(2) {
+        let (y0, y1) = (slab[0], slab[1]);
+        active_edges.retain(|edge: &Edge| edge.to.y() > y0);
+        while next_edge_index < edges.len() && edges[next_edge_index].from.y() <= y0 {
+            active_edges.push(edges[next_edge_index]);
+            next_edge_index += 1;
+        }
+
+        // Edges may cross inside the slab, so split it at the crossings first. Two edges cross
+        // exactly when they leave the slab in the opposite order to the one they enter it in, so
+        // sort the edges by where they enter and then insertion sort them by where they leave:
+        // each swap is one crossing. This takes O(n log n + k) time for n edges and k crossings,
+        // rather than testing every pair.
+        active_edges.sort_by(|a, b| compare_f32(a.x_at(y0), b.x_at(y0)));
+        let mut xs: Vec<(f32, f32)> = active_edges.iter().map(|edge| {
+            (edge.x_at(y0), edge.x_at(y1))
+        }).collect();
+        let mut piece_ys = vec![y0, y1];
+        for index in 1..xs.len() {
+            let mut position = index;
+            while position > 0 && xs[position - 1].1 > xs[position].1 {
+                let (a, b) = (xs[position - 1], xs[position]);
+                let (d0, d1) = (a.0 - b.0, a.1 - b.1);
+                if d0 * d1 < 0.0 {
+                    piece_ys.push(y0 + (y1 - y0) * d0 / (d0 - d1));
+                }
+                xs.swap(position - 1, position);
+                position -= 1;
+            }
+        }
+        piece_ys.sort_by(|a, b| compare_f32(*a, *b));
+        piece_ys.dedup();
+
+        for piece in piece_ys.windows(2) {
+            let (y0, y1) = (piece[0], piece[1]);
+            let mut crossings: Vec<Crossing> = active_edges.iter().map(|edge| {
+                Crossing {
+                    x0: edge.x_at(y0),
+                    x1: edge.x_at(y1),
+                    winding: edge.winding,
+                    source: edge.source,
+                }
+            }).collect();
+            crossings.sort_by(|a, b| compare_f32(a.x0 + a.x1, b.x0 + b.x1));
+            process_slab(y0, y1, &crossings);
+        }
+    }
+}
+
+// A non-horizontal edge of a flattened outline, oriented downward. `source` identifies the
+// outline it came from, for operations that combine several.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Edge {
+    from: Vector2F,
+    to: Vector2F,
+    winding: i32,
+    source: u32,
+}
+
+// Where an edge crosses the top and bottom of a slab.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Crossing {
+    pub(crate) x0: f32,
+    pub(crate) x1: f32,
+    pub(crate) winding: i32,
+    pub(crate) source: u32,
+}
+
+impl Edge {
+    fn new(from: Vector2F, to: Vector2F, source: u32) -> Option<Edge> {
+        if from.y() < to.y() {
+            Some(Edge { from, to, winding: 1, source })
+        } else if from.y() > to.y() {
+            Some(Edge { from: to, to: from, winding: -1, source })
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn x_at(&self, y: f32) -> f32 {
+        if y <= self.from.y() {
+            self.from.x()
+        } else if y >= self.to.y() {
+            self.to.x()
+        } else {
+            let t = (y - self.from.y()) / (self.to.y() - self.from.y());
+            self.from.x() + (self.to.x() - self.from.x()) * t
+        }
+    }
+}
+
+#[inline]
+fn compare_f32(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fill::FillRule;
+    use crate::outline::{Contour, Outline};
+    use crate::tessellation::{OutlineTessellator, TriangleMesh};
+    use pathfinder_geometry::transform2d::Transform2DF;
+    use pathfinder_geometry::vector::Vector2F;
+    use std::f32::consts::PI;
+
+    fn tessellate(outline: &Outline, fill_rule: FillRule) -> TriangleMesh {
+        let mut tessellator = OutlineTessellator::new(outline, fill_rule, 0.01);
+        tessellator.tessellate();
+        tessellator.into_mesh()
+    }
+
+    fn area(mesh: &TriangleMesh) -> f32 {
+        mesh.indices.chunks(3).map(|triangle| {
+            let a = mesh.vertices[triangle[0] as usize];
+            let b = mesh.vertices[triangle[1] as usize];
+            let c = mesh.vertices[triangle[2] as usize];
+            f32::abs((b - a).det(c - a)) * 0.5
+        }).sum()
+    }
+
+    fn push_square(outline: &mut Outline, origin: Vector2F, size: f32) {
+        let mut contour = Contour::new();
+        contour.push_endpoint(origin);
+        contour.push_endpoint(origin + Vector2F::new(size, 0.0));
+        contour.push_endpoint(origin + Vector2F::new(size, size));
+        contour.push_endpoint(origin + Vector2F::new(0.0, size));
+        contour.close();
+        outline.push_contour(contour);
+    }
+
+    #[test]
+    fn test_overlapping_squares() {
+        let mut outline = Outline::new();
+        push_square(&mut outline, Vector2F::new(0.0, 0.0), 10.0);
+        push_square(&mut outline, Vector2F::new(5.0, 5.0), 10.0);
+
+        assert!(f32::abs(area(&tessellate(&outline, FillRule::Winding)) - 175.0) < 0.01);
+        assert!(f32::abs(area(&tessellate(&outline, FillRule::EvenOdd)) - 150.0) < 0.01);
+    }
+
+    #[test]
+    fn test_self_intersecting_star() {
+        // A pentagram; the center pentagon has winding number 2.
+        let mut contour = Contour::new();
+        for point_index in 0..5 {
+            let angle = (point_index * 2) as f32 * PI * 2.0 / 5.0;
+            contour.push_endpoint(Vector2F::new(f32::cos(angle), f32::sin(angle)).scale(10.0));
+        }
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let winding_area = area(&tessellate(&outline, FillRule::Winding));
+        let even_odd_area = area(&tessellate(&outline, FillRule::EvenOdd));
+        let pentagon_area = winding_area - even_odd_area;
+        let inner_radius = 10.0 * f32::cos(PI * 2.0 / 5.0) / f32::cos(PI / 5.0);
+        let expected_pentagon_area = 2.5 * inner_radius * inner_radius * f32::sin(PI * 2.0 / 5.0);
+        assert!(f32::abs(pentagon_area - expected_pentagon_area) < 0.01);
+    }
+
+    #[test]
+    fn test_staircase_of_squares() {
+        // Each square overlaps the previous one, so every slab has crossing edges.
+        let mut outline = Outline::new();
+        for square_index in 0..20 {
+            push_square(&mut outline, Vector2F::splat(square_index as f32), 10.0);
+        }
+        let mesh = tessellate(&outline, FillRule::Winding);
+        assert!(f32::abs(area(&mesh) - (100.0 + 19.0 * 19.0)) < 0.01);
+    }
+
+    #[test]
+    fn test_circle() {
+        let mut contour = Contour::new();
+        contour.push_ellipse(&Transform2DF::from_scale(Vector2F::splat(10.0)));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let mesh = tessellate(&outline, FillRule::Winding);
+        assert!(f32::abs(area(&mesh) - PI * 100.0) < 1.0);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.length() <= 10.0 + 0.01));
+    }
+}
@@ -0,0 +1,151 @@
+// pathfinder/content/src/trim.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Trimming outlines to a range of distances along them, like the "Trim Paths" modifier of
+//! animation tools.
+
+use crate::outline::{Contour, Outline};
+use crate::segment::{Segment, SegmentKind};
+
+/// Keeps the parts of an outline between two distances along it.
+///
+/// Distances are measured from the start of the first contour, with the contours laid end to
+/// end. Closed contours include their closing segments, and any contour that is cut becomes open.
+pub struct OutlineTrim<'a> {
+    input: &'a Outline,
+    output: Outline,
+    start: f32,
+    end: f32,
+}
+
+impl<'a> OutlineTrim<'a> {
+    #[inline]
+    pub fn new(input: &'a Outline, start: f32, end: f32) -> OutlineTrim<'a> {
+        OutlineTrim { input, output: Outline::new(), start, end }
+    }
+
+    pub fn trim(&mut self) {
+        let mut contour_start = 0.0;
+        for contour in &self.input.contours {
+            let length = contour.length();
+            let (start, end) = (self.start - contour_start, self.end - contour_start);
+            contour_start += length;
+
+            if end <= 0.0 || start >= length || start >= end {
+                continue;
+            }
+            if start <= 0.0 && end >= length {
+                self.output.push_contour(contour.clone());
+                continue;
+            }
+            self.output.push_contour(trim_contour(contour, f32::max(start, 0.0), end));
+        }
+    }
+
+    #[inline]
+    pub fn into_outline(self) -> Outline {
+        self.output
+    }
+}
+
+fn trim_contour(input: &Contour, start: f32, end: f32) -> Contour {
+    let mut output = Contour::new();
+    let mut segment_start = 0.0;
+    for segment in input.iter() {
+        if segment_start >= end {
+            break;
+        }
+        let segment_end = segment_start + segment.arc_length();
+        if segment_end > start {
+            // Cut off the part after `end` first, so that distances from the start of the
+            // segment stay valid.
+            let mut piece = segment;
+            if segment_end > end {
+                piece = piece.split(piece.time_for_distance(end - segment_start)).0;
+            }
+            if segment_start < start {
+                piece = piece.split(piece.time_for_distance(start - segment_start)).1;
+            }
+            push_segment(&mut output, &piece);
+        }
+        segment_start = segment_end;
+    }
+    output
+}
+
+fn push_segment(contour: &mut Contour, segment: &Segment) {
+    if contour.is_empty() {
+        contour.push_endpoint(segment.baseline.from());
+    }
+    match segment.kind {
+        SegmentKind::None => {}
+        SegmentKind::Line => contour.push_endpoint(segment.baseline.to()),
+        SegmentKind::Quadratic => {
+            contour.push_quadratic(segment.ctrl.from(), segment.baseline.to())
+        }
+        SegmentKind::Cubic => {
+            contour.push_cubic(segment.ctrl.from(), segment.ctrl.to(), segment.baseline.to())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline};
+    use crate::trim::OutlineTrim;
+    use pathfinder_geometry::transform2d::Transform2DF;
+    use pathfinder_geometry::vector::Vector2F;
+    use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        f32::abs(a - b) < 0.01
+    }
+
+    #[test]
+    fn test_trim_square() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 10.0));
+        contour.push_endpoint(Vector2F::new(0.0, 10.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        assert!(approx_eq(outline.length(), 40.0));
+
+        let mut trim = OutlineTrim::new(&outline, 5.0, 35.0);
+        trim.trim();
+        let trimmed = trim.into_outline();
+        assert!(approx_eq(trimmed.length(), 30.0));
+
+        let contour = &trimmed.contours()[0];
+        assert!(!contour.is_closed());
+        assert_eq!(contour.position_of(0), Vector2F::new(5.0, 0.0));
+        assert_eq!(contour.last_position(), Some(Vector2F::new(0.0, 5.0)));
+    }
+
+    #[test]
+    fn test_trim_circle() {
+        let mut contour = Contour::new();
+        contour.push_ellipse(&Transform2DF::from_scale(Vector2F::splat(10.0)));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        assert!(approx_eq(outline.length(), 20.0 * PI));
+
+        // The ellipse starts 45 degrees before the positive X axis, so a quarter of it ends 45
+        // degrees after.
+        let mut trim = OutlineTrim::new(&outline, 0.0, 5.0 * PI);
+        trim.trim();
+        let trimmed = trim.into_outline();
+        assert!(approx_eq(trimmed.length(), 5.0 * PI));
+        let end = trimmed.contours()[0].last_position().unwrap();
+        assert!((end - Vector2F::new(1.0, 1.0).scale(10.0 * FRAC_1_SQRT_2)).length() < 0.01);
+    }
+}
@@ -22,7 +22,7 @@ use crate::device::{GroundProgram, GroundVertexArray};
 use crate::ui::{DemoUIModel, DemoUIPresenter, ScreenshotInfo, ScreenshotType, UIAction};
 use crate::window::{Event, Keycode, SVGPath, Window, WindowSize};
 use clap::{App, Arg};
-use pathfinder_export::{Export, FileFormat};
+use pathfinder_export::{Export, FileFormat, SvgOptions};
 use pathfinder_geometry::vector::{Vector2F, Vector2I};
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::transform2d::Transform2DF;
@@ -36,14 +36,14 @@ use pathfinder_renderer::gpu::renderer::{RenderStats, RenderTime, Renderer};
 use pathfinder_renderer::options::{BuildOptions, RenderTransform};
 use pathfinder_renderer::post::STEM_DARKENING_FACTORS;
 use pathfinder_renderer::scene::Scene;
-use pathfinder_svg::BuiltSVG;
+use pathfinder_svg::{BuildOptions as SVGBuildOptions, BuiltSVG};
 use pathfinder_ui::{MousePosition, UIEvent};
 use std::fs::File;
 use std::io::{BufWriter, Read};
 use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
-use usvg::{Options as UsvgOptions, Tree};
+use usvg::Options as UsvgOptions;
 
 #[cfg(any(not(target_os = "macos"), feature = "pf-gl"))]
 use pathfinder_gl::GLDevice as DeviceImpl;
@@ -151,8 +151,7 @@ impl<W> DemoApp<W> where W: Window {
         // Set up the executor.
         let executor = DemoExecutor::new(options.jobs);
 
-        let mut built_svg = load_scene(resources, &options.input_path);
-        let message = get_svg_building_message(&built_svg);
+        let (mut scene, message) = load_scene(resources, &options.input_path);
 
         let viewport = window.viewport(options.mode.view(0));
         let dest_framebuffer = DestFramebuffer::Default {
@@ -165,11 +164,10 @@ impl<W> DemoApp<W> where W: Window {
         };
 
         let renderer = Renderer::new(device, resources, dest_framebuffer, render_options);
-        let scene_metadata = SceneMetadata::new_clipping_view_box(&mut built_svg.scene,
-                                                                  viewport.size());
+        let scene_metadata = SceneMetadata::new_clipping_view_box(&mut scene, viewport.size());
         let camera = Camera::new(options.mode, scene_metadata.view_box, viewport.size());
 
-        let scene_proxy = SceneProxy::from_scene(built_svg.scene, executor);
+        let scene_proxy = SceneProxy::from_scene(scene, executor);
 
         let ground_program = GroundProgram::new(&renderer.device, resources);
         let ground_vertex_array = GroundVertexArray::new(&renderer.device,
@@ -438,17 +436,17 @@ impl<W> DemoApp<W> where W: Window {
                 }
 
                 Event::OpenSVG(ref svg_path) => {
-                    let mut built_svg = load_scene(self.window.resource_loader(), svg_path);
-                    self.ui_model.message = get_svg_building_message(&built_svg);
+                    let (mut scene, message) = load_scene(self.window.resource_loader(), svg_path);
+                    self.ui_model.message = message;
 
                     let viewport_size = self.window.viewport(self.ui_model.mode.view(0)).size();
                     self.scene_metadata =
-                        SceneMetadata::new_clipping_view_box(&mut built_svg.scene, viewport_size);
+                        SceneMetadata::new_clipping_view_box(&mut scene, viewport_size);
                     self.camera = Camera::new(self.ui_model.mode,
                                               self.scene_metadata.view_box,
                                               viewport_size);
 
-                    self.scene_proxy.replace_scene(built_svg.scene);
+                    self.scene_proxy.replace_scene(scene);
 
                     self.dirty = true;
                 }
@@ -552,7 +550,8 @@ impl<W> DemoApp<W> where W: Window {
             Some(ScreenshotInfo { kind: ScreenshotType::SVG, path }) => {
                 // FIXME(pcwalton): This won't work on Android.
                 let mut writer = BufWriter::new(File::create(path).unwrap());
-                self.scene_proxy.copy_scene().export(&mut writer, FileFormat::SVG).unwrap();
+                let format = FileFormat::SVG(SvgOptions::default());
+                self.scene_proxy.copy_scene().export(&mut writer, format).unwrap();
             }
         }
     }
@@ -756,7 +755,9 @@ pub enum UIVisibility {
     All,
 }
 
-fn load_scene(resource_loader: &dyn ResourceLoader, input_path: &SVGPath) -> BuiltSVG {
+// Returns the scene along with a message to show about it. If the SVG can't be read, the scene
+// is empty and the message says why.
+fn load_scene(resource_loader: &dyn ResourceLoader, input_path: &SVGPath) -> (Scene, String) {
     let mut data;
     match *input_path {
         SVGPath::Default => data = resource_loader.slurp(DEFAULT_SVG_VIRTUAL_PATH).unwrap(),
@@ -767,7 +768,13 @@ fn load_scene(resource_loader: &dyn ResourceLoader, input_path: &SVGPath) -> Bui
         }
     };
 
-    BuiltSVG::from_tree(Tree::from_data(&data, &UsvgOptions::default()).unwrap())
+    match BuiltSVG::from_data(&data, &UsvgOptions::default(), SVGBuildOptions::default()) {
+        Ok(built_svg) => {
+            let message = get_svg_building_message(&built_svg);
+            (built_svg.scene, message)
+        }
+        Err(error) => (Scene::new(), format!("Error: The SVG couldn't be read: {}.", error)),
+    }
 }
 
 fn center_of_window(window_size: &WindowSize) -> Vector2F {
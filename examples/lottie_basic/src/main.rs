@@ -8,16 +8,124 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! Experimental example for reading Lottie animations. This is very incomplete.
+//! Experimental example for playing Lottie animations. This is very incomplete.
+//!
+//! Space pauses and resumes playback, the left and right arrow keys step by a frame while paused,
+//! and the up and down arrow keys change the speed.
 
+use pathfinder_content::color::ColorF;
+use pathfinder_geometry::vector::Vector2I;
+use pathfinder_gl::{GLDevice, GLVersion};
+use pathfinder_gpu::resources::FilesystemResourceLoader;
 use pathfinder_lottie::Lottie;
+use pathfinder_lottie::player::LottiePlayer;
+use pathfinder_renderer::concurrent::rayon::RayonExecutor;
+use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
+use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererOptions};
+use pathfinder_renderer::gpu::renderer::Renderer;
+use pathfinder_renderer::options::BuildOptions;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::video::GLProfile;
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
+use std::time::Instant;
 
 fn main() {
     let path = env::args().skip(1).next().unwrap();
     let file = BufReader::new(File::open(path).unwrap());
     let lottie = Lottie::from_reader(file).unwrap();
-    println!("{:#?}", lottie);
+
+    // Set up SDL2.
+    let sdl_context = sdl2::init().unwrap();
+    let video = sdl_context.video().unwrap();
+
+    // Make sure we have at least a GL 3.0 context. Pathfinder requires this.
+    let gl_attributes = video.gl_attr();
+    gl_attributes.set_context_profile(GLProfile::Core);
+    gl_attributes.set_context_version(3, 3);
+
+    // Open a window the size of the animation.
+    let window_size = Vector2I::new(lottie.width.ceil() as i32, lottie.height.ceil() as i32);
+    let window = video.window("Lottie example", window_size.x() as u32, window_size.y() as u32)
+                      .opengl()
+                      .build()
+                      .unwrap();
+
+    // Create the GL context, and make it current.
+    let gl_context = window.gl_create_context().unwrap();
+    gl::load_with(|name| video.gl_get_proc_address(name) as *const _);
+    window.gl_make_current(&gl_context).unwrap();
+
+    // Create a Pathfinder renderer.
+    let mut renderer = Renderer::new(GLDevice::new(GLVersion::GL3, 0),
+                                     &FilesystemResourceLoader::locate(),
+                                     DestFramebuffer::full_window(window_size),
+                                     RendererOptions { background_color: Some(ColorF::white()) });
+
+    // Start playing.
+    let mut player = LottiePlayer::new(lottie, SceneProxy::new(RayonExecutor));
+    player.play();
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut last_tick = Instant::now();
+    let mut needs_redraw = true;
+    loop {
+        // Sleep until the next frame is due, or indefinitely while paused, waking early for input.
+        let first_event = match player.time_until_next_frame() {
+            Some(wait) => {
+                let millis = (wait.as_micros() + 999) / 1000;
+                event_pump.wait_event_timeout(millis.max(1) as u32)
+            }
+            None => Some(event_pump.wait_event()),
+        };
+
+        let events: Vec<Event> = first_event.into_iter().chain(event_pump.poll_iter()).collect();
+        for event in events {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    if player.is_playing() {
+                        player.pause()
+                    } else {
+                        player.play()
+                    }
+                }
+                Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
+                    let frame = player.current_frame().floor() - 1.0;
+                    player.seek(frame);
+                    needs_redraw = true;
+                }
+                Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
+                    let frame = player.current_frame().floor() + 1.0;
+                    player.seek(frame);
+                    needs_redraw = true;
+                }
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                    let speed = player.speed() * 2.0;
+                    player.set_speed(speed)
+                }
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                    let speed = player.speed() * 0.5;
+                    player.set_speed(speed)
+                }
+                Event::Window { win_event: WindowEvent::Exposed, .. } => needs_redraw = true,
+                _ => {}
+            }
+        }
+
+        // Only draw when the frame actually changed.
+        let now = Instant::now();
+        if player.tick(now - last_tick) {
+            needs_redraw = true;
+        }
+        last_tick = now;
+
+        if needs_redraw {
+            player.scene_proxy().build_and_render(&mut renderer, BuildOptions::default());
+            window.gl_swap_window();
+            needs_redraw = false;
+        }
+    }
 }
@@ -21,9 +21,11 @@ use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::video::GLProfile;
 use pathfinder_renderer::scene::Scene;
-use pathfinder_swf::{draw_paths_into_scene, process_swf_tags};
+use pathfinder_swf::process_swf_tags;
 use std::env;
 use std::fs::read;
+use std::thread;
+use std::time::{Duration, Instant};
 use pathfinder_geometry::transform2d::Transform2DF;
 
 fn main() {
@@ -106,31 +108,48 @@ fn main() {
         RendererOptions { background_color: Some(stage.background_color()) }
     );
     // Clear to swf stage background color.
-    let mut scene = Scene::new();
-    scene.set_view_box(RectF::new(
+    let view_box = RectF::new(
         Vector2F::default(),
         Vector2F::new(
             stage.width() as f32 * device_pixel_ratio,
             stage.height() as f32 * device_pixel_ratio)
-    ));
-    draw_paths_into_scene(&library, &mut scene);
-
-    // Render the canvas to screen.
-    let scene = SceneProxy::from_scene(scene, RayonExecutor);
+    );
     let mut build_options = BuildOptions::default();
     let scale_transform = Transform2DF::from_scale(
         Vector2F::new(device_pixel_ratio, device_pixel_ratio)
     );
     build_options.transform = RenderTransform::Transform2D(scale_transform);
-    scene.build_and_render(&mut renderer, build_options);
 
-    window.gl_swap_window();
-    // Wait for a keypress.
+    // Play the frames of the movie in a loop, at its frame rate.
+    let scene = SceneProxy::new(RayonExecutor);
+    let frame_duration = Duration::from_secs_f32(1.0 / stage.frame_rate().max(1.0));
+    let mut frames = stage.frames(&library);
+    let mut next_frame_time = Instant::now();
     let mut event_pump = sdl_context.event_pump().unwrap();
     loop {
-        match event_pump.wait_event() {
-            Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
-            _ => {}
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+                _ => {}
+            }
         }
+
+        if Instant::now() >= next_frame_time {
+            let frame = match frames.next() {
+                Some(frame) => frame,
+                None if stage.frame_count() > 0 => {
+                    frames = stage.frames(&library);
+                    frames.next().unwrap()
+                }
+                None => Scene::new(),
+            };
+            scene.replace_scene(frame);
+            scene.set_view_box(view_box);
+            scene.build_and_render(&mut renderer, build_options.clone());
+            window.gl_swap_window();
+            next_frame_time += frame_duration;
+        }
+
+        thread::sleep(next_frame_time.saturating_duration_since(Instant::now()));
     }
 }
@@ -5,17 +5,25 @@ use std::io::{self, Write};
 use std::fmt;
 
 mod pdf;
+mod raster;
+mod svg;
 use pdf::Pdf;
 
+pub use raster::PngOptions;
+pub use svg::SvgOptions;
+
 pub enum FileFormat {
     /// Scalable Vector Graphics
-    SVG,
+    SVG(SvgOptions),
     
     /// Portable Document Format
     PDF,
     
     /// PostScript
     PS,
+
+    /// Portable Network Graphics, rendered on the CPU
+    PNG(PngOptions),
 }
 
 pub trait Export {
@@ -25,40 +33,55 @@ pub trait Export {
 impl Export for Scene {
     fn export<W: Write>(&self, writer: &mut W, format: FileFormat) -> io::Result<()> {
         match format {
-            FileFormat::SVG => export_svg(self, writer),
+            FileFormat::SVG(ref options) => svg::export_svg(self, writer, options),
             FileFormat::PDF => export_pdf(self, writer),
-            FileFormat::PS => export_ps(self, writer)
+            FileFormat::PS => export_ps(self, writer),
+            FileFormat::PNG(ref options) => raster::export_png(self, writer, options),
         }
     }
 }
 
-fn export_svg<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
-    let view_box = scene.view_box();
-    writeln!(
-        writer,
-        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
-        view_box.origin().x(),
-        view_box.origin().y(),
-        view_box.size().x(),
-        view_box.size().y()
-    )?;
-    for (paint, outline, name) in scene.paths() {
-        write!(writer, "    <path")?;
-        if !name.is_empty() {
-            write!(writer, " id=\"{}\"", name)?;
+/// Entries for the document information dictionary of a PDF.
+#[derive(Clone, Debug, Default)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// The application that created the original content
+    pub creator: Option<String>,
+}
+
+/// Export scenes as the pages of one PDF document.
+/// Each page has the size of its scene's view box.
+pub fn export_pdf_pages<'a, W, I>(scenes: I, metadata: &PdfMetadata, writer: &mut W)
+    -> io::Result<()> where W: Write, I: IntoIterator<Item=&'a Scene>
+{
+    let mut pdf = Pdf::new();
+    let entries = [
+        ("Title", &metadata.title),
+        ("Author", &metadata.author),
+        ("Subject", &metadata.subject),
+        ("Keywords", &metadata.keywords),
+        ("Creator", &metadata.creator),
+    ];
+    for &(key, value) in entries.iter() {
+        if let Some(value) = value {
+            pdf.set_info(key, value);
         }
-        writeln!(
-            writer,
-            " fill=\"{:?}\" d=\"{:?}\" />",
-            paint.color, outline
-        )?;
     }
-    writeln!(writer, "</svg>")?;
-    Ok(())
+
+    for scene in scenes {
+        add_pdf_page(&mut pdf, scene);
+    }
+    pdf.write_to(writer)
 }
-    
+
 fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
-    let mut pdf = Pdf::new();
+    export_pdf_pages(Some(scene), &PdfMetadata::default(), writer)
+}
+
+fn add_pdf_page(pdf: &mut Pdf, scene: &Scene) {
     let view_box = scene.view_box();
     pdf.add_page(view_box.size());
     
@@ -86,7 +109,7 @@ fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
                         let p = segment.baseline.to();
                         let c1 = Vector2F::splat(2./3.) * c + Vector2F::splat(1./3.) * current;
                         let c2 = Vector2F::splat(2./3.) * c + Vector2F::splat(1./3.) * p;
-                        pdf.cubic_to(c1, c2, p);
+                        pdf.cubic_to(tr(c1), tr(c2), tr(p));
                     }
                     SegmentKind::Cubic => pdf.cubic_to(tr(segment.ctrl.from()), tr(segment.ctrl.to()), tr(segment.baseline.to()))
                 }
@@ -100,7 +123,6 @@ fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         // closes implicitly
         pdf.fill();
     }
-    pdf.write_to(writer)
 }
     
 fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
@@ -111,17 +133,15 @@ fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         }
     }
 
+    // The view box is mapped to (0, 0)-(width, height), flipped to have y pointing up,
+    // like the page of a PDF export.
     let view_box = scene.view_box();
+    let size = view_box.size();
     writeln!(writer, "%!PS-Adobe-3.0 EPSF-3.0")?;
-    writeln!(writer, "%%BoundingBox: {:.0} {:.0}",
-        P(view_box.origin()),
-        P(view_box.size()),
-    )?;
-    writeln!(writer, "%%HiResBoundingBox: {} {}",
-        P(view_box.origin()),
-        P(view_box.size()),
-    )?;
-    writeln!(writer, "0 {} translate", view_box.size().y())?;
+    writeln!(writer, "%%BoundingBox: 0 0 {} {}", size.x().ceil(), size.y().ceil())?;
+    writeln!(writer, "%%HiResBoundingBox: 0 0 {}", P(size))?;
+    writeln!(writer, "{} translate", P(Vector2F::new(-view_box.origin().x(),
+                                                     view_box.max_y())))?;
     writeln!(writer, "1 -1 scale")?;
     
     for (paint, outline, name) in scene.paths() {
@@ -172,3 +192,211 @@ fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
 }
 
 
+
+#[cfg(test)]
+mod test {
+    use super::{export_pdf_pages, Export, FileFormat, PdfMetadata};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{PathObject, Scene};
+
+    fn scene(size: Vector2F, colors: &[ColorU]) -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), size));
+        for &color in colors {
+            let mut contour = Contour::new();
+            contour.push_endpoint(Vector2F::new(0.0, 0.0));
+            contour.push_endpoint(Vector2F::new(10.0, 0.0));
+            contour.push_endpoint(Vector2F::new(10.0, 10.0));
+            contour.close();
+            let mut outline = Outline::new();
+            outline.push_contour(contour);
+            let paint = scene.push_paint(&Paint { color });
+            scene.push_path(PathObject::new(outline, paint, String::new()));
+        }
+        scene
+    }
+
+    #[test]
+    fn test_pdf_pages() {
+        let translucent = ColorU::new(255, 0, 0, 128);
+        let scenes = [
+            scene(Vector2F::new(100.0, 50.0), &[translucent, ColorU::black()]),
+            scene(Vector2F::new(20.0, 30.0), &[ColorU::black()]),
+        ];
+        let metadata = PdfMetadata {
+            title: Some("Test (1)".to_owned()),
+            author: Some("Köln".to_owned()),
+            ..PdfMetadata::default()
+        };
+        let mut output = vec![];
+        export_pdf_pages(scenes.iter(), &metadata, &mut output).unwrap();
+        let text = String::from_utf8_lossy(&output);
+
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/MediaBox [0 0 100 50]"));
+        assert!(text.contains("/MediaBox [0 0 20 30]"));
+        assert_eq!(text.matches("/Type /ExtGState").count(), 2);
+        assert!(text.contains("/GA128 << /Type /ExtGState /ca 0.5019608 >>"));
+        assert!(text.contains("/Title (Test \\(1\\))"));
+        assert!(text.contains("/Author <FEFF004B00F6006C006E>"));
+
+        // every xref entry points at its object
+        let startxref: usize = text.rsplit("startxref\n").next().unwrap()
+            .lines().next().unwrap().parse().unwrap();
+        assert!(output[startxref..].starts_with(b"xref\n"));
+        let xref = String::from_utf8_lossy(&output[startxref..]);
+        let entries = xref.lines().skip(3).take_while(|l| l.ends_with(" n "));
+        for (idx, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(output[offset..].starts_with(format!("{} 0 obj\n", idx + 1).as_bytes()));
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum Command {
+        Move(Vector2F),
+        Line(Vector2F),
+        Cubic(Vector2F, Vector2F, Vector2F),
+        Close,
+    }
+
+    // A view box away from the origin, with a line, a quadratic and a cubic segment in a closed
+    // contour and an open contour after it.
+    fn segment_scene() -> Scene {
+        let v = Vector2F::new;
+        let mut closed = Contour::new();
+        closed.push_endpoint(v(0.0, 40.0));
+        closed.push_endpoint(v(40.0, 40.0));
+        closed.push_quadratic(v(60.0, 50.0), v(40.0, 80.0));
+        closed.push_cubic(v(20.0, 90.0), v(0.0, 70.0), v(0.0, 60.0));
+        closed.close();
+        let mut open = Contour::new();
+        open.push_endpoint(v(-10.0, 35.0));
+        open.push_endpoint(v(70.0, 100.0));
+
+        let mut outline = Outline::new();
+        outline.push_contour(closed);
+        outline.push_contour(open);
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(v(-20.0, 30.0), v(100.0, 80.0)));
+        let paint = scene.push_paint(&Paint { color: ColorU::black() });
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+        scene
+    }
+
+    // The geometry of `segment_scene`, with the quadratic raised to a cubic.
+    fn segment_commands() -> Vec<Command> {
+        let v = Vector2F::new;
+        vec![
+            Command::Move(v(0.0, 40.0)),
+            Command::Line(v(40.0, 40.0)),
+            Command::Cubic(v(160.0 / 3.0, 140.0 / 3.0), v(160.0 / 3.0, 60.0), v(40.0, 80.0)),
+            Command::Cubic(v(20.0, 90.0), v(0.0, 70.0), v(0.0, 60.0)),
+            Command::Line(v(0.0, 40.0)),
+            Command::Close,
+            Command::Move(v(-10.0, 35.0)),
+            Command::Line(v(70.0, 100.0)),
+        ]
+    }
+
+    // Parses path construction operators, passing their points through `transform`.
+    // `names` are the operators for move, line, cubic and close, in that order.
+    fn parse_path<F>(content: &str, names: [&str; 4], transform: F) -> Vec<Command>
+        where F: Fn(Vector2F) -> Vector2F
+    {
+        let mut commands = vec![];
+        let mut operands: Vec<f32> = vec![];
+        let lines = content.lines().filter(|l| !l.starts_with('%'));
+        for token in lines.flat_map(str::split_whitespace) {
+            if let Ok(number) = token.parse() {
+                operands.push(number);
+                continue;
+            }
+            let point = |i: usize| transform(Vector2F::new(operands[2 * i], operands[2 * i + 1]));
+            if token == names[0] {
+                commands.push(Command::Move(point(0)));
+            } else if token == names[1] {
+                commands.push(Command::Line(point(0)));
+            } else if token == names[2] {
+                commands.push(Command::Cubic(point(0), point(1), point(2)));
+            } else if token == names[3] {
+                commands.push(Command::Close);
+            }
+            operands.clear();
+        }
+        commands
+    }
+
+    fn assert_same_geometry(actual: &[Command], expected: &[Command]) {
+        let close = |a: Vector2F, b: Vector2F| (a - b).length() < 1e-3;
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (&a, &e) in actual.iter().zip(expected) {
+            let same = match (a, e) {
+                (Command::Move(a), Command::Move(e)) | (Command::Line(a), Command::Line(e)) => {
+                    close(a, e)
+                }
+                (Command::Cubic(a0, a1, a2), Command::Cubic(e0, e1, e2)) => {
+                    close(a0, e0) && close(a1, e1) && close(a2, e2)
+                }
+                (Command::Close, Command::Close) => true,
+                _ => false,
+            };
+            assert!(same, "expected {:?}, got {:?}", e, a);
+        }
+    }
+
+    #[test]
+    fn test_pdf_geometry() {
+        let scene = segment_scene();
+        let mut output = vec![];
+        scene.export(&mut output, FileFormat::PDF).unwrap();
+
+        let text = String::from_utf8_lossy(&output);
+        assert!(text.contains("/MediaBox [0 0 100 80]"));
+
+        let start = output.windows(7).position(|w| w == b"stream\n").unwrap() + 7;
+        let end = output.windows(10).position(|w| w == b"\nendstream").unwrap();
+        let content = inflate::inflate_bytes_zlib(&output[start..end]).unwrap();
+        let content = String::from_utf8(content).unwrap();
+
+        // page space has its origin at the bottom left of the view box
+        let to_scene = |p: Vector2F| Vector2F::new(p.x() - 20.0, 110.0 - p.y());
+        let commands = parse_path(&content, ["m", "l", "c", "h"], to_scene);
+        assert_same_geometry(&commands, &segment_commands());
+    }
+
+    #[test]
+    fn test_ps_geometry() {
+        let scene = segment_scene();
+        let mut output = vec![];
+        scene.export(&mut output, FileFormat::PS).unwrap();
+        let text = String::from_utf8(output).unwrap();
+
+        assert!(text.contains("%%BoundingBox: 0 0 100 80\n"));
+        assert!(text.contains("%%HiResBoundingBox: 0 0 100 80\n"));
+
+        // apply the translate and scale to get to page space, then map back like for PDF
+        let operands = |operator: &str| {
+            let line = text.lines().find(|l| l.ends_with(operator)).unwrap();
+            let args: Vec<f32> = line.split_whitespace().take(2)
+                .map(|s| s.parse().unwrap())
+                .collect();
+            Vector2F::new(args[0], args[1])
+        };
+        let translation = operands(" translate");
+        let scale = operands(" scale");
+
+        let to_scene = |p: Vector2F| {
+            let page = p * scale + translation;
+            assert!(page.x() >= 0.0 && page.x() <= 100.0 && page.y() >= 0.0 && page.y() <= 80.0);
+            Vector2F::new(page.x() - 20.0, 110.0 - page.y())
+        };
+        let names = ["moveto", "lineto", "curveto", "closepath"];
+        let commands = parse_path(&text, names, to_scene);
+        assert_same_geometry(&commands, &segment_commands());
+    }
+}
@@ -55,6 +55,12 @@ pub struct Pdf {
     objects: Vec<PdfObject>,
     page_size: Option<Vector2F>,
     compression: Option<Compression>,
+    /// The fill alpha currently set on the page
+    alpha: u8,
+    /// The fill alphas the current page needs graphics states for
+    page_alphas: Vec<u8>,
+    /// Entries of the document information dictionary
+    info: Vec<(&'static str, String)>,
 }
 
 impl Default for Pdf {
@@ -84,7 +90,10 @@ impl Pdf {
                 },
             ],
             page_size: None,
-            compression: Some(Compression::Fast)
+            compression: Some(Compression::Fast),
+            alpha: 255,
+            page_alphas: Vec::new(),
+            info: vec![("Producer", "Pathfinder".to_owned())],
         }
     }
 
@@ -98,6 +107,12 @@ impl Pdf {
         self.objects.len()
     }
 
+    /// Set an entry of the document information dictionary, like `Title` or `Author`
+    pub fn set_info(&mut self, key: &'static str, value: &str) {
+        self.info.retain(|&(k, _)| k != key);
+        self.info.push((key, value.to_owned()));
+    }
+
     /// Set the color for all subsequent drawing operations
     #[inline]
     pub fn set_fill_color(&mut self, color: ColorU) {
@@ -107,6 +122,15 @@ impl Pdf {
             norm(color.g),
             norm(color.b)
         ).unwrap();
+
+        // alpha needs a graphics state, one per distinct value on the page
+        if color.a != self.alpha {
+            if !self.page_alphas.contains(&color.a) {
+                self.page_alphas.push(color.a);
+            }
+            writeln!(self.page_buffer, "/GA{} gs", color.a).unwrap();
+            self.alpha = color.a;
+        }
     }
 
     /// Move to a new page in the PDF document
@@ -121,6 +145,8 @@ impl Pdf {
         self.page_buffer
             .extend("/DeviceRGB cs /DeviceRGB CS\n1 j 1 J\n".bytes());
         self.page_size = Some(size);
+        self.alpha = 255;
+        self.page_alphas.clear();
     }
     
     pub fn move_to(&mut self, p: Vector2F)  {
@@ -155,13 +181,13 @@ impl Pdf {
             )
             .into_bytes();
             page.extend_from_slice(&compressed);
-            page.extend(b"endstream\n");
+            page.extend(b"\nendstream\n");
             page
         } else {
             let mut page = Vec::new();
             page.extend(format!("<< /Length {} >>\nstream\n", self.page_buffer.len()).bytes());
             page.extend(&self.page_buffer);
-            page.extend(b"endstream\n");
+            page.extend(b"\nendstream\n");
             page
         };
 
@@ -178,6 +204,15 @@ impl Pdf {
             write!(page_object, "/XObject {} 0 R ", idx+1).unwrap();
         }
 
+        if !self.page_alphas.is_empty() {
+            page_object.extend(b"/ExtGState <<\n");
+            for &alpha in &self.page_alphas {
+                writeln!(page_object, "/GA{} << /Type /ExtGState /ca {} >>",
+                    alpha, f32::from(alpha) / 255.0).unwrap();
+            }
+            page_object.extend(b">>\n");
+        }
+
         write!(page_object,
             " >>\n \
                 /MediaBox [0 0 {} {}]\n \
@@ -197,10 +232,18 @@ impl Pdf {
             self.end_page();
         }
 
+        // Create the document information dictionary
+        let mut info_object = b"<< ".to_vec();
+        for (key, value) in &self.info {
+            writeln!(info_object, "/{} {}", key, pdf_string(value)).unwrap();
+        }
+        info_object.extend(b">>\n");
+        let info_object_id = self.add_object(info_object, false, false);
+
         // Write out each object
         for (idx, obj) in self.objects.iter_mut().enumerate().skip(2) {
             obj.offset = Some(out.pos());
-            write!(out, "{} 0 obj\n", idx+1)?;
+            writeln!(out, "{} 0 obj", idx+1)?;
             out.write_all(&obj.contents)?;
             out.write_all(b"endobj\n")?;
         }
@@ -209,8 +252,8 @@ impl Pdf {
         self.objects[1].offset = Some(out.pos());
         out.write_all(b"2 0 obj\n")?;
         out.write_all(b"<< /Type /Pages\n")?;
-        write!(out,
-            "/Count {}\n",
+        writeln!(out,
+            "/Count {}",
             self.objects.iter().filter(|o| o.is_page).count()
         )?;
         out.write_all(b"/Kids [")?;
@@ -224,18 +267,19 @@ impl Pdf {
         out.write_all(b"1 0 obj\n<< /Type /Catalog\n/Pages 2 0 R >>\nendobj\n")?;
 
         // Write the cross-reference table
-        let startxref = out.pos() + 1; // NOTE: apparently there's some 1-based indexing??
+        let startxref = out.pos();
         out.write_all(b"xref\n")?;
-        write!(out, "0 {}\n", self.objects.len() + 1)?;
+        writeln!(out, "0 {}", self.objects.len() + 1)?;
         out.write_all(b"0000000000 65535 f \n")?;
 
         for obj in &self.objects {
-            write!(out, "{:010} 00000 f \n", obj.offset.unwrap())?;
+            writeln!(out, "{:010} 00000 n ", obj.offset.unwrap())?;
         }
 
         // Write the document trailer
         out.write_all(b"trailer\n")?;
-        write!(out, "<< /Size {}\n", self.objects.len())?;
+        writeln!(out, "<< /Size {}", self.objects.len() + 1)?;
+        writeln!(out, "/Info {} 0 R", info_object_id)?;
         out.write_all(b"/Root 1 0 R >>\n")?;
 
         // Write the offset to the xref table
@@ -247,3 +291,31 @@ impl Pdf {
         Ok(())
     }
 }
+
+/// Encode a text string as a PDF string object.
+/// ASCII stays a literal string, anything else becomes UTF-16BE with a byte order mark.
+fn pdf_string(text: &str) -> String {
+    if text.is_ascii() {
+        let mut out = String::from("(");
+        for c in text.chars() {
+            match c {
+                '\\' | '(' | ')' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                '\r' => out.push_str("\\r"),
+                '\n' => out.push_str("\\n"),
+                _ => out.push(c)
+            }
+        }
+        out.push(')');
+        out
+    } else {
+        let mut out = String::from("<FEFF");
+        for unit in text.encode_utf16() {
+            out.push_str(&format!("{:04X}", unit));
+        }
+        out.push('>');
+        out
+    }
+}
@@ -0,0 +1,365 @@
+//! Renders scenes to PNG images on the CPU, without a window or GPU.
+
+use pathfinder_content::color::ColorU;
+use pathfinder_geometry::vector::{Vector2F, Vector2I};
+use pathfinder_renderer::scene::Scene;
+use png::{BitDepth, ColorType, Encoder, HasParameters};
+use std::io::{self, Write};
+
+/// The maximum distance of flattened curves from the real ones, in pixels.
+const FLATTENING_TOLERANCE: f32 = 0.1;
+
+const METERS_PER_INCH: f32 = 0.0254;
+
+/// The most pixels an image can have, which is 8192 by 8192. Drawing takes about 24 bytes a pixel.
+const MAX_PIXEL_COUNT: usize = 1 << 26;
+
+/// Options for PNG output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PngOptions {
+    /// The size of the image in pixels. The view box is stretched to fill it.
+    /// If `None`, the view box is taken to be in points (1/72 inch), as in the PDF and PS exports,
+    /// and the size follows from `dpi`.
+    ///
+    /// Images of more than 8192 × 8192 pixels aren't drawn.
+    pub size: Option<Vector2I>,
+    /// The resolution in dots per inch, which is also recorded in the file.
+    pub dpi: f32,
+    /// The color behind the scene.
+    pub background: ColorU,
+}
+
+impl Default for PngOptions {
+    fn default() -> PngOptions {
+        PngOptions { size: None, dpi: 72.0, background: ColorU::transparent_black() }
+    }
+}
+
+pub(crate) fn export_png<W: Write>(
+    scene: &Scene,
+    writer: &mut W,
+    options: &PngOptions,
+) -> io::Result<()> {
+    let view_box = scene.view_box();
+    let size = options.size.unwrap_or_else(|| {
+        (view_box.size() * Vector2F::splat(options.dpi / 72.0)).ceil().to_i32()
+    });
+    if size.x() <= 0 || size.y() <= 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "image size must be positive"));
+    }
+    let (width, height) = (size.x() as usize, size.y() as usize);
+    match width.checked_mul(height) {
+        Some(pixel_count) if pixel_count <= MAX_PIXEL_COUNT => {}
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "image is too large")),
+    }
+
+    let view_size = view_box.size();
+    let scale = if view_size.x() > 0.0 && view_size.y() > 0.0 {
+        Vector2F::new(size.x() as f32 / view_size.x(), size.y() as f32 / view_size.y())
+    } else {
+        Vector2F::splat(0.0)
+    };
+    let to_pixels = |point: Vector2F| (point - view_box.origin()) * scale;
+    let tolerance = FLATTENING_TOLERANCE / f32::max(scale.x(), scale.y()).max(1.0e-6);
+
+    let mut canvas = Canvas::new(width, height, options.background);
+    let mut coverage = Coverage::new(width, height);
+    for (paint, outline, _) in scene.paths() {
+        if paint.color.a == 0 {
+            continue;
+        }
+        for polyline in outline.flatten(tolerance) {
+            let points = &polyline.points;
+            // Fills are closed implicitly, whether or not the contour is.
+            for (index, &point) in points.iter().enumerate() {
+                let next = points[(index + 1) % points.len()];
+                coverage.add_line(to_pixels(point), to_pixels(next));
+            }
+        }
+        canvas.fill(&coverage, paint.color);
+        coverage.clear();
+    }
+
+    let mut encoder = Encoder::new(writer, width as u32, height as u32);
+    encoder.set(ColorType::RGBA).set(BitDepth::Eight);
+    let mut png_writer = encoder.write_header()?;
+    let pixels_per_meter = (options.dpi / METERS_PER_INCH).round() as u32;
+    let mut physical_size = Vec::with_capacity(9);
+    physical_size.extend_from_slice(&pixels_per_meter.to_be_bytes());
+    physical_size.extend_from_slice(&pixels_per_meter.to_be_bytes());
+    physical_size.push(1); // the unit is meters
+    png_writer.write_chunk(*b"pHYs", &physical_size)?;
+    png_writer.write_image_data(&canvas.to_rgba8())?;
+    Ok(())
+}
+
+/// Premultiplied RGBA pixels in sRGB space, which is where the GPU renderer blends too.
+struct Canvas {
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, background: ColorU) -> Canvas {
+        Canvas { pixels: vec![premultiply(background, 1.0); width * height] }
+    }
+
+    fn fill(&mut self, coverage: &Coverage, color: ColorU) {
+        let (columns, rows) = match coverage.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        for row in rows.0..rows.1 {
+            let mut winding = 0.0;
+            for column in columns.0..columns.1 {
+                winding += coverage.accumulation[row * coverage.stride + column];
+                // Nonzero winding, with antialiased edges.
+                let alpha = f32::min(winding.abs(), 1.0);
+                if alpha == 0.0 {
+                    continue;
+                }
+                let source = premultiply(color, alpha);
+                let dest = &mut self.pixels[row * coverage.width + column];
+                for channel in 0..4 {
+                    dest[channel] = source[channel] + dest[channel] * (1.0 - source[3]);
+                }
+            }
+        }
+    }
+
+    fn to_rgba8(&self) -> Vec<u8> {
+        let to_u8 = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            let alpha = pixel[3];
+            for &value in &pixel[..3] {
+                data.push(to_u8(if alpha > 0.0 { value / alpha } else { 0.0 }));
+            }
+            data.push(to_u8(alpha));
+        }
+        data
+    }
+}
+
+fn premultiply(color: ColorU, coverage: f32) -> [f32; 4] {
+    let color = color.to_f32();
+    let alpha = color.a() * coverage;
+    [color.r() * alpha, color.g() * alpha, color.b() * alpha, alpha]
+}
+
+/// Signed area covered by the edges of one path, as differences along each row that add up to
+/// the winding number of each pixel.
+struct Coverage {
+    width: usize,
+    height: usize,
+    // One more column than the image, for the area right of the last pixel.
+    stride: usize,
+    accumulation: Vec<f32>,
+    // The columns and rows that were touched, as half-open ranges.
+    bounds: Option<((usize, usize), (usize, usize))>,
+}
+
+impl Coverage {
+    fn new(width: usize, height: usize) -> Coverage {
+        Coverage {
+            width,
+            height,
+            stride: width + 1,
+            accumulation: vec![0.0; (width + 1) * height],
+            bounds: None,
+        }
+    }
+
+    fn add_line(&mut self, from: Vector2F, to: Vector2F) {
+        let finite = |point: Vector2F| point.x().is_finite() && point.y().is_finite();
+        if from.y() == to.y() || !finite(from) || !finite(to) {
+            return;
+        }
+
+        // Split the line where it crosses pixel boundaries within the image, so that each piece
+        // lies in one row and one column.
+        let (width, height) = (self.width as f32, self.height as f32);
+        let vector = to - from;
+        let mut splits = vec![0.0, 1.0];
+        let mut split_at = |start: f32, end: f32, limit: f32| {
+            let (min, max) = (f32::min(start, end), f32::max(start, end));
+            let first = min.ceil().max(0.0) as i64;
+            let last = max.floor().min(limit) as i64;
+            for boundary in first..=last {
+                let t = (boundary as f32 - start) / (end - start);
+                if t > 0.0 && t < 1.0 {
+                    splits.push(t);
+                }
+            }
+        };
+        if vector.x() != 0.0 {
+            split_at(from.x(), to.x(), width);
+        }
+        split_at(from.y(), to.y(), height);
+        splits.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for pair in splits.windows(2) {
+            let start = from + vector * Vector2F::splat(pair[0]);
+            let end = from + vector * Vector2F::splat(pair[1]);
+            let delta_y = end.y() - start.y();
+            let middle = (start + end) * Vector2F::splat(0.5);
+            if delta_y == 0.0 || middle.y() < 0.0 || middle.y() >= height {
+                continue;
+            }
+
+            let row = middle.y() as usize;
+            // Area left of the image covers the whole row, and area right of it nothing.
+            let x = middle.x().max(0.0).min(width);
+            let column = (x as usize).min(self.width);
+            let fraction = x - column as f32;
+            let index = row * self.stride + column;
+            self.accumulation[index] += delta_y * (1.0 - fraction);
+            if column < self.width {
+                self.accumulation[index + 1] += delta_y * fraction;
+            }
+            self.extend_bounds(column, row);
+        }
+    }
+
+    fn clear(&mut self) {
+        if let Some((columns, rows)) = self.bounds.take() {
+            for row in rows.0..rows.1 {
+                let start = row * self.stride;
+                for value in &mut self.accumulation[(start + columns.0)..(start + self.stride)] {
+                    *value = 0.0;
+                }
+            }
+        }
+    }
+
+    fn extend_bounds(&mut self, column: usize, row: usize) {
+        // Once an edge touches a row, everything right of it may be covered.
+        let (columns, rows) = self.bounds.get_or_insert(((column, self.width), (row, row + 1)));
+        columns.0 = columns.0.min(column);
+        rows.0 = rows.0.min(row);
+        rows.1 = rows.1.max(row + 1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export_png, PngOptions};
+    use std::io::ErrorKind;
+    use pathfinder_content::color::ColorU;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{PathObject, Scene};
+
+    fn square(scene: &mut Scene, origin: Vector2F, size: f32, clockwise: bool, color: ColorU) {
+        let corners = [
+            origin,
+            origin + Vector2F::new(size, 0.0),
+            origin + Vector2F::splat(size),
+            origin + Vector2F::new(0.0, size),
+        ];
+        let mut contour = Contour::new();
+        if clockwise {
+            corners.iter().for_each(|&corner| contour.push_endpoint(corner));
+        } else {
+            corners.iter().rev().for_each(|&corner| contour.push_endpoint(corner));
+        }
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        let paint = scene.push_paint(&Paint { color });
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+    }
+
+    fn render(scene: &Scene, options: &PngOptions) -> (png::OutputInfo, Vec<u8>) {
+        let mut output = vec![];
+        export_png(scene, &mut output, options).unwrap();
+        let decoder = png::Decoder::new(&output[..]);
+        let (info, mut reader) = decoder.read_info().unwrap();
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        (info, pixels)
+    }
+
+    fn pixel(info: &png::OutputInfo, pixels: &[u8], x: usize, y: usize) -> [u8; 4] {
+        let index = (y * info.width as usize + x) * 4;
+        [pixels[index], pixels[index + 1], pixels[index + 2], pixels[index + 3]]
+    }
+
+    #[test]
+    fn test_fill_and_background() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::new(10.0, 10.0), Vector2F::splat(10.0)));
+        // A half-pixel edge at x = 12.5, and a square that sticks out of the view box.
+        square(&mut scene, Vector2F::new(12.5, 12.0), 4.0, true, ColorU::new(255, 0, 0, 255));
+        square(&mut scene, Vector2F::new(5.0, 18.0), 10.0, false, ColorU::new(0, 0, 255, 128));
+
+        let options = PngOptions { background: ColorU::white(), ..PngOptions::default() };
+        let (info, pixels) = render(&scene, &options);
+        assert_eq!((info.width, info.height), (10, 10));
+        assert_eq!(pixel(&info, &pixels, 0, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&info, &pixels, 3, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(&info, &pixels, 2, 3), [255, 128, 128, 255]);
+        assert_eq!(pixel(&info, &pixels, 0, 9), [127, 127, 255, 255]);
+        assert_eq!(pixel(&info, &pixels, 6, 9), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_nonzero_winding_and_size() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(4.0)));
+        // Overlapping squares of the same direction stay filled, and opposite ones cancel out.
+        let black = ColorU::black();
+        square(&mut scene, Vector2F::default(), 4.0, true, black);
+        let mut outline = scene.paths().next().unwrap().1.clone();
+        let mut hole = Contour::new();
+        hole.push_endpoint(Vector2F::new(1.0, 1.0));
+        hole.push_endpoint(Vector2F::new(1.0, 2.0));
+        hole.push_endpoint(Vector2F::new(2.0, 2.0));
+        hole.push_endpoint(Vector2F::new(2.0, 1.0));
+        hole.close();
+        outline.push_contour(hole);
+        let mut same = Contour::new();
+        same.push_endpoint(Vector2F::new(2.0, 2.0));
+        same.push_endpoint(Vector2F::new(3.0, 2.0));
+        same.push_endpoint(Vector2F::new(3.0, 3.0));
+        same.push_endpoint(Vector2F::new(2.0, 3.0));
+        same.close();
+        outline.push_contour(same);
+        let mut scene_with_holes = Scene::new();
+        scene_with_holes.set_view_box(scene.view_box());
+        let paint = scene_with_holes.push_paint(&Paint { color: black });
+        scene_with_holes.push_path(PathObject::new(outline, paint, String::new()));
+
+        let options = PngOptions { size: Some(Vector2I::new(8, 8)), ..PngOptions::default() };
+        let (info, pixels) = render(&scene_with_holes, &options);
+        assert_eq!((info.width, info.height), (8, 8));
+        assert_eq!(pixel(&info, &pixels, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&info, &pixels, 2, 2), [0, 0, 0, 0]);
+        assert_eq!(pixel(&info, &pixels, 5, 5), [0, 0, 0, 255]);
+
+        let options = PngOptions { dpi: 144.0, ..PngOptions::default() };
+        let (info, _) = render(&scene, &options);
+        assert_eq!((info.width, info.height), (8, 8));
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+        let export = |options: PngOptions| export_png(&scene, &mut vec![], &options);
+
+        let options = PngOptions { size: Some(Vector2I::new(0, 10)), ..PngOptions::default() };
+        assert_eq!(export(options).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let options = PngOptions { size: Some(Vector2I::splat(i32::MAX)), ..PngOptions::default() };
+        assert_eq!(export(options).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let options = PngOptions { size: Some(Vector2I::new(8193, 8192)), ..PngOptions::default() };
+        assert_eq!(export(options).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        // A high resolution can't make the image huge either.
+        let options = PngOptions { dpi: 1.0e9, ..PngOptions::default() };
+        assert_eq!(export(options).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let options = PngOptions { dpi: 720.0, ..PngOptions::default() };
+        assert!(export(options).is_ok());
+    }
+}
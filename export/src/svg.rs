@@ -0,0 +1,238 @@
+//! Writes scenes as SVG documents.
+
+use pathfinder_content::color::ColorU;
+use pathfinder_content::outline::Outline;
+use pathfinder_content::segment::SegmentKind;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::Scene;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// Options for SVG output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SvgOptions {
+    /// The number of decimal places to round coordinates to, or `None` to write them exactly.
+    pub precision: Option<usize>,
+    /// Whether to wrap runs of consecutive paths with the same paint in a `<g>` element that
+    /// sets the paint once. Paths are never reordered, so the result looks the same either way.
+    pub group_by_paint: bool,
+}
+
+pub(crate) fn export_svg<W: Write>(
+    scene: &Scene,
+    writer: &mut W,
+    options: &SvgOptions,
+) -> io::Result<()> {
+    let number = |value: f32| format_number(value, options.precision);
+    let view_box = scene.view_box();
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         viewBox=\"{} {} {} {}\">",
+        number(view_box.size().x()),
+        number(view_box.size().y()),
+        number(view_box.origin().x()),
+        number(view_box.origin().y()),
+        number(view_box.size().x()),
+        number(view_box.size().y())
+    )?;
+
+    // IDs must be unique, so only the first path with each name gets one.
+    let mut used_names = HashSet::new();
+    let mut current_group: Option<Paint> = None;
+    for (paint, outline, name) in scene.paths() {
+        let path_data = path_data(outline, options.precision);
+        if path_data.is_empty() {
+            continue;
+        }
+
+        if options.group_by_paint && current_group != Some(*paint) {
+            if current_group.is_some() {
+                writeln!(writer, "    </g>")?;
+            }
+            writeln!(writer, "    <g{}>", paint_attributes(paint))?;
+            current_group = Some(*paint);
+        }
+
+        let indent = if options.group_by_paint { "        " } else { "    " };
+        write!(writer, "{}<path", indent)?;
+        if !name.is_empty() && used_names.insert(name) {
+            write!(writer, " id=\"{}\"", escape(name))?;
+        }
+        if !options.group_by_paint {
+            write!(writer, "{}", paint_attributes(paint))?;
+        }
+        writeln!(writer, " d=\"{}\"/>", path_data)?;
+    }
+    if current_group.is_some() {
+        writeln!(writer, "    </g>")?;
+    }
+
+    writeln!(writer, "</svg>")?;
+    Ok(())
+}
+
+// The renderer fills with the nonzero winding rule.
+fn paint_attributes(paint: &Paint) -> String {
+    let ColorU { r, g, b, a } = paint.color;
+    let mut attributes = format!(" fill=\"#{:02x}{:02x}{:02x}\"", r, g, b);
+    if a < 255 {
+        let opacity = format_number(a as f32 / 255.0, Some(3));
+        attributes.push_str(&format!(" fill-opacity=\"{}\"", opacity));
+    }
+    attributes.push_str(" fill-rule=\"nonzero\"");
+    attributes
+}
+
+fn path_data(outline: &Outline, precision: Option<usize>) -> String {
+    let point = |point: Vector2F| {
+        format!(
+            "{} {}",
+            format_number(point.x(), precision),
+            format_number(point.y(), precision)
+        )
+    };
+
+    let mut commands = vec![];
+    for contour in outline.contours() {
+        let segment_count = contour.iter().count();
+        for (segment_index, segment) in contour.iter().enumerate() {
+            if segment_index == 0 {
+                commands.push(format!("M {}", point(segment.baseline.from())));
+            }
+            // The last segment of a closed contour closes it, which `Z` does already.
+            if contour.is_closed() && segment_index + 1 == segment_count {
+                break;
+            }
+
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => commands.push(format!("L {}", point(segment.baseline.to()))),
+                SegmentKind::Quadratic => commands.push(format!(
+                    "Q {} {}",
+                    point(segment.ctrl.from()),
+                    point(segment.baseline.to())
+                )),
+                SegmentKind::Cubic => commands.push(format!(
+                    "C {} {} {}",
+                    point(segment.ctrl.from()),
+                    point(segment.ctrl.to()),
+                    point(segment.baseline.to())
+                )),
+            }
+        }
+
+        if contour.is_closed() && segment_count > 0 {
+            commands.push("Z".to_owned());
+        }
+    }
+    commands.join(" ")
+}
+
+fn format_number(value: f32, precision: Option<usize>) -> String {
+    let mut string = match precision {
+        None => format!("{}", value),
+        Some(precision) => {
+            let string = format!("{:.*}", precision, value);
+            if string.contains('.') {
+                string.trim_end_matches('0').trim_end_matches('.').to_owned()
+            } else {
+                string
+            }
+        }
+    };
+    if string == "-0" {
+        string = "0".to_owned();
+    }
+    string
+}
+
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for character in string.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export_svg, format_number, SvgOptions};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{PathObject, Scene};
+
+    fn export(scene: &Scene, options: &SvgOptions) -> String {
+        let mut output = vec![];
+        export_svg(scene, &mut output, options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn push_path(scene: &mut Scene, contour: Contour, color: ColorU, name: &str) {
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        let paint_id = scene.push_paint(&Paint { color });
+        scene.push_path(PathObject::new(outline, paint_id, name.to_owned()));
+    }
+
+    #[test]
+    fn test_path_data_and_paint() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::new(-10.0, 5.0), Vector2F::new(100.0, 50.0)));
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        contour.push_quadratic(Vector2F::new(15.0, 5.0), Vector2F::new(10.0, 10.0));
+        contour.push_cubic(
+            Vector2F::new(8.0, 12.0),
+            Vector2F::new(2.0, 12.0),
+            Vector2F::new(0.0, 10.0),
+        );
+        contour.close();
+        push_path(&mut scene, contour, ColorU::new(255, 0, 128, 128), "a <\"b\">");
+
+        let output = export(&scene, &SvgOptions::default());
+        assert!(output.contains("viewBox=\"-10 5 100 50\""));
+        assert!(output.contains(
+            "<path id=\"a &lt;&quot;b&quot;&gt;\" fill=\"#ff0080\" fill-opacity=\"0.502\" \
+             fill-rule=\"nonzero\" d=\"M 0 0 L 10 0 Q 15 5 10 10 C 8 12 2 12 0 10 Z\"/>"
+        ));
+    }
+
+    #[test]
+    fn test_grouping_and_unique_ids() {
+        let mut scene = Scene::new();
+        for &color in &[ColorU::black(), ColorU::black(), ColorU::white()] {
+            let mut contour = Contour::new();
+            contour.push_endpoint(Vector2F::new(0.0, 0.0));
+            contour.push_endpoint(Vector2F::new(1.0, 1.0));
+            push_path(&mut scene, contour, color, "same");
+        }
+
+        let options = SvgOptions { group_by_paint: true, ..SvgOptions::default() };
+        let output = export(&scene, &options);
+        assert_eq!(output.matches("<g ").count(), 2);
+        assert_eq!(output.matches("</g>").count(), 2);
+        assert_eq!(output.matches("id=\"same\"").count(), 1);
+        assert!(!output.contains("<path fill"));
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(format_number(1.23456, Some(2)), "1.23");
+        assert_eq!(format_number(2.0, Some(3)), "2");
+        assert_eq!(format_number(-0.0001, Some(2)), "0");
+        assert_eq!(format_number(0.5, None), "0.5");
+    }
+}
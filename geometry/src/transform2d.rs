@@ -210,6 +210,25 @@ impl Transform2DF {
         other.post_mul(self)
     }
 
+    /// Returns the transform that applies `self` and then `other`.
+    ///
+    /// Unlike `post_mul`, this holds whether or not the two matrices commute.
+    #[inline]
+    pub fn then(&self, other: &Transform2DF) -> Transform2DF {
+        Transform2DF {
+            matrix: other.matrix.post_mul(&self.matrix),
+            vector: other.transform_point(self.vector),
+        }
+    }
+
+    /// Returns the transform that undoes this one. The matrix must be invertible.
+    #[inline]
+    pub fn inverse(&self) -> Transform2DF {
+        let matrix = self.matrix.inverse();
+        let vector = -matrix.transform_point(self.vector);
+        Transform2DF { matrix, vector }
+    }
+
     // TODO(pcwalton): Optimize better with SIMD.
     #[inline]
     pub fn to_3d(&self) -> Transform3DF {
@@ -294,3 +313,83 @@ impl Transform2DF {
         Vector2F(self.matrix.0.zw()).length()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::transform2d::Transform2DF;
+    use crate::vector::Vector2F;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(a: Vector2F, b: Vector2F) {
+        assert!((a - b).length() < 0.0001, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_post_mul() {
+        // The scale and rotation don't commute, so each order gives a different result.
+        let scale = Transform2DF::from_scale(Vector2F::new(2.0, 1.0));
+        let rotation = Transform2DF::from_rotation(FRAC_PI_2);
+        let translation = Transform2DF::from_translation(Vector2F::new(10.0, 20.0));
+        let point = Vector2F::new(1.0, 1.0);
+
+        // Like `Matrix2x2F::post_mul`, the matrix of `other` applies first: rotate to (-1, 1),
+        // then scale to (-2, 1).
+        let transform = scale.post_mul(&rotation);
+        assert_near(transform.transform_point(point), Vector2F::new(-2.0, 1.0));
+        let transform = rotation.pre_mul(&scale);
+        assert_near(transform.transform_point(point), Vector2F::new(-2.0, 1.0));
+
+        // The translation of `self` goes through the matrix of `other`.
+        let transform = translation.post_mul(&scale);
+        assert_near(transform.transform_point(point), Vector2F::new(22.0, 21.0));
+        let transform = scale.post_mul(&translation);
+        assert_near(transform.transform_point(point), Vector2F::new(12.0, 21.0));
+
+        let transform = Transform2DF::from_scale_rotation_translation(Vector2F::new(2.0, 1.0),
+                                                                      FRAC_PI_2,
+                                                                      Vector2F::new(10.0, 20.0));
+        assert_near(transform.transform_point(point), Vector2F::new(8.0, 21.0));
+    }
+
+    #[test]
+    fn test_then() {
+        let scale = Transform2DF::from_scale(Vector2F::new(2.0, 1.0));
+        let rotation = Transform2DF::from_rotation(FRAC_PI_2);
+        let translation = Transform2DF::from_translation(Vector2F::new(10.0, 20.0));
+        let point = Vector2F::new(1.0, 1.0);
+
+        // Scale to (2, 1), then rotate to (-1, 2).
+        let transform = scale.then(&rotation);
+        assert_near(transform.transform_point(point), Vector2F::new(-1.0, 2.0));
+        // Rotate to (-1, 1), then scale to (-2, 1).
+        let transform = rotation.then(&scale);
+        assert_near(transform.transform_point(point), Vector2F::new(-2.0, 1.0));
+
+        let transform = translation.then(&scale).then(&rotation);
+        assert_near(transform.transform_point(point), Vector2F::new(-21.0, 22.0));
+        let transform = rotation.then(&scale.then(&translation));
+        assert_near(transform.transform_point(point), Vector2F::new(8.0, 21.0));
+
+        let pairs = [(scale, rotation), (rotation, translation), (translation, scale)];
+        for &(first, second) in &pairs {
+            let transform = first.then(&second);
+            assert_near(transform.transform_point(point),
+                        second.transform_point(first.transform_point(point)));
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Transform2DF::row_major(2.0, 0.5, -1.0, 3.0, 7.0, -4.0);
+        let inverse = transform.inverse();
+        for &point in &[Vector2F::default(), Vector2F::new(1.0, 2.0), Vector2F::new(-3.0, 5.0)] {
+            assert_near(inverse.transform_point(transform.transform_point(point)), point);
+            assert_near(transform.transform_point(inverse.transform_point(point)), point);
+        }
+
+        let identity = transform.post_mul(&inverse);
+        assert_near(identity.transform_point(Vector2F::new(4.0, -6.0)), Vector2F::new(4.0, -6.0));
+        let identity = inverse.post_mul(&transform);
+        assert_near(identity.transform_point(Vector2F::new(4.0, -6.0)), Vector2F::new(4.0, -6.0));
+    }
+}
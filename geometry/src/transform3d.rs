@@ -452,6 +452,6 @@ mod test {
         );
         assert!(m_inv.approx_eq(&m_inv_exp, 0.0001));
         let p2 = m_inv.transform_point(p1);
-        assert!(p0.approx_eq(&p2, 0.0001));
+        assert!(p0.approx_eq(p2, 0.0001));
     }
 }
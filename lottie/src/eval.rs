@@ -0,0 +1,446 @@
+// pathfinder/lottie/src/eval.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Evaluation of animated properties at a point in time.
+//!
+//! Each keyframe interpolates toward its end value, or the start value of the next keyframe if it
+//! has none, along the cubic Bézier easing curve given by its out and in handles. Hold keyframes
+//! keep their value until the next keyframe begins.
+
+use crate::{KeyframeValue, MultidimensionalPropertyValue, OffsetInterpolation, OffsetKeyframe};
+use crate::{PropertyValue, ShapeKeyframeProperty, ShapeProperty, ShapeVertices};
+use pathfinder_geometry::util::{clamp, lerp};
+use pathfinder_geometry::vector::Vector2F;
+
+const EASING_EPSILON: f32 = 0.00001;
+const EASING_NEWTON_ITERATIONS: u32 = 8;
+const EASING_BISECTION_ITERATIONS: u32 = 32;
+
+const MOTION_PATH_SAMPLE_COUNT: usize = 32;
+
+impl PropertyValue {
+    /// Returns the value of this property at `time`, measured in frames.
+    pub fn value_at(&self, time: f32) -> f32 {
+        match *self {
+            PropertyValue::Value { value, .. } => value,
+            PropertyValue::KeyframedValue { ref keyframes, .. } => {
+                match sample(keyframes, time) {
+                    None => 0.0,
+                    Some(Sample::Held(value)) => first_component(value),
+                    Some(Sample::Between { keyframe, from, to, progress }) => {
+                        lerp(first_component(from), first_component(to), keyframe.ease(progress, 0))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MultidimensionalPropertyValue {
+    /// Returns the value of this property at `time`, measured in frames.
+    ///
+    /// Positions with spatial tangents move along the Bézier curve that the tangents describe, at
+    /// a speed given by the easing of the first dimension.
+    pub fn value_at(&self, time: f32) -> Vec<f32> {
+        match *self {
+            MultidimensionalPropertyValue::Value { ref value, .. } => value.clone(),
+            MultidimensionalPropertyValue::Split { ref x, ref y, .. } => {
+                vec![x.value_at(time), y.value_at(time)]
+            }
+            MultidimensionalPropertyValue::KeyframedValue { ref keyframes, .. } => {
+                match sample(keyframes, time) {
+                    None => vec![],
+                    Some(Sample::Held(value)) => value.clone(),
+                    Some(Sample::Between { keyframe, from, to, progress }) => {
+                        let mut value: Vec<f32> = from.iter().zip(to.iter()).enumerate().map(
+                            |(dimension, (&from, &to))| {
+                                lerp(from, to, keyframe.ease(progress, dimension))
+                            }).collect();
+
+                        let distance = keyframe.ease(progress, 0);
+                        if let Some(position) = keyframe.motion_path_position(from, to, distance) {
+                            value[0] = position.x();
+                            value[1] = position.y();
+                        }
+                        value
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ShapeVertices {
+    /// Returns the shape at `time`, measured in frames, if it has one.
+    pub fn value_at(&self, time: f32) -> Option<ShapeProperty> {
+        match *self {
+            ShapeVertices::Shape { ref value, .. } => Some(value.clone()),
+            ShapeVertices::ShapeKeyframed { ref value, .. } => {
+                match sample(value, time)? {
+                    Sample::Held(shape) => Some(shape.clone()),
+                    Sample::Between { keyframe, from, to, progress } => {
+                        Some(from.morph(to, keyframe.ease(progress, 0)))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ShapeProperty {
+    /// Blends this shape with `other` by moving each vertex and tangent a fraction `t` of the way
+    /// toward its counterpart.
+    ///
+    /// Shapes with different numbers of vertices can't be blended, so this returns a copy of
+    /// `self` for them.
+    pub fn morph(&self, other: &ShapeProperty, t: f32) -> ShapeProperty {
+        if self.vertices.len() != other.vertices.len() ||
+                self.in_points.len() != other.in_points.len() ||
+                self.out_points.len() != other.out_points.len() {
+            return self.clone();
+        }
+
+        let blend = |from: &[[f32; 2]], to: &[[f32; 2]]| {
+            from.iter().zip(to.iter()).map(|(from, to)| {
+                [lerp(from[0], to[0], t), lerp(from[1], to[1], t)]
+            }).collect()
+        };
+        ShapeProperty {
+            closed: self.closed,
+            in_points: blend(&self.in_points, &other.in_points),
+            out_points: blend(&self.out_points, &other.out_points),
+            vertices: blend(&self.vertices, &other.vertices),
+        }
+    }
+}
+
+// The keyframe that a time falls in, and how far through it the time is.
+enum Sample<'a, K> where K: Keyframe {
+    Held(&'a K::Value),
+    Between { keyframe: &'a K, from: &'a K::Value, to: &'a K::Value, progress: f32 },
+}
+
+fn sample<K>(keyframes: &[K], time: f32) -> Option<Sample<'_, K>> where K: Keyframe {
+    // Find the last keyframe that has begun, or the first one if none has.
+    let index = keyframes.iter().rposition(|keyframe| keyframe.time() <= time).unwrap_or(0);
+    let keyframe = keyframes.get(index)?;
+
+    // Older exports finish with a keyframe that has only a time, which takes the end value of the
+    // keyframe before it.
+    let from = match keyframe.start() {
+        Some(start) => start,
+        None => {
+            let previous = keyframes[..index].last()?;
+            return previous.end().or_else(|| previous.start()).map(Sample::Held);
+        }
+    };
+
+    let next = match keyframes.get(index + 1) {
+        Some(next) if !keyframe.is_hold() && time >= keyframe.time() => next,
+        _ => return Some(Sample::Held(from)),
+    };
+    let to = match keyframe.end().or_else(|| next.start()) {
+        Some(to) => to,
+        None => return Some(Sample::Held(from)),
+    };
+
+    let duration = next.time() - keyframe.time();
+    let progress = if duration > 0.0 {
+        f32::min((time - keyframe.time()) / duration, 1.0)
+    } else {
+        1.0
+    };
+    Some(Sample::Between { keyframe, from, to, progress })
+}
+
+trait Keyframe {
+    type Value;
+
+    fn time(&self) -> f32;
+    fn start(&self) -> Option<&Self::Value>;
+    fn end(&self) -> Option<&Self::Value>;
+    fn is_hold(&self) -> bool;
+    fn easing(&self) -> (Option<&OffsetInterpolation>, Option<&OffsetInterpolation>);
+
+    // Maps linear progress through this keyframe to eased progress. Properties may ease each
+    // dimension separately; those with a single pair of handles use it for every dimension.
+    fn ease(&self, progress: f32, dimension: usize) -> f32 {
+        let (out_value, in_value) = match self.easing() {
+            (Some(out_value), Some(in_value)) => (out_value, in_value),
+            _ => return progress,
+        };
+        let component = |values: &[f32]| values.get(dimension).or_else(|| values.first()).cloned();
+        match (component(&out_value.x),
+               component(&out_value.y),
+               component(&in_value.x),
+               component(&in_value.y)) {
+            (Some(out_x), Some(out_y), Some(in_x), Some(in_y)) => {
+                solve_easing(Vector2F::new(out_x, out_y), Vector2F::new(in_x, in_y), progress)
+            }
+            _ => progress,
+        }
+    }
+}
+
+impl Keyframe for KeyframeValue {
+    type Value = Vec<f32>;
+
+    #[inline]
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    fn start(&self) -> Option<&Vec<f32>> {
+        self.start.as_ref()
+    }
+
+    #[inline]
+    fn end(&self) -> Option<&Vec<f32>> {
+        self.end.as_ref()
+    }
+
+    #[inline]
+    fn is_hold(&self) -> bool {
+        self.hold != 0
+    }
+
+    #[inline]
+    fn easing(&self) -> (Option<&OffsetInterpolation>, Option<&OffsetInterpolation>) {
+        (self.out_value.as_ref(), self.in_value.as_ref())
+    }
+}
+
+impl Keyframe for OffsetKeyframe {
+    type Value = Vec<f32>;
+
+    #[inline]
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    fn start(&self) -> Option<&Vec<f32>> {
+        self.start.as_ref()
+    }
+
+    #[inline]
+    fn end(&self) -> Option<&Vec<f32>> {
+        self.end.as_ref()
+    }
+
+    #[inline]
+    fn is_hold(&self) -> bool {
+        self.hold != 0
+    }
+
+    #[inline]
+    fn easing(&self) -> (Option<&OffsetInterpolation>, Option<&OffsetInterpolation>) {
+        (self.out_value.as_ref(), self.in_value.as_ref())
+    }
+}
+
+impl Keyframe for ShapeKeyframeProperty {
+    type Value = ShapeProperty;
+
+    #[inline]
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    fn start(&self) -> Option<&ShapeProperty> {
+        self.start.first().and_then(|start| start.as_ref())
+    }
+
+    #[inline]
+    fn end(&self) -> Option<&ShapeProperty> {
+        self.end.first().and_then(|end| end.as_ref())
+    }
+
+    #[inline]
+    fn is_hold(&self) -> bool {
+        self.hold != 0
+    }
+
+    #[inline]
+    fn easing(&self) -> (Option<&OffsetInterpolation>, Option<&OffsetInterpolation>) {
+        (self.out_value.as_ref(), self.in_value.as_ref())
+    }
+}
+
+impl OffsetKeyframe {
+    // Returns the point a fraction `distance` of the way along the curved motion path from `from`
+    // to `to`, if this keyframe has spatial tangents. Motion along the path is uniform in arc
+    // length, so the curve parameter is found from a table of sampled lengths.
+    fn motion_path_position(&self, from: &[f32], to: &[f32], distance: f32) -> Option<Vector2F> {
+        let (out_tangent, in_tangent) = match (&self.out_tangent, &self.in_tangent) {
+            (Some(out_tangent), Some(in_tangent)) => {
+                (vector_from_components(out_tangent)?, vector_from_components(in_tangent)?)
+            }
+            _ => return None,
+        };
+        if out_tangent.is_zero() && in_tangent.is_zero() {
+            return None;
+        }
+
+        let (from, to) = (vector_from_components(from)?, vector_from_components(to)?);
+        let (ctrl0, ctrl1) = (from + out_tangent, to + in_tangent);
+        let point_at = |t: f32| cubic_bezier_point(from, ctrl0, ctrl1, to, t);
+
+        // Overshooting easing curves carry the motion past the ends of the path.
+        if distance <= 0.0 || distance >= 1.0 {
+            return Some(point_at(distance));
+        }
+
+        let mut lengths = Vec::with_capacity(MOTION_PATH_SAMPLE_COUNT + 1);
+        let (mut length, mut last_point) = (0.0, from);
+        lengths.push(0.0);
+        for sample_index in 1..=MOTION_PATH_SAMPLE_COUNT {
+            let point = point_at(sample_index as f32 / MOTION_PATH_SAMPLE_COUNT as f32);
+            length += (point - last_point).length();
+            lengths.push(length);
+            last_point = point;
+        }
+        if length == 0.0 {
+            return Some(from);
+        }
+
+        let target_length = distance * length;
+        let sample_index = lengths.iter()
+                                  .position(|&length| length >= target_length)
+                                  .unwrap_or(MOTION_PATH_SAMPLE_COUNT)
+                                  .max(1);
+        let (prev_length, next_length) = (lengths[sample_index - 1], lengths[sample_index]);
+        let fraction = if next_length > prev_length {
+            (target_length - prev_length) / (next_length - prev_length)
+        } else {
+            0.0
+        };
+        Some(point_at((sample_index as f32 - 1.0 + fraction) / MOTION_PATH_SAMPLE_COUNT as f32))
+    }
+}
+
+// Evaluates the easing curve running from (0, 0) to (1, 1), with the given control points, at
+// `x`, as CSS `cubic-bezier()` does.
+fn solve_easing(ctrl0: Vector2F, ctrl1: Vector2F, x: f32) -> f32 {
+    let (x0, x1) = (clamp(ctrl0.x(), 0.0, 1.0), clamp(ctrl1.x(), 0.0, 1.0));
+    let x = clamp(x, 0.0, 1.0);
+
+    // Newton's method converges quickly unless the curve is nearly flat in X, in which case
+    // bisection takes over.
+    let mut t = x;
+    for _ in 0..EASING_NEWTON_ITERATIONS {
+        let error = easing_coordinate(x0, x1, t) - x;
+        if f32::abs(error) < EASING_EPSILON {
+            return easing_coordinate(ctrl0.y(), ctrl1.y(), t);
+        }
+        let slope = easing_slope(x0, x1, t);
+        if f32::abs(slope) < EASING_EPSILON {
+            break;
+        }
+        t -= error / slope;
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    t = x;
+    for _ in 0..EASING_BISECTION_ITERATIONS {
+        let value = easing_coordinate(x0, x1, t);
+        if f32::abs(value - x) < EASING_EPSILON {
+            break;
+        }
+        if value < x {
+            low = t;
+        } else {
+            high = t;
+        }
+        t = (low + high) * 0.5;
+    }
+    easing_coordinate(ctrl0.y(), ctrl1.y(), t)
+}
+
+// One coordinate of a cubic Bézier curve from 0 to 1 with the given control coordinates.
+#[inline]
+fn easing_coordinate(ctrl0: f32, ctrl1: f32, t: f32) -> f32 {
+    let (a, b, c) = (1.0 + 3.0 * (ctrl0 - ctrl1), 3.0 * (ctrl1 - 2.0 * ctrl0), 3.0 * ctrl0);
+    ((a * t + b) * t + c) * t
+}
+
+#[inline]
+fn easing_slope(ctrl0: f32, ctrl1: f32, t: f32) -> f32 {
+    let (a, b, c) = (1.0 + 3.0 * (ctrl0 - ctrl1), 3.0 * (ctrl1 - 2.0 * ctrl0), 3.0 * ctrl0);
+    (3.0 * a * t + 2.0 * b) * t + c
+}
+
+fn cubic_bezier_point(from: Vector2F, ctrl0: Vector2F, ctrl1: Vector2F, to: Vector2F, t: f32)
+                      -> Vector2F {
+    let (p01, p12, p23) = (from.lerp(ctrl0, t), ctrl0.lerp(ctrl1, t), ctrl1.lerp(to, t));
+    let (p012, p123) = (p01.lerp(p12, t), p12.lerp(p23, t));
+    p012.lerp(p123, t)
+}
+
+#[inline]
+fn first_component(value: &[f32]) -> f32 {
+    value.first().cloned().unwrap_or(0.0)
+}
+
+#[inline]
+fn vector_from_components(components: &[f32]) -> Option<Vector2F> {
+    match *components {
+        [x, y, ..] => Some(Vector2F::new(x, y)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{MultidimensionalPropertyValue, PropertyValue};
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        f32::abs(a - b) < 0.001
+    }
+
+    #[test]
+    fn test_easing_and_hold() {
+        let property: PropertyValue = serde_json::from_str(r#"{
+            "a": 1,
+            "k": [
+                {"t": 0, "s": [0], "o": {"x": [0.42], "y": [0]}, "i": {"x": [0.58], "y": [1]}},
+                {"t": 10, "s": 100, "h": 1},
+                {"t": 20, "s": [50]}
+            ]
+        }"#).unwrap();
+
+        // Ease-in-out is symmetric about its midpoint and slow at its ends.
+        assert!(approx_eq(property.value_at(-5.0), 0.0));
+        assert!(approx_eq(property.value_at(5.0), 50.0));
+        assert!(property.value_at(1.0) < 10.0);
+        assert!(approx_eq(property.value_at(2.0) + property.value_at(8.0), 100.0));
+        assert!(approx_eq(property.value_at(15.0), 100.0));
+        assert!(approx_eq(property.value_at(25.0), 50.0));
+    }
+
+    #[test]
+    fn test_motion_path() {
+        let property: MultidimensionalPropertyValue = serde_json::from_str(r#"{
+            "a": 1,
+            "k": [
+                {"t": 0, "s": [0, 0], "to": [0, -10], "ti": [0, -10]},
+                {"t": 10, "s": [20, 0]}
+            ]
+        }"#).unwrap();
+
+        // The path is symmetric, so halfway along it is above the midpoint of the chord.
+        let position = property.value_at(5.0);
+        assert!(approx_eq(position[0], 10.0));
+        assert!(approx_eq(position[1], -7.5));
+        assert_eq!(property.value_at(10.0), vec![20.0, 0.0]);
+    }
+}
@@ -10,64 +10,192 @@
 
 //! Experimental support for Lottie. This is very incomplete.
 
-use serde::{Deserialize, Serialize};
-use serde_json::Error as JSONError;
+use serde::de::{DeserializeOwned, Error as DeserializeError};
+use serde::ser::Error as SerializeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Error as JSONError, Value as JSONValue};
 use std::io::Read;
 
+mod eval;
+mod mask;
+pub mod player;
+mod render;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Lottie {
     #[serde(rename = "v")]
     pub version: String,
     #[serde(rename = "fr")]
-    pub frame_rate: i64,
+    pub frame_rate: f64,
     #[serde(rename = "ip")]
-    pub in_point: i64,
+    pub in_point: f64,
     #[serde(rename = "op")]
-    pub out_point: i64,
+    pub out_point: f64,
     #[serde(rename = "w")]
     pub width: f64,
     #[serde(rename = "h")]
     pub height: f64,
     #[serde(rename = "ddd")]
+    #[serde(default)]
     pub three_d: i64,
+    #[serde(default)]
     pub assets: Vec<Asset>,
     pub layers: Vec<Layer>,
 }
 
+/// A precomposition or an image that layers can refer to by ID.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Asset {}
+pub struct Asset {
+    pub id: String,
+    /// The layers of a precomposition.
+    #[serde(default)]
+    pub layers: Option<Vec<Layer>>,
+    #[serde(rename = "w")]
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub height: Option<f64>,
+    /// The directory of an image, or its data URL prefix if it's embedded.
+    #[serde(rename = "u")]
+    #[serde(default)]
+    pub directory: Option<String>,
+    /// The file name of an image, or its data if it's embedded.
+    #[serde(rename = "p")]
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub embedded: i64,
+}
 
-// FIXME(pcwalton): Using an untagged enum is a botch here. There actually is a tag: it's just an
-// integer, which `serde_json` doesn't support natively.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum Layer {
+pub struct Layer {
+    #[serde(rename = "ddd")]
+    #[serde(default)]
+    pub three_d: i64,
+    #[serde(rename = "ind")]
+    #[serde(default)]
+    pub index: i64,
+    #[serde(rename = "nm")]
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "ks")]
+    pub transform: Transform,
+    #[serde(rename = "ao")]
+    #[serde(default)]
+    pub auto_orient: i64,
+    #[serde(rename = "ip")]
+    pub in_point: f32,
+    #[serde(rename = "op")]
+    pub out_point: f32,
+    #[serde(rename = "st")]
+    #[serde(default)]
+    pub start_time: f32,
+    #[serde(rename = "bm")]
+    #[serde(default)]
+    pub blend_mode: i64,
+    #[serde(rename = "sr")]
+    #[serde(default = "default_stretch")]
+    pub stretch: f32,
+    #[serde(rename = "ln")]
+    #[serde(default)]
+    pub layer_id: Option<String>,
+    /// The `index` of the layer whose transform this layer inherits.
+    #[serde(default)]
+    pub parent: Option<i64>,
+    /// Maps the time of this layer to the time within its precomposition, in seconds.
+    #[serde(rename = "tm")]
+    #[serde(default)]
+    pub time_remap: Option<PropertyValue>,
+    #[serde(rename = "masksProperties")]
+    #[serde(default)]
+    pub masks: Vec<Mask>,
+    /// Set if this layer is only used as the track matte of the layer below it.
+    #[serde(rename = "td")]
+    #[serde(default)]
+    pub is_track_matte: i64,
+    /// How the layer above this one mattes it, if it does: 1 for alpha, 2 for inverted alpha, 3
+    /// for luma, or 4 for inverted luma.
+    #[serde(rename = "tt")]
+    #[serde(default)]
+    pub track_matte_mode: Option<i64>,
+    #[serde(flatten)]
+    #[serde(deserialize_with = "deserialize_integer_tagged")]
+    #[serde(serialize_with = "serialize_integer_tagged")]
+    pub content: LayerContent,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mask {
+    #[serde(rename = "nm")]
+    #[serde(default)]
+    pub name: Option<String>,
+    pub mode: MaskMode,
+    #[serde(rename = "inv")]
+    #[serde(default)]
+    pub inverted: bool,
+    #[serde(rename = "pt")]
+    pub path: ShapeVertices,
+    #[serde(rename = "o")]
+    #[serde(default)]
+    pub opacity: Option<PropertyValue>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaskMode {
+    #[serde(rename = "n")]
+    None,
+    #[serde(rename = "a")]
+    Add,
+    #[serde(rename = "s")]
+    Subtract,
+    #[serde(rename = "i")]
+    Intersect,
+    #[serde(rename = "l")]
+    Lighten,
+    #[serde(rename = "d")]
+    Darken,
+    #[serde(rename = "f")]
+    Difference,
+}
+
+// Layers are tagged with integers in the `ty` field, which `serde` can't match against variant
+// names directly. See `deserialize_integer_tagged()`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "ty")]
+pub enum LayerContent {
+    #[serde(rename = "0")]
+    Precomposition {
+        #[serde(rename = "refId")]
+        reference_id: String,
+        #[serde(rename = "w")]
+        width: f64,
+        #[serde(rename = "h")]
+        height: f64,
+    },
+    #[serde(rename = "1")]
+    Solid {
+        #[serde(rename = "sc")]
+        color: String,
+        #[serde(rename = "sw")]
+        width: f64,
+        #[serde(rename = "sh")]
+        height: f64,
+    },
+    #[serde(rename = "2")]
+    Image {
+        #[serde(rename = "refId")]
+        reference_id: String,
+    },
+    #[serde(rename = "3")]
+    Null,
+    #[serde(rename = "4")]
     Shape {
-        #[serde(rename = "ddd")]
-        three_d: i64,
-        #[serde(rename = "ind")]
-        index: i64,
-        #[serde(rename = "nm")]
-        name: String,
-        #[serde(rename = "ks")]
-        transform: Transform,
-        #[serde(rename = "ao")]
-        auto_orient: i64,
-        #[serde(rename = "ip")]
-        in_point: i64,
-        #[serde(rename = "op")]
-        out_point: i64,
-        #[serde(rename = "st")]
-        start_time: i64,
-        #[serde(rename = "bm")]
-        blend_mode: i64,
-        #[serde(rename = "sr")]
-        stretch: i64,
-        #[serde(rename = "ln")]
-        #[serde(default)]
-        layer_id: Option<String>,
         shapes: Vec<Shape>,
     },
+    #[serde(other)]
+    Unimplemented,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -129,12 +257,23 @@ pub enum PropertyValue {
 pub struct KeyframeValue {
     #[serde(rename = "s")]
     #[serde(default)]
+    #[serde(deserialize_with = "deserialize_optional_scalar_or_vec")]
     pub start: Option<Vec<f32>>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_optional_scalar_or_vec")]
+    pub end: Option<Vec<f32>>,
     #[serde(rename = "t")]
-    pub time: i64,
+    pub time: f32,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub hold: i64,
     #[serde(rename = "i")]
     #[serde(default)]
-    pub interpolation: Option<OffsetInterpolation>,
+    pub in_value: Option<OffsetInterpolation>,
+    #[serde(rename = "o")]
+    #[serde(default)]
+    pub out_value: Option<OffsetInterpolation>,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -145,7 +284,9 @@ pub struct Interpolation {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OffsetInterpolation {
+    #[serde(deserialize_with = "deserialize_scalar_or_vec")]
     pub x: Vec<f32>,
+    #[serde(deserialize_with = "deserialize_scalar_or_vec")]
     pub y: Vec<f32>,
 }
 
@@ -154,14 +295,28 @@ pub struct OffsetKeyframe {
     #[serde(rename = "s")]
     #[serde(default)]
     pub start: Option<Vec<f32>>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub end: Option<Vec<f32>>,
     #[serde(rename = "t")]
-    pub time: i64,
+    pub time: f32,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub hold: i64,
     #[serde(rename = "i")]
     #[serde(default)]
     pub in_value: Option<OffsetInterpolation>,
     #[serde(rename = "o")]
     #[serde(default)]
     pub out_value: Option<OffsetInterpolation>,
+    /// The spatial tangent of the path into the next keyframe, relative to its value.
+    #[serde(rename = "ti")]
+    #[serde(default)]
+    pub in_tangent: Option<Vec<f32>>,
+    /// The spatial tangent of the path out of this keyframe, relative to its value.
+    #[serde(rename = "to")]
+    #[serde(default)]
+    pub out_tangent: Option<Vec<f32>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -186,12 +341,15 @@ pub enum MultidimensionalPropertyValue {
         #[serde(rename = "ix")]
         #[serde(default)]
         index: Option<i64>,
-        #[serde(rename = "ti")]
-        #[serde(default)]
-        in_tangent: Option<i64>,
-        #[serde(rename = "to")]
-        #[serde(default)]
-        out_tangent: Option<i64>,
+    },
+    /// A position whose X and Y coordinates are animated separately.
+    Split {
+        #[serde(rename = "s")]
+        split: bool,
+        #[serde(rename = "x")]
+        x: PropertyValue,
+        #[serde(rename = "y")]
+        y: PropertyValue,
     },
 }
 
@@ -203,6 +361,7 @@ pub enum Shape {
         #[serde(rename = "it")]
         items: Vec<Shape>,
         #[serde(rename = "nm")]
+        #[serde(default)]
         name: String,
     },
     #[serde(rename = "sh")]
@@ -224,6 +383,28 @@ pub enum Shape {
         #[serde(rename = "c")]
         color: MultidimensionalPropertyValue,
     },
+    #[serde(rename = "st")]
+    Stroke {
+        #[serde(rename = "nm")]
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        opacity: Option<PropertyValue>,
+        #[serde(rename = "c")]
+        color: MultidimensionalPropertyValue,
+        #[serde(rename = "w")]
+        width: PropertyValue,
+        #[serde(rename = "lc")]
+        #[serde(default)]
+        line_cap: Option<i64>,
+        #[serde(rename = "lj")]
+        #[serde(default)]
+        line_join: Option<i64>,
+        #[serde(rename = "ml")]
+        #[serde(default)]
+        miter_limit: Option<f32>,
+    },
     #[serde(rename = "tr")]
     Transform {
         #[serde(rename = "r")]
@@ -238,11 +419,70 @@ pub enum Shape {
         anchor_point: MultidimensionalPropertyValue,
         #[serde(rename = "s")]
         scale: MultidimensionalPropertyValue,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        opacity: Option<PropertyValue>,
+    },
+    #[serde(rename = "tm")]
+    Trim {
+        #[serde(rename = "nm")]
+        #[serde(default)]
+        name: Option<String>,
+        /// The start of the kept part of the paths, as a percentage of their length.
+        #[serde(rename = "s")]
+        start: PropertyValue,
+        /// The end of the kept part of the paths, as a percentage of their length.
+        #[serde(rename = "e")]
+        end: PropertyValue,
+        /// How far to shift the kept part along the paths, in degrees of a full turn.
+        #[serde(rename = "o")]
+        offset: PropertyValue,
+        /// 1 to trim each path separately, or 2 to trim all the paths as one.
+        #[serde(rename = "m")]
+        #[serde(default)]
+        mode: Option<i64>,
+    },
+    #[serde(rename = "rp")]
+    Repeater {
+        #[serde(rename = "nm")]
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(rename = "c")]
+        copies: PropertyValue,
+        #[serde(rename = "o")]
+        offset: PropertyValue,
+        /// 1 to draw each copy above the one before it, or 2 to draw it below.
+        #[serde(rename = "m")]
+        #[serde(default)]
+        composite: Option<i64>,
+        #[serde(rename = "tr")]
+        transform: RepeaterTransform,
     },
     #[serde(other)]
     Unimplemented,
 }
 
+/// The transform that a repeater applies once more to each successive copy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RepeaterTransform {
+    #[serde(rename = "p")]
+    pub position: MultidimensionalPropertyValue,
+    #[serde(rename = "a")]
+    pub anchor_point: MultidimensionalPropertyValue,
+    #[serde(rename = "s")]
+    pub scale: MultidimensionalPropertyValue,
+    #[serde(rename = "r")]
+    pub rotation: PropertyValue,
+    /// The opacity of the first copy, as a percentage.
+    #[serde(rename = "so")]
+    #[serde(default)]
+    pub start_opacity: Option<PropertyValue>,
+    /// The opacity of the last copy, as a percentage.
+    #[serde(rename = "eo")]
+    #[serde(default)]
+    pub end_opacity: Option<PropertyValue>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ShapeVertices {
@@ -295,8 +535,14 @@ pub struct ShapeKeyframeProperty {
     #[serde(rename = "s")]
     #[serde(default)]
     pub start: Vec<Option<ShapeProperty>>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub end: Vec<Option<ShapeProperty>>,
     #[serde(rename = "t")]
-    pub time: i64,
+    pub time: f32,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub hold: i64,
     #[serde(rename = "i")]
     #[serde(default)]
     pub in_value: Option<OffsetInterpolation>,
@@ -311,3 +557,61 @@ impl Lottie {
         serde_json::from_reader(reader)
     }
 }
+
+// Bodymovin writes one-dimensional values, and the easing handles of one-dimensional properties,
+// as either bare numbers or arrays.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalarOrVec {
+    Scalar(f32),
+    Vec(Vec<f32>),
+}
+
+impl ScalarOrVec {
+    fn into_vec(self) -> Vec<f32> {
+        match self {
+            ScalarOrVec::Scalar(value) => vec![value],
+            ScalarOrVec::Vec(values) => values,
+        }
+    }
+}
+
+fn deserialize_scalar_or_vec<'de, D>(deserializer: D) -> Result<Vec<f32>, D::Error>
+                                     where D: Deserializer<'de> {
+    ScalarOrVec::deserialize(deserializer).map(ScalarOrVec::into_vec)
+}
+
+fn deserialize_optional_scalar_or_vec<'de, D>(deserializer: D)
+                                              -> Result<Option<Vec<f32>>, D::Error>
+                                              where D: Deserializer<'de> {
+    Ok(Option::<ScalarOrVec>::deserialize(deserializer)?.map(ScalarOrVec::into_vec))
+}
+
+#[inline]
+fn default_stretch() -> f32 {
+    1.0
+}
+
+// Reads a value tagged with an integer `ty` field by rewriting the tag as a string, which the
+// variant names of `T` then match.
+fn deserialize_integer_tagged<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+                                         where D: Deserializer<'de>, T: DeserializeOwned {
+    let mut value = JSONValue::deserialize(deserializer)?;
+    if let Some(tag) = value.get_mut("ty") {
+        if let Some(index) = tag.as_i64() {
+            *tag = JSONValue::String(index.to_string());
+        }
+    }
+    T::deserialize(value).map_err(D::Error::custom)
+}
+
+fn serialize_integer_tagged<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+                                  where S: Serializer, T: Serialize {
+    let mut value = serde_json::to_value(value).map_err(S::Error::custom)?;
+    if let Some(tag) = value.get_mut("ty") {
+        if let Some(index) = tag.as_str().and_then(|tag| tag.parse::<i64>().ok()) {
+            *tag = JSONValue::from(index);
+        }
+    }
+    value.serialize(serializer)
+}
@@ -0,0 +1,133 @@
+// pathfinder/lottie/src/mask.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Layer masks and track mattes.
+//!
+//! The renderer has no clip paths or offscreen layers, so both are applied by cutting the paths
+//! of the layer geometrically. Mask opacity and feathering and the soft edges of mattes are not
+//! reproduced.
+
+use crate::render::RenderedPath;
+use crate::{Mask, MaskMode};
+use pathfinder_content::boolean::{BooleanOp, OutlineBoolean};
+use pathfinder_content::color::ColorF;
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::vector::Vector2F;
+
+const TOLERANCE: f32 = 0.25;
+
+const TRACK_MATTE_ALPHA_INVERTED: i64 = 2;
+const TRACK_MATTE_LUMA: i64 = 3;
+const TRACK_MATTE_LUMA_INVERTED: i64 = 4;
+
+// Cuts the paths of a layer down to the region its masks leave visible.
+pub(crate) fn apply_masks(paths: Vec<RenderedPath>,
+                          masks: &[Mask],
+                          transform: &Transform2DF,
+                          time: f32) -> Vec<RenderedPath> {
+    let masks: Vec<_> = masks.iter().filter(|mask| {
+        let opacity = mask.opacity.as_ref().map_or(100.0, |opacity| opacity.value_at(time));
+        mask.mode != MaskMode::None && opacity > 0.0
+    }).collect();
+    if masks.is_empty() || paths.is_empty() {
+        return paths;
+    }
+
+    // Stands in for the whole plane, for inverted masks and for masks that start by taking
+    // away.
+    let everything = {
+        let bounds = paths.iter().skip(1).fold(paths[0].outline.bounds(), |bounds, path| {
+            bounds.union_rect(path.outline.bounds())
+        });
+        Outline::from_rect(bounds.dilate(Vector2F::splat(1.0)))
+    };
+
+    let mut region = match masks[0].mode {
+        MaskMode::Add | MaskMode::Lighten | MaskMode::Difference => Outline::new(),
+        _ => everything.clone(),
+    };
+    for mask in masks {
+        let mut shape = Outline::new();
+        if let Some(vertices) = mask.path.value_at(time) {
+            shape.push_contour(vertices.to_contour());
+        }
+        shape.transform(transform);
+        if mask.inverted {
+            shape = combine(&everything, &shape, BooleanOp::Difference);
+        }
+
+        let op = match mask.mode {
+            MaskMode::Add | MaskMode::Lighten => BooleanOp::Union,
+            MaskMode::Subtract => BooleanOp::Difference,
+            MaskMode::Intersect | MaskMode::Darken => BooleanOp::Intersection,
+            MaskMode::Difference => BooleanOp::Xor,
+            MaskMode::None => continue,
+        };
+        region = combine(&region, &shape, op);
+    }
+
+    paths.into_iter().map(|path| {
+        RenderedPath {
+            outline: combine(&path.outline, &region, BooleanOp::Intersection),
+            ..path
+        }
+    }).filter(|path| !path.outline.contours().is_empty()).collect()
+}
+
+// Shows the paths of a layer through the layer above it. Each part of the matte takes the alpha
+// or luminance of its topmost path.
+pub(crate) fn apply_track_matte(paths: Vec<RenderedPath>,
+                                matte_paths: &[RenderedPath],
+                                mode: i64) -> Vec<RenderedPath> {
+    let inverted = mode == TRACK_MATTE_ALPHA_INVERTED || mode == TRACK_MATTE_LUMA_INVERTED;
+    let luma = mode == TRACK_MATTE_LUMA || mode == TRACK_MATTE_LUMA_INVERTED;
+
+    // Split the matte into disjoint regions, from top to bottom.
+    let mut covered = Outline::new();
+    let mut regions = vec![];
+    for matte_path in matte_paths.iter().rev() {
+        let region = combine(&matte_path.outline, &covered, BooleanOp::Difference);
+        covered = combine(&covered, &matte_path.outline, BooleanOp::Union);
+        let weight = if luma {
+            matte_path.color.luminance() * matte_path.color.a()
+        } else {
+            matte_path.color.a()
+        };
+        regions.push((region, if inverted { 1.0 - weight } else { weight }));
+    }
+
+    let mut output = vec![];
+    for path in paths {
+        for &(ref region, weight) in &regions {
+            let outline = combine(&path.outline, region, BooleanOp::Intersection);
+            let color = path.color;
+            let color = ColorF::new(color.r(), color.g(), color.b(), color.a() * weight);
+            push_path(&mut output, outline, color, &path.name);
+        }
+        if inverted {
+            let outline = combine(&path.outline, &covered, BooleanOp::Difference);
+            push_path(&mut output, outline, path.color, &path.name);
+        }
+    }
+    output
+}
+
+fn push_path(paths: &mut Vec<RenderedPath>, outline: Outline, color: ColorF, name: &str) {
+    if !outline.contours().is_empty() && color.a() > 0.0 {
+        paths.push(RenderedPath { outline, color, name: name.to_owned() });
+    }
+}
+
+fn combine(a: &Outline, b: &Outline, op: BooleanOp) -> Outline {
+    let mut boolean = OutlineBoolean::new(a, b, op, TOLERANCE);
+    boolean.compute();
+    boolean.into_outline()
+}
@@ -0,0 +1,244 @@
+// pathfinder/lottie/src/player.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Plays Lottie animations into a scene proxy.
+
+use crate::Lottie;
+use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
+use std::time::Duration;
+
+/// Owns an animation and the scene proxy it draws into, and keeps track of playback.
+///
+/// Time is measured in frames of the composition and advanced by wall-clock time, so playback
+/// runs at the same rate however often `tick()` is called. Frames may be fractional.
+pub struct LottiePlayer {
+    lottie: Lottie,
+    scene_proxy: SceneProxy,
+    frame: f64,
+    playing: bool,
+    looping: bool,
+    speed: f64,
+    loop_range: Option<(f64, f64)>,
+}
+
+impl LottiePlayer {
+    /// Creates a paused player showing the first frame of the animation.
+    pub fn new(lottie: Lottie, scene_proxy: SceneProxy) -> LottiePlayer {
+        let frame = lottie.in_point;
+        let player = LottiePlayer {
+            lottie,
+            scene_proxy,
+            frame,
+            playing: false,
+            looping: true,
+            speed: 1.0,
+            loop_range: None,
+        };
+        player.replace_scene();
+        player
+    }
+
+    #[inline]
+    pub fn lottie(&self) -> &Lottie {
+        &self.lottie
+    }
+
+    #[inline]
+    pub fn scene_proxy(&self) -> &SceneProxy {
+        &self.scene_proxy
+    }
+
+    #[inline]
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    #[inline]
+    pub fn current_frame(&self) -> f64 {
+        self.frame
+    }
+
+    /// Jumps to the given frame, clamped to the playback range, and draws it.
+    pub fn seek(&mut self, frame: f64) {
+        let (start, end) = self.range();
+        self.frame = f64::min(f64::max(frame, start), last_frame(start, end));
+        self.replace_scene();
+    }
+
+    /// Sets the playback rate. 1.0 plays at the frame rate of the animation, and negative speeds
+    /// play backward.
+    #[inline]
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    #[inline]
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Sets whether playback wraps around at the ends of the range or stops there.
+    #[inline]
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Restricts playback to the frames from `start` up to, but not including, `end`. The range
+    /// is clamped to the frames of the animation.
+    pub fn set_loop_range(&mut self, start: f64, end: f64) {
+        let start = f64::max(f64::min(start, end), self.lottie.in_point);
+        let end = f64::min(f64::max(start, end), self.lottie.out_point);
+        self.loop_range = Some((start, end));
+        let frame = self.frame;
+        self.seek(frame);
+    }
+
+    #[inline]
+    pub fn clear_loop_range(&mut self) {
+        self.loop_range = None;
+    }
+
+    /// Advances playback by the given wall-clock time and draws the new frame.
+    ///
+    /// Returns true if a new scene was sent to the proxy. Once a player that isn't looping
+    /// reaches the end of its range, it pauses.
+    pub fn tick(&mut self, elapsed: Duration) -> bool {
+        if !self.playing {
+            return false;
+        }
+
+        let (start, end) = self.range();
+        let frame_count = end - start;
+        let mut frame = self.frame + elapsed.as_secs_f64() * self.lottie.frame_rate * self.speed;
+        if frame_count <= 0.0 {
+            frame = start;
+        } else if self.looping {
+            frame = start + (frame - start).rem_euclid(frame_count);
+        } else if frame < start || frame >= end {
+            frame = f64::min(f64::max(frame, start), last_frame(start, end));
+            self.playing = false;
+        }
+
+        if frame == self.frame {
+            return false;
+        }
+        self.frame = frame;
+        self.replace_scene();
+        true
+    }
+
+    /// Returns the wall-clock time until playback reaches the next whole frame, or `None` if the
+    /// player is paused or stopped.
+    ///
+    /// Hosts can sleep for this long between ticks instead of drawing as fast as they can.
+    pub fn time_until_next_frame(&self) -> Option<Duration> {
+        let frames_per_second = self.lottie.frame_rate * self.speed;
+        if !self.playing || frames_per_second == 0.0 {
+            return None;
+        }
+        let frames = if frames_per_second > 0.0 {
+            self.frame.floor() + 1.0 - self.frame
+        } else {
+            self.frame - (self.frame.ceil() - 1.0)
+        };
+        Some(Duration::from_secs_f64(frames / frames_per_second.abs()))
+    }
+
+    fn range(&self) -> (f64, f64) {
+        self.loop_range.unwrap_or((self.lottie.in_point, self.lottie.out_point))
+    }
+
+    fn replace_scene(&self) {
+        self.scene_proxy.replace_scene(self.lottie.render_frame(self.frame as f32));
+    }
+}
+
+// The out point is exclusive, so playback that stops at the end shows the last whole frame.
+#[inline]
+fn last_frame(start: f64, end: f64) -> f64 {
+    f64::max(start, end - 1.0)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Lottie;
+    use crate::player::LottiePlayer;
+    use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+    use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
+    use std::time::Duration;
+
+    static EMPTY: &str = r#"{
+        "v": "5.5.2", "fr": 30, "ip": 0, "op": 60, "w": 100, "h": 100, "layers": []
+    }"#;
+
+    #[test]
+    fn test_playback() {
+        let lottie = Lottie::from_reader(EMPTY.as_bytes()).unwrap();
+        let mut player = LottiePlayer::new(lottie, SceneProxy::new(SequentialExecutor));
+        assert!(!player.tick(Duration::from_millis(500)));
+        assert_eq!(player.current_frame(), 0.0);
+
+        // Time advances by the clock, not by the number of ticks.
+        player.play();
+        assert!(player.tick(Duration::from_millis(500)));
+        assert!(player.tick(Duration::from_millis(500)));
+        assert_eq!(player.current_frame(), 30.0);
+        assert!(player.tick(Duration::from_secs(1)));
+        assert_eq!(player.current_frame(), 0.0);
+
+        // Seeking clamps into the loop range, and playing backward wraps to its end.
+        player.set_speed(-0.5);
+        player.set_loop_range(10.0, 40.0);
+        assert_eq!(player.current_frame(), 10.0);
+        assert!(player.tick(Duration::from_secs(1)));
+        assert_eq!(player.current_frame(), 25.0);
+
+        player.set_looping(false);
+        player.seek(15.0);
+        assert!(player.tick(Duration::from_secs(1)));
+        assert_eq!(player.current_frame(), 10.0);
+        assert!(!player.is_playing());
+
+        player.clear_loop_range();
+        player.seek(100.0);
+        assert_eq!(player.current_frame(), 59.0);
+    }
+
+    #[test]
+    fn test_time_until_next_frame() {
+        let lottie = Lottie::from_reader(EMPTY.as_bytes()).unwrap();
+        let mut player = LottiePlayer::new(lottie, SceneProxy::new(SequentialExecutor));
+        assert_eq!(player.time_until_next_frame(), None);
+
+        player.play();
+        player.seek(10.0);
+        assert_eq!(player.time_until_next_frame(), Some(Duration::from_secs_f64(1.0 / 30.0)));
+        player.seek(10.25);
+        assert_eq!(player.time_until_next_frame(), Some(Duration::from_secs_f64(0.75 / 30.0)));
+
+        player.set_speed(-2.0);
+        assert_eq!(player.time_until_next_frame(), Some(Duration::from_secs_f64(0.25 / 60.0)));
+        player.seek(10.0);
+        assert_eq!(player.time_until_next_frame(), Some(Duration::from_secs_f64(1.0 / 60.0)));
+
+        player.set_speed(0.0);
+        assert_eq!(player.time_until_next_frame(), None);
+    }
+}
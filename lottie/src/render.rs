@@ -0,0 +1,798 @@
+// pathfinder/lottie/src/render.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversion of Lottie animation frames to Pathfinder scenes.
+
+use crate::{Layer, LayerContent, Lottie, MultidimensionalPropertyValue, PropertyValue, Shape};
+use crate::{ShapeProperty, Transform};
+use crate::mask;
+use pathfinder_content::color::{ColorF, ColorU};
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_content::stroke::StrokeTransform;
+use pathfinder_content::trim::OutlineTrim;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::util::lerp;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{PathObject, Scene};
+
+const DEFAULT_MITER_LIMIT: f32 = 4.0;
+
+// Guards against precompositions that contain themselves.
+const MAX_PRECOMPOSITION_DEPTH: u32 = 16;
+
+impl Lottie {
+    /// Renders the frame at `time`, which is measured in frames, in the same units as the
+    /// `in_point` and `out_point` of the animation.
+    pub fn render_frame(&self, time: f32) -> Scene {
+        let mut renderer = FrameRenderer { lottie: self, paths: vec![], time };
+        renderer.render_composition(&self.layers, time, &Transform2DF::default(), 1.0, 0);
+
+        let mut scene = Scene::new();
+        let size = Vector2F::new(self.width as f32, self.height as f32);
+        scene.set_view_box(RectF::new(Vector2F::default(), size));
+        for path in renderer.paths {
+            let paint = scene.push_paint(&Paint { color: path.color.to_u8() });
+            scene.push_path(PathObject::new(path.outline, paint, path.name));
+        }
+        scene
+    }
+}
+
+struct FrameRenderer<'a> {
+    lottie: &'a Lottie,
+    // Paths are gathered here, from bottom to top, so that masks and mattes can still cut them
+    // before they go into the scene.
+    paths: Vec<RenderedPath>,
+    // The time of the layer being drawn, which its shapes are evaluated at.
+    time: f32,
+}
+
+pub(crate) struct RenderedPath {
+    pub(crate) outline: Outline,
+    pub(crate) color: ColorF,
+    pub(crate) name: String,
+}
+
+impl<'a> FrameRenderer<'a> {
+    fn render_composition(&mut self,
+                          layers: &[Layer],
+                          time: f32,
+                          transform: &Transform2DF,
+                          opacity: f32,
+                          depth: u32) {
+        // Layers are listed from top to bottom, so draw them in reverse.
+        for (layer_index, layer) in layers.iter().enumerate().rev() {
+            if layer.is_track_matte != 0 {
+                continue;
+            }
+
+            let first_path_index = self.paths.len();
+            self.render_layer(layers, layer, time, transform, opacity, depth);
+
+            // A track matte is the layer just above the one it mattes.
+            let (mode, matte_layer) = match (layer.track_matte_mode, layer_index.checked_sub(1)) {
+                (Some(mode), Some(matte_index)) => (mode, &layers[matte_index]),
+                _ => continue,
+            };
+            let first_matte_path_index = self.paths.len();
+            self.render_layer(layers, matte_layer, time, transform, 1.0, depth);
+            let matte_paths = self.paths.split_off(first_matte_path_index);
+            let paths = self.paths.split_off(first_path_index);
+            self.paths.extend(mask::apply_track_matte(paths, &matte_paths, mode));
+        }
+    }
+
+    fn render_layer(&mut self,
+                    layers: &[Layer],
+                    layer: &Layer,
+                    time: f32,
+                    transform: &Transform2DF,
+                    opacity: f32,
+                    depth: u32) {
+        if time < layer.in_point || time >= layer.out_point {
+            return;
+        }
+
+        // Parents pass on their transforms but not their opacities.
+        let layer_time = local_time(layer, time);
+        let opacity = opacity * percentage_at(&layer.transform.opacity, layer_time);
+        let transform = layer_transform(layers, layer, time).then(transform);
+
+        let first_path_index = self.paths.len();
+        match layer.content {
+            LayerContent::Shape { ref shapes } => {
+                self.time = layer_time;
+                self.render_group(shapes, &transform, opacity);
+            }
+            LayerContent::Solid { ref color, width, height } => {
+                let color = match ColorU::from_css(color) {
+                    None => return,
+                    Some(color) => color.to_f32(),
+                };
+                let rect = RectF::new(Vector2F::default(),
+                                      Vector2F::new(width as f32, height as f32));
+                let mut outline = Outline::from_rect(rect);
+                outline.transform(&transform);
+                let color = ColorF::new(color.r(), color.g(), color.b(), color.a() * opacity);
+                self.push_colored_path(outline, color, layer.name.clone());
+            }
+            LayerContent::Precomposition { ref reference_id, .. } => {
+                // TODO: Clip precompositions to their bounds.
+                let asset = self.lottie.assets.iter().find(|asset| asset.id == *reference_id);
+                let layers = match asset.and_then(|asset| asset.layers.as_ref()) {
+                    Some(layers) if depth < MAX_PRECOMPOSITION_DEPTH => layers,
+                    _ => return,
+                };
+                let inner_time = match layer.time_remap {
+                    Some(ref time_remap) => {
+                        time_remap.value_at(layer_time) * self.lottie.frame_rate as f32
+                    }
+                    None => layer_time,
+                };
+                self.render_composition(layers, inner_time, &transform, opacity, depth + 1);
+            }
+            // TODO: Draw image layers once scenes support image paints.
+            LayerContent::Image { .. } | LayerContent::Null | LayerContent::Unimplemented => {}
+        }
+
+        if !layer.masks.is_empty() {
+            let paths = self.paths.split_off(first_path_index);
+            self.paths.extend(mask::apply_masks(paths, &layer.masks, &transform, layer_time));
+        }
+    }
+
+    // Draws the fills and strokes of a group of shapes. Styles apply to all the paths that precede
+    // them in the group, including those in nested groups, and items are listed from top to
+    // bottom.
+    fn render_group(&mut self, items: &[Shape], transform: &Transform2DF, opacity: f32) {
+        let (transform, opacity) = match group_transform(items, self.time) {
+            None => (*transform, opacity),
+            Some((group_transform, group_opacity)) => {
+                (group_transform.then(transform), opacity * group_opacity)
+            }
+        };
+
+        // A repeater copies everything above it, styles included. Styles below it apply to all
+        // the copies of the paths.
+        match items.iter().rposition(|item| item.is_repeater()) {
+            None => self.render_styles(items, 0, &transform, opacity),
+            Some(repeater_index) => {
+                for (copy_transform, copy_opacity) in
+                        repeater_copies(&items[repeater_index], self.time) {
+                    self.render_group(&items[..repeater_index],
+                                      &copy_transform.then(&transform),
+                                      opacity * copy_opacity);
+                }
+                self.render_styles(items, repeater_index + 1, &transform, opacity);
+            }
+        }
+    }
+
+    fn render_styles(&mut self,
+                     items: &[Shape],
+                     first_item_index: usize,
+                     transform: &Transform2DF,
+                     opacity: f32) {
+        for (item_index, item) in items.iter().enumerate().skip(first_item_index).rev() {
+            match *item {
+                Shape::Group { items: ref kids, .. } => {
+                    self.render_group(kids, transform, opacity)
+                }
+                Shape::Fill { opacity: ref fill_opacity, ref color, ref name } => {
+                    let mut outline = self.collect_paths(&items[..item_index]);
+                    outline.transform(transform);
+                    let opacity = opacity * percentage_at(fill_opacity, self.time);
+                    self.push_path(outline, color, opacity, name);
+                }
+                Shape::Stroke {
+                    ref name,
+                    opacity: ref stroke_opacity,
+                    ref color,
+                    ref width,
+                    line_cap,
+                    line_join,
+                    miter_limit,
+                } => {
+                    let outline = self.collect_paths(&items[..item_index]);
+                    let style = StrokeStyle {
+                        line_width: width.value_at(self.time),
+                        line_cap: match line_cap {
+                            Some(2) => LineCap::Round,
+                            Some(3) => LineCap::Square,
+                            _ => LineCap::Butt,
+                        },
+                        line_join: match line_join {
+                            Some(2) => LineJoin::Round,
+                            Some(3) => LineJoin::Bevel,
+                            _ => LineJoin::Miter(miter_limit.unwrap_or(DEFAULT_MITER_LIMIT)),
+                        },
+                    };
+                    let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, style);
+                    stroke_to_fill.set_transform(transform, StrokeTransform::Scaling);
+                    stroke_to_fill.offset();
+                    let outline = stroke_to_fill.into_outline();
+
+                    let opacity = opacity * percentage_at(stroke_opacity, self.time);
+                    self.push_path(outline, color, opacity, name);
+                }
+                Shape::Shape { .. } |
+                Shape::Transform { .. } |
+                Shape::Trim { .. } |
+                Shape::Repeater { .. } |
+                Shape::Unimplemented => {}
+            }
+        }
+    }
+
+    // Gathers the paths among `items`, and in groups nested within them, into one outline in the
+    // coordinate system of `items`. Trims and repeaters modify the paths gathered before them.
+    fn collect_paths(&self, items: &[Shape]) -> Outline {
+        let mut outline = Outline::new();
+        for item in items {
+            match *item {
+                Shape::Shape { ref vertices, .. } => {
+                    if let Some(shape) = vertices.value_at(self.time) {
+                        outline.push_contour(shape.to_contour());
+                    }
+                }
+                Shape::Group { items: ref kids, .. } => {
+                    let mut group_outline = self.collect_paths(kids);
+                    if let Some((group_transform, _)) = group_transform(kids, self.time) {
+                        group_outline.transform(&group_transform);
+                    }
+                    append_outline(&mut outline, &group_outline);
+                }
+                Shape::Trim { ref start, ref end, ref offset, mode, .. } => {
+                    outline = trim_outline(&outline,
+                                           start.value_at(self.time) * 0.01,
+                                           end.value_at(self.time) * 0.01,
+                                           offset.value_at(self.time) / 360.0,
+                                           mode == Some(2));
+                }
+                Shape::Repeater { .. } => {
+                    let mut copies = Outline::new();
+                    for (copy_transform, _) in repeater_copies(item, self.time) {
+                        let mut copy = outline.clone();
+                        copy.transform(&copy_transform);
+                        append_outline(&mut copies, &copy);
+                    }
+                    outline = copies;
+                }
+                Shape::Fill { .. } |
+                Shape::Stroke { .. } |
+                Shape::Transform { .. } |
+                Shape::Unimplemented => {}
+            }
+        }
+        outline
+    }
+
+    fn push_path(&mut self,
+                 outline: Outline,
+                 color: &MultidimensionalPropertyValue,
+                 opacity: f32,
+                 name: &Option<String>) {
+        let color = color.value_at(self.time);
+        let component = |index: usize| color.get(index).cloned().unwrap_or(0.0);
+        let color = ColorF::new(component(0), component(1), component(2), opacity);
+        self.push_colored_path(outline, color, name.clone().unwrap_or_default());
+    }
+
+    fn push_colored_path(&mut self, outline: Outline, color: ColorF, name: String) {
+        if !outline.contours().is_empty() && color.a() > 0.0 {
+            self.paths.push(RenderedPath { outline, color, name });
+        }
+    }
+}
+
+// Returns the transform of a layer, including those it inherits from its chain of parents.
+fn layer_transform(layers: &[Layer], layer: &Layer, time: f32) -> Transform2DF {
+    let mut transform = layer.transform.to_transform_2d(local_time(layer, time));
+    let mut parent_index = layer.parent;
+
+    // A malformed file could make a layer its own ancestor, so stop after visiting every layer.
+    for _ in 0..layers.len() {
+        let parent = match parent_index.and_then(|index| {
+            layers.iter().find(|layer| layer.index == index)
+        }) {
+            None => break,
+            Some(parent) => parent,
+        };
+        let parent_transform = parent.transform.to_transform_2d(local_time(parent, time));
+        transform = transform.then(&parent_transform);
+        parent_index = parent.parent;
+    }
+    transform
+}
+
+// Returns the time within a layer, at which its properties, shapes and masks are evaluated. A
+// stretch of 2 plays the layer at half speed.
+fn local_time(layer: &Layer, time: f32) -> f32 {
+    let stretch = if layer.stretch != 0.0 { layer.stretch } else { 1.0 };
+    (time - layer.start_time) / stretch
+}
+
+impl Shape {
+    #[inline]
+    fn is_repeater(&self) -> bool {
+        matches!(*self, Shape::Repeater { .. })
+    }
+}
+
+// Returns the transform and opacity of the `tr` item of a group, if it has one.
+fn group_transform(items: &[Shape], time: f32) -> Option<(Transform2DF, f32)> {
+    items.iter().filter_map(|item| {
+        match *item {
+            Shape::Transform {
+                ref rotation,
+                ref skew,
+                ref skew_axis,
+                ref position,
+                ref anchor_point,
+                ref scale,
+                ref opacity,
+            } => {
+                let transform = compose_transform(anchor_point.value_at(time),
+                                                  position.value_at(time),
+                                                  scale.value_at(time),
+                                                  rotation.value_at(time),
+                                                  skew.value_at(time),
+                                                  skew_axis.value_at(time));
+                Some((transform, percentage_at(opacity, time)))
+            }
+            _ => None,
+        }
+    }).next()
+}
+
+impl Transform {
+    fn to_transform_2d(&self, time: f32) -> Transform2DF {
+        let value_or_zero = |value: &Option<PropertyValue>| {
+            value.as_ref().map(|value| value.value_at(time)).unwrap_or(0.0)
+        };
+        compose_transform(self.anchor_point.value_at(time),
+                          self.position.value_at(time),
+                          self.scale.value_at(time),
+                          self.rotation.value_at(time),
+                          value_or_zero(&self.skew),
+                          value_or_zero(&self.skew_axis))
+    }
+}
+
+// Builds the transform that moves the anchor point to the origin, then scales, skews, rotates,
+// and translates to the position, as After Effects does. Scales are percentages and angles are in
+// degrees.
+fn compose_transform(anchor_point: Vec<f32>,
+                     position: Vec<f32>,
+                     scale: Vec<f32>,
+                     rotation: f32,
+                     skew: f32,
+                     skew_axis: f32)
+                     -> Transform2DF {
+    let anchor_point = vector_from_components(&anchor_point, 0.0);
+    let position = vector_from_components(&position, 0.0);
+    let scale = vector_from_components(&scale, 100.0).scale(0.01);
+
+    let mut transform = Transform2DF::from_translation(-anchor_point);
+    transform = transform.then(&Transform2DF::from_scale(scale));
+    if skew != 0.0 {
+        let skew_axis = skew_axis.to_radians();
+        let shear = Transform2DF::row_major(1.0, f32::tan(-skew.to_radians()), 0.0, 1.0, 0.0, 0.0);
+        transform = transform.then(&Transform2DF::from_rotation(skew_axis));
+        transform = transform.then(&shear);
+        transform = transform.then(&Transform2DF::from_rotation(-skew_axis));
+    }
+    transform.then(&Transform2DF::from_rotation(rotation.to_radians())).post_translate(position)
+}
+
+// Returns the transform and opacity of each copy that a repeater makes, in drawing order.
+fn repeater_copies(repeater: &Shape, time: f32) -> Vec<(Transform2DF, f32)> {
+    let (copies, offset, composite, transform) = match *repeater {
+        Shape::Repeater { ref copies, ref offset, composite, ref transform, .. } => {
+            (copies, offset, composite, transform)
+        }
+        _ => return vec![],
+    };
+
+    let copy_count = f32::max(copies.value_at(time).round(), 0.0) as usize;
+    let offset = offset.value_at(time);
+    let anchor_point = vector_from_components(&transform.anchor_point.value_at(time), 0.0);
+    let position = vector_from_components(&transform.position.value_at(time), 0.0);
+    let scale = vector_from_components(&transform.scale.value_at(time), 100.0).scale(0.01);
+    let rotation = transform.rotation.value_at(time).to_radians();
+    let start_opacity = percentage_at(&transform.start_opacity, time);
+    let end_opacity = percentage_at(&transform.end_opacity, time);
+
+    // Each copy applies the transform once more than the one before it.
+    let mut result: Vec<_> = (0..copy_count).map(|copy_index| {
+        let steps = copy_index as f32 + offset;
+        let scale = Vector2F::new(scale.x().powf(steps), scale.y().powf(steps));
+        let transform = Transform2DF::from_translation(-anchor_point);
+        let transform = transform.then(&Transform2DF::from_scale(scale));
+        let transform = transform.then(&Transform2DF::from_rotation(rotation * steps))
+            .post_translate(anchor_point + position.scale(steps));
+        let fraction = if copy_count > 1 {
+            copy_index as f32 / (copy_count - 1) as f32
+        } else {
+            0.0
+        };
+        (transform, lerp(start_opacity, end_opacity, fraction))
+    }).collect();
+
+    if composite == Some(2) {
+        result.reverse();
+    }
+    result
+}
+
+// Keeps the parts of the paths between fractions `start` and `end` of their length, shifted
+// along by `offset` and wrapping around. Each contour is trimmed separately unless `as_one` is
+// set.
+fn trim_outline(outline: &Outline, start: f32, end: f32, offset: f32, as_one: bool) -> Outline {
+    let (mut start, mut end) = (f32::min(start, end) + offset, f32::max(start, end) + offset);
+    if end - start >= 1.0 {
+        return outline.clone();
+    }
+    let whole_turns = start.floor();
+    start -= whole_turns;
+    end -= whole_turns;
+
+    let trim = |input: &Outline, output: &mut Outline| {
+        let length = input.length();
+        let mut ranges = vec![(start, f32::min(end, 1.0))];
+        if end > 1.0 {
+            ranges.push((0.0, end - 1.0));
+        }
+        for (range_start, range_end) in ranges {
+            let mut trim = OutlineTrim::new(input, range_start * length, range_end * length);
+            trim.trim();
+            append_outline(output, &trim.into_outline());
+        }
+    };
+
+    let mut output = Outline::new();
+    if as_one {
+        trim(outline, &mut output);
+    } else {
+        for contour in outline.contours() {
+            let mut input = Outline::new();
+            input.push_contour(contour.clone());
+            trim(&input, &mut output);
+        }
+    }
+    output
+}
+
+fn append_outline(outline: &mut Outline, other: &Outline) {
+    for contour in other.contours() {
+        outline.push_contour(contour.clone());
+    }
+}
+
+#[inline]
+fn vector_from_components(components: &[f32], default: f32) -> Vector2F {
+    Vector2F::new(components.first().cloned().unwrap_or(default),
+                  components.get(1).cloned().unwrap_or(default))
+}
+
+// Evaluates an optional percentage, like an opacity, as a fraction of 1.
+fn percentage_at(value: &Option<PropertyValue>, time: f32) -> f32 {
+    match *value {
+        None => 1.0,
+        Some(ref value) => value.value_at(time) * 0.01,
+    }
+}
+
+impl ShapeProperty {
+    pub(crate) fn to_contour(&self) -> Contour {
+        let mut contour = Contour::new();
+        let vertex = |index: usize| vector_from_components(&self.vertices[index], 0.0);
+        let in_tangent = |index: usize| {
+            self.in_points.get(index).map(|point| vector_from_components(point, 0.0))
+                                     .unwrap_or_default()
+        };
+        let out_tangent = |index: usize| {
+            self.out_points.get(index).map(|point| vector_from_components(point, 0.0))
+                                      .unwrap_or_default()
+        };
+        let push_segment = |contour: &mut Contour, from_index: usize, to_index: usize| {
+            let (out_tangent, in_tangent) = (out_tangent(from_index), in_tangent(to_index));
+            if out_tangent.is_zero() && in_tangent.is_zero() {
+                contour.push_endpoint(vertex(to_index));
+            } else {
+                contour.push_cubic(vertex(from_index) + out_tangent,
+                                   vertex(to_index) + in_tangent,
+                                   vertex(to_index));
+            }
+        };
+
+        if self.vertices.is_empty() {
+            return contour;
+        }
+        contour.push_endpoint(vertex(0));
+        for index in 1..self.vertices.len() {
+            push_segment(&mut contour, index - 1, index);
+        }
+        if self.closed {
+            // A curved closing segment has to be spelled out; a straight one is implied.
+            let last_index = self.vertices.len() - 1;
+            if !out_tangent(last_index).is_zero() || !in_tangent(0).is_zero() {
+                push_segment(&mut contour, last_index, 0);
+            }
+            contour.close();
+        }
+        contour
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Lottie;
+    use pathfinder_content::color::ColorU;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+
+    static SQUARE: &str = r#"{
+        "v": "5.5.2", "fr": 30, "ip": 0, "op": 60, "w": 100, "h": 100, "ddd": 0, "assets": [],
+        "layers": [{
+            "ddd": 0, "ty": 4, "ind": 1, "nm": "Square", "ao": 0, "ip": 0, "op": 60, "st": 0,
+            "bm": 0, "sr": 1,
+            "ks": {
+                "p": {"a": 0, "k": [50, 50]}, "a": {"a": 0, "k": [0, 0]},
+                "s": {"a": 0, "k": [200, 200]}, "r": {"a": 0, "k": 0}, "o": {"a": 0, "k": 50}
+            },
+            "shapes": [{
+                "ty": "gr", "nm": "Group",
+                "it": [
+                    {
+                        "ty": "sh",
+                        "ks": {
+                            "a": 0,
+                            "k": {
+                                "c": true,
+                                "i": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "o": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "v": [[-10, -10], [10, -10], [10, 10], [-10, 10]]
+                            }
+                        }
+                    },
+                    {"ty": "st", "c": {"a": 0, "k": [0, 0, 1, 1]}, "w": {"a": 0, "k": 2}},
+                    {"ty": "fl", "c": {"a": 0, "k": [1, 0, 0, 1]}, "o": {"a": 0, "k": 100}},
+                    {
+                        "ty": "tr", "p": {"a": 0, "k": [0, 0]}, "a": {"a": 0, "k": [0, 0]},
+                        "s": {"a": 0, "k": [100, 100]}, "r": {"a": 0, "k": 0},
+                        "sk": {"a": 0, "k": 0}, "sa": {"a": 0, "k": 0}
+                    }
+                ]
+            }]
+        }]
+    }"#;
+
+    #[test]
+    fn test_render_square() {
+        let lottie = Lottie::from_reader(SQUARE.as_bytes()).unwrap();
+        let scene = lottie.render_frame(0.0);
+        assert_eq!(scene.path_count(), 2);
+
+        // The fill is drawn first, beneath the stroke above it in the list.
+        let (_, outline, _) = scene.paths().next().unwrap();
+        let bounds = outline.bounds();
+        assert!((bounds.origin() - Vector2F::new(30.0, 30.0)).length() < 0.01);
+        assert!((bounds.size() - Vector2F::new(40.0, 40.0)).length() < 0.01);
+        assert_eq!(scene.monochrome_color(), None);
+
+        assert_eq!(lottie.render_frame(60.0).path_count(), 0);
+    }
+
+    static PRECOMPOSITION: &str = r##"{
+        "v": "5.5.2", "fr": 30, "ip": 0, "op": 60, "w": 100, "h": 100,
+        "assets": [{
+            "id": "comp_0",
+            "layers": [{
+                "ty": 1, "ind": 1, "ip": 0, "op": 60, "sc": "#00ff00", "sw": 10, "sh": 10,
+                "ks": {
+                    "p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]},
+                    "r": {"k": 0}
+                }
+            }]
+        }],
+        "layers": [
+            {
+                "ty": 5, "ind": 1, "ip": 0, "op": 60, "t": {},
+                "ks": {
+                    "p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]},
+                    "r": {"k": 0}
+                }
+            },
+            {
+                "ty": 0, "ind": 2, "parent": 3, "refId": "comp_0", "w": 10, "h": 10, "ip": 0,
+                "op": 60, "tm": {"k": [{"t": 0, "s": [1]}, {"t": 60, "s": [3]}]},
+                "ks": {
+                    "p": {"k": [5, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]},
+                    "r": {"k": 0}
+                }
+            },
+            {
+                "ty": 3, "ind": 3, "ip": 0, "op": 60,
+                "ks": {
+                    "p": {"s": true, "x": {"k": 20}, "y": {"k": 30}},
+                    "a": {"k": [0, 0]}, "s": {"k": [200, 200]}, "r": {"k": 0}
+                }
+            }
+        ]
+    }"##;
+
+    #[test]
+    fn test_render_precomposition() {
+        let lottie = Lottie::from_reader(PRECOMPOSITION.as_bytes()).unwrap();
+        let scene = lottie.render_frame(0.0);
+        assert_eq!(scene.path_count(), 1);
+
+        // The solid is offset within its parent, which scales and moves it.
+        let (_, outline, _) = scene.paths().next().unwrap();
+        let bounds = outline.bounds();
+        assert!((bounds.origin() - Vector2F::new(30.0, 30.0)).length() < 0.01);
+        assert!((bounds.size() - Vector2F::new(20.0, 20.0)).length() < 0.01);
+
+        // Time remapping runs the precomposition past the end of its solid.
+        assert_eq!(lottie.render_frame(30.0).path_count(), 0);
+
+        let json = serde_json::to_string(&lottie).unwrap();
+        let lottie = Lottie::from_reader(json.as_bytes()).unwrap();
+        assert_eq!(lottie.render_frame(0.0).path_count(), 1);
+    }
+
+    static MASKED_SOLID: &str = r##"{
+        "v": "5.5.2", "fr": 30, "ip": 0, "op": 60, "w": 100, "h": 100,
+        "layers": [
+            {
+                "ty": 1, "ind": 1, "td": 1, "ip": 0, "op": 60, "sc": "#ffffff", "sw": 50,
+                "sh": 50,
+                "ks": {
+                    "p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]},
+                    "r": {"k": 0}
+                }
+            },
+            {
+                "ty": 1, "ind": 2, "tt": 1, "ip": 0, "op": 60, "sc": "#ff0000", "sw": 100,
+                "sh": 100,
+                "ks": {
+                    "p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]},
+                    "r": {"k": 0}
+                },
+                "masksProperties": [
+                    {
+                        "mode": "a", "inv": false, "o": {"k": 100},
+                        "pt": {
+                            "a": 0,
+                            "k": {
+                                "c": true,
+                                "i": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "o": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "v": [[20, 20], [60, 20], [60, 60], [20, 60]]
+                            }
+                        }
+                    },
+                    {
+                        "mode": "s", "inv": false, "o": {"k": 100},
+                        "pt": {
+                            "a": 0,
+                            "k": {
+                                "c": true,
+                                "i": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "o": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "v": [[40, 0], [100, 0], [100, 100], [40, 100]]
+                            }
+                        }
+                    }
+                ]
+            }
+        ]
+    }"##;
+
+    #[test]
+    fn test_render_masks_and_track_matte() {
+        let lottie = Lottie::from_reader(MASKED_SOLID.as_bytes()).unwrap();
+        let scene = lottie.render_frame(0.0);
+
+        // The masks leave a 20x40 strip, and the matte above cuts off its bottom 10 pixels. The
+        // matte itself is not drawn.
+        assert!(scene.path_count() > 0);
+        let bounds = scene.paths().map(|(_, outline, _)| outline.bounds()).fold(None, |a, b| {
+            Some(a.map_or(b, |a: RectF| a.union_rect(b)))
+        }).unwrap();
+        assert!((bounds.origin() - Vector2F::new(20.0, 20.0)).length() < 0.01);
+        assert!((bounds.size() - Vector2F::new(20.0, 30.0)).length() < 0.01);
+        assert_eq!(scene.monochrome_color(), Some(ColorU::new(255, 0, 0, 255)));
+    }
+
+    static STRETCHED: &str = r#"{
+        "v": "5.5.2", "fr": 30, "ip": 0, "op": 100, "w": 100, "h": 100,
+        "layers": [{
+            "ty": 4, "ind": 1, "ip": 0, "op": 100, "st": 10, "sr": 2,
+            "ks": {
+                "p": {"a": 1, "k": [{"t": 0, "s": [0, 0]}, {"t": 20, "s": [40, 0]}]},
+                "a": {"k": [0, 0]}, "s": {"k": [100, 100]}, "r": {"k": 0}
+            },
+            "shapes": [{
+                "ty": "gr",
+                "it": [
+                    {
+                        "ty": "sh",
+                        "ks": {
+                            "a": 0,
+                            "k": {
+                                "c": true,
+                                "i": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "o": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                "v": [[-5, -5], [5, -5], [5, 5], [-5, 5]]
+                            }
+                        }
+                    },
+                    {"ty": "fl", "c": {"k": [1, 0, 0, 1]}, "o": {"k": 100}},
+                    {
+                        "ty": "tr",
+                        "p": {"a": 1, "k": [{"t": 0, "s": [0, 0]}, {"t": 20, "s": [0, 40]}]},
+                        "a": {"k": [0, 0]}, "s": {"k": [100, 100]}, "r": {"k": 0},
+                        "sk": {"k": 0}, "sa": {"k": 0}
+                    }
+                ]
+            }]
+        }]
+    }"#;
+
+    static ROTATED_PARENT: &str = r##"{
+        "v": "5.5.2", "fr": 30, "ip": 0, "op": 60, "w": 100, "h": 100,
+        "layers": [
+            {
+                "ty": 1, "ind": 1, "parent": 2, "ip": 0, "op": 60, "sc": "#ff0000", "sw": 20,
+                "sh": 10,
+                "ks": {
+                    "p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [300, 100]},
+                    "r": {"k": 90}
+                }
+            },
+            {
+                "ty": 3, "ind": 2, "ip": 0, "op": 60,
+                "ks": {
+                    "p": {"k": [50, 50]}, "a": {"k": [0, 0]}, "s": {"k": [200, 100]},
+                    "r": {"k": 0}
+                }
+            }
+        ]
+    }"##;
+
+    #[test]
+    fn test_render_rotated_parent() {
+        // The solid is scaled to 60 by 10 before it's rotated to 10 by 60, and only then does its
+        // parent widen it, to the lower left of the position.
+        let lottie = Lottie::from_reader(ROTATED_PARENT.as_bytes()).unwrap();
+        let scene = lottie.render_frame(0.0);
+        let (_, outline, _) = scene.paths().next().unwrap();
+        let bounds = outline.bounds();
+        assert!((bounds.origin() - Vector2F::new(30.0, 50.0)).length() < 0.01, "{:?}", bounds);
+        assert!((bounds.size() - Vector2F::new(20.0, 60.0)).length() < 0.01);
+    }
+
+    #[test]
+    fn test_render_stretched_layer() {
+        // At frame 30 the layer, which starts at 10 and plays at half speed, is at its frame 10.
+        // The layer transform and the shapes in it both use that time.
+        let lottie = Lottie::from_reader(STRETCHED.as_bytes()).unwrap();
+        let scene = lottie.render_frame(30.0);
+        let (_, outline, _) = scene.paths().next().unwrap();
+        let bounds = outline.bounds();
+        assert!((bounds.origin() - Vector2F::new(15.0, 15.0)).length() < 0.01, "{:?}", bounds);
+        assert!((bounds.size() - Vector2F::new(10.0, 10.0)).length() < 0.01);
+    }
+}
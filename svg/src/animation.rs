@@ -0,0 +1,1230 @@
+// pathfinder/svg/src/animation.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SMIL animation: `<animate>`, `<set>`, `<animateTransform>`, and `<animateMotion>`.
+//!
+//! usvg drops animation elements, so the source document is read separately. To draw the
+//! animation at some time, the animated values are written into the attributes of the elements
+//! they target, the animation elements are removed, and the resulting static document is built
+//! like any other.
+//!
+//! Begin and end times may be offsets, or may refer to the beginning or end of other animations,
+//! as in `begin="0s; loop.end"`. Event-based times aren't supported, so animations that begin on
+//! an event never begin. Inherited property values aren't known, so an animation from the
+//! current value of an attribute that isn't set on the element starts from zero.
+
+use crate::motion::MotionPath;
+use crate::xml::{Document, Element};
+use crate::{BuildOptions, BuiltSVG};
+use pathfinder_content::color::ColorU;
+use pathfinder_geometry::util::lerp;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::scene::Scene;
+use roxmltree::Error as XmlError;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::str::{self, Utf8Error};
+use usvg::{Error as UsvgError, Options as UsvgOptions, Tree};
+
+// Bounds the work done resolving begin and end times that depend on each other.
+const MAX_RESOLUTION_PASSES: u32 = 10000;
+
+const ANIMATION_ELEMENT_NAMES: [&str; 5] =
+    ["animate", "animateColor", "animateMotion", "animateTransform", "set"];
+
+/// An SVG document with SMIL animations, which can be built into a scene at any time.
+pub struct AnimatedSVG {
+    document: Document,
+    animations: Vec<Animation>,
+    options: UsvgOptions,
+}
+
+#[derive(Debug)]
+pub enum AnimationError {
+    /// The document isn't UTF-8.
+    Utf8(Utf8Error),
+    /// The document isn't well-formed XML.
+    Xml(XmlError),
+    /// usvg couldn't build the document without its animations.
+    Usvg(UsvgError),
+}
+
+impl AnimatedSVG {
+    /// Reads an uncompressed SVG document and its animations.
+    ///
+    /// The options are used by usvg each time the document is built.
+    pub fn from_data(data: &[u8], options: &UsvgOptions) -> Result<AnimatedSVG, AnimationError> {
+        let text = str::from_utf8(data).map_err(AnimationError::Utf8)?;
+        let document = Document::parse(text).map_err(AnimationError::Xml)?;
+        let animations = collect_animations(&document);
+        let animated_svg = AnimatedSVG { document, animations, options: options.clone() };
+        animated_svg.tree_at(0.0).map_err(AnimationError::Usvg)?;
+        Ok(animated_svg)
+    }
+
+    /// The time, in seconds, after which nothing changes any more, or `None` if some animation
+    /// goes on forever.
+    pub fn duration(&self) -> Option<f32> {
+        if self.has_timing_cycle() {
+            return None;
+        }
+
+        let mut duration = 0.0;
+        for (animation, intervals) in self.animations.iter().zip(self.intervals(f32::INFINITY)) {
+            for interval in intervals {
+                // Without a simple duration, the value is the same for the whole interval.
+                let last_change = if animation.timing.simple_duration.is_finite() {
+                    interval.end
+                } else if animation.fill_freeze || interval.end.is_infinite() {
+                    interval.begin
+                } else {
+                    interval.end
+                };
+                if last_change.is_infinite() {
+                    return None;
+                }
+                duration = f32::max(duration, last_change);
+            }
+        }
+        Some(duration)
+    }
+
+    /// Writes out the document as it is at the given time, in seconds, without its animations.
+    pub fn document_at(&self, time: f32) -> String {
+        self.document_at_with_intervals(time, &self.intervals(time))
+    }
+
+    pub fn tree_at(&self, time: f32) -> Result<Tree, UsvgError> {
+        Tree::from_data(self.document_at(time).as_bytes(), &self.options)
+    }
+
+    pub fn build_at(&self, time: f32, options: BuildOptions) -> Result<BuiltSVG, UsvgError> {
+        Ok(BuiltSVG::from_tree_with_options(self.tree_at(time)?, options))
+    }
+
+    pub fn scene_at(&self, time: f32) -> Result<Scene, UsvgError> {
+        Ok(self.build_at(time, BuildOptions::default())?.scene)
+    }
+
+    /// Samples the animation `frame_rate` times a second, from the start for `duration` seconds.
+    ///
+    /// Each scene can be handed to a `SceneProxy` as it's drawn.
+    pub fn scenes(&self, frame_rate: f32, duration: f32) -> Scenes {
+        let frame_count = f32::max((duration * frame_rate).ceil(), 1.0) as usize;
+        Scenes {
+            animated_svg: self,
+            intervals: self.intervals(duration),
+            frame_rate,
+            frame_count,
+            next_frame_index: 0,
+        }
+    }
+
+    fn document_at_with_intervals(&self, time: f32, intervals: &[Vec<Interval>]) -> String {
+        // Later animations take priority: those that began later, then those later in the
+        // document.
+        let mut active: Vec<_> = self.animations.iter().zip(intervals).filter_map(|entry| {
+            let (animation, intervals) = entry;
+            animation.progress_at(intervals, time).map(|progress| (animation, progress))
+        }).collect();
+        active.sort_by(|(_, a), (_, b)| {
+            a.interval_begin.partial_cmp(&b.interval_begin).unwrap_or(Ordering::Equal)
+        });
+
+        let mut values: BTreeMap<(usize, String), String> = BTreeMap::new();
+        let mut motions: BTreeMap<usize, (Vector2F, f32)> = BTreeMap::new();
+        for (animation, progress) in active {
+            match animation.kind {
+                AnimationKind::Attribute { ref name, ref values_list, transform, ref base } => {
+                    let key = (animation.target, name.clone());
+                    let underlying = values.get(&key).cloned().or_else(|| base.clone());
+                    let value = animation.attribute_value(values_list,
+                                                          transform,
+                                                          underlying.as_deref(),
+                                                          &progress);
+                    values.insert(key, value);
+                }
+                AnimationKind::Motion { ref motion, rotate, .. } => {
+                    let (point, mut angle) = animation.motion(motion, &progress);
+                    angle = match rotate {
+                        MotionRotate::Auto => angle,
+                        MotionRotate::AutoReverse => angle + PI,
+                        MotionRotate::Angle(angle) => angle,
+                    };
+                    let underlying = motions.entry(animation.target).or_default();
+                    if animation.additive {
+                        *underlying = (underlying.0 + point, underlying.1 + angle);
+                    } else {
+                        *underlying = (point, angle);
+                    }
+                }
+            }
+        }
+
+        // Motion is applied on top of the `transform` attribute, animated or not.
+        for (target, (point, angle)) in motions {
+            let key = (target, "transform".to_owned());
+            let transform = values.get(&key).cloned().or_else(|| {
+                self.animations.iter().filter_map(|animation| {
+                    match animation.kind {
+                        AnimationKind::Motion { ref base_transform, .. }
+                                if animation.target == target => base_transform.clone(),
+                        _ => None,
+                    }
+                }).next()
+            });
+            let motion = format!("translate({} {}) rotate({})",
+                                 point.x(),
+                                 point.y(),
+                                 angle.to_degrees());
+            values.insert(key, match transform {
+                None => motion,
+                Some(transform) => format!("{} {}", motion, transform),
+            });
+        }
+
+        let mut overrides: HashMap<usize, Vec<(String, String)>> = HashMap::new();
+        for ((target, name), value) in values {
+            overrides.entry(target).or_default().push((name, value));
+        }
+        self.document.write(is_animation_element, &overrides)
+    }
+
+    // Finds the intervals during which each animation is active, up to the given time.
+    fn intervals(&self, horizon: f32) -> Vec<Vec<Interval>> {
+        let ids: HashMap<&str, usize> = self.animations.iter().enumerate().filter_map(|entry| {
+            let (animation_index, animation) = entry;
+            animation.id.as_ref().map(|id| (&**id, animation_index))
+        }).collect();
+
+        // Times that refer to other animations are resolved as their intervals become known.
+        let mut intervals = vec![vec![]; self.animations.len()];
+        for _ in 0..MAX_RESOLUTION_PASSES {
+            let next_intervals: Vec<_> = self.animations.iter().map(|animation| {
+                let timing = &animation.timing;
+                let begins = instance_times(&timing.begin, &ids, &intervals, horizon);
+                let ends = instance_times(&timing.end, &ids, &intervals, f32::INFINITY);
+                timing.intervals(&begins, &ends)
+            }).collect();
+            if next_intervals == intervals {
+                break;
+            }
+            intervals = next_intervals;
+        }
+        intervals
+    }
+
+    // Whether any animation begins or ends relative to itself, possibly through others, which
+    // makes it repeat forever.
+    fn has_timing_cycle(&self) -> bool {
+        let ids: HashMap<&str, usize> = self.animations.iter().enumerate().filter_map(|entry| {
+            let (animation_index, animation) = entry;
+            animation.id.as_ref().map(|id| (&**id, animation_index))
+        }).collect();
+        let dependencies: Vec<Vec<usize>> = self.animations.iter().map(|animation| {
+            animation.timing.begin.iter().chain(animation.timing.end.iter()).filter_map(|time| {
+                match *time {
+                    TimeValue::SyncBase { ref id, .. } => ids.get(&**id).cloned(),
+                    TimeValue::Offset(_) => None,
+                }
+            }).collect()
+        }).collect();
+
+        // Follow the dependencies from each animation, looking for a way back to it.
+        (0..self.animations.len()).any(|start| {
+            let mut visited = vec![false; self.animations.len()];
+            let mut stack = dependencies[start].clone();
+            while let Some(animation_index) = stack.pop() {
+                if animation_index == start {
+                    return true;
+                }
+                if !visited[animation_index] {
+                    visited[animation_index] = true;
+                    stack.extend_from_slice(&dependencies[animation_index]);
+                }
+            }
+            false
+        })
+    }
+}
+
+/// A lazy iterator over evenly spaced samples of an animation, yielding one scene per sample.
+pub struct Scenes<'a> {
+    animated_svg: &'a AnimatedSVG,
+    intervals: Vec<Vec<Interval>>,
+    frame_rate: f32,
+    frame_count: usize,
+    next_frame_index: usize,
+}
+
+impl<'a> Iterator for Scenes<'a> {
+    type Item = Result<Scene, UsvgError>;
+
+    fn next(&mut self) -> Option<Result<Scene, UsvgError>> {
+        if self.next_frame_index == self.frame_count {
+            return None;
+        }
+        let time = self.next_frame_index as f32 / self.frame_rate;
+        self.next_frame_index += 1;
+
+        let document = self.animated_svg.document_at_with_intervals(time, &self.intervals);
+        Some(Tree::from_data(document.as_bytes(), &self.animated_svg.options).map(|tree| {
+            BuiltSVG::from_tree(tree).scene
+        }))
+    }
+}
+
+struct Animation {
+    id: Option<String>,
+    // The number of the targeted element, counting in document order from the root at 0.
+    target: usize,
+    kind: AnimationKind,
+    timing: Timing,
+    calc_mode: CalcMode,
+    key_times: Option<Vec<f32>>,
+    key_splines: Vec<[f32; 4]>,
+    additive: bool,
+    accumulate: bool,
+    fill_freeze: bool,
+    // Whether the animation goes from the underlying value to the only one of its values.
+    to_animation: bool,
+}
+
+enum AnimationKind {
+    Attribute {
+        name: String,
+        values_list: Vec<Value>,
+        // Whether the values are transforms, which are added by composing them.
+        transform: bool,
+        // The value of the attribute in the document.
+        base: Option<String>,
+    },
+    Motion {
+        motion: Motion,
+        rotate: MotionRotate,
+        // The `transform` attribute of the target in the document.
+        base_transform: Option<String>,
+    },
+}
+
+enum Motion {
+    Path { path: MotionPath, key_points: Option<Vec<f32>> },
+    Points(Vec<Vector2F>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MotionRotate {
+    Auto,
+    AutoReverse,
+    Angle(f32),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CalcMode {
+    Discrete,
+    Linear,
+    Paced,
+    Spline,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TransformType {
+    Translate,
+    Scale,
+    Rotate,
+    SkewX,
+    SkewY,
+}
+
+// How far an animation is through its simple duration.
+struct Progress {
+    // The begin time of the current interval, which decides priority.
+    interval_begin: f32,
+    // From 0.0 to 1.0.
+    fraction: f32,
+    // The number of repeats completed.
+    iteration: f32,
+}
+
+impl Animation {
+    fn progress_at(&self, intervals: &[Interval], time: f32) -> Option<Progress> {
+        let interval = intervals.iter().rev().find(|interval| interval.begin <= time)?;
+        let frozen = time >= interval.end;
+        let elapsed = if !frozen {
+            time - interval.begin
+        } else if self.fill_freeze {
+            interval.end - interval.begin
+        } else {
+            return None;
+        };
+
+        let simple_duration = self.timing.simple_duration;
+        if !simple_duration.is_finite() {
+            return Some(Progress { interval_begin: interval.begin, fraction: 0.0, iteration: 0.0 });
+        }
+        let mut iteration = (elapsed / simple_duration).floor();
+        let mut fraction = elapsed / simple_duration - iteration;
+        // Animations frozen at the end of a repeat keep its last value.
+        if frozen && fraction == 0.0 && iteration > 0.0 {
+            iteration -= 1.0;
+            fraction = 1.0;
+        }
+        Some(Progress { interval_begin: interval.begin, fraction, iteration })
+    }
+
+    fn attribute_value(&self,
+                       values: &[Value],
+                       transform: bool,
+                       underlying: Option<&str>,
+                       progress: &Progress)
+                       -> String {
+        let from_underlying;
+        let values = if self.to_animation {
+            let to = &values[0];
+            let from = underlying.map(Value::parse).unwrap_or_else(|| to.zero());
+            from_underlying = [from, to.clone()];
+            &from_underlying[..]
+        } else {
+            values
+        };
+
+        let (index, t) = self.locate(values, progress.fraction);
+        let mut value = match values.get(index + 1) {
+            Some(next) if t > 0.0 => values[index].lerp(next, t),
+            _ => values[index].clone(),
+        };
+        if self.accumulate && !self.to_animation && progress.iteration > 0.0 {
+            value = value.add(&values[values.len() - 1].scale(progress.iteration));
+        }
+
+        match underlying {
+            Some(underlying) if self.additive && !self.to_animation => {
+                if transform {
+                    format!("{} {}", underlying, value)
+                } else {
+                    Value::parse(underlying).add(&value).to_string()
+                }
+            }
+            _ => value.to_string(),
+        }
+    }
+
+    // Returns the offset of the target from where it is, and the direction of motion.
+    fn motion(&self, motion: &Motion, progress: &Progress) -> (Vector2F, f32) {
+        let sample = |fraction: f32| {
+            match *motion {
+                Motion::Path { ref path, ref key_points } => {
+                    let fraction = match *key_points {
+                        None => fraction,
+                        Some(ref key_points) => {
+                            let (index, t) = self.locate_with(self.calc_mode,
+                                                              key_points.len(),
+                                                              fraction,
+                                                              |index| {
+                                (key_points[index + 1] - key_points[index]).abs()
+                            });
+                            let next = key_points.get(index + 1).unwrap_or(&key_points[index]);
+                            lerp(key_points[index], *next, t)
+                        }
+                    };
+                    path.sample(fraction * path.length())
+                }
+                Motion::Points(ref points) => {
+                    let (index, t) = self.locate_with(self.calc_mode,
+                                                      points.len(),
+                                                      fraction,
+                                                      |index| {
+                        (points[index + 1] - points[index]).length()
+                    });
+                    let direction = match (points.get(index + 1), index) {
+                        (Some(&next), _) => next - points[index],
+                        (None, 0) => Vector2F::default(),
+                        (None, _) => points[index] - points[index - 1],
+                    };
+                    let next = points.get(index + 1).unwrap_or(&points[index]);
+                    (points[index].lerp(*next, t), f32::atan2(direction.y(), direction.x()))
+                }
+            }
+        };
+
+        let (mut point, angle) = sample(progress.fraction);
+        if self.accumulate && progress.iteration > 0.0 {
+            point = point + sample(1.0).0.scale(progress.iteration);
+        }
+        (point, angle)
+    }
+
+    // Finds where in the values the fraction of the simple duration falls: the index of a value,
+    // and how far it is from there to the next.
+    fn locate(&self, values: &[Value], fraction: f32) -> (usize, f32) {
+        // Values that can't be interpolated are stepped through.
+        let mut calc_mode = self.calc_mode;
+        if values.windows(2).any(|pair| !pair[0].is_interpolable_with(&pair[1])) {
+            calc_mode = CalcMode::Discrete;
+        }
+        self.locate_with(calc_mode, values.len(), fraction, |index| {
+            values[index].distance(&values[index + 1])
+        })
+    }
+
+    fn locate_with<F>(&self, calc_mode: CalcMode, count: usize, fraction: f32, distance: F)
+                      -> (usize, f32)
+                      where F: Fn(usize) -> f32 {
+        if count < 2 {
+            return (0, 0.0);
+        }
+        let key_times = self.key_times.as_ref().filter(|key_times| key_times.len() == count);
+
+        if calc_mode == CalcMode::Discrete {
+            let key_times = key_times.cloned().unwrap_or_else(|| {
+                (0..count).map(|index| index as f32 / count as f32).collect()
+            });
+            let index = key_times.iter().rposition(|&time| time <= fraction).unwrap_or(0);
+            return (index, 0.0);
+        }
+
+        let evenly_spaced = || {
+            (0..count).map(|index| index as f32 / (count - 1) as f32).collect::<Vec<_>>()
+        };
+        let key_times = if calc_mode == CalcMode::Paced {
+            // Space the values by the distance between them, for a constant speed.
+            let mut total_distance = 0.0;
+            let mut key_times = vec![0.0];
+            for index in 0..(count - 1) {
+                total_distance += distance(index);
+                key_times.push(total_distance);
+            }
+            if total_distance > 0.0 {
+                key_times.iter().map(|distance| distance / total_distance).collect()
+            } else {
+                evenly_spaced()
+            }
+        } else {
+            key_times.cloned().unwrap_or_else(evenly_spaced)
+        };
+
+        let index = key_times[..(count - 1)].iter()
+                                            .rposition(|&time| time <= fraction)
+                                            .unwrap_or(0);
+        let span = key_times[index + 1] - key_times[index];
+        let mut t = if span > 0.0 {
+            ((fraction - key_times[index]) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        if calc_mode == CalcMode::Spline {
+            if let Some(key_spline) = self.key_splines.get(index) {
+                t = ease(key_spline, t);
+            }
+        }
+        (index, t)
+    }
+}
+
+// Evaluates a cubic Bézier easing curve from (0, 0) to (1, 1) with the given control points.
+fn ease(&[x1, y1, x2, y2]: &[f32; 4], x: f32) -> f32 {
+    let bezier = |p1: f32, p2: f32, t: f32| {
+        let u = 1.0 - t;
+        3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+    };
+    // The curve is monotonic in x, so bisect.
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..24 {
+        let middle = (low + high) * 0.5;
+        if bezier(x1, x2, middle) < x {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    bezier(y1, y2, (low + high) * 0.5)
+}
+
+struct Timing {
+    begin: Vec<TimeValue>,
+    end: Vec<TimeValue>,
+    // Infinite if indefinite.
+    simple_duration: f32,
+    repeat_count: Option<f32>,
+    repeat_duration: Option<f32>,
+}
+
+enum TimeValue {
+    Offset(f32),
+    // The beginning or end of another animation, with an offset.
+    SyncBase { id: String, end: bool, offset: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Interval {
+    begin: f32,
+    // Infinite if the animation is active from then on.
+    end: f32,
+}
+
+impl Timing {
+    fn active_duration(&self) -> f32 {
+        match (self.repeat_count, self.repeat_duration) {
+            (None, None) => self.simple_duration,
+            (Some(repeat_count), None) => self.simple_duration * repeat_count,
+            (None, Some(repeat_duration)) => repeat_duration,
+            (Some(repeat_count), Some(repeat_duration)) => {
+                f32::min(self.simple_duration * repeat_count, repeat_duration)
+            }
+        }
+    }
+
+    // Each begin starts an interval, which lasts until it runs its course, the next end, or the
+    // next begin, whichever comes first.
+    fn intervals(&self, begins: &[f32], ends: &[f32]) -> Vec<Interval> {
+        let active_duration = self.active_duration();
+        begins.iter().enumerate().map(|(begin_index, &begin)| {
+            let mut end = begin + active_duration;
+            if let Some(&next_begin) = begins.get(begin_index + 1) {
+                end = f32::min(end, next_begin);
+            }
+            if let Some(&end_time) = ends.iter().find(|&&end_time| end_time >= begin) {
+                end = f32::min(end, end_time);
+            }
+            Interval { begin, end }
+        }).collect()
+    }
+}
+
+// The times given by a `begin` or `end` list, sorted, given the intervals known so far.
+fn instance_times(values: &[TimeValue],
+                  ids: &HashMap<&str, usize>,
+                  intervals: &[Vec<Interval>],
+                  horizon: f32)
+                  -> Vec<f32> {
+    let mut times = vec![];
+    for value in values {
+        match *value {
+            TimeValue::Offset(offset) => times.push(offset),
+            TimeValue::SyncBase { ref id, end, offset } => {
+                let syncbase_index = match ids.get(&**id) {
+                    Some(&syncbase_index) => syncbase_index,
+                    None => continue,
+                };
+                for interval in &intervals[syncbase_index] {
+                    let time = if end { interval.end } else { interval.begin } + offset;
+                    if time.is_finite() {
+                        times.push(time);
+                    }
+                }
+            }
+        }
+    }
+    times.retain(|&time| time <= horizon);
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    times.dedup();
+    times
+}
+
+// A value of an attribute, read well enough to interpolate.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Color(ColorU),
+    // Text with numbers in it, like `10px` or `M0 0 L10 20`. The numbers go between the pieces
+    // of text, of which there is one more.
+    Numbers { text: Vec<String>, numbers: Vec<f32> },
+    // Anything else, which can only be stepped to.
+    Other(String),
+}
+
+impl Value {
+    fn parse(string: &str) -> Value {
+        if let Some(color) = ColorU::from_css(string) {
+            return Value::Color(color);
+        }
+
+        let bytes = string.as_bytes();
+        let (mut text, mut numbers) = (vec![], vec![]);
+        let (mut piece_start, mut position) = (0, 0);
+        while position < bytes.len() {
+            if number_may_start(bytes, position, position == piece_start && !numbers.is_empty()) {
+                if let Some(end) = number_end(bytes, position) {
+                    if let Ok(number) = string[position..end].parse() {
+                        text.push(string[piece_start..position].to_owned());
+                        numbers.push(number);
+                        position = end;
+                        piece_start = end;
+                        continue;
+                    }
+                }
+            }
+            position += 1;
+        }
+        if numbers.is_empty() {
+            return Value::Other(string.to_owned());
+        }
+        text.push(string[piece_start..].to_owned());
+        Value::Numbers { text, numbers }
+    }
+
+    fn transform(transform_type: TransformType, string: &str) -> Value {
+        let numbers: Vec<f32> = string.split(|character: char| {
+            character == ',' || character.is_whitespace()
+        }).filter_map(|number| number.parse().ok()).collect();
+        let number = |index: usize, default: f32| numbers.get(index).cloned().unwrap_or(default);
+        let (name, numbers) = match transform_type {
+            TransformType::Translate => ("translate", vec![number(0, 0.0), number(1, 0.0)]),
+            TransformType::Scale => ("scale", vec![number(0, 1.0), number(1, number(0, 1.0))]),
+            TransformType::Rotate => {
+                ("rotate", vec![number(0, 0.0), number(1, 0.0), number(2, 0.0)])
+            }
+            TransformType::SkewX => ("skewX", vec![number(0, 0.0)]),
+            TransformType::SkewY => ("skewY", vec![number(0, 0.0)]),
+        };
+        let mut text = vec![format!("{}(", name)];
+        text.extend((1..numbers.len()).map(|_| " ".to_owned()));
+        text.push(")".to_owned());
+        Value::Numbers { text, numbers }
+    }
+
+    fn zero(&self) -> Value {
+        match *self {
+            Value::Color(_) => Value::Color(ColorU::transparent_black()),
+            Value::Numbers { ref text, ref numbers } => {
+                Value::Numbers { text: text.clone(), numbers: vec![0.0; numbers.len()] }
+            }
+            Value::Other(_) => self.clone(),
+        }
+    }
+
+    fn is_interpolable_with(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Color(_), Value::Color(_)) => true,
+            (Value::Numbers { text: ref a, .. }, Value::Numbers { text: ref b, .. }) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| {
+                    normalize_separators(a) == normalize_separators(b)
+                })
+            }
+            _ => false,
+        }
+    }
+
+    fn lerp(&self, other: &Value, t: f32) -> Value {
+        self.combine(other, |a, b| lerp(a, b, t))
+    }
+
+    fn add(&self, other: &Value) -> Value {
+        self.combine(other, |a, b| a + b)
+    }
+
+    fn scale(&self, factor: f32) -> Value {
+        self.combine(self, |a, _| a * factor)
+    }
+
+    // Combines the numbers of two values with the same form. Otherwise, the other value wins.
+    fn combine<F>(&self, other: &Value, function: F) -> Value where F: Fn(f32, f32) -> f32 {
+        if !self.is_interpolable_with(other) {
+            return other.clone();
+        }
+        match (self, other) {
+            (Value::Color(a), Value::Color(b)) => {
+                let channel = |a: u8, b: u8| {
+                    function(a as f32, b as f32).round().clamp(0.0, 255.0) as u8
+                };
+                Value::Color(ColorU::new(channel(a.r, b.r),
+                                         channel(a.g, b.g),
+                                         channel(a.b, b.b),
+                                         channel(a.a, b.a)))
+            }
+            (Value::Numbers { ref text, numbers: ref a },
+             Value::Numbers { numbers: ref b, .. }) => {
+                Value::Numbers {
+                    text: text.clone(),
+                    numbers: a.iter().zip(b).map(|(&a, &b)| function(a, b)).collect(),
+                }
+            }
+            _ => other.clone(),
+        }
+    }
+
+    fn distance(&self, other: &Value) -> f32 {
+        match (self, other) {
+            (Value::Color(a), Value::Color(b)) => {
+                let (a, b) = (a.to_f32(), b.to_f32());
+                ((a.r() - b.r()).powi(2) + (a.g() - b.g()).powi(2) + (a.b() - b.b()).powi(2))
+                    .sqrt() * 255.0
+            }
+            (Value::Numbers { numbers: ref a, .. }, Value::Numbers { numbers: ref b, .. }) => {
+                a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            Value::Color(color) => {
+                write!(formatter, "#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+            }
+            Value::Numbers { ref text, ref numbers } => {
+                write!(formatter, "{}", text[0])?;
+                for (number, text) in numbers.iter().zip(&text[1..]) {
+                    write!(formatter, "{}{}", number, text)?;
+                }
+                Ok(())
+            }
+            Value::Other(ref string) => write!(formatter, "{}", string),
+        }
+    }
+}
+
+// Numbers may start after a separator, right after another number, or after a lone letter as
+// in path data. Digits that are part of a name, as in `url(#gradient2)`, aren't numbers.
+fn number_may_start(bytes: &[u8], position: usize, after_number: bool) -> bool {
+    let is_name_byte = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'#' || byte == b'_';
+    if position == 0 || after_number {
+        return true;
+    }
+    let previous = bytes[position - 1];
+    if !is_name_byte(previous) {
+        return true;
+    }
+    previous.is_ascii_alphabetic() && (position == 1 || !is_name_byte(bytes[position - 2]))
+}
+
+// Returns the end of the number starting at the given position, if there is one.
+fn number_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let at = |index: usize| bytes.get(index).cloned().unwrap_or(0);
+    let mut end = start;
+    if at(end) == b'+' || at(end) == b'-' {
+        end += 1;
+    }
+    let digits_start = end;
+    while at(end).is_ascii_digit() {
+        end += 1;
+    }
+    let mut digit_count = end - digits_start;
+    if at(end) == b'.' && at(end + 1).is_ascii_digit() {
+        end += 1;
+        while at(end).is_ascii_digit() {
+            end += 1;
+            digit_count += 1;
+        }
+    }
+    if digit_count == 0 {
+        return None;
+    }
+    // Units like `em` and `ex` aren't exponents.
+    if at(end) == b'e' || at(end) == b'E' {
+        let mut exponent_end = end + 1;
+        if at(exponent_end) == b'+' || at(exponent_end) == b'-' {
+            exponent_end += 1;
+        }
+        if at(exponent_end).is_ascii_digit() {
+            end = exponent_end;
+            while at(end).is_ascii_digit() {
+                end += 1;
+            }
+        }
+    }
+    Some(end)
+}
+
+fn normalize_separators(text: &str) -> String {
+    text.split(|character: char| character == ',' || character.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_animation_element(element: &Element) -> bool {
+    matches!(element.svg_name(), Some(name) if ANIMATION_ELEMENT_NAMES.contains(&name))
+}
+
+fn collect_animations(document: &Document) -> Vec<Animation> {
+    let elements = document.elements();
+    let ids: HashMap<&str, usize> = elements.iter().enumerate().filter_map(|entry| {
+        let (element_index, element) = entry;
+        element.attribute("id").map(|id| (id, element_index))
+    }).collect();
+
+    elements.iter().filter_map(|element| {
+        if !is_animation_element(element) {
+            return None;
+        }
+        let target = match element.href() {
+            Some(href) if href.starts_with('#') => *ids.get(&href[1..])?,
+            Some(_) => return None,
+            None => element.parent?,
+        };
+        parse_animation(element, target, document, &ids)
+    }).collect()
+}
+
+fn parse_animation(element: &Element,
+                   target_index: usize,
+                   document: &Document,
+                   ids: &HashMap<&str, usize>)
+                   -> Option<Animation> {
+    let target = &document.elements()[target_index];
+    let attribute = |name: &str| element.attribute(name).map(|value| value.trim().to_owned());
+    let list = |name: &str| -> Vec<String> {
+        attribute(name).map(|value| {
+            value.split(';')
+                 .map(|item| item.trim().to_owned())
+                 .filter(|item| !item.is_empty())
+                 .collect()
+        }).unwrap_or_else(Vec::new)
+    };
+
+    let element_name = element.svg_name()?;
+    let timing = Timing {
+        begin: match attribute("begin") {
+            None => vec![TimeValue::Offset(0.0)],
+            Some(_) => list("begin").iter().filter_map(|time| parse_time_value(time)).collect(),
+        },
+        end: list("end").iter().filter_map(|time| parse_time_value(time)).collect(),
+        simple_duration: attribute("dur").and_then(|duration| parse_clock_value(&duration))
+                                         .filter(|&duration| duration > 0.0)
+                                         .unwrap_or(f32::INFINITY),
+        repeat_count: attribute("repeatCount").and_then(|count| parse_repeat(&count, |count| {
+            count.parse().ok()
+        })),
+        repeat_duration: attribute("repeatDur").and_then(|duration| {
+            parse_repeat(&duration, parse_clock_value)
+        }),
+    };
+
+    let calc_mode = match attribute("calcMode").as_deref() {
+        _ if element_name == "set" => CalcMode::Discrete,
+        Some("discrete") => CalcMode::Discrete,
+        Some("linear") => CalcMode::Linear,
+        Some("paced") => CalcMode::Paced,
+        Some("spline") => CalcMode::Spline,
+        _ if element_name == "animateMotion" => CalcMode::Paced,
+        _ => CalcMode::Linear,
+    };
+    let key_times = attribute("keyTimes").map(|_| {
+        list("keyTimes").iter().filter_map(|time| time.parse().ok()).collect()
+    });
+    let key_splines = list("keySplines").iter().filter_map(|spline| {
+        let numbers: Vec<f32> = spline.split(|character: char| {
+            character == ',' || character.is_whitespace()
+        }).filter_map(|number| number.parse().ok()).collect();
+        match numbers[..] {
+            [x1, y1, x2, y2] => Some([x1, y1, x2, y2]),
+            _ => None,
+        }
+    }).collect();
+
+    let mut additive = attribute("additive").as_deref() == Some("sum");
+    let accumulate = attribute("accumulate").as_deref() == Some("sum");
+    let fill_freeze = attribute("fill").as_deref() == Some("freeze");
+
+    // Work out the values from `values`, or failing that, `from`, `to`, and `by`.
+    let (kind, to_animation) = if element_name == "animateMotion" {
+        let rotate = match attribute("rotate").as_deref() {
+            Some("auto") => MotionRotate::Auto,
+            Some("auto-reverse") => MotionRotate::AutoReverse,
+            Some(angle) => MotionRotate::Angle(angle.parse::<f32>().ok()?.to_radians()),
+            None => MotionRotate::Angle(0.0),
+        };
+
+        let mpaths = document.children(element).filter(|kid| kid.svg_name() == Some("mpath"));
+        let path_data = mpaths.filter_map(|mpath| {
+            let href = mpath.href()?;
+            let path_index = *ids.get(href.trim_start_matches('#'))?;
+            document.elements()[path_index].attribute("d").map(str::to_owned)
+        }).next().or_else(|| attribute("path"));
+        let motion = match path_data {
+            Some(path_data) => {
+                let key_points = attribute("keyPoints").map(|_| {
+                    list("keyPoints").iter().filter_map(|point| point.parse().ok()).collect()
+                });
+                Motion::Path { path: MotionPath::from_path_data(&path_data), key_points }
+            }
+            None => {
+                let (values, by) = from_to_by(&list("values"),
+                                              attribute("from"),
+                                              attribute("to"),
+                                              attribute("by"),
+                                              Value::parse);
+                additive |= by;
+                let mut points = values?.into_iter().map(|value| {
+                    match value {
+                        Value::Numbers { ref numbers, .. } if numbers.len() == 2 => {
+                            Some(Vector2F::new(numbers[0], numbers[1]))
+                        }
+                        _ => None,
+                    }
+                }).collect::<Option<Vec<_>>>()?;
+                // Motion to a point starts from where the element is.
+                if list("values").is_empty() && points.len() == 1 {
+                    points.insert(0, Vector2F::default());
+                }
+                Motion::Points(points)
+            }
+        };
+        let base_transform = target.attribute("transform").map(str::to_owned);
+        (AnimationKind::Motion { motion, rotate, base_transform }, false)
+    } else {
+        let name = attribute("attributeName")?;
+        let transform_type = if element_name == "animateTransform" {
+            Some(match attribute("type").as_deref() {
+                None | Some("translate") => TransformType::Translate,
+                Some("scale") => TransformType::Scale,
+                Some("rotate") => TransformType::Rotate,
+                Some("skewX") => TransformType::SkewX,
+                Some("skewY") => TransformType::SkewY,
+                Some(_) => return None,
+            })
+        } else {
+            None
+        };
+        let parse_value = |string: &str| {
+            match transform_type {
+                None => Value::parse(string),
+                Some(transform_type) => Value::transform(transform_type, string),
+            }
+        };
+
+        let (values, by) = from_to_by(&list("values"),
+                                      attribute("from"),
+                                      attribute("to"),
+                                      attribute("by"),
+                                      parse_value);
+        additive |= by;
+        let mut values = values?;
+        let mut to_animation = values.len() == 1 && element_name != "set" &&
+            list("values").is_empty();
+        // Transforms have no underlying value to interpolate from, so they start from the
+        // identity transform instead.
+        if transform_type.is_some() && (by || to_animation) {
+            let to = values.pop()?;
+            values = vec![parse_value(""), to];
+            to_animation = false;
+        }
+        let base = target.attribute(&name).map(|base| base.trim().to_owned()).or_else(|| {
+            target.style_property(&name)
+        });
+        (AnimationKind::Attribute {
+            name,
+            values_list: values,
+            transform: transform_type.is_some(),
+            base,
+        }, to_animation)
+    };
+
+    Some(Animation {
+        id: attribute("id"),
+        target: target_index,
+        kind,
+        timing,
+        calc_mode,
+        key_times,
+        key_splines,
+        additive,
+        accumulate,
+        fill_freeze,
+        to_animation,
+    })
+}
+
+// Works out the values of an animation. `by` animations, which also return true, add to the
+// underlying value. `to` animations return just the value to animate to.
+fn from_to_by<F>(values: &[String],
+                 from: Option<String>,
+                 to: Option<String>,
+                 by: Option<String>,
+                 parse: F)
+                 -> (Option<Vec<Value>>, bool)
+                 where F: Fn(&str) -> Value {
+    if !values.is_empty() {
+        return (Some(values.iter().map(|value| parse(value)).collect()), false);
+    }
+    let (from, to, by) = (from.map(|from| parse(&from)),
+                          to.map(|to| parse(&to)),
+                          by.map(|by| parse(&by)));
+    match (from, to, by) {
+        (Some(from), Some(to), _) => (Some(vec![from, to]), false),
+        (Some(from), None, Some(by)) => (Some(vec![from.clone(), from.add(&by)]), false),
+        (None, None, Some(by)) => (Some(vec![by.zero(), by]), true),
+        (None, Some(to), _) => (Some(vec![to]), false),
+        _ => (None, false),
+    }
+}
+
+fn parse_repeat<F>(string: &str, parse: F) -> Option<f32> where F: Fn(&str) -> Option<f32> {
+    if string == "indefinite" {
+        Some(f32::INFINITY)
+    } else {
+        parse(string).filter(|&value| value > 0.0)
+    }
+}
+
+fn parse_time_value(string: &str) -> Option<TimeValue> {
+    if let Some(offset) = parse_offset(string) {
+        return Some(TimeValue::Offset(offset));
+    }
+    for &(keyword, end) in &[(".begin", false), (".end", true)] {
+        if let Some(keyword_index) = string.rfind(keyword) {
+            let rest = string[(keyword_index + keyword.len())..].trim();
+            let offset = if rest.is_empty() { 0.0 } else { parse_offset(rest)? };
+            let id = string[..keyword_index].trim().to_owned();
+            return Some(TimeValue::SyncBase { id, end, offset });
+        }
+    }
+    None
+}
+
+fn parse_offset(string: &str) -> Option<f32> {
+    let string = string.trim();
+    match string.strip_prefix('-') {
+        Some(string) => parse_clock_value(string).map(|offset| -offset),
+        None => parse_clock_value(string.strip_prefix('+').unwrap_or(string)),
+    }
+}
+
+// Parses a clock value, like `02:30`, `1.5s`, or `200ms`, into seconds.
+fn parse_clock_value(string: &str) -> Option<f32> {
+    let string = string.trim();
+    if string.contains(':') {
+        let mut seconds = 0.0;
+        for part in string.split(':') {
+            seconds = seconds * 60.0 + part.trim().parse::<f32>().ok()?;
+        }
+        return Some(seconds);
+    }
+    for &(suffix, scale) in &[("ms", 0.001), ("min", 60.0), ("h", 3600.0), ("s", 1.0)] {
+        if let Some(value) = string.strip_suffix(suffix) {
+            return value.trim().parse::<f32>().ok().map(|value| value * scale);
+        }
+    }
+    string.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnimatedSVG, AnimationError};
+    use crate::xml::Document;
+    use usvg::Options as UsvgOptions;
+
+    fn animate(body: &str) -> AnimatedSVG {
+        let source = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                              xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+                              width=\"100\" height=\"100\">{}</svg>",
+                             body);
+        AnimatedSVG::from_data(source.as_bytes(), &UsvgOptions::default()).unwrap()
+    }
+
+    // Returns the value of an attribute of the element with the given ID at the given time.
+    fn attribute_at(svg: &AnimatedSVG, time: f32, id: &str, name: &str) -> Option<String> {
+        let document = Document::parse(&svg.document_at(time)).unwrap();
+        let element = document.elements().iter().find(|element| {
+            element.attribute("id") == Some(id)
+        }).unwrap();
+        element.attribute(name).map(str::to_owned)
+    }
+
+    fn number_at(svg: &AnimatedSVG, time: f32, id: &str, name: &str) -> f32 {
+        attribute_at(svg, time, id, name).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_animate_with_key_times() {
+        let svg = animate("<rect id=\"r\" width=\"10\" height=\"10\">\
+                           <animate attributeName=\"x\" values=\"0;10;30\" keyTimes=\"0;0.5;1\" \
+                                    dur=\"2s\" fill=\"freeze\"/>\
+                           </rect>");
+        assert_eq!(svg.duration(), Some(2.0));
+        assert_eq!(number_at(&svg, 0.5, "r", "x"), 5.0);
+        assert_eq!(number_at(&svg, 1.5, "r", "x"), 20.0);
+        assert_eq!(number_at(&svg, 3.0, "r", "x"), 30.0);
+        assert!(!svg.document_at(0.0).contains("animate"));
+    }
+
+    #[test]
+    fn test_animate_with_key_splines() {
+        let svg = animate("<rect id=\"r\" width=\"10\" height=\"10\" x=\"7\">\
+                           <animate attributeName=\"x\" values=\"0;100\" calcMode=\"spline\" \
+                                    keySplines=\"0.42 0 0.58 1\" dur=\"1s\"/>\
+                           </rect>");
+        // The ease-in-out curve is symmetric about its middle, and slow at the ends.
+        assert!((number_at(&svg, 0.5, "r", "x") - 50.0).abs() < 0.01);
+        let x = number_at(&svg, 0.25, "r", "x");
+        assert!((x - 12.9).abs() < 0.1, "{}", x);
+        let x = number_at(&svg, 0.75, "r", "x");
+        assert!((x - 87.1).abs() < 0.1, "{}", x);
+        // Without `fill="freeze"`, the document value comes back at the end.
+        assert_eq!(number_at(&svg, 1.0, "r", "x"), 7.0);
+    }
+
+    #[test]
+    fn test_animate_transform() {
+        let svg = animate("<g id=\"g\" transform=\"scale(2)\">\
+                           <animateTransform attributeName=\"transform\" type=\"rotate\" \
+                                             from=\"0 5 5\" to=\"90 5 5\" dur=\"1s\" \
+                                             additive=\"sum\" repeatCount=\"2\"/>\
+                           </g>");
+        assert_eq!(svg.duration(), Some(2.0));
+        assert_eq!(attribute_at(&svg, 0.5, "g", "transform").unwrap(),
+                   "scale(2) rotate(45 5 5)");
+        assert_eq!(attribute_at(&svg, 1.25, "g", "transform").unwrap(),
+                   "scale(2) rotate(22.5 5 5)");
+        assert_eq!(attribute_at(&svg, 2.5, "g", "transform").unwrap(), "scale(2)");
+    }
+
+    #[test]
+    fn test_animate_motion_with_key_points() {
+        let svg = animate("<circle id=\"c\" r=\"1\">\
+                           <animateMotion path=\"M0 0 H100\" keyPoints=\"0;0.25;1\" \
+                                          keyTimes=\"0;0.5;1\" calcMode=\"linear\" dur=\"1s\"/>\
+                           </circle>");
+        assert_eq!(attribute_at(&svg, 0.25, "c", "transform").unwrap(),
+                   "translate(12.5 0) rotate(0)");
+        assert_eq!(attribute_at(&svg, 0.75, "c", "transform").unwrap(),
+                   "translate(62.5 0) rotate(0)");
+    }
+
+    #[test]
+    fn test_animate_motion_along_mpath() {
+        let svg = animate("<path id=\"p\" d=\"M0 0 V100\"/>\
+                           <rect id=\"r\" width=\"1\" height=\"1\" transform=\"scale(2)\">\
+                           <animateMotion dur=\"2s\" rotate=\"auto\">\
+                           <mpath xlink:href=\"#p\"/>\
+                           </animateMotion>\
+                           </rect>");
+        // Motion goes on top of the transform of the element.
+        assert_eq!(attribute_at(&svg, 1.0, "r", "transform").unwrap(),
+                   "translate(0 50) rotate(90) scale(2)");
+    }
+
+    #[test]
+    fn test_sync_base_begin() {
+        let svg = animate("<rect id=\"r\" width=\"10\" height=\"10\">\
+                           <animate id=\"a\" attributeName=\"x\" to=\"10\" dur=\"1s\"/>\
+                           <set attributeName=\"y\" to=\"5\" begin=\"a.end + 0.5s\"/>\
+                           </rect>");
+        assert_eq!(attribute_at(&svg, 1.25, "r", "y"), None);
+        assert_eq!(number_at(&svg, 1.5, "r", "y"), 5.0);
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let options = UsvgOptions::default();
+        match AnimatedSVG::from_data(b"<svg><rect></svg>", &options) {
+            Err(AnimationError::Xml(_)) => {}
+            _ => panic!("mismatched end tag accepted"),
+        }
+        match AnimatedSVG::from_data(b"<svg>\xff</svg>", &options) {
+            Err(AnimationError::Utf8(_)) => {}
+            _ => panic!("invalid UTF-8 accepted"),
+        }
+    }
+}
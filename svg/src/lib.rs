@@ -13,10 +13,14 @@
 #[macro_use]
 extern crate bitflags;
 
+use crate::text::TextRenderer;
+use font_kit::source::Source;
 use pathfinder_content::color::ColorU;
+use pathfinder_content::marker::MarkerVertex;
 use pathfinder_content::outline::Outline;
 use pathfinder_content::segment::{Segment, SegmentFlags};
 use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_content::stroke::StrokeTransform;
 use pathfinder_content::transform::Transform2DFPathIter;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::RectF;
@@ -24,17 +28,225 @@ use pathfinder_geometry::transform2d::Transform2DF;
 use pathfinder_geometry::vector::Vector2F;
 use pathfinder_renderer::paint::Paint;
 use pathfinder_renderer::scene::{PathObject, Scene};
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
 use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::iter;
 use std::mem;
-use usvg::{Color as SvgColor, LineCap as UsvgLineCap, LineJoin as UsvgLineJoin, Node, NodeExt};
-use usvg::{NodeKind, Opacity, Paint as UsvgPaint, PathSegment as UsvgPathSegment};
-use usvg::{Rect as UsvgRect, Transform as UsvgTransform, Tree, Visibility};
+use usvg::{Align, AspectRatio, Color as SvgColor, LineCap as UsvgLineCap};
+use usvg::{LineJoin as UsvgLineJoin, Node, NodeExt};
+use usvg::{NodeKind, Opacity, Options as UsvgOptions, Paint as UsvgPaint};
+use usvg::{PathSegment as UsvgPathSegment, Rect as UsvgRect, Transform as UsvgTransform, Tree};
+use usvg::{Units, Visibility};
+
+pub use crate::animation::{AnimatedSVG, AnimationError, Scenes};
+pub use crate::source::SourceError;
+pub use crate::text::{TextAnchor, TextElement, TextSpan, TextStroke};
+
+mod animation;
+mod mask;
+mod motion;
+mod source;
+mod text;
+mod xml;
 
 const HAIRLINE_STROKE_WIDTH: f32 = 0.0333;
 
+const DEFAULT_DPI: f32 = 96.0;
+
 pub struct BuiltSVG {
     pub scene: Scene,
+    /// A summary of the unsupported features found.
     pub result_flags: BuildResultFlags,
+    /// Each use of an unsupported feature, in the order found.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The node each path of the scene was drawn for, indexed like the paths.
+    pub path_sources: Vec<PathSource>,
+    source_attributes: SourceAttributes,
+    text_renderer: TextRenderer,
+    // The `<mask>` elements and their paths in the tree, by ID.
+    masks: HashMap<String, (Node, String)>,
+    // The IDs of the text elements that have been drawn.
+    drawn_text_ids: HashSet<String>,
+    // The scenes set aside while masked groups and their masks are drawn into their own.
+    saved_scenes: Vec<SavedScene>,
+    stop_at_unsupported: bool,
+}
+
+/// The node a path of the scene was drawn for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathSource {
+    /// The ID of the node, which is empty if it has none.
+    pub node_id: String,
+    /// Where the node is in the tree usvg built, as in `Diagnostic::node_path`. `None` for text
+    /// whose node usvg dropped.
+    pub node_path: Option<String>,
+    pub role: PathRole,
+}
+
+impl PathSource {
+    fn new(node: &Node, path: &str, role: PathRole) -> PathSource {
+        PathSource { node_id: node.id().to_string(), node_path: Some(path.to_owned()), role }
+    }
+}
+
+/// What part of its node a path draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathRole {
+    Fill,
+    Stroke,
+    /// A glyph of a `<text>` element.
+    Text,
+}
+
+/// A use of a feature that couldn't be drawn as specified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// The feature, as one of the `BuildResultFlags`.
+    pub feature: BuildResultFlags,
+    /// The ID of the node, which is empty if it has none.
+    pub node_id: String,
+    /// Where the node is in the tree usvg built, such as `/svg/g[0]/path[2]`, where the numbers
+    /// are the indices of the nodes among their siblings. `None` if the node isn't in the tree.
+    pub node_path: Option<String>,
+    pub handling: Handling,
+}
+
+/// What was drawn in place of an unsupported feature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handling {
+    /// Nothing was drawn for it.
+    Skipped,
+    /// It was drawn without the feature, or with a simpler stand-in.
+    Approximated,
+    /// Building stopped there, as `BuildOptions::stop_at_unsupported` asks.
+    Error,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        let handling = match self.handling {
+            Handling::Skipped => "skipped",
+            Handling::Approximated => "approximated",
+            Handling::Error => "error",
+        };
+        write!(formatter, "{} {}", self.feature, handling)?;
+        if !self.node_id.is_empty() {
+            write!(formatter, " in #{}", self.node_id)?;
+        }
+        if let Some(ref node_path) = self.node_path {
+            write!(formatter, " at {}", node_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Information from the source document that usvg doesn't preserve, keyed by node ID.
+///
+/// `BuiltSVG::from_data` reads these from the source document. Callers that build a tree
+/// themselves must collect them themselves.
+#[derive(Clone, Default)]
+pub struct SourceAttributes {
+    /// The IDs of the paths that have `vector-effect="non-scaling-stroke"` set.
+    pub non_scaling_stroke_ids: HashSet<String>,
+    /// The markers of each path.
+    pub markers: HashMap<String, PathMarkers>,
+    /// The `<text>` elements, which are laid out and drawn in place of the node with the same
+    /// ID. Elements with no such node, because usvg dropped them, are drawn on top.
+    pub texts: HashMap<String, TextElement>,
+}
+
+/// The `marker-start`, `marker-mid`, and `marker-end` properties of a path.
+#[derive(Clone, Default)]
+pub struct PathMarkers {
+    pub start: Option<Marker>,
+    pub mid: Option<Marker>,
+    pub end: Option<Marker>,
+}
+
+/// A `<marker>` element.
+///
+/// Marker content isn't clipped to the marker viewport, as if `overflow="visible"` were set.
+#[derive(Clone)]
+pub struct Marker {
+    /// A node whose children are the content of the marker. The node is kept whole, so it can be
+    /// in the tree being built.
+    pub content: Node,
+    /// The `viewBox` attribute. The content is scaled uniformly to fit it into the marker
+    /// size, as with the default `preserveAspectRatio`.
+    pub view_box: Option<RectF>,
+    /// The `markerWidth` and `markerHeight` attributes.
+    pub size: Vector2F,
+    /// The `refX` and `refY` attributes, in the coordinate system of the content.
+    pub ref_point: Vector2F,
+    pub units: MarkerUnits,
+    pub orient: MarkerOrient,
+}
+
+/// The `markerUnits` attribute.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarkerUnits {
+    StrokeWidth,
+    UserSpaceOnUse,
+}
+
+/// The `orient` attribute.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarkerOrient {
+    Auto,
+    AutoStartReverse,
+    /// A fixed angle, in radians.
+    Angle(f32),
+}
+
+/// How to place the document in the scene.
+pub struct BuildOptions {
+    /// A transform applied to the whole document, after it has been fitted to `size`.
+    pub transform: Transform2DF,
+    /// The size, in pixels, to fit the view box into. If `None`, the document keeps the
+    /// coordinates of its view box, scaled by the DPI.
+    pub size: Option<Vector2F>,
+    /// Overrides the `preserveAspectRatio` attribute of the document when fitting it to `size`.
+    ///
+    /// Content outside the size isn't clipped when slicing.
+    pub preserve_aspect_ratio: Option<AspectRatio>,
+    /// The resolution to draw at when no size is given. User units are CSS pixels, which are
+    /// 96 to the inch.
+    pub dpi: f32,
+    pub view_box: ViewBoxOption,
+    pub source_attributes: SourceAttributes,
+    /// Where to find fonts for text. If `None`, the fonts installed on the system are used, and
+    /// they're only looked for if there's text to draw.
+    pub font_source: Option<Box<dyn Source>>,
+    /// Stops building at the first unsupported feature, which is recorded with
+    /// `Handling::Error`. What was drawn before it is kept.
+    pub stop_at_unsupported: bool,
+}
+
+impl Default for BuildOptions {
+    fn default() -> BuildOptions {
+        BuildOptions {
+            transform: Transform2DF::default(),
+            size: None,
+            preserve_aspect_ratio: None,
+            dpi: DEFAULT_DPI,
+            view_box: ViewBoxOption::Document,
+            source_attributes: SourceAttributes::default(),
+            font_source: None,
+            stop_at_unsupported: false,
+        }
+    }
+}
+
+/// Which rectangle of user space is shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewBoxOption {
+    /// The `viewBox` of the document.
+    Document,
+    /// The rectangle from the origin to the `width` and `height` of the document, as if it had no
+    /// `viewBox`.
+    Ignore,
+    Override(RectF),
 }
 
 bitflags! {
@@ -60,63 +272,121 @@ bitflags! {
 }
 
 impl BuiltSVG {
-    // TODO(pcwalton): Allow a global transform to be set.
-    pub fn from_tree(tree: Tree) -> BuiltSVG {
-        let global_transform = Transform2DF::default();
+    /// Reads an SVG document and builds it.
+    ///
+    /// The markers, non-scaling strokes, and text, which usvg drops or converts to paths, are read
+    /// from the source document and added to the source attributes of the options. Markers and
+    /// text the options already give for a node are kept. Compressed `.svgz` documents are built
+    /// by usvg alone, without them.
+    pub fn from_data(data: &[u8], usvg_options: &UsvgOptions, mut options: BuildOptions)
+                     -> Result<BuiltSVG, SourceError> {
+        let (tree, source_attributes) = source::read(data, usvg_options)?;
+        options.source_attributes
+               .non_scaling_stroke_ids
+               .extend(source_attributes.non_scaling_stroke_ids);
+        for (id, markers) in source_attributes.markers {
+            options.source_attributes.markers.entry(id).or_insert(markers);
+        }
+        for (id, text) in source_attributes.texts {
+            options.source_attributes.texts.entry(id).or_insert(text);
+        }
+        Ok(BuiltSVG::from_tree_with_options(tree, options))
+    }
 
-        let mut built_svg = BuiltSVG {
-            scene: Scene::new(),
-            result_flags: BuildResultFlags::empty(),
-        };
+    pub fn from_tree(tree: Tree) -> BuiltSVG {
+        BuiltSVG::from_tree_with_source_attributes(tree, SourceAttributes::default())
+    }
 
-        let root = &tree.root();
-        match *root.borrow() {
-            NodeKind::Svg(ref svg) => {
-                built_svg.scene.set_view_box(usvg_rect_to_euclid_rect(&svg.view_box.rect));
-                for kid in root.children() {
-                    built_svg.process_node(&kid, &global_transform);
-                }
-            }
-            _ => unreachable!(),
-        };
+    /// Builds the tree, applying the attributes that usvg dropped from the source document.
+    ///
+    /// Text is drawn with the fonts installed on the system.
+    pub fn from_tree_with_source_attributes(tree: Tree, source_attributes: SourceAttributes)
+                                            -> BuiltSVG {
+        BuiltSVG::from_tree_with_options(tree, BuildOptions {
+            source_attributes,
+            ..BuildOptions::default()
+        })
+    }
 
-        // FIXME(pcwalton): This is needed to avoid stack exhaustion in debug builds when
-        // recursively dropping reference counts on very large SVGs. :(
-        mem::forget(tree);
+    /// Builds the tree, drawing text with fonts from the given source.
+    pub fn from_tree_with_font_source(tree: Tree,
+                                      source_attributes: SourceAttributes,
+                                      font_source: Box<dyn Source>)
+                                      -> BuiltSVG {
+        BuiltSVG::from_tree_with_options(tree, BuildOptions {
+            source_attributes,
+            font_source: Some(font_source),
+            ..BuildOptions::default()
+        })
+    }
 
-        built_svg
+    /// Builds the tree, placing it in the scene as the options describe.
+    pub fn from_tree_with_options(tree: Tree, options: BuildOptions) -> BuiltSVG {
+        SVGBuilder::new(tree, options).finish()
     }
 
-    fn process_node(&mut self, node: &Node, transform: &Transform2DF) {
+    // Draws the node, and queues its children to be drawn after it. Returns true if the children
+    // were queued.
+    fn process_node(&mut self,
+                    node: &Node,
+                    tree_path: &str,
+                    transform: &Transform2DF,
+                    in_marker: bool,
+                    release: bool,
+                    stack: &mut Vec<Work>)
+                    -> bool {
         let node_transform = usvg_transform_to_transform_2d(&node.transform());
-        let transform = transform.pre_mul(&node_transform);
+        let transform = node_transform.then(transform);
+
+        // usvg converts text to paths itself, so draw our own layout instead.
+        let text = self.source_attributes.texts.get(&*node.id()).cloned();
+        if let Some(text) = text {
+            self.drawn_text_ids.insert(node.id().to_string());
+            self.process_text(&text, &node.id(), Some(tree_path.to_owned()), &transform);
+            return false;
+        }
 
         match *node.borrow() {
             NodeKind::Group(ref group) => {
                 if group.clip_path.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_ATTR);
+                    self.report(node, tree_path,
+                                BuildResultFlags::UNSUPPORTED_CLIP_PATH_ATTR,
+                                Handling::Approximated);
                 }
                 if group.filter.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_FILTER_ATTR);
-                }
-                if group.mask.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
+                    self.report(node, tree_path,
+                                BuildResultFlags::UNSUPPORTED_FILTER_ATTR,
+                                Handling::Approximated);
                 }
 
-                for kid in node.children() {
-                    self.process_node(&kid, &transform)
+                let mask = group.mask.as_ref().and_then(|mask_id| self.masks.get(mask_id));
+                match mask.cloned() {
+                    None => {
+                        if group.mask.is_some() {
+                            self.report(node, tree_path,
+                                        BuildResultFlags::UNSUPPORTED_MASK_ATTR,
+                                        Handling::Approximated);
+                        }
+                    }
+                    Some(mask) => {
+                        // Draw the content into its own scene, so that it can be masked.
+                        let (mask, mask_path) = mask;
+                        self.set_scene_aside();
+                        stack.push(Work::DrawMask { mask, mask_path, transform, in_marker });
+                    }
                 }
+                queue_children(node, tree_path, &transform, in_marker, release, stack);
+                return true;
             }
             NodeKind::Path(ref path) if path.visibility == Visibility::Visible => {
                 if let Some(ref fill) = path.fill {
-                    let style = self.scene.push_paint(&Paint::from_svg_paint(
-                        &fill.paint,
-                        fill.opacity,
-                        &mut self.result_flags,
-                    ));
+                    if let UsvgPaint::Link(_) = fill.paint {
+                        self.report(node, tree_path,
+                                    BuildResultFlags::UNSUPPORTED_LINK_PAINT,
+                                    Handling::Approximated);
+                    }
+                    let style = self.scene.push_paint(&Paint::from_svg_paint(&fill.paint,
+                                                                             fill.opacity));
 
                     let path = UsvgPathToSegments::new(path.segments.iter().cloned());
                     let path = Transform2DFPathIter::new(path, &transform);
@@ -124,14 +394,17 @@ impl BuiltSVG {
 
                     let name = format!("Fill({})", node.id());
                     self.scene.push_path(PathObject::new(outline, style, name));
+                    self.path_sources.push(PathSource::new(node, tree_path, PathRole::Fill));
                 }
 
                 if let Some(ref stroke) = path.stroke {
-                    let style = self.scene.push_paint(&Paint::from_svg_paint(
-                        &stroke.paint,
-                        stroke.opacity,
-                        &mut self.result_flags,
-                    ));
+                    if let UsvgPaint::Link(_) = stroke.paint {
+                        self.report(node, tree_path,
+                                    BuildResultFlags::UNSUPPORTED_LINK_PAINT,
+                                    Handling::Approximated);
+                    }
+                    let style = self.scene.push_paint(&Paint::from_svg_paint(&stroke.paint,
+                                                                             stroke.opacity));
 
                     let stroke_style = StrokeStyle {
                         line_width: f32::max(stroke.width.value() as f32, HAIRLINE_STROKE_WIDTH),
@@ -143,56 +416,565 @@ impl BuiltSVG {
                     let path = UsvgPathToSegments::new(path.segments.iter().cloned());
                     let outline = Outline::from_segments(path);
 
+                    let non_scaling_stroke_ids = &self.source_attributes.non_scaling_stroke_ids;
+                    let stroke_transform = if non_scaling_stroke_ids.contains(&*node.id()) {
+                        StrokeTransform::NonScaling
+                    } else {
+                        StrokeTransform::Scaling
+                    };
+
                     let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+                    stroke_to_fill.set_transform(&transform, stroke_transform);
                     stroke_to_fill.offset();
-                    let mut outline = stroke_to_fill.into_outline();
-                    outline.transform(&transform);
+                    let outline = stroke_to_fill.into_outline();
 
                     let name = format!("Stroke({})", node.id());
                     self.scene.push_path(PathObject::new(outline, style, name));
+                    self.path_sources.push(PathSource::new(node, tree_path, PathRole::Stroke));
+                }
+
+                // Markers aren't drawn inside marker content, which would otherwise recurse
+                // without bound.
+                let markers = if in_marker {
+                    None
+                } else {
+                    self.source_attributes.markers.get(&*node.id()).cloned()
+                };
+                if let Some(markers) = markers {
+                    let stroke_width = match path.stroke {
+                        Some(ref stroke) => stroke.width.value() as f32,
+                        None => 1.0,
+                    };
+                    let path = UsvgPathToSegments::new(path.segments.iter().cloned());
+                    let outline = Outline::from_segments(path);
+                    process_markers(&markers, &outline, stroke_width, &transform, stack);
                 }
             }
             NodeKind::Path(..) => {}
             NodeKind::ClipPath(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_CLIP_PATH_NODE);
+                self.report(node, tree_path, BuildResultFlags::UNSUPPORTED_CLIP_PATH_NODE,
+                            Handling::Skipped);
             }
             NodeKind::Defs { .. } => {
-                if node.has_children() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_DEFS_NODE);
+                // Masks are drawn where they're referenced.
+                if !node.children().all(|kid| is_mask(&kid)) {
+                    self.report(node, tree_path, BuildResultFlags::UNSUPPORTED_DEFS_NODE,
+                                Handling::Skipped);
                 }
             }
             NodeKind::Filter(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_FILTER_NODE);
+                self.report(node, tree_path, BuildResultFlags::UNSUPPORTED_FILTER_NODE,
+                            Handling::Skipped);
             }
             NodeKind::Image(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_IMAGE_NODE);
+                self.report(node, tree_path, BuildResultFlags::UNSUPPORTED_IMAGE_NODE,
+                            Handling::Skipped);
             }
             NodeKind::LinearGradient(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_LINEAR_GRADIENT_NODE);
-            }
-            NodeKind::Mask(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_MASK_NODE);
+                self.report(node, tree_path,
+                            BuildResultFlags::UNSUPPORTED_LINEAR_GRADIENT_NODE,
+                            Handling::Skipped);
             }
+            NodeKind::Mask(..) => {}
             NodeKind::Pattern(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_PATTERN_NODE);
+                self.report(node, tree_path, BuildResultFlags::UNSUPPORTED_PATTERN_NODE,
+                            Handling::Skipped);
             }
             NodeKind::RadialGradient(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_RADIAL_GRADIENT_NODE);
+                self.report(node, tree_path,
+                            BuildResultFlags::UNSUPPORTED_RADIAL_GRADIENT_NODE,
+                            Handling::Skipped);
             }
             NodeKind::Svg(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_NESTED_SVG_NODE);
+                self.report(node, tree_path, BuildResultFlags::UNSUPPORTED_NESTED_SVG_NODE,
+                            Handling::Skipped);
             }
         }
+        false
     }
+
+    // Queues the mask content to be drawn, now that the content of the masked group has been
+    // drawn into the current scene.
+    fn draw_mask(&mut self,
+                 mask_node: Node,
+                 mask_path: &str,
+                 transform: Transform2DF,
+                 in_marker: bool,
+                 stack: &mut Vec<Work>) {
+        let content = self.restore_scene();
+        let (units, content_units, rect) = match *mask_node.borrow() {
+            NodeKind::Mask(ref mask) => (mask.units, mask.content_units, mask.rect),
+            _ => unreachable!(),
+        };
+
+        // NOTE: The bounding box is that of the drawn content, strokes included, mapped back to
+        // user space, which overestimates it when the group is rotated.
+        let bounding_box = match mask::bounds(&content.scene) {
+            Some(bounds) => transform.inverse().transform_rect(&bounds),
+            None => return,
+        };
+        let bounding_box_transform = Transform2DF::from_scale(bounding_box.size())
+            .post_translate(bounding_box.origin());
+
+        let mut clip_rect = usvg_rect_to_euclid_rect(&rect);
+        if units == Units::ObjectBoundingBox {
+            clip_rect = bounding_box_transform.transform_rect(&clip_rect);
+        }
+        let mut clip = Outline::from_rect(clip_rect);
+        clip.transform(&transform);
+
+        let content_transform = match content_units {
+            Units::ObjectBoundingBox => bounding_box_transform.then(&transform),
+            Units::UserSpaceOnUse => transform,
+        };
+
+        // Masks inside the mask content are applied as it's drawn.
+        self.set_scene_aside();
+        stack.push(Work::ApplyMask {
+            content,
+            clip,
+            mask: mask_node.clone(),
+            mask_path: mask_path.to_owned(),
+            first_diagnostic: self.diagnostics.len(),
+        });
+        queue_children(&mask_node, mask_path, &content_transform, in_marker, false, stack);
+    }
+
+    // Masks the content of a group with the mask content drawn into the current scene.
+    fn apply_mask(&mut self,
+                  content: SavedScene,
+                  clip: Outline,
+                  mask_node: &Node,
+                  mask_path: &str,
+                  first_diagnostic: usize) {
+        let mask = self.restore_scene();
+        let mask::Masked { scene, source_indices, approximated } =
+            mask::apply_mask(&content.scene, &mask.scene, &clip);
+        for ((paint, outline, name), source_index) in scene.paths().zip(source_indices) {
+            let paint_id = self.scene.push_paint(paint);
+            self.scene.push_path(PathObject::new(outline.clone(), paint_id, name.to_owned()));
+            self.path_sources.push(content.path_sources[source_index].clone());
+        }
+
+        // Gradients in the mask content are drawn as solid colors, so their luminance is
+        // approximated too.
+        let has_gradients = self.diagnostics[first_diagnostic..].iter().any(|diagnostic| {
+            diagnostic.feature == BuildResultFlags::UNSUPPORTED_LINK_PAINT
+        });
+        if approximated || has_gradients {
+            self.report(mask_node, mask_path, BuildResultFlags::UNSUPPORTED_MASK_NODE,
+                        Handling::Approximated);
+        }
+    }
+
+    // Sets the scene being built aside and starts drawing into an empty one.
+    fn set_scene_aside(&mut self) {
+        self.saved_scenes.push(SavedScene {
+            scene: mem::replace(&mut self.scene, Scene::new()),
+            path_sources: mem::replace(&mut self.path_sources, vec![]),
+        });
+    }
+
+    // Goes back to drawing into the scene set aside last, and returns the current one.
+    fn restore_scene(&mut self) -> SavedScene {
+        let saved = self.saved_scenes.pop().unwrap();
+        SavedScene {
+            scene: mem::replace(&mut self.scene, saved.scene),
+            path_sources: mem::replace(&mut self.path_sources, saved.path_sources),
+        }
+    }
+
+    fn process_text(&mut self,
+                    text: &TextElement,
+                    node_id: &str,
+                    node_path: Option<String>,
+                    transform: &Transform2DF) {
+        let drawn_all = self.text_renderer.draw_text(text, transform, &mut self.scene);
+        let source = PathSource {
+            node_id: node_id.to_owned(),
+            node_path: node_path.clone(),
+            role: PathRole::Text,
+        };
+        let glyph_path_count = self.scene.path_count() - self.path_sources.len();
+        self.path_sources.extend(iter::repeat(source).take(glyph_path_count));
+
+        if !drawn_all {
+            self.push_diagnostic(Diagnostic {
+                feature: BuildResultFlags::UNSUPPORTED_TEXT_NODE,
+                node_id: node_id.to_owned(),
+                node_path,
+                handling: Handling::Approximated,
+            });
+        }
+    }
+
+    fn report(&mut self, node: &Node, path: &str, feature: BuildResultFlags, handling: Handling) {
+        self.push_diagnostic(Diagnostic {
+            feature,
+            node_id: node.id().to_string(),
+            node_path: Some(path.to_owned()),
+            handling,
+        });
+    }
+
+    fn push_diagnostic(&mut self, mut diagnostic: Diagnostic) {
+        if self.stop_at_unsupported {
+            diagnostic.handling = Handling::Error;
+        }
+        self.result_flags.insert(diagnostic.feature);
+        self.diagnostics.push(diagnostic);
+    }
+
+    // Whether an unsupported feature stopped the build.
+    fn stopped(&self) -> bool {
+        matches!(self.diagnostics.last(),
+                 Some(diagnostic) if diagnostic.handling == Handling::Error)
+    }
+
+    // Goes back to the scene that was set aside first, dropping whatever was being drawn for
+    // masked groups.
+    fn restore_outermost_scene(&mut self) {
+        while !self.saved_scenes.is_empty() {
+            self.restore_scene();
+        }
+    }
+}
+
+/// Builds a scene from a tree a number of nodes at a time.
+///
+/// The tree is walked without recursion, and each node is let go of once it has been drawn, so
+/// memory is freed as the build goes and very large documents don't exhaust the stack.
+pub struct SVGBuilder {
+    built_svg: BuiltSVG,
+    tree: Tree,
+    stack: Vec<Work>,
+    global_transform: Transform2DF,
+    // The content nodes of the markers, which are drawn wherever they're used and so are never
+    // let go of while building.
+    marker_contents: Vec<Node>,
+}
+
+// A step of building the scene. Steps are taken from the top of a stack.
+enum Work {
+    // Nodes carry their paths in the tree, since the nodes drawn before them have been detached.
+    Node { node: Node, path: String, transform: Transform2DF, in_marker: bool, release: bool },
+    // Draws the mask of a group, once the content of the group has been drawn.
+    DrawMask { mask: Node, mask_path: String, transform: Transform2DF, in_marker: bool },
+    // Masks the content of a group, once its mask has been drawn. The diagnostics from
+    // `first_diagnostic` on were found while drawing the mask.
+    ApplyMask {
+        content: SavedScene,
+        clip: Outline,
+        mask: Node,
+        mask_path: String,
+        first_diagnostic: usize,
+    },
+}
+
+// A scene that was set aside, along with the sources of its paths.
+struct SavedScene {
+    scene: Scene,
+    path_sources: Vec<PathSource>,
+}
+
+impl SVGBuilder {
+    pub fn new(tree: Tree, options: BuildOptions) -> SVGBuilder {
+        let BuildOptions {
+            transform,
+            size,
+            preserve_aspect_ratio,
+            dpi,
+            view_box,
+            source_attributes,
+            font_source,
+            stop_at_unsupported,
+        } = options;
+
+        let mut built_svg = BuiltSVG {
+            scene: Scene::new(),
+            result_flags: BuildResultFlags::empty(),
+            diagnostics: vec![],
+            path_sources: vec![],
+            source_attributes,
+            text_renderer: TextRenderer::new(font_source),
+            drawn_text_ids: HashSet::new(),
+            masks: HashMap::new(),
+            saved_scenes: vec![],
+            stop_at_unsupported,
+        };
+
+        for kid in tree.defs().children() {
+            if let NodeKind::Mask(ref mask) = *kid.borrow() {
+                built_svg.masks.insert(mask.id.clone(), (kid.clone(), node_path(&kid)));
+            }
+        }
+
+        let mut marker_contents: Vec<Node> = vec![];
+        for markers in built_svg.source_attributes.markers.values() {
+            for marker in markers.start.iter().chain(markers.mid.iter()).chain(markers.end.iter()) {
+                if !marker_contents.contains(&marker.content) {
+                    marker_contents.push(marker.content.clone());
+                }
+            }
+        }
+
+        let mut stack = vec![];
+        let root = tree.root();
+        let global_transform = match *root.borrow() {
+            NodeKind::Svg(ref svg) => {
+                let document_size = Vector2F::new(svg.size.width() as f32,
+                                                  svg.size.height() as f32);
+                let view_box_rect = match view_box {
+                    ViewBoxOption::Document => usvg_rect_to_euclid_rect(&svg.view_box.rect),
+                    ViewBoxOption::Ignore => RectF::new(Vector2F::default(), document_size),
+                    ViewBoxOption::Override(rect) => rect,
+                };
+
+                // Without a target size, the document keeps the coordinates of its view box.
+                let dpi_scale = dpi / DEFAULT_DPI;
+                let (viewport, fit_transform) = match size {
+                    None => {
+                        (view_box_rect.scale_xy(Vector2F::splat(dpi_scale)),
+                         Transform2DF::from_scale(Vector2F::splat(dpi_scale)))
+                    }
+                    Some(size) => {
+                        let aspect = preserve_aspect_ratio.unwrap_or(svg.view_box.aspect);
+                        (RectF::new(Vector2F::default(), size),
+                         fit_view_box(view_box_rect, size, &aspect))
+                    }
+                };
+
+                built_svg.scene.set_view_box(transform.transform_rect(&viewport));
+                fit_transform.then(&transform)
+            }
+            _ => unreachable!(),
+        };
+        queue_children(&root, &node_path(&root), &global_transform, false, true, &mut stack);
+
+        SVGBuilder { built_svg, tree, stack, global_transform, marker_contents }
+    }
+
+    /// Draws at least `node_count` more nodes, unless the tree runs out. Returns true once every
+    /// node has been drawn, or an unsupported feature has stopped the build.
+    ///
+    /// Masked groups are drawn whole, so more nodes than asked for may be drawn.
+    pub fn process_nodes(&mut self, node_count: usize) -> bool {
+        let mut processed_node_count = 0;
+        while processed_node_count < node_count || !self.built_svg.saved_scenes.is_empty() {
+            if self.built_svg.stopped() {
+                break;
+            }
+            match self.stack.pop() {
+                None => break,
+                Some(Work::Node { node, path, transform, in_marker, release }) => {
+                    let release = release && !self.marker_contents.contains(&node);
+                    let children_queued = self.built_svg.process_node(&node,
+                                                                      &path,
+                                                                      &transform,
+                                                                      in_marker,
+                                                                      release,
+                                                                      &mut self.stack);
+                    if release {
+                        release_node(&node, children_queued, &self.marker_contents);
+                    }
+                    processed_node_count += 1;
+                }
+                Some(Work::DrawMask { mask, mask_path, transform, in_marker }) => {
+                    self.built_svg.draw_mask(mask,
+                                             &mask_path,
+                                             transform,
+                                             in_marker,
+                                             &mut self.stack);
+                }
+                Some(Work::ApplyMask { content, clip, mask, mask_path, first_diagnostic }) => {
+                    self.built_svg.apply_mask(content, clip, &mask, &mask_path, first_diagnostic);
+                }
+            }
+        }
+        if self.built_svg.stopped() {
+            self.built_svg.restore_outermost_scene();
+            for work in self.stack.drain(..) {
+                if let Work::Node { node, release: true, .. } = work {
+                    release_tree(&node, &self.marker_contents);
+                }
+            }
+        }
+        self.stack.is_empty()
+    }
+
+    /// The scene drawn so far.
+    #[inline]
+    pub fn built_svg(&self) -> &BuiltSVG {
+        &self.built_svg
+    }
+
+    /// Draws the rest of the tree and returns the finished scene.
+    pub fn finish(mut self) -> BuiltSVG {
+        while !self.process_nodes(usize::MAX) {}
+
+        let mut undrawn_texts: Vec<_> =
+            self.built_svg.source_attributes.texts.iter().filter(|(id, _)| {
+                !self.built_svg.drawn_text_ids.contains(*id)
+            }).map(|(id, text)| (id.clone(), text.clone())).collect();
+        if self.built_svg.stopped() {
+            undrawn_texts.clear();
+        }
+        undrawn_texts.sort_by(|a, b| a.0.cmp(&b.0));
+        for (id, text) in undrawn_texts {
+            self.built_svg.process_text(&text, &id, None, &self.global_transform);
+        }
+
+        // Take apart whatever is left of the tree, so that dropping it doesn't recurse.
+        release_tree(&self.tree.root(), &self.marker_contents);
+        for (_, (mask, _)) in self.built_svg.masks.drain() {
+            release_tree(&mask, &self.marker_contents);
+        }
+        for content in &self.marker_contents {
+            release_tree(content, &self.marker_contents);
+        }
+        self.built_svg
+    }
+}
+
+// Queues the children of a node to be drawn in order, after anything already queued.
+fn queue_children(node: &Node,
+                  path: &str,
+                  transform: &Transform2DF,
+                  in_marker: bool,
+                  release: bool,
+                  stack: &mut Vec<Work>) {
+    let first_index = stack.len();
+    push_children(node, path, transform, in_marker, release, stack);
+    stack[first_index..].reverse();
+}
+
+fn push_children(node: &Node,
+                 path: &str,
+                 transform: &Transform2DF,
+                 in_marker: bool,
+                 release: bool,
+                 stack: &mut Vec<Work>) {
+    for (kid_index, kid) in node.children().enumerate() {
+        let path = format!("{}/{}[{}]", path, element_name(&kid), kid_index);
+        stack.push(Work::Node { node: kid, path, transform: *transform, in_marker, release });
+    }
+}
+
+// Detaches a drawn node from the tree, so that it's freed once nothing refers to it. Children
+// that weren't queued to be drawn are taken apart too.
+fn release_node(node: &Node, children_queued: bool, marker_contents: &[Node]) {
+    node.detach();
+    if children_queued {
+        return;
+    }
+    let kids: Vec<_> = node.children().collect();
+    for kid in kids {
+        // Masks and marker content are drawn wherever they're referenced, so they're kept.
+        if is_mask(&kid) || marker_contents.contains(&kid) {
+            kid.detach();
+        } else {
+            release_tree(&kid, marker_contents);
+        }
+    }
+}
+
+// Detaches every node of a subtree from the others, deepest first, so that no node owns another
+// when they're dropped.
+//
+// Masks and marker content are drawn wherever they're referenced, so below the root they're only
+// detached from their parents, and their own content is kept.
+fn release_tree(root: &Node, marker_contents: &[Node]) {
+    let mut nodes = vec![root.clone()];
+    let mut stack: Vec<_> = root.children().collect();
+    while let Some(node) = stack.pop() {
+        if !is_mask(&node) && !marker_contents.contains(&node) {
+            stack.extend(node.children());
+        }
+        nodes.push(node);
+    }
+    for node in nodes.iter().rev() {
+        node.detach();
+    }
+}
+
+fn is_mask(node: &Node) -> bool {
+    match *node.borrow() {
+        NodeKind::Mask(..) => true,
+        _ => false,
+    }
+}
+
+// Queues the content of each marker to be drawn at the path vertices it applies to. A single
+// vertex receives both the start and end markers.
+fn process_markers(markers: &PathMarkers,
+                   outline: &Outline,
+                   stroke_width: f32,
+                   transform: &Transform2DF,
+                   stack: &mut Vec<Work>) {
+    let vertices = MarkerVertex::from_outline(outline);
+    let last_vertex_index = match vertices.len() {
+        0 => return,
+        vertex_count => vertex_count - 1,
+    };
+
+    let first_index = stack.len();
+    for (vertex_index, vertex) in vertices.iter().enumerate() {
+        let (is_start, is_end) = (vertex_index == 0, vertex_index == last_vertex_index);
+        if is_start {
+            if let Some(ref marker) = markers.start {
+                process_marker(marker, vertex, true, stroke_width, transform, stack);
+            }
+        }
+        if !is_start && !is_end {
+            if let Some(ref marker) = markers.mid {
+                process_marker(marker, vertex, false, stroke_width, transform, stack);
+            }
+        }
+        if is_end {
+            if let Some(ref marker) = markers.end {
+                process_marker(marker, vertex, false, stroke_width, transform, stack);
+            }
+        }
+    }
+    stack[first_index..].reverse();
+}
+
+fn process_marker(marker: &Marker,
+                  vertex: &MarkerVertex,
+                  is_start: bool,
+                  stroke_width: f32,
+                  transform: &Transform2DF,
+                  stack: &mut Vec<Work>) {
+    let angle = match marker.orient {
+        MarkerOrient::Auto => vertex.angle,
+        MarkerOrient::AutoStartReverse if is_start => vertex.angle + PI,
+        MarkerOrient::AutoStartReverse => vertex.angle,
+        MarkerOrient::Angle(angle) => angle,
+    };
+    let units_scale = match marker.units {
+        MarkerUnits::StrokeWidth => stroke_width,
+        MarkerUnits::UserSpaceOnUse => 1.0,
+    };
+    let view_box_scale = match marker.view_box {
+        Some(view_box) if view_box.size().x() > 0.0 && view_box.size().y() > 0.0 => {
+            let scale = marker.size.scale_xy(Vector2F::new(1.0 / view_box.size().x(),
+                                                            1.0 / view_box.size().y()));
+            f32::min(scale.x(), scale.y())
+        }
+        _ => 1.0,
+    };
+
+    // Map the reference point of the content onto the vertex.
+    let marker_transform =
+        Transform2DF::from_translation(-marker.ref_point)
+            .post_scale(Vector2F::splat(view_box_scale * units_scale))
+            .post_rotate(angle)
+            .post_translate(vertex.position)
+            .then(transform);
+
+    // The caller puts the content of all the markers of the path in order.
+    let content_path = node_path(&marker.content);
+    push_children(&marker.content, &content_path, &marker_transform, true, false, stack);
 }
 
 impl Display for BuildResultFlags {
@@ -238,20 +1020,17 @@ impl Display for BuildResultFlags {
 }
 
 trait PaintExt {
-    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
-                      -> Self;
+    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity) -> Self;
 }
 
 impl PaintExt for Paint {
     #[inline]
-    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
-                      -> Paint {
+    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity) -> Paint {
         Paint {
             color: match *svg_paint {
                 UsvgPaint::Color(color) => ColorU::from_svg_color(color, opacity),
                 UsvgPaint::Link(_) => {
                     // TODO(pcwalton)
-                    result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
                     ColorU::black()
                 }
             },
@@ -259,6 +1038,72 @@ impl PaintExt for Paint {
     }
 }
 
+// Maps the view box into the rectangle from the origin to `size`.
+fn fit_view_box(view_box: RectF, size: Vector2F, aspect: &AspectRatio) -> Transform2DF {
+    if view_box.size().x() <= 0.0 || view_box.size().y() <= 0.0 {
+        return Transform2DF::default();
+    }
+    let scale = size.scale_xy(Vector2F::new(1.0 / view_box.size().x(),
+                                            1.0 / view_box.size().y()));
+    let (scale, align) = match aspect.align {
+        Align::None => (scale, Vector2F::default()),
+        align => {
+            let uniform_scale = if aspect.slice {
+                f32::max(scale.x(), scale.y())
+            } else {
+                f32::min(scale.x(), scale.y())
+            };
+            let align = match align {
+                Align::XMinYMin => Vector2F::new(0.0, 0.0),
+                Align::XMidYMin => Vector2F::new(0.5, 0.0),
+                Align::XMaxYMin => Vector2F::new(1.0, 0.0),
+                Align::XMinYMid => Vector2F::new(0.0, 0.5),
+                Align::XMidYMid | Align::None => Vector2F::new(0.5, 0.5),
+                Align::XMaxYMid => Vector2F::new(1.0, 0.5),
+                Align::XMinYMax => Vector2F::new(0.0, 1.0),
+                Align::XMidYMax => Vector2F::new(0.5, 1.0),
+                Align::XMaxYMax => Vector2F::new(1.0, 1.0),
+            };
+            (Vector2F::splat(uniform_scale), align)
+        }
+    };
+    let leftover = size - view_box.size().scale_xy(scale);
+    Transform2DF::from_translation(-view_box.origin())
+        .post_scale(scale)
+        .post_translate(leftover.scale_xy(align))
+}
+
+// Describes where a node is in the tree, by element name and index among its siblings.
+fn node_path(node: &Node) -> String {
+    let mut components = vec![];
+    for ancestor in node.ancestors() {
+        if ancestor.parent().is_none() {
+            components.push(element_name(&ancestor).to_owned());
+        } else {
+            let index = ancestor.preceding_siblings().count() - 1;
+            components.push(format!("{}[{}]", element_name(&ancestor), index));
+        }
+    }
+    components.reverse();
+    format!("/{}", components.join("/"))
+}
+
+fn element_name(node: &Node) -> &'static str {
+    match *node.borrow() {
+        NodeKind::Svg(..) => "svg",
+        NodeKind::Defs { .. } => "defs",
+        NodeKind::LinearGradient(..) => "linearGradient",
+        NodeKind::RadialGradient(..) => "radialGradient",
+        NodeKind::ClipPath(..) => "clipPath",
+        NodeKind::Mask(..) => "mask",
+        NodeKind::Pattern(..) => "pattern",
+        NodeKind::Filter(..) => "filter",
+        NodeKind::Group(..) => "g",
+        NodeKind::Path(..) => "path",
+        NodeKind::Image(..) => "image",
+    }
+}
+
 fn usvg_rect_to_euclid_rect(rect: &UsvgRect) -> RectF {
     RectF::new(
         Vector2F::new(rect.x() as f32, rect.y() as f32),
@@ -407,3 +1252,490 @@ impl LineJoinExt for LineJoin {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{BuildOptions, BuildResultFlags, BuiltSVG, Diagnostic, Handling, Marker};
+    use super::{MarkerOrient, MarkerUnits, PathMarkers, PathRole, PathSource, SVGBuilder};
+    use super::{SourceAttributes, ViewBoxOption, fit_view_box};
+    use crate::source;
+    use font_kit::handle::Handle;
+    use font_kit::sources::mem::MemSource;
+    use pathfinder_content::color::ColorU;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2DF;
+    use pathfinder_geometry::vector::Vector2F;
+    use std::f32::consts::FRAC_PI_2;
+    use std::iter;
+    use std::sync::Arc;
+    use usvg::{Align, AspectRatio, Options as UsvgOptions, Tree};
+
+    static OVERPASS: &[u8] = include_bytes!("../../resources/fonts/overpass-regular.otf");
+
+    fn document(body: &str) -> String {
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                      xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+                      width=\"100\" height=\"100\">{}</svg>",
+                body)
+    }
+
+    // The bounds of the paths drawn in the given color.
+    fn bounds_in_color(built_svg: &BuiltSVG, color: ColorU) -> Vec<RectF> {
+        built_svg.scene.paths().filter(|(paint, _, _)| paint.color == color).map(|(_, outline, _)| {
+            outline.bounds()
+        }).collect()
+    }
+
+    fn assert_rect_eq(rect: RectF, origin: Vector2F, lower_right: Vector2F) {
+        let tolerance = 0.01;
+        assert!((rect.origin() - origin).length() < tolerance, "{:?}", rect);
+        assert!((rect.lower_right() - lower_right).length() < tolerance, "{:?}", rect);
+    }
+
+    // Builds the document with Overpass as the only font, whatever fonts are installed.
+    fn build_with_overpass(source: &str, transform: Transform2DF) -> BuiltSVG {
+        let font = Handle::from_memory(Arc::new(OVERPASS.to_vec()), 0);
+        let font_source = MemSource::from_fonts(iter::once(font)).unwrap();
+        let options = BuildOptions {
+            transform,
+            font_source: Some(Box::new(font_source)),
+            ..BuildOptions::default()
+        };
+        BuiltSVG::from_data(source.as_bytes(), &UsvgOptions::default(), options).unwrap()
+    }
+
+    // The bounds of the glyphs drawn for text, in order.
+    fn glyph_bounds(built_svg: &BuiltSVG) -> Vec<RectF> {
+        built_svg.scene.paths().zip(built_svg.path_sources.iter()).filter(|(_, source)| {
+            source.role == PathRole::Text
+        }).map(|((_, outline, _), _)| outline.bounds()).collect()
+    }
+
+    #[test]
+    fn test_nested_transforms() {
+        // The opacity keeps the group, so its transform is applied after the rectangle's.
+        let source = document("<g transform=\"translate(50 50) scale(2 1)\" opacity=\"0.5\">\
+                                 <rect transform=\"rotate(90)\" width=\"4\" height=\"2\"/>\
+                               </g>");
+        let built_svg = BuiltSVG::from_data(source.as_bytes(),
+                                            &UsvgOptions::default(),
+                                            BuildOptions::default()).unwrap();
+
+        let bounds: Vec<_> = built_svg.scene.paths().map(|(_, outline, _)| {
+            outline.bounds()
+        }).collect();
+        assert_eq!(bounds.len(), 1);
+        assert_rect_eq(bounds[0], Vector2F::new(46.0, 50.0), Vector2F::new(50.0, 54.0));
+    }
+
+    #[test]
+    fn test_markers_from_source() {
+        let source = document("<defs><marker id=\"dot\" markerWidth=\"4\" markerHeight=\"4\" \
+                                             refX=\"1\" refY=\"1\" \
+                                             markerUnits=\"userSpaceOnUse\">\
+                                     <rect width=\"2\" height=\"2\" fill=\"red\"/>\
+                                     </marker></defs>\
+                               <g style=\"marker-end: url(#dot)\">\
+                                 <path d=\"M10 10 L50 10\" stroke=\"black\" fill=\"none\"/>\
+                               </g>");
+        let built_svg = BuiltSVG::from_data(source.as_bytes(),
+                                            &UsvgOptions::default(),
+                                            BuildOptions::default()).unwrap();
+
+        let marker_bounds = bounds_in_color(&built_svg, ColorU { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(marker_bounds.len(), 1);
+        assert_rect_eq(marker_bounds[0], Vector2F::new(49.0, 9.0), Vector2F::new(51.0, 11.0));
+    }
+
+    #[test]
+    fn test_marker_under_nonuniform_scale() {
+        // The marker is rotated into place at the vertex before the group stretches it.
+        let source = document("<defs><marker id=\"bar\" markerWidth=\"10\" \
+                                             markerHeight=\"10\" orient=\"90\" \
+                                             markerUnits=\"userSpaceOnUse\">\
+                                     <rect width=\"4\" height=\"2\" fill=\"red\"/>\
+                                     </marker></defs>\
+                               <g transform=\"scale(2 1)\">\
+                                 <path d=\"M10 10 L30 10\" stroke=\"black\" fill=\"none\" \
+                                       marker-end=\"url(#bar)\"/>\
+                               </g>");
+        let built_svg = BuiltSVG::from_data(source.as_bytes(),
+                                            &UsvgOptions::default(),
+                                            BuildOptions::default()).unwrap();
+
+        let marker_bounds = bounds_in_color(&built_svg, ColorU { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(marker_bounds.len(), 1);
+        assert_rect_eq(marker_bounds[0], Vector2F::new(56.0, 10.0), Vector2F::new(60.0, 14.0));
+    }
+
+    #[test]
+    fn test_text_glyph_placement() {
+        let source = document("<text x=\"10\" y=\"50\" font-family=\"Overpass\" \
+                                     font-size=\"20\">HH</text>");
+        let bounds = glyph_bounds(&build_with_overpass(&source, Transform2DF::default()));
+        assert_eq!(bounds.len(), 2);
+
+        // Both glyphs sit on the baseline, with the first one starting at the text position.
+        for glyph_bounds in &bounds {
+            assert!((glyph_bounds.max_y() - 50.0).abs() < 0.5, "{:?}", glyph_bounds);
+            assert!(glyph_bounds.height() > 10.0 && glyph_bounds.height() < 20.0);
+        }
+        assert!(bounds[0].min_x() >= 10.0 && bounds[0].min_x() < 14.0, "{:?}", bounds[0]);
+        assert!(bounds[1].min_x() > bounds[0].max_x());
+    }
+
+    #[test]
+    fn test_text_span_offsets() {
+        let plain = document("<text x=\"10\" y=\"50\" font-family=\"Overpass\" \
+                                    font-size=\"20\">H<tspan>H</tspan></text>");
+        let plain = glyph_bounds(&build_with_overpass(&plain, Transform2DF::default()));
+        let shifted = document("<text x=\"10\" y=\"50\" font-family=\"Overpass\" \
+                                      font-size=\"20\">\
+                                  H<tspan dx=\"5\" dy=\"-10\">H</tspan>\
+                                  <tspan x=\"100\">H</tspan>\
+                                </text>");
+        let shifted = glyph_bounds(&build_with_overpass(&shifted, Transform2DF::default()));
+        assert_eq!(plain.len(), 2);
+        assert_eq!(shifted.len(), 3);
+
+        assert_rect_eq(shifted[0], plain[0].origin(), plain[0].lower_right());
+        let offset = Vector2F::new(5.0, -10.0);
+        assert_rect_eq(shifted[1], plain[1].origin() + offset, plain[1].lower_right() + offset);
+
+        // An absolute `x` moves the pen back, but the earlier `dy` still applies.
+        let offset = Vector2F::new(90.0, -10.0);
+        assert_rect_eq(shifted[2], plain[0].origin() + offset, plain[0].lower_right() + offset);
+    }
+
+    #[test]
+    fn test_text_fill_and_stroke() {
+        let source = document("<text x=\"10\" y=\"50\" font-family=\"Overpass\" \
+                                     font-size=\"20\" fill=\"red\" stroke=\"blue\" \
+                                     stroke-width=\"2\">I</text>");
+        let transform = Transform2DF::from_scale(Vector2F::new(2.0, 1.0));
+        let built_svg = build_with_overpass(&source, transform);
+        let fill = bounds_in_color(&built_svg, ColorU { r: 255, g: 0, b: 0, a: 255 });
+        let stroke = bounds_in_color(&built_svg, ColorU { r: 0, g: 0, b: 255, a: 255 });
+        assert_eq!(fill.len(), 1);
+        assert_eq!(stroke.len(), 1);
+
+        // The stroke is one unit wide on each side in user space, so the horizontal scale
+        // widens it in X only.
+        let (fill, stroke) = (fill[0], stroke[0]);
+        let tolerance = 0.05;
+        assert!((fill.min_x() - stroke.min_x() - 2.0).abs() < tolerance, "{:?}", stroke);
+        assert!((stroke.max_x() - fill.max_x() - 2.0).abs() < tolerance, "{:?}", stroke);
+        assert!((fill.min_y() - stroke.min_y() - 1.0).abs() < tolerance, "{:?}", stroke);
+        assert!((stroke.max_y() - fill.max_y() - 1.0).abs() < tolerance, "{:?}", stroke);
+    }
+
+    #[test]
+    fn test_compressed_document() {
+        // `<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="5"
+        // height="5"/></svg>`, compressed with gzip. Its source can't be read, so it's left to
+        // usvg.
+        static SVGZ: [u8; 96] = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0xb3, 0x29, 0x2e, 0x4b,
+            0x57, 0xa8, 0xc8, 0xcd, 0xc9, 0x2b, 0xb6, 0x55, 0xca, 0x28, 0x29, 0x29, 0xb0, 0xd2,
+            0xd7, 0x2f, 0x2f, 0x2f, 0xd7, 0x2b, 0x37, 0xd6, 0xcb, 0x2f, 0x4a, 0xd7, 0x37, 0x32,
+            0x30, 0x30, 0xd0, 0x07, 0xaa, 0x50, 0x52, 0x28, 0xcf, 0x4c, 0x29, 0xc9, 0xb0, 0x55,
+            0x32, 0x34, 0x50, 0x52, 0xc8, 0x48, 0xcd, 0x4c, 0xcf, 0x28, 0x01, 0xb3, 0xed, 0x6c,
+            0x8a, 0x52, 0x93, 0x4b, 0x60, 0x92, 0xa6, 0x08, 0x39, 0x53, 0x25, 0x7d, 0x3b, 0x1b,
+            0x90, 0x4e, 0x3b, 0x00, 0x94, 0x9d, 0xab, 0x65, 0x61, 0x00, 0x00, 0x00
+        ];
+        let built_svg = BuiltSVG::from_data(&SVGZ,
+                                            &UsvgOptions::default(),
+                                            BuildOptions::default()).unwrap();
+        let bounds: Vec<_> = built_svg.scene.paths().map(|(_, outline, _)| {
+            outline.bounds()
+        }).collect();
+        assert_eq!(bounds.len(), 1);
+        assert_rect_eq(bounds[0], Vector2F::default(), Vector2F::splat(5.0));
+    }
+
+    #[test]
+    fn test_marker_after_defs() {
+        // The marker content is in the `<defs>` of the tree being built, which is processed, and
+        // let go of, before the path.
+        let source = document("<defs><clipPath id=\"content\">\
+                                 <rect width=\"2\" height=\"2\"/>\
+                               </clipPath></defs>\
+                               <g clip-path=\"url(#content)\">\
+                                 <path id=\"p\" d=\"M10 10 L50 10\" \
+                                       stroke=\"black\" fill=\"none\"/>\
+                               </g>");
+        let tree = Tree::from_data(source.as_bytes(), &UsvgOptions::default()).unwrap();
+        let content = tree.defs().children().find(|kid| &*kid.id() == "content").unwrap();
+
+        let marker = Marker {
+            content,
+            view_box: None,
+            size: Vector2F::new(4.0, 4.0),
+            ref_point: Vector2F::new(1.0, 1.0),
+            units: MarkerUnits::UserSpaceOnUse,
+            orient: MarkerOrient::Angle(0.0),
+        };
+        let mut source_attributes = SourceAttributes::default();
+        source_attributes.markers.insert("p".to_owned(), PathMarkers {
+            start: Some(marker.clone()),
+            mid: None,
+            end: Some(marker),
+        });
+        let built_svg = BuiltSVG::from_tree_with_source_attributes(tree, source_attributes);
+
+        // The stroke, then both markers.
+        let bounds: Vec<_> = built_svg.scene.paths().map(|(_, outline, _)| {
+            outline.bounds()
+        }).collect();
+        assert_eq!(bounds.len(), 3);
+        assert_rect_eq(bounds[1], Vector2F::new(9.0, 9.0), Vector2F::new(11.0, 11.0));
+        assert_rect_eq(bounds[2], Vector2F::new(49.0, 9.0), Vector2F::new(51.0, 11.0));
+    }
+
+    #[test]
+    fn test_incremental_build() {
+        let source = document("<defs>\
+                                 <rect id=\"tile\" width=\"5\" height=\"5\" fill=\"blue\"/>\
+                                 <marker id=\"dot\" markerUnits=\"userSpaceOnUse\">\
+                                   <circle r=\"1\" fill=\"red\"/>\
+                                 </marker>\
+                                 <mask id=\"half\">\
+                                   <rect width=\"100\" height=\"50\" fill=\"white\"/>\
+                                 </mask>\
+                               </defs>\
+                               <use xlink:href=\"#tile\" x=\"10\"/>\
+                               <g mask=\"url(#half)\">\
+                                 <use xlink:href=\"#tile\" x=\"20\" y=\"47.5\"/>\
+                               </g>\
+                               <polyline points=\"10 80 50 80 90 80\" stroke=\"black\" \
+                                         fill=\"none\" marker-mid=\"url(#dot)\" \
+                                         style=\"marker-start: url(#dot)\"/>");
+
+        // Building a node at a time lets go of each node once it's drawn, which mustn't lose
+        // anything that's drawn again later.
+        let build = |node_count| {
+            let usvg_options = UsvgOptions::default();
+            let (tree, source_attributes) = source::read(source.as_bytes(), &usvg_options).unwrap();
+            let options = BuildOptions { source_attributes, ..BuildOptions::default() };
+            let mut builder = SVGBuilder::new(tree, options);
+            while !builder.process_nodes(node_count) {}
+            builder.finish()
+        };
+        let summary = |built_svg: &BuiltSVG| -> Vec<_> {
+            built_svg.scene.paths().map(|(paint, outline, name)| {
+                (paint.color, outline.bounds(), name.to_owned())
+            }).collect()
+        };
+        let whole = build(usize::MAX);
+        let incremental = build(1);
+        assert_eq!(summary(&whole), summary(&incremental));
+        assert_eq!(whole.path_sources, incremental.path_sources);
+        assert_eq!(whole.diagnostics, incremental.diagnostics);
+
+        // Both uses of the tile are drawn, the second cut in half by the mask.
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+        let tiles = bounds_in_color(&whole, blue);
+        assert_eq!(tiles.len(), 2);
+        assert_rect_eq(tiles[0], Vector2F::new(10.0, 0.0), Vector2F::new(15.0, 5.0));
+        assert_rect_eq(tiles[1], Vector2F::new(20.0, 47.5), Vector2F::new(25.0, 50.0));
+
+        // The same marker content is drawn at the start and middle of the polyline.
+        let dots = bounds_in_color(&whole, ColorU { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(dots.len(), 2);
+        assert_rect_eq(dots[0], Vector2F::new(9.0, 79.0), Vector2F::new(11.0, 81.0));
+        assert_rect_eq(dots[1], Vector2F::new(49.0, 79.0), Vector2F::new(51.0, 81.0));
+    }
+
+    #[test]
+    fn test_mask_on_rotated_group() {
+        // The mask content covers the left half of the bounding box of the group's content,
+        // which the rotation turns into the top half.
+        let source = document("<defs><mask id=\"left\" maskContentUnits=\"objectBoundingBox\">\
+                                 <rect width=\"0.5\" height=\"1\" fill=\"white\"/>\
+                               </mask></defs>\
+                               <g transform=\"rotate(90 50 50)\" mask=\"url(#left)\">\
+                                 <rect x=\"20\" y=\"40\" width=\"40\" height=\"10\" \
+                                       fill=\"blue\"/>\
+                               </g>");
+        let built_svg = BuiltSVG::from_data(source.as_bytes(),
+                                            &UsvgOptions::default(),
+                                            BuildOptions::default()).unwrap();
+
+        let bounds = bounds_in_color(&built_svg, ColorU { r: 0, g: 0, b: 255, a: 255 });
+        assert_eq!(bounds.len(), 1);
+        assert_rect_eq(bounds[0], Vector2F::new(50.0, 20.0), Vector2F::new(60.0, 40.0));
+    }
+
+    fn aspect_ratio(align: Align, slice: bool) -> AspectRatio {
+        AspectRatio { defer: false, align, slice }
+    }
+
+    #[test]
+    fn test_fit_view_box() {
+        let view_box = RectF::new(Vector2F::new(10.0, 10.0), Vector2F::new(100.0, 50.0));
+        let size = Vector2F::splat(200.0);
+        let corners = |aspect: AspectRatio| {
+            let transform = fit_view_box(view_box, size, &aspect);
+            (transform.transform_point(view_box.origin()),
+             transform.transform_point(view_box.lower_right()))
+        };
+
+        // Meeting scales by 2 and centers vertically; slicing scales by 4 and centers
+        // horizontally; `none` stretches.
+        assert_eq!(corners(aspect_ratio(Align::XMidYMid, false)),
+                   (Vector2F::new(0.0, 50.0), Vector2F::new(200.0, 150.0)));
+        assert_eq!(corners(aspect_ratio(Align::XMidYMid, true)),
+                   (Vector2F::new(-100.0, 0.0), Vector2F::new(300.0, 200.0)));
+        assert_eq!(corners(aspect_ratio(Align::None, false)),
+                   (Vector2F::new(0.0, 0.0), Vector2F::new(200.0, 200.0)));
+        assert_eq!(corners(aspect_ratio(Align::XMaxYMax, false)),
+                   (Vector2F::new(0.0, 100.0), Vector2F::new(200.0, 200.0)));
+
+        // An empty view box draws nothing sensible, so it's left alone.
+        let empty = RectF::new(Vector2F::default(), Vector2F::new(0.0, 50.0));
+        let transform = fit_view_box(empty, size, &aspect_ratio(Align::XMidYMid, false));
+        assert_eq!(transform, Transform2DF::default());
+    }
+
+    #[test]
+    fn test_build_options() {
+        let source = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"50\" \
+                           viewBox=\"0 0 100 50\" preserveAspectRatio=\"xMinYMin slice\">\
+                        <rect width=\"100\" height=\"50\"/>\
+                      </svg>";
+        let build = |options: BuildOptions| {
+            let tree = Tree::from_data(source.as_bytes(), &UsvgOptions::default()).unwrap();
+            let built_svg = BuiltSVG::from_tree_with_options(tree, options);
+            let bounds: Vec<_> = built_svg.scene.paths().map(|(_, outline, _)| {
+                outline.bounds()
+            }).collect();
+            assert_eq!(bounds.len(), 1);
+            (built_svg.scene.view_box(), bounds[0])
+        };
+        let rect = |x, y, width, height| {
+            RectF::new(Vector2F::new(x, y), Vector2F::new(width, height))
+        };
+
+        // Without a size, the DPI scales the document.
+        let (view_box, bounds) = build(BuildOptions { dpi: 192.0, ..BuildOptions::default() });
+        assert_eq!(view_box, rect(0.0, 0.0, 200.0, 100.0));
+        assert_eq!(bounds, rect(0.0, 0.0, 200.0, 100.0));
+
+        // The document's own `preserveAspectRatio` is used unless it's overridden, and the
+        // transform applies after fitting.
+        let (view_box, bounds) = build(BuildOptions {
+            size: Some(Vector2F::splat(200.0)),
+            transform: Transform2DF::from_translation(Vector2F::new(5.0, 0.0)),
+            ..BuildOptions::default()
+        });
+        assert_eq!(view_box, rect(5.0, 0.0, 200.0, 200.0));
+        assert_eq!(bounds, rect(5.0, 0.0, 400.0, 200.0));
+
+        let (view_box, bounds) = build(BuildOptions {
+            size: Some(Vector2F::splat(200.0)),
+            preserve_aspect_ratio: Some(aspect_ratio(Align::XMidYMid, false)),
+            ..BuildOptions::default()
+        });
+        assert_eq!(view_box, rect(0.0, 0.0, 200.0, 200.0));
+        assert_eq!(bounds, rect(0.0, 50.0, 200.0, 100.0));
+
+        let (_, bounds) = build(BuildOptions {
+            size: Some(Vector2F::new(50.0, 50.0)),
+            preserve_aspect_ratio: Some(aspect_ratio(Align::None, false)),
+            ..BuildOptions::default()
+        });
+        assert_eq!(bounds, rect(0.0, 0.0, 50.0, 50.0));
+
+        // The document is squashed to fit before it's rotated.
+        let (view_box, bounds) = build(BuildOptions {
+            size: Some(Vector2F::new(50.0, 50.0)),
+            preserve_aspect_ratio: Some(aspect_ratio(Align::None, false)),
+            transform: Transform2DF::from_rotation(FRAC_PI_2),
+            ..BuildOptions::default()
+        });
+        assert_rect_eq(view_box, Vector2F::new(-50.0, 0.0), Vector2F::new(0.0, 50.0));
+        assert_rect_eq(bounds, Vector2F::new(-50.0, 0.0), Vector2F::new(0.0, 50.0));
+
+        // Overriding the view box shows another part of the document.
+        let (view_box, bounds) = build(BuildOptions {
+            size: Some(Vector2F::splat(100.0)),
+            view_box: ViewBoxOption::Override(rect(50.0, 0.0, 50.0, 50.0)),
+            ..BuildOptions::default()
+        });
+        assert_eq!(view_box, rect(0.0, 0.0, 100.0, 100.0));
+        assert_eq!(bounds, rect(-100.0, 0.0, 200.0, 100.0));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let source = document("<defs><mask id=\"all\">\
+                                 <rect width=\"100\" height=\"100\" fill=\"white\"/>\
+                               </mask></defs>\
+                               <rect id=\"before\" width=\"10\" height=\"10\"/>\
+                               <g mask=\"url(#all)\">\
+                                 <rect id=\"inside\" x=\"20\" width=\"10\" height=\"10\"/>\
+                                 <image id=\"picture\" x=\"40\" width=\"10\" height=\"10\" \
+                                        xlink:href=\"data:image/png;base64,iVBORw0KGgoAAAANSUhE\
+                                        UgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9aw\
+                                        AAAABJRU5ErkJggg==\"/>\
+                               </g>\
+                               <rect id=\"after\" x=\"60\" width=\"10\" height=\"10\"/>");
+        let build = |stop_at_unsupported| {
+            let tree = Tree::from_data(source.as_bytes(), &UsvgOptions::default()).unwrap();
+            let options = BuildOptions { stop_at_unsupported, ..BuildOptions::default() };
+            BuiltSVG::from_tree_with_options(tree, options)
+        };
+        let drawn_ids = |built_svg: &BuiltSVG| -> Vec<_> {
+            built_svg.path_sources.iter().map(|source| source.node_id.clone()).collect()
+        };
+
+        // The image is skipped, and everything else is drawn.
+        let built_svg = build(false);
+        let expected = Diagnostic {
+            feature: BuildResultFlags::UNSUPPORTED_IMAGE_NODE,
+            node_id: "picture".to_owned(),
+            node_path: Some("/svg/g[2]/image[1]".to_owned()),
+            handling: Handling::Skipped,
+        };
+        assert_eq!(built_svg.diagnostics, [expected.clone()]);
+        assert_eq!(built_svg.result_flags, BuildResultFlags::UNSUPPORTED_IMAGE_NODE);
+        assert_eq!(expected.to_string(), "<image> skipped in #picture at /svg/g[2]/image[1]");
+        assert_eq!(drawn_ids(&built_svg), ["before", "inside", "after"]);
+
+        // Stopping at the image drops the masked group it's in, and everything after it.
+        let built_svg = build(true);
+        assert_eq!(built_svg.diagnostics, [Diagnostic { handling: Handling::Error, ..expected }]);
+        assert_eq!(built_svg.result_flags, BuildResultFlags::UNSUPPORTED_IMAGE_NODE);
+        assert_eq!(drawn_ids(&built_svg), ["before"]);
+        assert_eq!(built_svg.scene.path_count(), 1);
+    }
+
+    #[test]
+    fn test_path_sources() {
+        let source = document("<defs><marker id=\"arrow\" markerUnits=\"userSpaceOnUse\">\
+                                 <rect id=\"tip\" width=\"1\" height=\"1\"/>\
+                               </marker></defs>\
+                               <path id=\"line\" d=\"M10 10 L50 10 L50 50\" fill=\"blue\" \
+                                     stroke=\"black\" marker-end=\"url(#arrow)\"/>\
+                               <rect x=\"60\" width=\"10\" height=\"10\"/>");
+        let built_svg = BuiltSVG::from_data(source.as_bytes(),
+                                            &UsvgOptions::default(),
+                                            BuildOptions::default()).unwrap();
+
+        // usvg drops the `<marker>`, and turns the rectangles into paths. The marker content is a
+        // tree of its own.
+        let path_source = |node_id: &str, node_path: &str, role| {
+            PathSource { node_id: node_id.to_owned(), node_path: Some(node_path.to_owned()), role }
+        };
+        assert_eq!(built_svg.path_sources, [
+            path_source("line", "/svg/path[1]", PathRole::Fill),
+            path_source("line", "/svg/path[1]", PathRole::Stroke),
+            path_source("tip", "/svg/path[1]", PathRole::Fill),
+            path_source("", "/svg/path[2]", PathRole::Fill),
+        ]);
+        assert_eq!(built_svg.path_sources.len(), built_svg.scene.path_count());
+
+        let names: Vec<_> = built_svg.scene.paths().map(|(_, _, name)| name.to_owned()).collect();
+        assert_eq!(names, ["Fill(line)", "Stroke(line)", "Fill(tip)", "Fill()"]);
+    }
+}
@@ -0,0 +1,158 @@
+// pathfinder/svg/src/mask.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Luminance masks.
+//!
+//! The renderer has no offscreen layers, so masks are applied by cutting the masked paths into
+//! the regions covered by each path of the mask content, and scaling their alpha by the
+//! luminance of that region. This is exact for masks made of solid colors, as long as the
+//! translucent ones don't overlap anything below them: each region takes the luminance of the
+//! topmost path alone.
+
+use pathfinder_content::boolean::{self, BooleanOp, OutlineBoolean};
+use pathfinder_content::color::ColorU;
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{PathObject, Scene};
+
+const TOLERANCE: f32 = 0.25;
+
+/// A masked scene.
+pub(crate) struct Masked {
+    pub(crate) scene: Scene,
+    /// The index of the content path each path of the scene was cut from.
+    pub(crate) source_indices: Vec<usize>,
+    /// Whether the mask has translucent paths that may overlap others, whose luminance is
+    /// approximated.
+    pub(crate) approximated: bool,
+}
+
+/// Scales the alpha of the paths of `content` by the luminance of the paths of `mask` that
+/// cover them, and cuts away everything outside `clip`.
+pub(crate) fn apply_mask(content: &Scene, mask: &Scene, clip: &Outline) -> Masked {
+    // Split the mask into disjoint regions.
+    let mask_outlines: Vec<_> = mask.paths().map(|(_, outline, _)| outline).collect();
+    let visible_regions = boolean::visible_regions(&mask_outlines, TOLERANCE);
+
+    let (mut regions, mut approximated) = (vec![], false);
+    let mut bounds_below: Option<RectF> = None;
+    for ((paint, outline, _), region) in mask.paths().zip(visible_regions) {
+        // Checking the bounds alone may find overlaps that aren't there, but is cheap.
+        bounds_below = match bounds_below {
+            None => Some(outline.bounds()),
+            Some(bounds) => {
+                if !paint.is_opaque() && bounds.intersects(outline.bounds()) {
+                    approximated = true;
+                }
+                Some(bounds.union_rect(outline.bounds()))
+            }
+        };
+
+        // Masks use the luminance of linear RGB, per the `color-interpolation` of `<mask>`.
+        let weight = paint.color.to_f32().luminance() * paint.color.a as f32 / 255.0;
+        if weight > 0.0 && !region.contours().is_empty() {
+            regions.push((combine(&region, clip, BooleanOp::Intersection), weight));
+        }
+    }
+
+    let (mut output, mut source_indices) = (Scene::new(), vec![]);
+    for (path_index, (paint, outline, name)) in content.paths().enumerate() {
+        for &(ref region, weight) in &regions {
+            if region.contours().is_empty() || !region.bounds().intersects(outline.bounds()) {
+                continue;
+            }
+            let outline = combine(outline, region, BooleanOp::Intersection);
+            let alpha = (paint.color.a as f32 * weight).round() as u8;
+            if outline.contours().is_empty() || alpha == 0 {
+                continue;
+            }
+            let paint_id = output.push_paint(&Paint { color: ColorU { a: alpha, ..paint.color } });
+            output.push_path(PathObject::new(outline, paint_id, name.to_owned()));
+            source_indices.push(path_index);
+        }
+    }
+    Masked { scene: output, source_indices, approximated }
+}
+
+pub(crate) fn bounds(scene: &Scene) -> Option<RectF> {
+    scene.paths().fold(None, |bounds, (_, outline, _)| {
+        match bounds {
+            None => Some(outline.bounds()),
+            Some(bounds) => Some(outline.bounds().union_rect(bounds)),
+        }
+    })
+}
+
+fn combine(a: &Outline, b: &Outline, op: BooleanOp) -> Outline {
+    let mut boolean = OutlineBoolean::new(a, b, op, TOLERANCE);
+    boolean.compute();
+    boolean.into_outline()
+}
+
+#[cfg(test)]
+mod test {
+    use super::apply_mask;
+    use pathfinder_content::color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{PathObject, Scene};
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> RectF {
+        RectF::new(Vector2F::new(x, y), Vector2F::new(width, height))
+    }
+
+    fn scene(rects: &[(RectF, ColorU)]) -> Scene {
+        let mut scene = Scene::new();
+        for &(rect, color) in rects {
+            let paint_id = scene.push_paint(&Paint { color });
+            scene.push_path(PathObject::new(Outline::from_rect(rect), paint_id, String::new()));
+        }
+        scene
+    }
+
+    #[test]
+    fn test_apply_mask() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let white = ColorU::white();
+        let translucent_white = ColorU { a: 128, ..white };
+        let content = scene(&[(rect(0.0, 0.0, 10.0, 10.0), red)]);
+        let clip = Outline::from_rect(rect(0.0, 0.0, 10.0, 8.0));
+
+        // The translucent half doesn't overlap the opaque one, so the mask is exact.
+        let mask = scene(&[
+            (rect(0.0, 0.0, 10.0, 5.0), white),
+            (rect(0.0, 5.0, 10.0, 5.0), translucent_white),
+        ]);
+        let masked = apply_mask(&content, &mask, &clip);
+        assert!(!masked.approximated);
+        assert_eq!(masked.source_indices, [0, 0]);
+        let pieces: Vec<_> = masked.scene.paths().map(|(paint, outline, _)| {
+            (paint.color.a, outline.bounds())
+        }).collect();
+        assert_eq!(pieces, [(255, rect(0.0, 0.0, 10.0, 5.0)), (128, rect(0.0, 5.0, 10.0, 3.0))]);
+
+        // A translucent path over another takes the place of what's below it.
+        let mask = scene(&[
+            (rect(0.0, 0.0, 10.0, 10.0), white),
+            (rect(0.0, 5.0, 10.0, 5.0), translucent_white),
+        ]);
+        let masked = apply_mask(&content, &mask, &clip);
+        assert!(masked.approximated);
+        let alphas: Vec<_> = masked.scene.paths().map(|(paint, _, _)| paint.color.a).collect();
+        assert_eq!(alphas, [255, 128]);
+
+        // Black hides everything.
+        let mask = scene(&[(rect(0.0, 0.0, 10.0, 10.0), ColorU::black())]);
+        assert_eq!(apply_mask(&content, &mask, &clip).scene.path_count(), 0);
+    }
+}
@@ -0,0 +1,353 @@
+// pathfinder/svg/src/motion.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The paths that `<animateMotion>` moves elements along.
+
+use pathfinder_content::outline::{Contour, Outline, Polyline};
+use pathfinder_geometry::vector::Vector2F;
+use std::f32::consts::PI;
+
+const TOLERANCE: f32 = 0.1;
+
+pub(crate) struct MotionPath {
+    polylines: Vec<Polyline>,
+}
+
+impl MotionPath {
+    /// Parses SVG path data. As in rendering, the path is cut short at the first error.
+    pub(crate) fn from_path_data(data: &str) -> MotionPath {
+        MotionPath { polylines: parse_path_data(data).flatten(TOLERANCE) }
+    }
+
+    pub(crate) fn length(&self) -> f32 {
+        self.polylines.iter().flat_map(edges).map(|(from, to)| (to - from).length()).sum()
+    }
+
+    /// Returns the point at the given distance along the path, and the direction of the path
+    /// there as an angle in radians. Moving to the start of a new subpath takes no distance.
+    pub(crate) fn sample(&self, distance: f32) -> (Vector2F, f32) {
+        let mut last_edge = None;
+        let mut distance_left = distance;
+        for (from, to) in self.polylines.iter().flat_map(edges) {
+            let vector = to - from;
+            let length = vector.length();
+            if length == 0.0 {
+                continue;
+            }
+            let angle = f32::atan2(vector.y(), vector.x());
+            if distance_left <= length {
+                return (from.lerp(to, f32::max(distance_left, 0.0) / length), angle);
+            }
+            distance_left -= length;
+            last_edge = Some((to, angle));
+        }
+
+        match last_edge {
+            Some(end) => end,
+            None => {
+                let start = self.polylines.iter().filter_map(|polyline| {
+                    polyline.points.first()
+                }).next();
+                (start.cloned().unwrap_or_default(), 0.0)
+            }
+        }
+    }
+}
+
+// The edges of a polyline, including the closing edge of a closed one.
+fn edges(polyline: &Polyline) -> impl Iterator<Item = (Vector2F, Vector2F)> + '_ {
+    let points = &polyline.points;
+    let closing_edge = if polyline.closed && points.len() > 1 {
+        Some((points[points.len() - 1], points[0]))
+    } else {
+        None
+    };
+    points.windows(2).map(|pair| (pair[0], pair[1])).chain(closing_edge)
+}
+
+fn parse_path_data(data: &str) -> Outline {
+    let mut parser = PathDataParser {
+        data: data.as_bytes(),
+        position: 0,
+        outline: Outline::new(),
+        contour: Contour::new(),
+        current: Vector2F::default(),
+        subpath_start: Vector2F::default(),
+        last_control: None,
+    };
+    parser.parse();
+    parser.finish_contour();
+    parser.outline
+}
+
+struct PathDataParser<'a> {
+    data: &'a [u8],
+    position: usize,
+    outline: Outline,
+    contour: Contour,
+    current: Vector2F,
+    subpath_start: Vector2F,
+    // The last control point of the previous segment and whether it was cubic, to be reflected
+    // by the smooth curve commands.
+    last_control: Option<(Vector2F, bool)>,
+}
+
+impl<'a> PathDataParser<'a> {
+    fn parse(&mut self) -> Option<()> {
+        let mut command = None;
+        loop {
+            self.skip_separators();
+            let byte = match self.data.get(self.position) {
+                None => return Some(()),
+                Some(&byte) => byte,
+            };
+            if byte.is_ascii_alphabetic() {
+                command = Some(byte);
+                self.position += 1;
+            }
+
+            let command_byte = command?;
+            let relative = command_byte.is_ascii_lowercase();
+            let origin = if relative { self.current } else { Vector2F::default() };
+            let mut last_control = None;
+            match command_byte.to_ascii_lowercase() {
+                b'm' => {
+                    let point = origin + self.point()?;
+                    self.finish_contour();
+                    self.contour.push_endpoint(point);
+                    self.current = point;
+                    self.subpath_start = point;
+                    // Further pairs of coordinates are lines.
+                    command = Some(if relative { b'l' } else { b'L' });
+                }
+                b'l' => {
+                    let point = origin + self.point()?;
+                    self.line_to(point);
+                }
+                b'h' => {
+                    let x = origin.x() + self.number()?;
+                    self.line_to(Vector2F::new(x, self.current.y()));
+                }
+                b'v' => {
+                    let y = origin.y() + self.number()?;
+                    self.line_to(Vector2F::new(self.current.x(), y));
+                }
+                b'c' | b's' => {
+                    let ctrl0 = if command_byte.eq_ignore_ascii_case(&b'c') {
+                        origin + self.point()?
+                    } else {
+                        self.reflected_control(true)
+                    };
+                    let ctrl1 = origin + self.point()?;
+                    let point = origin + self.point()?;
+                    self.start_contour();
+                    self.contour.push_cubic(ctrl0, ctrl1, point);
+                    self.current = point;
+                    last_control = Some((ctrl1, true));
+                }
+                b'q' | b't' => {
+                    let ctrl = if command_byte.eq_ignore_ascii_case(&b'q') {
+                        origin + self.point()?
+                    } else {
+                        self.reflected_control(false)
+                    };
+                    let point = origin + self.point()?;
+                    self.start_contour();
+                    self.contour.push_quadratic(ctrl, point);
+                    self.current = point;
+                    last_control = Some((ctrl, false));
+                }
+                b'a' => {
+                    let radii = Vector2F::new(self.number()?.abs(), self.number()?.abs());
+                    let x_axis_rotation = self.number()?;
+                    let large_arc = self.flag()?;
+                    let sweep = self.flag()?;
+                    let point = origin + self.point()?;
+                    self.arc_to(radii, x_axis_rotation.to_radians(), large_arc, sweep, point);
+                }
+                b'z' => {
+                    self.start_contour();
+                    self.contour.close();
+                    self.current = self.subpath_start;
+                    self.finish_contour();
+                    command = None;
+                }
+                _ => return None,
+            }
+            self.last_control = last_control;
+        }
+    }
+
+    fn line_to(&mut self, point: Vector2F) {
+        self.start_contour();
+        self.contour.push_endpoint(point);
+        self.current = point;
+    }
+
+    // Approximates an elliptical arc with cubic curves, each sweeping at most a quarter turn,
+    // per the endpoint-to-center conversion in appendix F.6.5 of SVG 1.1.
+    fn arc_to(&mut self,
+              mut radii: Vector2F,
+              x_axis_rotation: f32,
+              large_arc: bool,
+              sweep: bool,
+              point: Vector2F) {
+        if point == self.current {
+            return;
+        }
+        if radii.x() == 0.0 || radii.y() == 0.0 {
+            self.line_to(point);
+            return;
+        }
+
+        let (sin, cos) = x_axis_rotation.sin_cos();
+        let rotate = |vector: Vector2F| {
+            Vector2F::new(cos * vector.x() - sin * vector.y(), sin * vector.x() + cos * vector.y())
+        };
+        let half_chord = (self.current - point).scale(0.5);
+        let from = Vector2F::new(cos * half_chord.x() + sin * half_chord.y(),
+                                 cos * half_chord.y() - sin * half_chord.x());
+
+        // Scale the radii up if they're too small to reach.
+        let lambda = (from.x() / radii.x()).powi(2) + (from.y() / radii.y()).powi(2);
+        if lambda > 1.0 {
+            radii = radii.scale(lambda.sqrt());
+        }
+
+        let (rx2, ry2) = (radii.x() * radii.x(), radii.y() * radii.y());
+        let (x2, y2) = (from.x() * from.x(), from.y() * from.y());
+        let mut coefficient = f32::max((rx2 * ry2 - rx2 * y2 - ry2 * x2) / (rx2 * y2 + ry2 * x2),
+                                       0.0).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+        let center = Vector2F::new(radii.x() * from.y() / radii.y(),
+                                   -radii.y() * from.x() / radii.x()).scale(coefficient);
+
+        let angle = |vector: Vector2F| f32::atan2(vector.y(), vector.x());
+        let start_angle = angle((from - center).scale_xy(Vector2F::new(1.0 / radii.x(),
+                                                                       1.0 / radii.y())));
+        let end_angle = angle((from.scale(-1.0) - center).scale_xy(Vector2F::new(1.0 / radii.x(),
+                                                                                 1.0 / radii.y())));
+        let mut sweep_angle = end_angle - start_angle;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += PI * 2.0;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= PI * 2.0;
+        }
+
+        let center = rotate(center) + (self.current + point).scale(0.5);
+        let point_at = |angle: f32| {
+            center + rotate(Vector2F::new(radii.x() * angle.cos(), radii.y() * angle.sin()))
+        };
+        let tangent_at = |angle: f32| {
+            rotate(Vector2F::new(-radii.x() * angle.sin(), radii.y() * angle.cos()))
+        };
+
+        let piece_count = (sweep_angle.abs() / (PI * 0.5)).ceil().max(1.0) as u32;
+        let piece_angle = sweep_angle / piece_count as f32;
+        let handle_length = 4.0 / 3.0 * (piece_angle * 0.25).tan();
+        self.start_contour();
+        for piece_index in 0..piece_count {
+            let angle0 = start_angle + piece_angle * piece_index as f32;
+            let angle1 = angle0 + piece_angle;
+            let to = if piece_index + 1 == piece_count { point } else { point_at(angle1) };
+            self.contour.push_cubic(point_at(angle0) + tangent_at(angle0).scale(handle_length),
+                                    to - tangent_at(angle1).scale(handle_length),
+                                    to);
+        }
+        self.current = point;
+    }
+
+    fn reflected_control(&self, cubic: bool) -> Vector2F {
+        match self.last_control {
+            Some((control, last_cubic)) if last_cubic == cubic => {
+                self.current.scale(2.0) - control
+            }
+            _ => self.current,
+        }
+    }
+
+    // Drawing after closing a subpath starts a new one at the same point.
+    fn start_contour(&mut self) {
+        if self.contour.is_empty() {
+            self.contour.push_endpoint(self.current);
+        }
+    }
+
+    fn finish_contour(&mut self) {
+        let contour = std::mem::replace(&mut self.contour, Contour::new());
+        if !contour.is_empty() {
+            self.outline.push_contour(contour);
+        }
+    }
+
+    fn point(&mut self) -> Option<Vector2F> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Some(Vector2F::new(x, y))
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.position;
+        let mut end = start;
+        let at = |index: usize| self.data.get(index).cloned().unwrap_or(0);
+        if at(end) == b'+' || at(end) == b'-' {
+            end += 1;
+        }
+        while at(end).is_ascii_digit() {
+            end += 1;
+        }
+        if at(end) == b'.' {
+            end += 1;
+            while at(end).is_ascii_digit() {
+                end += 1;
+            }
+        }
+        if at(end) == b'e' || at(end) == b'E' {
+            let mut exponent_end = end + 1;
+            if at(exponent_end) == b'+' || at(exponent_end) == b'-' {
+                exponent_end += 1;
+            }
+            if at(exponent_end).is_ascii_digit() {
+                end = exponent_end;
+                while at(end).is_ascii_digit() {
+                    end += 1;
+                }
+            }
+        }
+
+        let number = std::str::from_utf8(&self.data[start..end]).ok()?.parse().ok()?;
+        self.position = end;
+        Some(number)
+    }
+
+    // Arc flags may be written without separators, as in `a10 10 0 01 20 20`.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.data.get(self.position) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return None,
+        };
+        self.position += 1;
+        Some(flag)
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if !byte.is_ascii_whitespace() && byte != b',' {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+}
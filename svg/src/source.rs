@@ -0,0 +1,934 @@
+// pathfinder/svg/src/source.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reads the markers, the `vector-effect` property, and the text, which usvg drops or converts to
+//! paths, from the source document.
+//!
+//! What's read is keyed by the IDs of the shapes and `<text>` elements it applies to, so those
+//! without IDs are given one in the document that usvg builds. The marker properties are set to
+//! `none` in that document, so that markers are only ever drawn once.
+//!
+//! Only attributes and `style` declarations are read, not style sheets. The content of each marker
+//! is built as a document of its own, so it can't refer to elements elsewhere in the document.
+//! Text is read with the default `xml:space` handling, and only the first of the coordinates in
+//! each `x`, `y`, `dx`, and `dy` list is used.
+//!
+//! Compressed `.svgz` documents are left to usvg, so nothing is read from their source.
+
+use crate::text::{TextAnchor, TextElement, TextSpan, TextStroke};
+use crate::xml::{Content, Document, Element};
+use crate::{Marker, MarkerOrient, MarkerUnits, PathMarkers, SourceAttributes};
+use crate::usvg_rect_to_euclid_rect;
+use pathfinder_content::color::ColorU;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::Vector2F;
+use roxmltree::Error as XmlError;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::mem;
+use std::str::{self, Utf8Error};
+use usvg::{Error as UsvgError, NodeKind, Options as UsvgOptions, Tree};
+
+// The first bytes of a gzip stream, which `.svgz` documents are.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// In the order of `PathMarkers`.
+const MARKER_PROPERTIES: [&str; 3] = ["marker-start", "marker-mid", "marker-end"];
+
+const MARKABLE_ELEMENT_NAMES: [&str; 4] = ["line", "path", "polygon", "polyline"];
+
+const SHAPE_ELEMENT_NAMES: [&str; 7] =
+    ["circle", "ellipse", "line", "path", "polygon", "polyline", "rect"];
+
+// Elements whose content isn't drawn where it is. Text in them is left to usvg.
+const UNRENDERED_ELEMENT_NAMES: [&str; 6] =
+    ["clipPath", "defs", "marker", "mask", "pattern", "symbol"];
+
+#[derive(Debug)]
+pub enum SourceError {
+    /// The document isn't UTF-8.
+    Utf8(Utf8Error),
+    /// The document isn't well-formed XML.
+    Xml(XmlError),
+    /// usvg couldn't build the document, or the content of one of its markers.
+    Usvg(UsvgError),
+}
+
+impl Display for SourceError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            SourceError::Utf8(ref error) => write!(formatter, "not UTF-8: {}", error),
+            SourceError::Xml(ref error) => write!(formatter, "malformed XML: {}", error),
+            SourceError::Usvg(ref error) => write!(formatter, "usvg failed: {:?}", error),
+        }
+    }
+}
+
+impl Error for SourceError {}
+
+// What the source says about the document, before usvg builds any of it.
+struct Collected {
+    // Attributes to change in the document that usvg builds, by element number.
+    overrides: HashMap<usize, Vec<(String, String)>>,
+    // The IDs of the start, mid, and end markers of each shape, by the ID of the shape.
+    path_markers: HashMap<String, [Option<String>; 3]>,
+    markers: HashMap<String, MarkerDefinition>,
+    // The IDs of the shapes with `vector-effect="non-scaling-stroke"` set.
+    non_scaling_stroke_ids: HashSet<String>,
+    // The runs of text of each `<text>` element, by its ID.
+    texts: HashMap<String, Vec<SpanDefinition>>,
+}
+
+// A `<marker>` element, with its content as a document of its own.
+struct MarkerDefinition {
+    content: String,
+    view_box: Option<RectF>,
+    // The `markerWidth` and `markerHeight`, and `refX` and `refY`, attributes.
+    size: [Length; 2],
+    ref_point: [Length; 2],
+    units: MarkerUnits,
+    orient: MarkerOrient,
+}
+
+// A run of text, with the lengths that position it left as they're written.
+struct SpanDefinition {
+    span: TextSpan,
+    // The `x` and `y`, and `dx` and `dy`, attributes.
+    position: [Option<Length>; 2],
+    shift: [Option<Length>; 2],
+}
+
+// The properties that runs of text inherit from the elements around them.
+#[derive(Clone)]
+struct TextStyle {
+    font_families: Vec<String>,
+    font_size: f32,
+    font_weight: u16,
+    text_anchor: TextAnchor,
+    fill: Option<ColorU>,
+    fill_opacity: f32,
+    stroke: Option<ColorU>,
+    stroke_opacity: f32,
+    stroke_width: f32,
+}
+
+// A length as it's written in the source, which can only be converted to user units once the
+// size of the viewport is known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Length {
+    number: f32,
+    unit: LengthUnit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LengthUnit {
+    None,
+    Px,
+    Em,
+    Ex,
+    In,
+    Cm,
+    Mm,
+    Pt,
+    Pc,
+    Percent,
+}
+
+/// Reads an SVG document, and builds the tree usvg sees along with the attributes it doesn't.
+pub(crate) fn read(data: &[u8], options: &UsvgOptions)
+                   -> Result<(Tree, SourceAttributes), SourceError> {
+    if data.starts_with(&GZIP_MAGIC) {
+        let tree = Tree::from_data(data, options).map_err(SourceError::Usvg)?;
+        return Ok((tree, SourceAttributes::default()));
+    }
+
+    let text = str::from_utf8(data).map_err(SourceError::Utf8)?;
+    let document = Document::parse(text).map_err(SourceError::Xml)?;
+    let collected = collect(&document, options);
+    let tree = Tree::from_data(document.write(|_| false, &collected.overrides).as_bytes(), options)
+        .map_err(SourceError::Usvg)?;
+
+    // Percentages are of the viewport, as usvg resolves them.
+    let viewport_size = match *tree.root().borrow() {
+        NodeKind::Svg(ref svg) => usvg_rect_to_euclid_rect(&svg.view_box.rect).size(),
+        _ => Vector2F::default(),
+    };
+    let resolve = |lengths: [Length; 2]| {
+        let font_size = options.font_size as f32;
+        Vector2F::new(lengths[0].to_user_units(options, font_size, viewport_size.x()),
+                      lengths[1].to_user_units(options, font_size, viewport_size.y()))
+    };
+
+    // Build the content of the markers that are used.
+    let used_marker_ids: HashSet<&str> = collected.path_markers.values().flat_map(|ids| {
+        ids.iter().filter_map(|id| id.as_ref().map(|id| &**id))
+    }).collect();
+    let mut markers = HashMap::new();
+    for (id, definition) in &collected.markers {
+        if !used_marker_ids.contains(&**id) {
+            continue;
+        }
+        let content = Tree::from_data(definition.content.as_bytes(), options)
+            .map_err(SourceError::Usvg)?;
+        markers.insert(&**id, Marker {
+            content: content.root(),
+            view_box: definition.view_box,
+            size: resolve(definition.size),
+            ref_point: resolve(definition.ref_point),
+            units: definition.units,
+            orient: definition.orient,
+        });
+    }
+
+    let path_markers = collected.path_markers.iter().map(|(path_id, ids)| {
+        let marker = |index: usize| {
+            ids[index].as_ref().and_then(|id| markers.get(&**id)).cloned()
+        };
+        (path_id.clone(), PathMarkers { start: marker(0), mid: marker(1), end: marker(2) })
+    }).collect();
+
+    let texts = collected.texts.into_iter().map(|(text_id, definitions)| {
+        let spans = definitions.into_iter().map(|definition| {
+            let SpanDefinition { mut span, position, shift } = definition;
+            let font_size = span.font_size;
+            let resolve = |length: Option<Length>, percent_base: f32| {
+                length.map(|length| length.to_user_units(options, font_size, percent_base))
+            };
+            span.x = resolve(position[0], viewport_size.x());
+            span.y = resolve(position[1], viewport_size.y());
+            span.dx = resolve(shift[0], viewport_size.x()).unwrap_or(0.0);
+            span.dy = resolve(shift[1], viewport_size.y()).unwrap_or(0.0);
+            span
+        }).collect();
+        (text_id, TextElement { spans })
+    }).collect();
+
+    Ok((tree, SourceAttributes {
+        non_scaling_stroke_ids: collected.non_scaling_stroke_ids,
+        markers: path_markers,
+        texts,
+    }))
+}
+
+fn collect(document: &Document, options: &UsvgOptions) -> Collected {
+    let elements = document.elements();
+    let ids: HashSet<&str> = elements.iter().filter_map(|element| {
+        element.attribute("id")
+    }).collect();
+    let markers: HashMap<String, MarkerDefinition> = elements.iter().filter_map(|element| {
+        if element.svg_name() != Some("marker") {
+            return None;
+        }
+        let id = element.attribute("id")?;
+        Some((id.to_owned(), parse_marker(element, document)))
+    }).collect();
+
+    let mut collected = Collected {
+        overrides: HashMap::new(),
+        path_markers: HashMap::new(),
+        markers,
+        non_scaling_stroke_ids: HashSet::new(),
+        texts: HashMap::new(),
+    };
+
+    // Marker properties are inherited, so each element starts from those of its parent, which
+    // comes before it.
+    let mut element_markers: Vec<[Option<String>; 3]> = Vec::with_capacity(elements.len());
+    for (element_index, element) in elements.iter().enumerate() {
+        let mut markers = match element.parent {
+            Some(parent) => element_markers[parent].clone(),
+            None => Default::default(),
+        };
+        let mut overrides = vec![];
+        let mut sets_markers = false;
+        for (property_index, &name) in MARKER_PROPERTIES.iter().enumerate() {
+            if let Some(value) = marker_property(element, name) {
+                markers[property_index] = value;
+                sets_markers = true;
+            }
+        }
+        if sets_markers {
+            overrides.extend(MARKER_PROPERTIES.iter().map(|name| {
+                (name.to_string(), "none".to_owned())
+            }));
+            if style_declarations(element).any(|(name, _)| name == "marker") {
+                overrides.push(("marker".to_owned(), "none".to_owned()));
+            }
+        }
+
+        let is_markable = matches!(element.svg_name(),
+                                   Some(name) if MARKABLE_ELEMENT_NAMES.contains(&name));
+        let has_markers = markers.iter().any(|id| {
+            matches!(id, Some(id) if collected.markers.contains_key(id))
+        });
+        if is_markable && has_markers {
+            let id = shape_id(element, element_index, &ids, &mut overrides);
+            collected.path_markers.insert(id, markers.clone());
+        }
+
+        // `vector-effect` isn't inherited.
+        let is_shape = matches!(element.svg_name(),
+                                Some(name) if SHAPE_ELEMENT_NAMES.contains(&name));
+        let vector_effect = property(element, "vector-effect");
+        if is_shape && vector_effect.as_ref().map(|value| &**value) == Some("non-scaling-stroke") {
+            let id = shape_id(element, element_index, &ids, &mut overrides);
+            collected.non_scaling_stroke_ids.insert(id);
+        }
+
+        // usvg converts text to paths in a group with the ID of the `<text>` element, which is
+        // drawn with the runs read here instead.
+        if element.svg_name() == Some("text") && is_rendered(document, element) {
+            let mut spans = vec![];
+            let style = inherited_text_style(document, element, options);
+            let mut cursor = TextCursor::default();
+            collect_text(document, element, &style, options, &mut cursor, &mut spans);
+            // Trailing spaces are stripped, like leading ones.
+            while let Some(definition) = spans.last_mut() {
+                let trimmed_length = definition.span.text.trim_end_matches(' ').len();
+                definition.span.text.truncate(trimmed_length);
+                if trimmed_length > 0 {
+                    break;
+                }
+                spans.pop();
+            }
+            let id = shape_id(element, element_index, &ids, &mut overrides);
+            collected.texts.insert(id, spans);
+        }
+
+        if !overrides.is_empty() {
+            collected.overrides.insert(element_index, overrides);
+        }
+        element_markers.push(markers);
+    }
+    collected
+}
+
+// The ID of a shape, which is made up for shapes that have none.
+fn shape_id(element: &Element,
+            element_index: usize,
+            ids: &HashSet<&str>,
+            overrides: &mut Vec<(String, String)>)
+            -> String {
+    if let Some(id) = element.attribute("id") {
+        return id.to_owned();
+    }
+    // The shape may have been given an ID already.
+    if let Some(&(_, ref id)) = overrides.iter().find(|(name, _)| name == "id") {
+        return id.clone();
+    }
+    let mut id = format!("pathfinder-{}", element_index);
+    while ids.contains(&*id) {
+        id.push('_');
+    }
+    overrides.push(("id".to_owned(), id.clone()));
+    id
+}
+
+// The value of a property set on the element, in its `style` or as an attribute. Returns `None` if
+// the property isn't set, or is set to `inherit`.
+fn property(element: &Element, name: &str) -> Option<String> {
+    let value = element.style_property(name).or_else(|| {
+        element.attribute(name).map(|value| value.trim().to_owned())
+    })?;
+    if value == "inherit" {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+// The value of a marker property set on the element: the ID of a marker, or `None` for `none`.
+// Returns `None` if the property isn't set, so that it's inherited.
+fn marker_property(element: &Element, name: &str) -> Option<Option<String>> {
+    // Declarations in `style` take precedence over attributes, and later ones over earlier ones.
+    let mut value = element.attribute(name).map(str::to_owned);
+    for (property, property_value) in style_declarations(element) {
+        if property == name || property == "marker" {
+            value = Some(property_value.to_owned());
+        }
+    }
+
+    let value = value?;
+    if value == "none" {
+        return Some(None);
+    }
+    let url = value.strip_prefix("url(")?.strip_suffix(')')?;
+    let id = url.trim().trim_matches(|character| character == '"' || character == '\'');
+    Some(Some(id.strip_prefix('#')?.to_owned()))
+}
+
+fn style_declarations(element: &Element) -> impl Iterator<Item = (&str, &str)> {
+    element.attribute("style").unwrap_or("").split(';').filter_map(|declaration| {
+        let colon_index = declaration.find(':')?;
+        Some((declaration[..colon_index].trim(), declaration[(colon_index + 1)..].trim()))
+    })
+}
+
+// Where the next run of text goes, and whether the text so far ends in a space, which spaces at the
+// start of the next run collapse into.
+struct TextCursor {
+    position: [Option<Length>; 2],
+    shift: [Option<Length>; 2],
+    ends_in_space: bool,
+}
+
+impl Default for TextCursor {
+    fn default() -> TextCursor {
+        // Leading spaces are stripped.
+        TextCursor { position: [None; 2], shift: [None; 2], ends_in_space: true }
+    }
+}
+
+// Adds the runs of text of the element and of the `<tspan>` and `<a>` elements in it, in document
+// order.
+fn collect_text(document: &Document,
+                element: &Element,
+                style: &TextStyle,
+                options: &UsvgOptions,
+                cursor: &mut TextCursor,
+                spans: &mut Vec<SpanDefinition>) {
+    // The position of an element applies to its first character, and the innermost element wins.
+    let first_length = |name| {
+        element.attribute(name).and_then(|value| {
+            value.split(|character: char| character == ',' || character.is_whitespace())
+                 .find(|part| !part.is_empty())
+                 .and_then(parse_length)
+        })
+    };
+    for (index, &(position_name, shift_name)) in [("x", "dx"), ("y", "dy")].iter().enumerate() {
+        if let Some(length) = first_length(position_name) {
+            cursor.position[index] = Some(length);
+        }
+        if let Some(length) = first_length(shift_name) {
+            cursor.shift[index] = Some(length);
+        }
+    }
+
+    for content in element.content() {
+        match *content {
+            Content::Text(ref text) => {
+                // Newlines are removed, and tabs and runs of spaces become single spaces.
+                let mut collapsed = String::with_capacity(text.len());
+                for character in text.chars() {
+                    match character {
+                        '\n' | '\r' => {}
+                        ' ' | '\t' => {
+                            if !cursor.ends_in_space {
+                                collapsed.push(' ');
+                                cursor.ends_in_space = true;
+                            }
+                        }
+                        _ => {
+                            collapsed.push(character);
+                            cursor.ends_in_space = false;
+                        }
+                    }
+                }
+                if collapsed.is_empty() {
+                    continue;
+                }
+                spans.push(SpanDefinition {
+                    span: style.to_span(collapsed),
+                    position: mem::take(&mut cursor.position),
+                    shift: mem::take(&mut cursor.shift),
+                });
+            }
+            Content::Element(child_index) => {
+                let child = &document.elements()[child_index];
+                match child.svg_name() {
+                    Some("tspan") | Some("a") => {}
+                    _ => continue,
+                }
+                let mut child_style = style.clone();
+                apply_text_style(child, &mut child_style, options);
+                collect_text(document, child, &child_style, options, cursor, spans);
+            }
+        }
+    }
+}
+
+// Whether the element is drawn where it is, rather than only where it's referred to, if at all.
+fn is_rendered(document: &Document, element: &Element) -> bool {
+    let mut ancestor = element.parent;
+    while let Some(ancestor_index) = ancestor {
+        let element = &document.elements()[ancestor_index];
+        if matches!(element.svg_name(), Some(name) if UNRENDERED_ELEMENT_NAMES.contains(&name)) {
+            return false;
+        }
+        ancestor = element.parent;
+    }
+    true
+}
+
+// The text properties of the element, as it inherits them from the elements around it.
+fn inherited_text_style(document: &Document, element: &Element, options: &UsvgOptions)
+                        -> TextStyle {
+    let mut ancestors = vec![element];
+    while let Some(parent) = ancestors[ancestors.len() - 1].parent {
+        ancestors.push(&document.elements()[parent]);
+    }
+
+    let mut style = TextStyle {
+        font_families: vec![],
+        font_size: options.font_size as f32,
+        font_weight: 400,
+        text_anchor: TextAnchor::Start,
+        fill: Some(ColorU::black()),
+        fill_opacity: 1.0,
+        stroke: None,
+        stroke_opacity: 1.0,
+        stroke_width: 1.0,
+    };
+    for ancestor in ancestors.iter().rev() {
+        apply_text_style(ancestor, &mut style, options);
+    }
+    style
+}
+
+// Sets the text properties that the element sets. Values that can't be parsed are ignored, so the
+// inherited ones are kept.
+fn apply_text_style(element: &Element, style: &mut TextStyle, options: &UsvgOptions) {
+    if let Some(families) = property(element, "font-family") {
+        style.font_families = families.split(',').map(|family| {
+            family.trim().trim_matches(|character| character == '"' || character == '\'').to_owned()
+        }).filter(|family| !family.is_empty()).collect();
+    }
+    if let Some(size) = property(element, "font-size").and_then(|value| parse_length(&value)) {
+        // `em` units and percentages are of the inherited size.
+        style.font_size = size.to_user_units(options, style.font_size, style.font_size);
+    }
+    if let Some(weight) = property(element, "font-weight") {
+        style.font_weight = match &*weight {
+            "normal" => 400,
+            "bold" => 700,
+            "bolder" if style.font_weight < 350 => 400,
+            "bolder" if style.font_weight < 550 => 700,
+            "bolder" => 900,
+            "lighter" if style.font_weight < 550 => 100,
+            "lighter" if style.font_weight < 750 => 400,
+            "lighter" => 700,
+            number => {
+                match number.parse::<u16>() {
+                    Ok(weight) if weight >= 1 && weight <= 1000 => weight,
+                    _ => style.font_weight,
+                }
+            }
+        };
+    }
+    match property(element, "text-anchor").as_ref().map(|value| &**value) {
+        Some("start") => style.text_anchor = TextAnchor::Start,
+        Some("middle") => style.text_anchor = TextAnchor::Middle,
+        Some("end") => style.text_anchor = TextAnchor::End,
+        _ => {}
+    }
+
+    if let Some(fill) = property(element, "fill").and_then(|value| parse_paint(&value)) {
+        style.fill = fill;
+    }
+    if let Some(stroke) = property(element, "stroke").and_then(|value| parse_paint(&value)) {
+        style.stroke = stroke;
+    }
+    let opacity = |name| {
+        property(element, name).and_then(|value| value.parse::<f32>().ok())
+                               .map(|opacity| f32::min(f32::max(opacity, 0.0), 1.0))
+    };
+    if let Some(opacity) = opacity("fill-opacity") {
+        style.fill_opacity = opacity;
+    }
+    if let Some(opacity) = opacity("stroke-opacity") {
+        style.stroke_opacity = opacity;
+    }
+    if let Some(width) = property(element, "stroke-width").and_then(|value| parse_length(&value)) {
+        // NOTE: Percentages, which are of the diagonal of the viewport, aren't supported.
+        style.stroke_width = width.to_user_units(options, style.font_size, 0.0);
+    }
+}
+
+// Parses a `fill` or `stroke` value: a color, or `None` for `none`. Text can't be painted with
+// paint servers, so their fallback colors are used instead, or nothing if there are none. Returns
+// `None` for values that can't be parsed, and for `currentColor`, so that the inherited paint is
+// kept.
+fn parse_paint(string: &str) -> Option<Option<ColorU>> {
+    let string = string.trim();
+    if string == "none" {
+        return Some(None);
+    }
+    if string.starts_with("url(") {
+        let fallback = &string[(string.find(')')? + 1)..];
+        return Some(parse_paint(fallback).unwrap_or(None));
+    }
+    ColorU::from_css(string).map(Some)
+}
+
+impl TextStyle {
+    fn to_span(&self, text: String) -> TextSpan {
+        let with_opacity = |color: ColorU, opacity: f32| {
+            ColorU { a: (color.a as f32 * opacity).round() as u8, ..color }
+        };
+        let stroke = match self.stroke {
+            Some(color) if self.stroke_width > 0.0 => {
+                Some(TextStroke {
+                    color: with_opacity(color, self.stroke_opacity),
+                    width: self.stroke_width,
+                })
+            }
+            _ => None,
+        };
+        TextSpan {
+            text,
+            font_families: self.font_families.clone(),
+            font_size: self.font_size,
+            font_weight: self.font_weight,
+            text_anchor: self.text_anchor,
+            fill: self.fill.map(|color| with_opacity(color, self.fill_opacity)),
+            stroke,
+            ..TextSpan::default()
+        }
+    }
+}
+
+fn parse_marker(element: &Element, document: &Document) -> MarkerDefinition {
+    let length = |name, default| {
+        element.attribute(name)
+               .and_then(parse_length)
+               .unwrap_or(Length { number: default, unit: LengthUnit::None })
+    };
+    let view_box = element.attribute("viewBox").and_then(|view_box| {
+        let numbers = view_box.split(|character: char| {
+            character == ',' || character.is_whitespace()
+        }).filter(|part| !part.is_empty()).map(str::parse).collect::<Result<Vec<f32>, _>>().ok()?;
+        match numbers[..] {
+            [x, y, width, height] => {
+                Some(RectF::new(Vector2F::new(x, y), Vector2F::new(width, height)))
+            }
+            _ => None,
+        }
+    });
+
+    MarkerDefinition {
+        content: document.extract(element),
+        view_box,
+        size: [length("markerWidth", 3.0), length("markerHeight", 3.0)],
+        ref_point: [length("refX", 0.0), length("refY", 0.0)],
+        units: match element.attribute("markerUnits") {
+            Some("userSpaceOnUse") => MarkerUnits::UserSpaceOnUse,
+            _ => MarkerUnits::StrokeWidth,
+        },
+        orient: element.attribute("orient")
+                       .and_then(parse_orient)
+                       .unwrap_or(MarkerOrient::Angle(0.0)),
+    }
+}
+
+fn parse_length(string: &str) -> Option<Length> {
+    let string = string.trim();
+    let number_length = string.find(|character: char| {
+        !(character.is_ascii_digit() || "+-.eE".contains(character))
+    }).unwrap_or(string.len());
+
+    // An exponent can't be told from the `em` and `ex` units until the number is parsed, so
+    // shorten the number until it parses.
+    let mut split = number_length;
+    loop {
+        if let Ok(number) = string[..split].parse::<f32>() {
+            let unit = match &string[split..] {
+                "" => LengthUnit::None,
+                "px" => LengthUnit::Px,
+                "em" => LengthUnit::Em,
+                "ex" => LengthUnit::Ex,
+                "in" => LengthUnit::In,
+                "cm" => LengthUnit::Cm,
+                "mm" => LengthUnit::Mm,
+                "pt" => LengthUnit::Pt,
+                "pc" => LengthUnit::Pc,
+                "%" => LengthUnit::Percent,
+                _ => return None,
+            };
+            return Some(Length { number, unit });
+        }
+        if split == 0 {
+            return None;
+        }
+        split -= 1;
+    }
+}
+
+impl Length {
+    // Converts the length as usvg does, with `font_size` as the size that `em` and `ex` units are
+    // of, and `percent_base` as the size that percentages are of.
+    fn to_user_units(self, options: &UsvgOptions, font_size: f32, percent_base: f32) -> f32 {
+        let dpi = options.dpi as f32;
+        let scale = match self.unit {
+            LengthUnit::None | LengthUnit::Px => 1.0,
+            LengthUnit::Em => font_size,
+            LengthUnit::Ex => font_size * 0.5,
+            LengthUnit::In => dpi,
+            LengthUnit::Cm => dpi / 2.54,
+            LengthUnit::Mm => dpi / 25.4,
+            LengthUnit::Pt => dpi / 72.0,
+            LengthUnit::Pc => dpi / 6.0,
+            LengthUnit::Percent => percent_base / 100.0,
+        };
+        self.number * scale
+    }
+}
+
+fn parse_orient(string: &str) -> Option<MarkerOrient> {
+    match string.trim() {
+        "auto" => Some(MarkerOrient::Auto),
+        "auto-start-reverse" => Some(MarkerOrient::AutoStartReverse),
+        string => {
+            // `grad` has to be tried before `rad`.
+            let units = [
+                ("deg", PI / 180.0),
+                ("grad", PI / 200.0),
+                ("rad", 1.0),
+                ("turn", 2.0 * PI),
+            ];
+            for &(suffix, scale) in &units {
+                if let Some(value) = string.strip_suffix(suffix) {
+                    let angle = value.parse::<f32>().ok()?;
+                    return Some(MarkerOrient::Angle(angle * scale));
+                }
+            }
+            string.parse::<f32>().ok().map(|degrees| MarkerOrient::Angle(degrees.to_radians()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Length, LengthUnit, collect, parse_length, parse_orient, read};
+    use crate::text::{TextAnchor, TextStroke};
+    use crate::xml::Document;
+    use crate::{MarkerOrient, MarkerUnits};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use std::collections::HashMap;
+    use std::f32::consts::PI;
+    use usvg::Options as UsvgOptions;
+
+    fn length(number: f32, unit: LengthUnit) -> Length {
+        Length { number, unit }
+    }
+
+    static SOURCE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <marker id="dot" viewBox="0 0 10 10" refX="5" refY="5" markerWidth="6"
+            markerUnits="userSpaceOnUse" orient="auto"><circle cx="5" cy="5" r="5"/></marker>
+    <marker id="bar" markerWidth=" 2mm " refX="50%"><rect width="1" height="3"/></marker>
+  </defs>
+  <g marker-start="url(#dot)" style="marker-end: url(#bar)">
+    <path id="a" d="M0 0 L10 0"/>
+    <path d="M0 10 L10 10" style="marker: none; marker-mid: url( '#dot' )"/>
+    <rect id="b" width="1" height="1"/>
+  </g>
+  <line marker-end="url(#missing)" x2="10"/>
+  <g vector-effect="non-scaling-stroke">
+    <circle id="c" r="1" style="vector-effect: non-scaling-stroke"/>
+    <rect width="1" height="1" vector-effect="non-scaling-stroke"/>
+    <ellipse rx="1" ry="1"/>
+    <rect id="d" width="1" height="1" vector-effect="non-scaling-stroke"
+          style="vector-effect: none"/>
+  </g>
+</svg>"##;
+
+    #[test]
+    fn test_marker_properties() {
+        let document = Document::parse(SOURCE).unwrap();
+        let collected = collect(&document, &UsvgOptions::default());
+
+        // Properties are inherited, and `none` or the shorthand replaces them.
+        let some = |id: &str| Some(id.to_owned());
+        assert_eq!(collected.path_markers["a"], [some("dot"), None, some("bar")]);
+        assert_eq!(collected.path_markers["pathfinder-8"], [None, some("dot"), None]);
+        assert_eq!(collected.path_markers.len(), 2);
+
+        // Markers are switched off wherever they're set, and shapes without IDs are given one.
+        let output = Document::parse(&document.write(|_| false, &collected.overrides)).unwrap();
+        for element in output.elements() {
+            assert!(element.style_property("marker-end").is_none());
+            if let Some(value) = element.attribute("marker-start") {
+                assert_eq!(value, "none");
+            }
+        }
+        let path = &output.elements()[8];
+        assert_eq!(path.attribute("id"), Some("pathfinder-8"));
+        assert_eq!(path.attribute("marker-mid"), Some("none"));
+        assert_eq!(path.attribute("marker"), Some("none"));
+        assert_eq!(path.attribute("style"), None);
+    }
+
+    #[test]
+    fn test_non_scaling_strokes() {
+        let document = Document::parse(SOURCE).unwrap();
+        let collected = collect(&document, &UsvgOptions::default());
+
+        // The property isn't inherited, and `style` takes precedence over the attribute.
+        let mut ids: Vec<_> = collected.non_scaling_stroke_ids.iter().map(|id| &**id).collect();
+        ids.sort();
+        assert_eq!(ids, ["c", "pathfinder-13"]);
+
+        let output = Document::parse(&document.write(|_| false, &collected.overrides)).unwrap();
+        assert_eq!(output.elements()[13].attribute("id"), Some("pathfinder-13"));
+    }
+
+    #[test]
+    fn test_marker_elements() {
+        let document = Document::parse(SOURCE).unwrap();
+        let collected = collect(&document, &UsvgOptions::default());
+
+        let dot = &collected.markers["dot"];
+        let view_box = RectF::new(Vector2F::default(), Vector2F::splat(10.0));
+        assert_eq!(dot.view_box, Some(view_box));
+        assert_eq!(dot.size, [length(6.0, LengthUnit::None), length(3.0, LengthUnit::None)]);
+        assert_eq!(dot.ref_point, [length(5.0, LengthUnit::None); 2]);
+        assert_eq!(dot.units, MarkerUnits::UserSpaceOnUse);
+        assert_eq!(dot.orient, MarkerOrient::Auto);
+
+        // The content is a document of its own.
+        let content = Document::parse(&dot.content).unwrap();
+        let names: Vec<_> = content.elements().iter().map(|element| element.svg_name()).collect();
+        assert_eq!(names, [Some("svg"), Some("circle")]);
+
+        let bar = &collected.markers["bar"];
+        assert_eq!((bar.view_box, bar.units), (None, MarkerUnits::StrokeWidth));
+        assert_eq!(bar.orient, MarkerOrient::Angle(0.0));
+        assert_eq!(bar.size[0], length(2.0, LengthUnit::Mm));
+        assert_eq!(bar.ref_point[0], length(50.0, LengthUnit::Percent));
+
+        assert_eq!(parse_orient("auto-start-reverse"), Some(MarkerOrient::AutoStartReverse));
+        assert_eq!(parse_orient("sideways"), None);
+        for &(string, radians) in &[("90", PI / 2.0), ("200grad", PI), ("0.5turn", PI)] {
+            match parse_orient(string) {
+                Some(MarkerOrient::Angle(angle)) => assert!((angle - radians).abs() < 0.0001),
+                _ => panic!("{} isn't an angle", string),
+            }
+        }
+    }
+
+    static TEXT_SOURCE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100"
+     font-family="Overpass, 'Open Sans', serif" font-size="20">
+  <g fill="red" stroke="blue" style="stroke-width: 2; stroke-opacity: 0.5">
+    <text id="a" x="10 20" y="5%" dx="1em" text-anchor="middle">
+      Hello,   <tspan dy="-2" font-size="50%" font-weight="bold" fill="none">big</tspan>
+      <tspan x="0" stroke="none">world</tspan>!
+    </text>
+  </g>
+  <text y="50" fill="url(#missing) green"> </text>
+  <text>x<title>Not text</title></text>
+  <defs><text id="d">Unused</text></defs>
+</svg>"##;
+
+    #[test]
+    fn test_texts() {
+        let document = Document::parse(TEXT_SOURCE).unwrap();
+        let collected = collect(&document, &UsvgOptions::default());
+
+        // Spaces collapse across runs, and are stripped from the ends of the text.
+        let text = &collected.texts["a"];
+        let strings: Vec<_> = text.iter().map(|definition| &*definition.span.text).collect();
+        assert_eq!(strings, ["Hello, ", "big", " ", "world", "!"]);
+
+        // Positions apply to the first character of the element that sets them.
+        assert_eq!(text[0].position, [Some(length(10.0, LengthUnit::None)),
+                                      Some(length(5.0, LengthUnit::Percent))]);
+        assert_eq!(text[0].shift, [Some(length(1.0, LengthUnit::Em)), None]);
+        assert_eq!(text[1].shift, [None, Some(length(-2.0, LengthUnit::None))]);
+        assert_eq!(text[2].position, [None, None]);
+        assert_eq!(text[3].position, [Some(length(0.0, LengthUnit::None)), None]);
+
+        // Properties are inherited from the elements around the text.
+        let hello = &text[0].span;
+        assert_eq!(hello.font_families, ["Overpass", "Open Sans", "serif"]);
+        assert_eq!((hello.font_size, hello.font_weight), (20.0, 400));
+        assert_eq!(hello.text_anchor, TextAnchor::Middle);
+        assert_eq!(hello.fill, Some(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let stroke = TextStroke { color: ColorU { r: 0, g: 0, b: 255, a: 128 }, width: 2.0 };
+        assert_eq!(hello.stroke, Some(stroke));
+        let big = &text[1].span;
+        assert_eq!((big.font_size, big.font_weight), (10.0, 700));
+        assert_eq!((big.fill, big.stroke), (None, Some(stroke)));
+        assert_eq!((text[2].span.font_size, text[3].span.stroke), (20.0, None));
+
+        // Texts without IDs are given one, and those in `<defs>` are left to usvg.
+        assert!(collected.texts["pathfinder-5"].is_empty());
+        let strings: Vec<_> = collected.texts["pathfinder-6"].iter().map(|definition| {
+            &*definition.span.text
+        }).collect();
+        assert_eq!(strings, ["x"]);
+        assert_eq!(collected.texts.len(), 3);
+
+        // Lengths are resolved once the viewport is known, with `em` units of the font size.
+        let options = UsvgOptions::default();
+        let (_, source_attributes) = read(TEXT_SOURCE.as_bytes(), &options).unwrap();
+        let spans = &source_attributes.texts["a"].spans;
+        assert_eq!((spans[0].x, spans[0].y, spans[0].dx, spans[0].dy),
+                   (Some(10.0), Some(5.0), 20.0, 0.0));
+        assert_eq!((spans[1].x, spans[1].y, spans[1].dx, spans[1].dy), (None, None, 0.0, -2.0));
+        assert_eq!(source_attributes.texts["pathfinder-5"].spans.len(), 0);
+    }
+
+    #[test]
+    fn test_lengths() {
+        assert_eq!(parse_length("1.5"), Some(length(1.5, LengthUnit::None)));
+        assert_eq!(parse_length(" -2px\n"), Some(length(-2.0, LengthUnit::Px)));
+        assert_eq!(parse_length("1e1mm"), Some(length(10.0, LengthUnit::Mm)));
+        assert_eq!(parse_length("2em"), Some(length(2.0, LengthUnit::Em)));
+        assert_eq!(parse_length(".5ex"), Some(length(0.5, LengthUnit::Ex)));
+        assert_eq!(parse_length("25%"), Some(length(25.0, LengthUnit::Percent)));
+        for &string in &["", "px", "3 mm", "3furlongs", "auto"] {
+            assert_eq!(parse_length(string), None, "{:?}", string);
+        }
+
+        let options = UsvgOptions { dpi: 72.0, ..UsvgOptions::default() };
+        let user_units = |string| {
+            parse_length(string).unwrap().to_user_units(&options, 10.0, 200.0)
+        };
+        for &(string, expected) in &[
+            ("3", 3.0),
+            ("3px", 3.0),
+            ("1in", 72.0),
+            ("2.54cm", 72.0),
+            ("25.4mm", 72.0),
+            ("9pt", 9.0),
+            ("1pc", 12.0),
+            ("2em", 20.0),
+            ("2ex", 10.0),
+            ("25%", 50.0),
+        ] {
+            assert!((user_units(string) - expected).abs() < 0.001, "{}", string);
+        }
+    }
+
+    #[test]
+    fn test_untouched_documents_round_trip() {
+        // Documents that need nothing changed are passed to usvg exactly as they were read.
+        let source = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE svg [ <!ENTITY color "red"> ]>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     viewBox="0 0 10 10">
+  <!-- <rect/> -->
+  <style><![CDATA[ rect > * { fill: blue } ]]></style>
+  <rect id="r" width='5' height="5" fill="&color;" style="stroke:red;;" vector-effect="none"/>
+  <use xlink:href="#r" x="5"/>
+  <text id="t" x="1" y="9">A &amp; <tspan dy="1">B</tspan></text>
+</svg>
+"##;
+        let document = Document::parse(source).unwrap();
+        let collected = collect(&document, &UsvgOptions::default());
+        assert!(collected.overrides.is_empty());
+        assert_eq!(document.write(|_| false, &collected.overrides), source);
+        assert_eq!(document.write(|_| false, &HashMap::new()), source);
+    }
+}
@@ -0,0 +1,259 @@
+// pathfinder/svg/src/text.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Lays out `<text>` elements and draws their glyphs.
+
+use font_kit::family_name::FamilyName;
+use font_kit::hinting::HintingOptions;
+use font_kit::properties::{Properties, Weight};
+use font_kit::loader::Loader;
+use font_kit::source::{Source, SystemSource};
+use pathfinder_content::color::ColorU;
+use pathfinder_content::outline::Outline;
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_content::stroke::StrokeTransform;
+use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_text::{SceneExt, TextRenderMode};
+use skribo::{FontCollection, FontFamily, Layout, TextStyle};
+use std::collections::HashMap;
+use std::mem;
+
+/// A `<text>` element, flattened into the runs of text of it and its `<tspan>` children, in
+/// document order.
+#[derive(Clone, Default)]
+pub struct TextElement {
+    pub spans: Vec<TextSpan>,
+}
+
+/// A run of text with a single style, with the properties it inherits already resolved.
+#[derive(Clone)]
+pub struct TextSpan {
+    pub text: String,
+    /// The `x` and `y` attributes. Setting either starts a new text chunk, which is what
+    /// `text-anchor` aligns.
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    /// The `dx` and `dy` attributes, which shift this span and those that follow it.
+    pub dx: f32,
+    pub dy: f32,
+    /// The `font-family` list, in order of preference. Generic family names are recognized.
+    pub font_families: Vec<String>,
+    pub font_size: f32,
+    /// The `font-weight`, from 100 to 900.
+    pub font_weight: u16,
+    pub text_anchor: TextAnchor,
+    pub fill: Option<ColorU>,
+    pub stroke: Option<TextStroke>,
+}
+
+impl Default for TextSpan {
+    fn default() -> TextSpan {
+        TextSpan {
+            text: String::new(),
+            x: None,
+            y: None,
+            dx: 0.0,
+            dy: 0.0,
+            font_families: vec![],
+            font_size: 16.0,
+            font_weight: 400,
+            text_anchor: TextAnchor::Start,
+            fill: Some(ColorU::black()),
+            stroke: None,
+        }
+    }
+}
+
+/// The `text-anchor` property.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextAnchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStroke {
+    pub color: ColorU,
+    pub width: f32,
+}
+
+pub(crate) struct TextRenderer {
+    // The source of fonts. If `None`, the fonts installed on the system are used, and looked for
+    // when they're first needed.
+    font_source: Option<Box<dyn Source>>,
+    // The fonts chosen for each list of families and weight. `None` if there was no match.
+    font_collections: HashMap<(Vec<String>, u16), Option<FontCollection>>,
+}
+
+impl TextRenderer {
+    pub(crate) fn new(font_source: Option<Box<dyn Source>>) -> TextRenderer {
+        TextRenderer { font_source, font_collections: HashMap::new() }
+    }
+
+    /// Draws the text into the scene. Returns false if any of it couldn't be drawn.
+    pub(crate) fn draw_text(&mut self,
+                            text: &TextElement,
+                            transform: &Transform2DF,
+                            scene: &mut Scene)
+                            -> bool {
+        let mut drawn_all = true;
+        let mut chunk = TextChunk::default();
+        let mut pen = Vector2F::default();
+        for (span_index, span) in text.spans.iter().enumerate() {
+            if span_index == 0 || span.x.is_some() || span.y.is_some() {
+                drawn_all &= chunk.draw(pen, transform, scene);
+                pen = Vector2F::new(span.x.unwrap_or(pen.x()), span.y.unwrap_or(pen.y()));
+                chunk = TextChunk { runs: vec![], anchor: span.text_anchor, start: pen.x() };
+            }
+            pen = pen + Vector2F::new(span.dx, span.dy);
+
+            let collection = match self.font_collection(&span.font_families, span.font_weight) {
+                Some(collection) => collection,
+                None => {
+                    drawn_all = false;
+                    continue;
+                }
+            };
+            let style = TextStyle { size: span.font_size };
+            let layout = skribo::layout(&style, collection, &span.text);
+            let origin = pen;
+            pen = pen + Vector2F::new(layout.advance.x, 0.0);
+            chunk.runs.push(TextRun { span: span.clone(), layout, origin });
+        }
+        drawn_all &= chunk.draw(pen, transform, scene);
+        drawn_all
+    }
+
+    fn font_collection(&mut self, families: &[String], weight: u16) -> Option<&FontCollection> {
+        let font_source = &mut self.font_source;
+        self.font_collections.entry((families.to_vec(), weight)).or_insert_with(|| {
+            let mut family_names: Vec<_> = families.iter().map(|family| {
+                match &*family.to_lowercase() {
+                    "serif" => FamilyName::Serif,
+                    "sans-serif" => FamilyName::SansSerif,
+                    "monospace" => FamilyName::Monospace,
+                    "cursive" => FamilyName::Cursive,
+                    "fantasy" => FamilyName::Fantasy,
+                    _ => FamilyName::Title(family.clone()),
+                }
+            }).collect();
+            // The initial value of `font-family` depends on the user agent. Ours is serif.
+            family_names.push(FamilyName::Serif);
+
+            let mut properties = Properties::new();
+            properties.weight(Weight(weight as f32));
+            let font_source = font_source.get_or_insert_with(|| Box::new(SystemSource::new()));
+            let font = font_source.select_best_match(&family_names, &properties).ok()?
+                                  .load()
+                                  .ok()?;
+            let mut collection = FontCollection::new();
+            collection.add_family(FontFamily::new_from_font(font));
+            Some(collection)
+        }).as_ref()
+    }
+}
+
+// Text from one absolutely positioned point onward, which is aligned as a whole.
+struct TextChunk {
+    runs: Vec<TextRun>,
+    anchor: TextAnchor,
+    start: f32,
+}
+
+impl Default for TextChunk {
+    fn default() -> TextChunk {
+        TextChunk { runs: vec![], anchor: TextAnchor::Start, start: 0.0 }
+    }
+}
+
+struct TextRun {
+    span: TextSpan,
+    layout: Layout,
+    origin: Vector2F,
+}
+
+impl TextChunk {
+    // Draws the runs, given the position of the pen at the end of the chunk.
+    fn draw(&mut self, end: Vector2F, transform: &Transform2DF, scene: &mut Scene) -> bool {
+        let advance = end.x() - self.start;
+        let offset = Vector2F::new(match self.anchor {
+            TextAnchor::Start => 0.0,
+            TextAnchor::Middle => -advance * 0.5,
+            TextAnchor::End => -advance,
+        }, 0.0);
+
+        let mut drawn_all = true;
+        for run in mem::replace(&mut self.runs, vec![]) {
+            let span = &run.span;
+
+            // The glyphs are laid out in user space, and only transformed once they've been
+            // stroked, so that strokes scale with the transform as those of other shapes do.
+            let mut outlines = vec![];
+            for glyph in &run.layout.glyphs {
+                let font = &*glyph.font.font;
+                let position = run.origin + offset + Vector2F::new(glyph.offset.x, glyph.offset.y);
+                match glyph_outline(font, glyph.glyph_id, span.font_size, position) {
+                    Some(outline) => outlines.push(outline),
+                    None => drawn_all = false,
+                }
+            }
+
+            if let Some(color) = span.fill {
+                let paint_id = scene.push_paint(&Paint { color });
+                for outline in &outlines {
+                    let mut outline = outline.clone();
+                    outline.transform(transform);
+                    scene.push_path(PathObject::new(outline, paint_id, String::new()));
+                }
+            }
+            if let Some(stroke) = span.stroke {
+                let paint_id = scene.push_paint(&Paint { color: stroke.color });
+                let stroke_style = StrokeStyle {
+                    line_width: stroke.width,
+                    line_cap: LineCap::Butt,
+                    line_join: LineJoin::Miter(4.0),
+                };
+                for outline in &outlines {
+                    let mut stroke_to_fill = OutlineStrokeToFill::new(outline, stroke_style);
+                    stroke_to_fill.set_transform(transform, StrokeTransform::Scaling);
+                    stroke_to_fill.offset();
+                    let outline = stroke_to_fill.into_outline();
+                    scene.push_path(PathObject::new(outline, paint_id, String::new()));
+                }
+            }
+        }
+        drawn_all
+    }
+}
+
+// The outline of a glyph in user space, with its origin at `position`. Returns `None` if the glyph
+// couldn't be loaded.
+fn glyph_outline<F>(font: &F, glyph_id: u32, font_size: f32, position: Vector2F)
+                    -> Option<Outline>
+                    where F: Loader {
+    // Font units point up.
+    let scale = font_size / font.metrics().units_per_em as f32;
+    let transform = Transform2DF::from_scale(Vector2F::new(scale, -scale)).post_translate(position);
+
+    // The glyph is drawn into a scene of its own, to take its outline back out.
+    let mut scene = Scene::new();
+    let paint_id = scene.push_paint(&Paint { color: ColorU::black() });
+    scene.push_glyph(font,
+                     glyph_id,
+                     &transform,
+                     TextRenderMode::Fill,
+                     HintingOptions::None,
+                     paint_id).ok()?;
+    scene.paths().next().map(|(_, outline, _)| outline.clone())
+}
@@ -0,0 +1,427 @@
+// pathfinder/svg/src/xml.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reads an SVG document with roxmltree and writes it back out with some elements left out and
+//! some attributes changed.
+//!
+//! The output is spliced together from the source text, so everything that isn't changed, such as
+//! text, comments, entity references, and the document type declaration, comes out as it was.
+
+use roxmltree::Error as XmlError;
+use std::collections::HashMap;
+use std::ops::Range;
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+pub(crate) struct Document {
+    text: String,
+    // In document order, so that the index of an element is its number, starting with the root
+    // at 0.
+    elements: Vec<Element>,
+}
+
+pub(crate) struct Element {
+    name: String,
+    namespace: Option<String>,
+    attributes: Vec<Attribute>,
+    pub(crate) parent: Option<usize>,
+    children: Vec<usize>,
+    content: Vec<Content>,
+    // The whole element in the source, and the offset just past its last attribute, where new
+    // attributes go.
+    range: Range<usize>,
+    attributes_end: usize,
+}
+
+/// A child of an element, in document order.
+pub(crate) enum Content {
+    /// An element, by number.
+    Element(usize),
+    /// Character data, with entity and character references replaced.
+    Text(String),
+}
+
+struct Attribute {
+    // The name as it appears in the source, with its prefix.
+    qualified_name: String,
+    local_name: String,
+    namespace: Option<String>,
+    // With entity and character references replaced.
+    value: String,
+    range: Range<usize>,
+    value_range: Range<usize>,
+}
+
+impl Document {
+    pub(crate) fn parse(text: &str) -> Result<Document, XmlError> {
+        let document = roxmltree::Document::parse(text)?;
+
+        // The elements enclosing the current one, innermost last.
+        let mut ancestors: Vec<usize> = vec![];
+        let mut elements: Vec<Element> = vec![];
+        for node in document.root_element().descendants() {
+            let range = node.range();
+            while let Some(&ancestor) = ancestors.last() {
+                if elements[ancestor].range.end > range.start {
+                    break;
+                }
+                ancestors.pop();
+            }
+
+            if node.is_text() {
+                if let Some(&parent) = ancestors.last() {
+                    let text = node.text().unwrap_or("").to_owned();
+                    elements[parent].content.push(Content::Text(text));
+                }
+                continue;
+            }
+            if !node.is_element() {
+                continue;
+            }
+
+            let attributes: Vec<_> = node.attributes().iter().map(|attribute| {
+                let range = attribute.range();
+                let name_length = text[range.start..].find(|character: char| {
+                    character == '=' || character.is_whitespace()
+                }).unwrap_or(0);
+                Attribute {
+                    qualified_name: text[range.start..(range.start + name_length)].to_owned(),
+                    local_name: attribute.name().to_owned(),
+                    namespace: attribute.namespace().map(str::to_owned),
+                    value: attribute.value().to_owned(),
+                    range,
+                    value_range: attribute.value_range(),
+                }
+            }).collect();
+            let attributes_end = match attributes.last() {
+                Some(attribute) => attribute.range.end,
+                None => {
+                    let name_length = text[(range.start + 1)..].find(|character: char| {
+                        character == '/' || character == '>' || character.is_whitespace()
+                    }).unwrap_or(0);
+                    range.start + 1 + name_length
+                }
+            };
+
+            let element_index = elements.len();
+            let parent = ancestors.last().cloned();
+            if let Some(parent) = parent {
+                elements[parent].children.push(element_index);
+                elements[parent].content.push(Content::Element(element_index));
+            }
+            elements.push(Element {
+                name: node.tag_name().name().to_owned(),
+                namespace: node.tag_name().namespace().map(str::to_owned),
+                attributes,
+                parent,
+                children: vec![],
+                content: vec![],
+                range,
+                attributes_end,
+            });
+            ancestors.push(element_index);
+        }
+
+        Ok(Document { text: text.to_owned(), elements })
+    }
+
+    #[inline]
+    pub(crate) fn elements(&self) -> &[Element] {
+        &self.elements
+    }
+
+    pub(crate) fn children<'a>(&'a self, element: &'a Element)
+                               -> impl Iterator<Item = &'a Element> + 'a {
+        element.children.iter().map(move |&element_index| &self.elements[element_index])
+    }
+
+    /// Writes the document back out.
+    ///
+    /// The elements for which `skip` returns true are left out, along with their content, and the
+    /// attributes in `overrides` replace those of the element with the same number.
+    pub(crate) fn write<F>(&self, skip: F, overrides: &HashMap<usize, Vec<(String, String)>>)
+                           -> String
+                           where F: Fn(&Element) -> bool {
+        // Replacements for ranges of the source, in order.
+        let mut edits: Vec<(Range<usize>, String)> = vec![];
+        let mut skipped_end = 0;
+        for (element_index, element) in self.elements.iter().enumerate() {
+            if element.range.start < skipped_end {
+                continue;
+            }
+            if skip(element) {
+                edits.push((element.range.clone(), String::new()));
+                skipped_end = element.range.end;
+            } else if let Some(overrides) = overrides.get(&element_index) {
+                element.push_attribute_edits(&self.text, overrides, &mut edits);
+            }
+        }
+
+        let mut output = String::with_capacity(self.text.len());
+        let mut position = 0;
+        for (range, replacement) in edits {
+            output.push_str(&self.text[position..range.start]);
+            output.push_str(&replacement);
+            position = range.end;
+        }
+        output.push_str(&self.text[position..]);
+        output
+    }
+
+    /// Writes a document of the content of the element, inside the root element and after the
+    /// prolog of this one, so that namespace prefixes and entities still resolve.
+    pub(crate) fn extract(&self, element: &Element) -> String {
+        let root = &self.elements[0];
+        let root_start_tag = &self.text[root.range.start..self.start_tag_end(root)];
+        let root_start_tag = root_start_tag.trim_end_matches('>').trim_end_matches('/');
+        let root_name = root_start_tag[1..].split(char::is_whitespace).next().unwrap_or("");
+
+        let content_start = self.start_tag_end(element);
+        let content = if self.text[..content_start].ends_with("/>") {
+            ""
+        } else {
+            let content_end = self.text[..element.range.end].rfind("</").unwrap_or(content_start);
+            &self.text[content_start..content_end]
+        };
+
+        let prolog = &self.text[..root.range.start];
+        format!("{}{}>{}</{}>", prolog, root_start_tag, content, root_name)
+    }
+
+    // The offset just past the start tag. Attribute values, which may contain `>`, all come before
+    // `attributes_end`.
+    fn start_tag_end(&self, element: &Element) -> usize {
+        match self.text[element.attributes_end..element.range.end].find('>') {
+            Some(index) => element.attributes_end + index + 1,
+            None => element.range.end,
+        }
+    }
+}
+
+impl Element {
+    /// The local name, if this is an SVG element.
+    pub(crate) fn svg_name(&self) -> Option<&str> {
+        match self.namespace {
+            Some(ref namespace) if namespace == SVG_NAMESPACE => Some(&self.name),
+            _ => None,
+        }
+    }
+
+    /// The child elements and character data, in document order.
+    #[inline]
+    pub(crate) fn content(&self) -> &[Content] {
+        &self.content
+    }
+
+    /// The value of the attribute with the given name and no namespace.
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|attribute| {
+            attribute.namespace.is_none() && attribute.local_name == name
+        }).map(|attribute| &*attribute.value)
+    }
+
+    /// The `href` attribute, in either its SVG 2 or its XLink form.
+    pub(crate) fn href(&self) -> Option<&str> {
+        self.attribute("href").or_else(|| {
+            self.attributes.iter().find(|attribute| {
+                attribute.namespace.as_deref() == Some(XLINK_NAMESPACE) &&
+                    attribute.local_name == "href"
+            }).map(|attribute| &*attribute.value)
+        })
+    }
+
+    /// The value of a property in the `style` attribute.
+    pub(crate) fn style_property(&self, name: &str) -> Option<String> {
+        let style = self.attribute("style")?;
+        style.split(';').rev().filter_map(|declaration| {
+            let colon_index = declaration.find(':')?;
+            if declaration[..colon_index].trim() != name {
+                return None;
+            }
+            Some(declaration[(colon_index + 1)..].trim().to_owned())
+        }).next()
+    }
+
+    fn push_attribute_edits(&self,
+                            text: &str,
+                            overrides: &[(String, String)],
+                            edits: &mut Vec<(Range<usize>, String)>) {
+        let is_overridden = |name: &str| {
+            overrides.iter().any(|(override_name, _)| override_name == name)
+        };
+        // Removing an attribute takes the whitespace before it too.
+        let removal = |range: &Range<usize>| text[..range.start].trim_end().len()..range.end;
+
+        for attribute in &self.attributes {
+            if is_overridden(&attribute.qualified_name) {
+                edits.push((removal(&attribute.range), String::new()));
+                continue;
+            }
+            if attribute.qualified_name != "style" {
+                continue;
+            }
+
+            // Declarations in `style` take precedence over attributes, so drop those that are
+            // overridden.
+            let declarations: Vec<_> = attribute.value.split(';').map(str::trim).filter(|part| {
+                !part.is_empty()
+            }).collect();
+            let kept: Vec<_> = declarations.iter().cloned().filter(|declaration| {
+                !is_overridden(declaration.split(':').next().unwrap_or("").trim())
+            }).collect();
+            if kept.len() == declarations.len() {
+                continue;
+            }
+            if kept.is_empty() {
+                edits.push((removal(&attribute.range), String::new()));
+            } else {
+                edits.push((attribute.value_range.clone(), escape(&kept.join(";"))));
+            }
+        }
+
+        let added: String = overrides.iter().map(|(name, value)| {
+            format!(" {}=\"{}\"", name, escape(value))
+        }).collect();
+        edits.push((self.attributes_end..self.attributes_end, added));
+    }
+}
+
+// Escapes a value so that it can go between either kind of quotes.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Content, Document};
+    use std::collections::HashMap;
+
+    static SOURCE: &str = r##"<?xml version="1.0"?>
+<!DOCTYPE svg [ <!ENTITY color "red"> ]>
+<!-- A comment, with <markup> in it. -->
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:s="http://www.w3.org/2000/svg"
+     xmlns:l="http://www.w3.org/1999/xlink">
+  <title>Tom &amp; Jerry <![CDATA[<3]]></title>
+  <rect id="a" fill='&color;' data-label='say "hi"' style="opacity: 0.5; stroke : red"/>
+  <s:g id="b"><s:circle r="1"/></s:g>
+  <use id="c" l:href="#a"/>
+</svg>
+"##;
+
+    fn index_of(document: &Document, id: &str) -> usize {
+        document.elements().iter().position(|element| element.attribute("id") == Some(id)).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let document = Document::parse(SOURCE).unwrap();
+        assert_eq!(document.write(|_| false, &HashMap::new()), SOURCE);
+    }
+
+    #[test]
+    fn test_elements_and_attributes() {
+        let document = Document::parse(SOURCE).unwrap();
+        let names: Vec<_> = document.elements().iter().map(|element| element.svg_name()).collect();
+        assert_eq!(names, [Some("svg"), Some("title"), Some("rect"), Some("g"), Some("circle"),
+                           Some("use")]);
+
+        let rect = &document.elements()[index_of(&document, "a")];
+        assert_eq!(rect.parent, Some(0));
+        assert_eq!(rect.attribute("fill"), Some("red"));
+        assert_eq!(rect.attribute("data-label"), Some("say \"hi\""));
+        assert_eq!(rect.style_property("stroke"), Some("red".to_owned()));
+
+        let group = &document.elements()[index_of(&document, "b")];
+        let kids: Vec<_> = document.children(group).map(|kid| kid.svg_name()).collect();
+        assert_eq!(kids, [Some("circle")]);
+
+        // Character data comes with references replaced, between the elements around it.
+        let title: String = document.elements()[1].content().iter().map(|content| {
+            match *content {
+                Content::Text(ref text) => &**text,
+                Content::Element(_) => panic!("the title has no elements"),
+            }
+        }).collect();
+        assert_eq!(title, "Tom & Jerry <3");
+        let root_content = document.elements()[0].content().iter().filter_map(|content| {
+            match *content {
+                Content::Element(element_index) => Some(element_index),
+                Content::Text(_) => None,
+            }
+        }).collect::<Vec<_>>();
+        assert_eq!(root_content, [1, 2, 3, 5]);
+
+        // The XLink namespace is found by its URI, whatever its prefix.
+        assert_eq!(document.elements()[index_of(&document, "c")].href(), Some("#a"));
+    }
+
+    #[test]
+    fn test_skip_and_override() {
+        let document = Document::parse(SOURCE).unwrap();
+        let mut overrides = HashMap::new();
+        overrides.insert(index_of(&document, "a"), vec![
+            ("fill".to_owned(), "<'&'>".to_owned()),
+            ("stroke".to_owned(), "green".to_owned()),
+        ]);
+        let output = document.write(|element| element.attribute("id") == Some("b"), &overrides);
+
+        assert!(output.contains(r#"<rect id="a" data-label='say "hi"' style="opacity: 0.5" "#));
+        assert!(output.contains(r#" fill="&lt;&apos;&amp;&apos;&gt;" stroke="green"/>"#));
+        assert!(!output.contains("s:g") && !output.contains("circle"));
+        assert!(output.contains("<title>Tom &amp; Jerry <![CDATA[<3]]></title>"));
+
+        // The output reads back with the new values.
+        let document = Document::parse(&output).unwrap();
+        let rect = &document.elements()[index_of(&document, "a")];
+        assert_eq!(rect.attribute("fill"), Some("<'&'>"));
+        assert_eq!(rect.style_property("stroke"), None);
+    }
+
+    #[test]
+    fn test_extract() {
+        let document = Document::parse(SOURCE).unwrap();
+        let group = &document.elements()[index_of(&document, "b")];
+        let extracted = document.extract(group);
+        assert!(extracted.starts_with("<?xml version=\"1.0\"?>\n<!DOCTYPE svg"));
+        let root_end = "xmlns:l=\"http://www.w3.org/1999/xlink\">";
+        assert!(extracted.ends_with(&format!("{}<s:circle r=\"1\"/></svg>", root_end)));
+
+        let extracted = Document::parse(&extracted).unwrap();
+        let names: Vec<_> = extracted.elements().iter().map(|element| element.svg_name()).collect();
+        assert_eq!(names, [Some("svg"), Some("circle")]);
+
+        // Elements without content give an empty root.
+        let rect = &document.elements()[index_of(&document, "a")];
+        assert_eq!(Document::parse(&document.extract(rect)).unwrap().elements().len(), 1);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(Document::parse("").is_err());
+        assert!(Document::parse("<svg><g></svg>").is_err());
+        assert!(Document::parse("<svg></svg><svg></svg>").is_err());
+        assert!(Document::parse("<a:svg xmlns:a='x'></b:svg>").is_err());
+        assert!(Document::parse("<svg x='1' x='2'/>").is_err());
+        assert!(Document::parse("<svg x='&unknown;'/>").is_err());
+        assert!(Document::parse("<p:svg/>").is_err());
+    }
+}
@@ -0,0 +1,248 @@
+// pathfinder/swf/src/bitmap.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use pathfinder_content::color::{ColorF, ColorU};
+use swf_tree::ImageType;
+use swf_tree::tags::DefineBitmap;
+
+const FORMAT_COLOR_MAPPED: u8 = 3;
+const FORMAT_RGB15: u8 = 4;
+const FORMAT_RGB32: u8 = 5;
+
+pub(crate) struct Bitmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<ColorU>,
+}
+
+impl Bitmap {
+    // The pixels are in rows, from the top.
+    pub(crate) fn new(width: usize, height: usize, pixels: Vec<ColorU>) -> Bitmap {
+        debug_assert_eq!(pixels.len(), width * height);
+        Bitmap { width, height, pixels }
+    }
+
+    #[inline]
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    // Averages the pixels in the given range of columns and rows.
+    pub(crate) fn average_color(&self, x0: usize, x1: usize, y0: usize, y1: usize) -> ColorU {
+        let (mut sum, mut count) = (ColorF::transparent_black(), 0);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let pixel = self.pixels[y * self.width + x].to_f32().premultiply();
+                sum = ColorF::new(sum.r() + pixel.r(),
+                                  sum.g() + pixel.g(),
+                                  sum.b() + pixel.b(),
+                                  sum.a() + pixel.a());
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return ColorU::transparent_black();
+        }
+        let scale = 1.0 / count as f32;
+        ColorF::new(sum.r() * scale, sum.g() * scale, sum.b() * scale, sum.a() * scale)
+            .unpremultiply()
+            .to_u8()
+    }
+}
+
+// Decodes the lossless bitmap formats. The tag data starts with the format byte, followed by
+// the dimensions, the color table size for color-mapped bitmaps, and then the zlib-compressed
+// pixels.
+//
+// TODO: Decode JPEG, PNG and GIF data too.
+pub(crate) fn decode_bitmap(tag: &DefineBitmap) -> Option<Bitmap> {
+    let has_alpha = match tag.media_type {
+        ImageType::SwfLossless1 => false,
+        ImageType::SwfLossless2 => true,
+        _ => return None,
+    };
+
+    let data = &tag.data[..];
+    if data.len() < 5 {
+        return None;
+    }
+    let format = data[0];
+    let width = u16::from_le_bytes([data[1], data[2]]) as usize;
+    let height = u16::from_le_bytes([data[3], data[4]]) as usize;
+    if width != tag.width as usize || height != tag.height as usize {
+        return None;
+    }
+    let (color_table_size, compressed) = if format == FORMAT_COLOR_MAPPED {
+        (*data.get(5)? as usize + 1, &data[6..])
+    } else {
+        (0, &data[5..])
+    };
+    let bytes = inflate::inflate_bytes_zlib(compressed).ok()?;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    match format {
+        FORMAT_COLOR_MAPPED => {
+            let entry_size = if has_alpha { 4 } else { 3 };
+            let table_length = color_table_size * entry_size;
+            let color_table = bytes.get(..table_length)?.chunks(entry_size).map(|entry| {
+                let alpha = if has_alpha { entry[3] } else { 255 };
+                unpremultiply(entry[0], entry[1], entry[2], alpha)
+            }).collect::<Vec<_>>();
+            // Rows are padded to 32 bits.
+            let stride = (width + 3) & !3;
+            for y in 0..height {
+                for x in 0..width {
+                    let index = *bytes.get(table_length + y * stride + x)? as usize;
+                    pixels.push(color_table.get(index).cloned().unwrap_or(ColorU::black()));
+                }
+            }
+        }
+        FORMAT_RGB15 => {
+            let stride = (width * 2 + 3) & !3;
+            for y in 0..height {
+                for x in 0..width {
+                    let offset = y * stride + x * 2;
+                    let pixel = u16::from_be_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]);
+                    let channel = |shift: u16| (((pixel >> shift) & 0x1f) as u32 * 255 / 31) as u8;
+                    pixels.push(ColorU::new(channel(10), channel(5), channel(0), 255));
+                }
+            }
+        }
+        FORMAT_RGB32 => {
+            let argb = bytes.get(..width * height * 4)?;
+            for pixel in argb.chunks(4) {
+                // The first byte is reserved, not alpha, in bitmaps without alpha.
+                let alpha = if has_alpha { pixel[0] } else { 255 };
+                pixels.push(unpremultiply(pixel[1], pixel[2], pixel[3], alpha));
+            }
+        }
+        _ => return None,
+    }
+
+    Some(Bitmap::new(width, height, pixels))
+}
+
+// Bitmaps with alpha store premultiplied colors.
+#[inline]
+fn unpremultiply(r: u8, g: u8, b: u8, a: u8) -> ColorU {
+    if a == 0 || a == 255 {
+        return ColorU::new(r, g, b, a);
+    }
+    let channel = |value: u8| u32::min(value as u32 * 255 / a as u32, 255) as u8;
+    ColorU::new(channel(r), channel(g), channel(b), a)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_bitmap, Bitmap, FORMAT_COLOR_MAPPED, FORMAT_RGB15, FORMAT_RGB32};
+    use pathfinder_content::color::ColorU;
+    use swf_tree::ImageType;
+    use swf_tree::tags::DefineBitmap;
+
+    // Wraps the data in a zlib stream made of one uncompressed block.
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        let length = data.len() as u16;
+        let mut stream = vec![0x78, 0x01, 0x01];
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(data);
+        stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        stream
+    }
+
+    fn tag(media_type: ImageType, format: u8, width: u16, height: u16, extra: &[u8], pixels: &[u8])
+           -> DefineBitmap {
+        let mut data = vec![format];
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(extra);
+        data.extend_from_slice(&zlib(pixels));
+        DefineBitmap { id: 1, width, height, media_type, data }
+    }
+
+    #[test]
+    fn test_rgb32() {
+        // Without alpha, the first byte of each pixel is ignored.
+        let pixels = [0x00, 255, 0, 0, 0x12, 0, 0, 255];
+        let bitmap = decode_bitmap(&tag(ImageType::SwfLossless1, FORMAT_RGB32, 2, 1, &[], &pixels))
+            .unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (2, 1));
+        assert_eq!(bitmap.pixels, [ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255)]);
+
+        // With alpha, it's the alpha of a premultiplied color.
+        let pixels = [128, 64, 32, 0, 0, 9, 9, 9];
+        let bitmap = decode_bitmap(&tag(ImageType::SwfLossless2, FORMAT_RGB32, 2, 1, &[], &pixels))
+            .unwrap();
+        assert_eq!(bitmap.pixels, [ColorU::new(127, 63, 0, 128), ColorU::new(9, 9, 9, 0)]);
+    }
+
+    #[test]
+    fn test_color_mapped() {
+        // Two colors, and rows of three indices padded to four bytes.
+        let table = [255, 0, 0, 0, 255, 0];
+        let indices = [0, 1, 0, 0xff, 1, 1, 7, 0xff];
+        let mut pixels = table.to_vec();
+        pixels.extend_from_slice(&indices);
+        let tag = tag(ImageType::SwfLossless1, FORMAT_COLOR_MAPPED, 3, 2, &[1], &pixels);
+        let bitmap = decode_bitmap(&tag).unwrap();
+        let (red, green) = (ColorU::new(255, 0, 0, 255), ColorU::new(0, 255, 0, 255));
+        // Indices past the end of the table are black.
+        assert_eq!(bitmap.pixels, [red, green, red, green, green, ColorU::black()]);
+    }
+
+    #[test]
+    fn test_rgb15() {
+        // Rows of one two-byte pixel are padded to four bytes.
+        let pixels = [0x7c, 0x00, 0, 0, 0x00, 0x1f, 0, 0];
+        let bitmap = decode_bitmap(&tag(ImageType::SwfLossless1, FORMAT_RGB15, 1, 2, &[], &pixels))
+            .unwrap();
+        assert_eq!(bitmap.pixels, [ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255)]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let pixels = [0, 255, 0, 0];
+        let mut bad_size = tag(ImageType::SwfLossless1, FORMAT_RGB32, 1, 1, &[], &pixels);
+        bad_size.width = 2;
+        assert!(decode_bitmap(&bad_size).is_none());
+        let jpeg = tag(ImageType::Jpeg, FORMAT_RGB32, 1, 1, &[], &pixels);
+        assert!(decode_bitmap(&jpeg).is_none());
+        let short = tag(ImageType::SwfLossless1, FORMAT_RGB32, 2, 1, &[], &pixels);
+        assert!(decode_bitmap(&short).is_none());
+    }
+
+    #[test]
+    fn test_average_color() {
+        let bitmap = Bitmap {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                ColorU::new(255, 0, 0, 255),
+                ColorU::transparent_black(),
+                ColorU::new(0, 0, 255, 255),
+                ColorU::new(0, 0, 255, 255),
+            ],
+        };
+        // Transparent pixels don't darken the average.
+        assert_eq!(bitmap.average_color(0, 2, 0, 1), ColorU::new(255, 0, 0, 128));
+        assert_eq!(bitmap.average_color(0, 1, 0, 2), ColorU::new(128, 0, 128, 255));
+        assert_eq!(bitmap.average_color(1, 1, 0, 2), ColorU::transparent_black());
+    }
+}
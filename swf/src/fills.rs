@@ -0,0 +1,578 @@
+// pathfinder/swf/src/fills.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Gradient and bitmap fills.
+//!
+//! Pathfinder paints are solid colors, so these fills are drawn by cutting the shape into pieces
+//! that each get a single color: bands for gradients, and cells for bitmaps.
+
+use crate::timeline::{matrix_to_transform, ColorTransform};
+use crate::{Symbol, SymbolId, SymbolLibrary};
+use pathfinder_content::boolean::{BooleanOp, OutlineBoolean};
+use pathfinder_content::color::ColorU;
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::util::lerp;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{PathObject, Scene};
+use std::f32::consts::PI;
+use swf_tree::{fill_styles, morph_fill_styles, FillStyle, GradientSpread, Matrix};
+use swf_tree::{MorphColorStop, MorphFillStyle, MorphGradient, StraightSRgba8};
+
+// Gradients are defined on a square from -16384 to 16384 twips, which their matrices place in
+// the shape.
+const GRADIENT_SQUARE_HALF_SIZE: f32 = 16384.0 / 20.0;
+
+const GRADIENT_BAND_COUNT: usize = 64;
+const MAX_GRADIENT_BANDS: usize = 1024;
+const CIRCLE_SEGMENT_COUNT: usize = 64;
+
+const MAX_BITMAP_CELLS: usize = 32;
+const MAX_BITMAP_TILES: usize = 64;
+
+const TOLERANCE: f32 = 0.25;
+
+pub(crate) enum SwfFill {
+    Solid(Paint),
+    Gradient(SwfGradient),
+    Bitmap(SwfBitmapFill),
+}
+
+pub(crate) struct SwfGradient {
+    kind: GradientKind,
+    transform: Transform2DF,
+    spread: Spread,
+    // Sorted by position, from 0.0 to 1.0.
+    stops: Vec<(f32, ColorU)>,
+}
+
+#[derive(Clone, Copy)]
+enum Spread {
+    Pad,
+    Reflect,
+    Repeat,
+}
+
+#[derive(Clone, Copy)]
+enum GradientKind {
+    Linear,
+    Radial,
+    // The focal point lies on the X axis of the gradient square, from -1.0 to 1.0.
+    Focal(f32),
+}
+
+pub(crate) struct SwfBitmapFill {
+    bitmap_id: SymbolId,
+    transform: Transform2DF,
+    repeating: bool,
+}
+
+pub(crate) fn decode_fill_style(fill_style: &FillStyle) -> SwfFill {
+    match fill_style {
+        FillStyle::Solid(fill_styles::Solid { color }) => {
+            SwfFill::Solid(Paint { color: to_color(color) })
+        }
+        FillStyle::LinearGradient(fill_styles::LinearGradient { matrix, gradient }) => {
+            SwfFill::Gradient(SwfGradient::new(GradientKind::Linear, matrix, gradient))
+        }
+        FillStyle::RadialGradient(fill_styles::RadialGradient { matrix, gradient }) => {
+            SwfFill::Gradient(SwfGradient::new(GradientKind::Radial, matrix, gradient))
+        }
+        FillStyle::FocalGradient(fill_styles::FocalGradient { matrix, gradient, focal_point }) => {
+            // The focal point is in 8.8 fixed point.
+            let focal_point = f32::max(f32::min(focal_point.epsilons as f32 / 256.0, 1.0), -1.0);
+            SwfFill::Gradient(SwfGradient::new(GradientKind::Focal(focal_point), matrix, gradient))
+        }
+        FillStyle::Bitmap(fill_styles::Bitmap { bitmap_id, matrix, repeating, .. }) => {
+            // Bitmap matrices map pixels to twips.
+            let transform = Transform2DF::from_scale(Vector2F::splat(1.0 / 20.0))
+                .post_mul(&matrix_to_transform(matrix));
+            SwfFill::Bitmap(SwfBitmapFill {
+                bitmap_id: *bitmap_id,
+                transform,
+                repeating: *repeating,
+            })
+        }
+    }
+}
+
+// Decodes the fill style of a morph shape as it is partway, by `t`, from its start to its end.
+pub(crate) fn decode_morph_fill_style(fill_style: &MorphFillStyle, t: f32) -> SwfFill {
+    let gradient = |kind, matrix, morph_matrix, gradient: &MorphGradient| {
+        let stops = gradient.colors.iter().map(|stop: &MorphColorStop| {
+            let ratio = lerp(stop.ratio as f32, stop.morph_ratio as f32, t) / 255.0;
+            (ratio, lerp_color(&stop.color, &stop.morph_color, t))
+        }).collect();
+        let transform = lerp_matrix(matrix, morph_matrix, t);
+        SwfFill::Gradient(SwfGradient::from_parts(kind, transform, &gradient.spread, stops))
+    };
+    match fill_style {
+        MorphFillStyle::Solid(morph_fill_styles::Solid { color, morph_color }) => {
+            SwfFill::Solid(Paint { color: lerp_color(color, morph_color, t) })
+        }
+        MorphFillStyle::LinearGradient(morph_fill_styles::LinearGradient {
+            matrix,
+            morph_matrix,
+            gradient: morph_gradient,
+        }) => gradient(GradientKind::Linear, matrix, morph_matrix, morph_gradient),
+        MorphFillStyle::RadialGradient(morph_fill_styles::RadialGradient {
+            matrix,
+            morph_matrix,
+            gradient: morph_gradient,
+        }) => gradient(GradientKind::Radial, matrix, morph_matrix, morph_gradient),
+        MorphFillStyle::FocalGradient(morph_fill_styles::FocalGradient {
+            matrix,
+            morph_matrix,
+            gradient: morph_gradient,
+            focal_point,
+            morph_focal_point,
+        }) => {
+            // The focal points are in 8.8 fixed point.
+            let (from, to) = (focal_point.epsilons as f32, morph_focal_point.epsilons as f32);
+            let focal_point = f32::max(f32::min(lerp(from, to, t) / 256.0, 1.0), -1.0);
+            gradient(GradientKind::Focal(focal_point), matrix, morph_matrix, morph_gradient)
+        }
+        MorphFillStyle::Bitmap(morph_fill_styles::Bitmap {
+            bitmap_id,
+            matrix,
+            morph_matrix,
+            repeating,
+            ..
+        }) => {
+            let transform = Transform2DF::from_scale(Vector2F::splat(1.0 / 20.0))
+                .post_mul(&lerp_matrix(matrix, morph_matrix, t));
+            SwfFill::Bitmap(SwfBitmapFill {
+                bitmap_id: *bitmap_id,
+                transform,
+                repeating: *repeating,
+            })
+        }
+    }
+}
+
+impl SwfFill {
+    // Fills the outline, which has already been moved into place by `transform`.
+    pub(crate) fn draw_into_scene(&self,
+                                  outline: &Outline,
+                                  transform: &Transform2DF,
+                                  color_transform: &ColorTransform,
+                                  library: &SymbolLibrary,
+                                  scene: &mut Scene) {
+        let mut painter = Painter { scene, color_transform };
+        match self {
+            SwfFill::Solid(paint) => painter.push_path(outline.clone(), paint.color),
+            SwfFill::Gradient(gradient) => gradient.draw(outline, transform, &mut painter),
+            SwfFill::Bitmap(bitmap_fill) => {
+                bitmap_fill.draw(outline, transform, library, &mut painter)
+            }
+        }
+    }
+}
+
+impl SwfGradient {
+    fn new(kind: GradientKind, matrix: &swf_tree::Matrix, gradient: &swf_tree::Gradient)
+           -> SwfGradient {
+        let stops = gradient.colors.iter().map(|stop| {
+            (stop.ratio as f32 / 255.0, to_color(&stop.color))
+        }).collect();
+        SwfGradient::from_parts(kind, matrix_to_transform(matrix), &gradient.spread, stops)
+    }
+
+    fn from_parts(kind: GradientKind,
+                  transform: Transform2DF,
+                  spread: &GradientSpread,
+                  mut stops: Vec<(f32, ColorU)>)
+                  -> SwfGradient {
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        SwfGradient {
+            kind,
+            transform,
+            spread: match spread {
+                GradientSpread::Pad => Spread::Pad,
+                GradientSpread::Reflect => Spread::Reflect,
+                GradientSpread::Repeat => Spread::Repeat,
+            },
+            stops,
+        }
+    }
+
+    // NOTE: Colors are interpolated in sRGB even for gradients that ask for linear RGB.
+    fn color_at(&self, t: f32) -> ColorU {
+        let index = match self.stops.iter().position(|&(position, _)| position > t) {
+            None => return self.stops.last().map_or(ColorU::transparent_black(), |stop| stop.1),
+            Some(0) => return self.stops[0].1,
+            Some(index) => index,
+        };
+        let ((from_position, from_color), (to_position, to_color)) =
+            (self.stops[index - 1], self.stops[index]);
+        let t = (t - from_position) / (to_position - from_position);
+        from_color.to_f32().lerp(to_color.to_f32(), t).to_u8()
+    }
+
+    fn draw(&self, outline: &Outline, transform: &Transform2DF, painter: &mut Painter) {
+        let to_scene = self.transform.then(transform);
+        let bounds = to_scene.inverse().transform_rect(&outline.bounds());
+        let size = GRADIENT_SQUARE_HALF_SIZE;
+
+        // Find the range of gradient positions the outline covers.
+        let (t_min, t_max) = match self.kind {
+            GradientKind::Linear => {
+                ((bounds.min_x() + size) / (2.0 * size), (bounds.max_x() + size) / (2.0 * size))
+            }
+            GradientKind::Radial | GradientKind::Focal(_) => {
+                let focal_offset = match self.kind {
+                    GradientKind::Focal(focal_point) => f32::abs(focal_point),
+                    _ => 0.0,
+                };
+                let corners = [
+                    bounds.origin(),
+                    bounds.upper_right(),
+                    bounds.lower_right(),
+                    bounds.lower_left(),
+                ];
+                let max_distance = corners.iter().fold(0.0, |max_distance, corner| {
+                    f32::max(max_distance, corner.length())
+                });
+                (0.0, max_distance / size + focal_offset)
+            }
+        };
+
+        let bands = match gradient_bands(t_min, t_max, self.spread) {
+            Some(bands) => bands,
+            None => {
+                // The gradient repeats too often to draw band by band, so use its middle color.
+                let color = self.color_at(0.5);
+                painter.push_path(outline.clone(), color);
+                return;
+            }
+        };
+
+        for (t0, t1, color_t) in bands {
+            let mut band = match self.kind {
+                GradientKind::Linear => {
+                    let x0 = t0 * 2.0 * size - size;
+                    let x1 = t1 * 2.0 * size - size;
+                    let rect = RectF::from_points(Vector2F::new(x0, bounds.min_y() - 1.0),
+                                                  Vector2F::new(x1, bounds.max_y() + 1.0));
+                    Outline::from_rect(rect)
+                }
+                GradientKind::Radial => ring_outline(Vector2F::default(), t0, t1),
+                GradientKind::Focal(focal_point) => {
+                    ring_outline(Vector2F::new(focal_point * size, 0.0), t0, t1)
+                }
+            };
+            band.transform(&to_scene);
+            let piece = combine(outline, &band, BooleanOp::Intersection);
+            painter.push_path(piece, self.color_at(color_t));
+        }
+    }
+}
+
+impl SwfBitmapFill {
+    fn draw(&self,
+            outline: &Outline,
+            transform: &Transform2DF,
+            library: &SymbolLibrary,
+            painter: &mut Painter) {
+        let bitmap = match library.symbol(self.bitmap_id) {
+            Some(Symbol::Bitmap(bitmap)) => bitmap,
+            _ => return,
+        };
+        let (width, height) = (bitmap.width(), bitmap.height());
+        if width == 0 || height == 0 {
+            return;
+        }
+        let to_scene = self.transform.then(transform);
+
+        // NOTE: Outside a bitmap that doesn't repeat, Flash stretches its edge pixels. We
+        // leave that area empty.
+        let (tiles_x, tiles_y) = if self.repeating {
+            let bounds = to_scene.inverse().transform_rect(&outline.bounds());
+            ((bounds.min_x() / width as f32).floor() as i32..
+                (bounds.max_x() / width as f32).ceil() as i32,
+             (bounds.min_y() / height as f32).floor() as i32..
+                (bounds.max_y() / height as f32).ceil() as i32)
+        } else {
+            (0..1, 0..1)
+        };
+        if tiles_x.len() * tiles_y.len() > MAX_BITMAP_TILES {
+            let color = bitmap.average_color(0, width, 0, height);
+            painter.push_path(outline.clone(), color);
+            return;
+        }
+
+        // Draw each tile as a grid of cells, each with the average color of its pixels.
+        let (cells_x, cells_y) = (usize::min(width, MAX_BITMAP_CELLS),
+                                  usize::min(height, MAX_BITMAP_CELLS));
+        for tile_y in tiles_y {
+            for tile_x in tiles_x.clone() {
+                let tile_origin = Vector2F::new((tile_x * width as i32) as f32,
+                                                (tile_y * height as i32) as f32);
+                for cell_y in 0..cells_y {
+                    let (y0, y1) = (cell_y * height / cells_y, (cell_y + 1) * height / cells_y);
+                    for cell_x in 0..cells_x {
+                        let (x0, x1) = (cell_x * width / cells_x, (cell_x + 1) * width / cells_x);
+                        let color = bitmap.average_color(x0, x1, y0, y1);
+                        let rect = RectF::from_points(
+                            tile_origin + Vector2F::new(x0 as f32, y0 as f32),
+                            tile_origin + Vector2F::new(x1 as f32, y1 as f32));
+                        let mut cell = Outline::from_rect(rect);
+                        cell.transform(&to_scene);
+                        painter.push_path(combine(outline, &cell, BooleanOp::Intersection), color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Splits the range of gradient positions from `t_min` to `t_max` into bands, each with the
+// position its color is taken from. Returns `None` if there would be too many.
+fn gradient_bands(t_min: f32, t_max: f32, spread: Spread)
+                  -> Option<Vec<(f32, f32, f32)>> {
+    let mut bands = vec![];
+    let (first_period, last_period) = match spread {
+        Spread::Pad => {
+            if t_min < 0.0 {
+                bands.push((t_min, 0.0, 0.0));
+            }
+            if t_max > 1.0 {
+                bands.push((1.0, t_max, 1.0));
+            }
+            (0, 0)
+        }
+        Spread::Reflect | Spread::Repeat => {
+            (t_min.floor() as i32, t_max.ceil() as i32 - 1)
+        }
+    };
+
+    let period_count = (last_period - first_period + 1).max(1) as usize;
+    if period_count > MAX_GRADIENT_BANDS {
+        return None;
+    }
+    let band_count = usize::max(usize::min(GRADIENT_BAND_COUNT,
+                                           MAX_GRADIENT_BANDS / period_count), 1);
+    for period in first_period..=last_period {
+        for band_index in 0..band_count {
+            let t0 = period as f32 + band_index as f32 / band_count as f32;
+            let t1 = period as f32 + (band_index + 1) as f32 / band_count as f32;
+            let (t0, t1) = (f32::max(t0, t_min), f32::min(t1, t_max));
+            if t0 >= t1 {
+                continue;
+            }
+            let mut color_t = (band_index as f32 + 0.5) / band_count as f32;
+            if let Spread::Reflect = spread {
+                if period.rem_euclid(2) != 0 {
+                    color_t = 1.0 - color_t;
+                }
+            }
+            bands.push((t0, t1, color_t));
+        }
+    }
+    Some(bands)
+}
+
+// The region between the circles for gradient positions `t0` and `t1`. Circles shrink toward
+// the focal point as the position goes to zero.
+fn ring_outline(focal_point: Vector2F, t0: f32, t1: f32) -> Outline {
+    let mut outline = Outline::new();
+    outline.push_contour(circle_contour(focal_point, t1, false));
+    if t0 > 0.0 {
+        // Winding the inner circle the other way cuts it out.
+        outline.push_contour(circle_contour(focal_point, t0, true));
+    }
+    outline
+}
+
+fn circle_contour(focal_point: Vector2F, t: f32, reverse: bool) -> Contour {
+    let center = focal_point.scale(1.0 - t);
+    let radius = t * GRADIENT_SQUARE_HALF_SIZE;
+    let mut contour = Contour::new();
+    for point_index in 0..CIRCLE_SEGMENT_COUNT {
+        let mut angle = point_index as f32 / CIRCLE_SEGMENT_COUNT as f32 * 2.0 * PI;
+        if reverse {
+            angle = -angle;
+        }
+        contour.push_endpoint(center + Vector2F::new(angle.cos(), angle.sin()).scale(radius));
+    }
+    contour.close();
+    contour
+}
+
+pub(crate) fn combine(a: &Outline, b: &Outline, op: BooleanOp) -> Outline {
+    let mut boolean = OutlineBoolean::new(a, b, op, TOLERANCE);
+    boolean.compute();
+    boolean.into_outline()
+}
+
+// Pushes paths into a scene, with their colors transformed.
+struct Painter<'a> {
+    scene: &'a mut Scene,
+    color_transform: &'a ColorTransform,
+}
+
+impl<'a> Painter<'a> {
+    fn push_path(&mut self, outline: Outline, color: ColorU) {
+        let color = self.color_transform.apply(color);
+        if outline.contours().is_empty() || color.is_fully_transparent() {
+            return;
+        }
+        let paint_id = self.scene.push_paint(&Paint { color });
+        self.scene.push_path(PathObject::new(outline, paint_id, String::new()));
+    }
+}
+
+#[inline]
+pub(crate) fn to_color(color: &StraightSRgba8) -> ColorU {
+    ColorU { r: color.r, g: color.g, b: color.b, a: color.a }
+}
+
+pub(crate) fn lerp_color(from: &StraightSRgba8, to: &StraightSRgba8, t: f32) -> ColorU {
+    to_color(from).to_f32().lerp(to_color(to).to_f32(), t).to_u8()
+}
+
+// Interpolates the entries of two matrices, which is how morph shapes move their fills.
+pub(crate) fn lerp_matrix(from: &Matrix, to: &Matrix, t: f32) -> Transform2DF {
+    let fixed = |from: i32, to: i32| lerp(from as f32, to as f32, t) / 65536.0;
+    let twips = |from: i32, to: i32| lerp(from as f32, to as f32, t) / 20.0;
+    Transform2DF::row_major(fixed(from.scale_x.epsilons, to.scale_x.epsilons),
+                            fixed(from.rotate_skew1.epsilons, to.rotate_skew1.epsilons),
+                            fixed(from.rotate_skew0.epsilons, to.rotate_skew0.epsilons),
+                            fixed(from.scale_y.epsilons, to.scale_y.epsilons),
+                            twips(from.translate_x, to.translate_x),
+                            twips(from.translate_y, to.translate_y))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{gradient_bands, GradientKind, Spread, SwfBitmapFill, SwfFill};
+    use super::{SwfGradient, GRADIENT_BAND_COUNT, GRADIENT_SQUARE_HALF_SIZE};
+    use crate::bitmap::Bitmap;
+    use crate::timeline::ColorTransform;
+    use crate::{Symbol, SymbolLibrary};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2DF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::scene::Scene;
+    use std::collections::HashMap;
+    use swf_tree::GradientSpread;
+
+    const RED: ColorU = ColorU { r: 255, g: 0, b: 0, a: 255 };
+    const BLUE: ColorU = ColorU { r: 0, g: 0, b: 255, a: 255 };
+
+    fn draw(fill: &SwfFill, rect: RectF, library: &SymbolLibrary) -> Vec<(RectF, ColorU)> {
+        let mut scene = Scene::new();
+        fill.draw_into_scene(&Outline::from_rect(rect),
+                             &Transform2DF::default(),
+                             &ColorTransform::default(),
+                             library,
+                             &mut scene);
+        scene.paths().map(|(paint, outline, _)| (outline.bounds(), paint.color)).collect()
+    }
+
+    fn approx_eq(a: RectF, b: RectF) -> bool {
+        (a.origin() - b.origin()).length() < 0.01 && (a.size() - b.size()).length() < 0.01
+    }
+
+    #[test]
+    fn test_gradient_colors() {
+        // Stops are sorted, and the ends are padded with the first and last colors.
+        let stops = vec![(1.0, BLUE), (0.0, RED)];
+        let gradient = SwfGradient::from_parts(GradientKind::Linear,
+                                               Transform2DF::default(),
+                                               &GradientSpread::Pad,
+                                               stops);
+        assert_eq!(gradient.color_at(-1.0), RED);
+        assert_eq!(gradient.color_at(0.0), RED);
+        assert_eq!(gradient.color_at(0.5), ColorU::new(128, 0, 128, 255));
+        assert_eq!(gradient.color_at(1.0), BLUE);
+        assert_eq!(gradient.color_at(2.0), BLUE);
+    }
+
+    #[test]
+    fn test_gradient_bands() {
+        let bands = gradient_bands(-0.5, 1.5, Spread::Pad).unwrap();
+        assert_eq!(bands.len(), GRADIENT_BAND_COUNT + 2);
+        assert_eq!(bands[0], (-0.5, 0.0, 0.0));
+        assert_eq!(bands[1], (1.0, 1.5, 1.0));
+
+        // Reflected periods run backward, and bands are cut to the range.
+        let bands = gradient_bands(0.5, 2.0, Spread::Reflect).unwrap();
+        assert_eq!(bands.len(), GRADIENT_BAND_COUNT / 2 + GRADIENT_BAND_COUNT);
+        let (t0, t1, color_t) = bands[0];
+        assert_eq!((t0, t1), (0.5, 0.5 + 1.0 / GRADIENT_BAND_COUNT as f32));
+        assert!(color_t > 0.5);
+        let (t0, _, color_t) = bands[GRADIENT_BAND_COUNT / 2];
+        assert_eq!(t0, 1.0);
+        assert!(color_t > 0.99);
+        let (_, _, color_t) = gradient_bands(1.0, 2.0, Spread::Repeat).unwrap()[0];
+        assert!(color_t < 0.01);
+
+        assert!(gradient_bands(0.0, 1.0e6, Spread::Repeat).is_none());
+    }
+
+    #[test]
+    fn test_linear_gradient_fill() {
+        // Map the gradient square onto 0 to 100 on both axes.
+        let scale = 50.0 / GRADIENT_SQUARE_HALF_SIZE;
+        let transform = Transform2DF::from_scale(Vector2F::splat(scale))
+            .post_mul(&Transform2DF::from_translation(Vector2F::splat(50.0)));
+        let fill = SwfFill::Gradient(SwfGradient::from_parts(GradientKind::Linear,
+                                                             transform,
+                                                             &GradientSpread::Pad,
+                                                             vec![(0.0, RED), (1.0, BLUE)]));
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(100.0));
+        let library = SymbolLibrary(HashMap::new());
+        let pieces = draw(&fill, rect, &library);
+
+        // The shape is cut into vertical bands, going from red to blue.
+        assert_eq!(pieces.len(), GRADIENT_BAND_COUNT);
+        let width = 100.0 / GRADIENT_BAND_COUNT as f32;
+        let (first_bounds, first_color) = pieces[0];
+        let first_rect = RectF::new(Vector2F::default(), Vector2F::new(width, 100.0));
+        assert!(approx_eq(first_bounds, first_rect));
+        assert!(first_color.r > 250 && first_color.b < 5);
+        let (last_bounds, last_color) = pieces[GRADIENT_BAND_COUNT - 1];
+        assert!((last_bounds.max_x() - 100.0).abs() < 0.01);
+        assert!(last_color.b > 250 && last_color.r < 5);
+    }
+
+    #[test]
+    fn test_bitmap_fill() {
+        let mut symbols = HashMap::new();
+        symbols.insert(1, Symbol::Bitmap(Bitmap::new(2, 1, vec![RED, BLUE])));
+        let library = SymbolLibrary(symbols);
+
+        // Each pixel is 10 units square.
+        let transform = Transform2DF::from_scale(Vector2F::splat(10.0));
+        let fill = SwfFill::Bitmap(SwfBitmapFill { bitmap_id: 1, transform, repeating: false });
+        let rect = RectF::new(Vector2F::default(), Vector2F::new(40.0, 10.0));
+        let pieces = draw(&fill, rect, &library);
+        assert_eq!(pieces.len(), 2);
+        assert!(approx_eq(pieces[0].0, RectF::new(Vector2F::default(), Vector2F::splat(10.0))));
+        assert_eq!((pieces[0].1, pieces[1].1), (RED, BLUE));
+
+        // Repeating bitmaps tile the whole shape.
+        let fill = SwfFill::Bitmap(SwfBitmapFill { bitmap_id: 1, transform, repeating: true });
+        let pieces = draw(&fill, rect, &library);
+        let colors: Vec<_> = pieces.iter().map(|piece| piece.1).collect();
+        assert_eq!(colors, [RED, BLUE, RED, BLUE]);
+        let last_rect = RectF::new(Vector2F::new(30.0, 0.0), Vector2F::splat(10.0));
+        assert!(approx_eq(pieces[3].0, last_rect));
+
+        // Missing bitmaps draw nothing.
+        let fill = SwfFill::Bitmap(SwfBitmapFill { bitmap_id: 2, transform, repeating: true });
+        assert!(draw(&fill, rect, &library).is_empty());
+    }
+}
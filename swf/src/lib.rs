@@ -11,17 +11,29 @@
 use std::ops::Add;
 use pathfinder_content::color::{ColorU, ColorF};
 use pathfinder_content::outline::{Outline, Contour};
+use pathfinder_geometry::transform2d::Transform2DF;
 use pathfinder_geometry::vector::Vector2F;
 use pathfinder_content::stroke::{OutlineStrokeToFill, StrokeStyle};
-use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_renderer::scene::Scene;
+use std::collections::HashMap;
 
 use swf_tree;
 use swf_tree::tags::SetBackgroundColor;
-use swf_tree::{Tag, SRgb8, Movie};
+use swf_tree::{Tag, SRgb8, Movie, MorphShape};
 
-use crate::shapes::{GraphicLayers, PaintOrLine};
+use crate::bitmap::Bitmap;
+use crate::shapes::{GraphicLayers, PaintOrLine, StyleLayer};
+use crate::text::{Font, Text};
+use crate::timeline::{ColorTransform, Timeline};
 
+pub use crate::timeline::Frames;
+
+mod bitmap;
+mod fills;
+mod morph;
 mod shapes;
+mod text;
+mod timeline;
 
 type SymbolId = u16;
 
@@ -72,15 +84,19 @@ impl Add for Point2<Twips> {
 
 enum Symbol {
     Graphic(GraphicLayers),
-    // Timeline, // TODO(jon)
+    Bitmap(Bitmap),
+    Sprite(Timeline),
+    MorphShape(MorphShape),
+    Font(Font),
+    Text(Text),
 }
 
 pub struct Stage {
-    // TODO(jon): Support some kind of lazy frames iterator.
-    // frames: Timeline,
+    timeline: Timeline,
     background_color: SRgb8,
     width: i32,
     height: i32,
+    frame_rate: f32,
 }
 
 impl Stage {
@@ -100,29 +116,41 @@ impl Stage {
             a: 255,
         }.to_f32()
     }
-}
 
+    /// The number of frames shown per second.
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.timeline.len()
+    }
+
+    /// Returns an iterator that builds a scene for each frame of the main timeline in turn.
+    pub fn frames<'a>(&'a self, library: &'a SymbolLibrary) -> Frames<'a> {
+        Frames::new(library, self)
+    }
+}
 
-pub struct SymbolLibrary(Vec<Symbol>);
+pub struct SymbolLibrary(HashMap<SymbolId, Symbol>);
 
 impl SymbolLibrary {
-    fn add_symbol(&mut self, symbol: Symbol) {
-        self.0.push(symbol);
+    fn add_symbol(&mut self, symbol_id: SymbolId, symbol: Symbol) {
+        self.0.insert(symbol_id, symbol);
     }
 
-    fn symbols(&self) -> &Vec<Symbol> {
-        &self.0
+    fn symbol(&self, symbol_id: SymbolId) -> Option<&Symbol> {
+        self.0.get(&symbol_id)
     }
 }
 
 pub fn process_swf_tags(movie: &Movie) -> (SymbolLibrary, Stage) {
-    let mut symbol_library = SymbolLibrary(Vec::new());
+    let mut symbol_library = SymbolLibrary(HashMap::new());
     let stage_width = Twips(movie.header.frame_size.x_max);
     let stage_height = Twips(movie.header.frame_size.y_max);
-    // let num_frames = movie.header.frame_count;
 
     let mut stage = Stage {
-        // frames: Timeline(Vec::new()), // TODO(jon)
+        timeline: Timeline::from_tags(&movie.tags),
         background_color: SRgb8 {
             r: 255,
             g: 255,
@@ -130,6 +158,8 @@ pub fn process_swf_tags(movie: &Movie) -> (SymbolLibrary, Stage) {
         },
         width: stage_width.as_f32() as i32,
         height: stage_height.as_f32() as i32,
+        // The frame rate is in 8.8 fixed point.
+        frame_rate: movie.header.frame_rate.epsilons as f32 / 256.0,
     };
 
     for tag in &movie.tags {
@@ -138,10 +168,32 @@ pub fn process_swf_tags(movie: &Movie) -> (SymbolLibrary, Stage) {
                 stage.background_color = *color;
             },
             Tag::DefineShape(shape) => {
-                symbol_library.add_symbol(Symbol::Graphic(shapes::decode_shape(&shape)));
-                // We will assume that symbol ids just go up, and are 1 based.
-                let symbol_id: SymbolId = shape.id;
-                debug_assert!(symbol_id as usize == symbol_library.0.len());
+                let symbol = Symbol::Graphic(shapes::decode_shape(&shape));
+                symbol_library.add_symbol(shape.id, symbol);
+            }
+            Tag::DefineSprite(sprite) => {
+                let symbol = Symbol::Sprite(Timeline::from_tags(&sprite.tags));
+                symbol_library.add_symbol(sprite.id, symbol);
+            }
+            Tag::DefineMorphShape(morph_shape) => {
+                let symbol = Symbol::MorphShape(morph_shape.shape.clone());
+                symbol_library.add_symbol(morph_shape.id, symbol);
+            }
+            Tag::DefineFont(font) => {
+                if let Some(decoded_font) = text::decode_font(font) {
+                    symbol_library.add_symbol(font.id, Symbol::Font(decoded_font));
+                }
+            }
+            Tag::DefineGlyphFont(font) => {
+                symbol_library.add_symbol(font.id, Symbol::Font(text::decode_glyph_font(font)));
+            }
+            Tag::DefineText(text) => {
+                symbol_library.add_symbol(text.id, Symbol::Text(Text::new(text)));
+            }
+            Tag::DefineBitmap(bitmap) => {
+                if let Some(decoded_bitmap) = bitmap::decode_bitmap(bitmap) {
+                    symbol_library.add_symbol(bitmap.id, Symbol::Bitmap(decoded_bitmap));
+                }
             }
             _ => ()
         }
@@ -149,58 +201,82 @@ pub fn process_swf_tags(movie: &Movie) -> (SymbolLibrary, Stage) {
     (symbol_library, stage)
 }
 
-#[allow(irrefutable_let_patterns)]
+/// Draws every shape in the library on top of one another, untransformed, in the order of their
+/// IDs.
+///
+/// This ignores the timeline, so sprites, text and morph shapes aren't drawn. Use
+/// `Stage::frames()` to draw the movie as it plays.
 pub fn draw_paths_into_scene(library: &SymbolLibrary, scene: &mut Scene) {
-    for symbol in library.symbols() {
-        // NOTE: Right now symbols only contain graphics.
-        if let Symbol::Graphic(graphic) = symbol {
-            for style_layer in graphic.layers() {
-                let mut path = Outline::new();
-                let paint_id = scene.push_paint(&style_layer.fill());
-
-                for shape in style_layer.shapes() {
-                    let mut contour = Contour::new();
-                    let Point2 { x, y } = shape.outline.first().unwrap().from.as_f32();
-                    contour.push_endpoint(Vector2F::new(x, y));
-                    for segment in &shape.outline {
-                        let Point2 { x, y } = segment.to.as_f32();
-                        match segment.ctrl {
-                            Some(ctrl) => {
-                                let Point2 { x: ctrl_x, y: ctrl_y } = ctrl.as_f32();
-                                contour.push_quadratic(
-                                    Vector2F::new(ctrl_x, ctrl_y),
-                                    Vector2F::new(x, y)
-                                );
-                            }
-                            None => {
-                                contour.push_endpoint(Vector2F::new(x, y));
-                            },
-                        }
-                    }
-                    if shape.is_closed() {
-                        // NOTE: I'm not sure if this really does anything in this context,
-                        // since all our closed shapes already have coincident start and end points.
-                        contour.close();
-                    }
-                    path.push_contour(contour);
-                }
+    let mut symbol_ids: Vec<_> = library.0.keys().cloned().collect();
+    symbol_ids.sort();
+    for symbol_id in symbol_ids {
+        if let Some(Symbol::Graphic(graphic)) = library.symbol(symbol_id) {
+            draw_graphic_into_scene(graphic,
+                                    library,
+                                    &Transform2DF::default(),
+                                    &ColorTransform::default(),
+                                    scene);
+        }
+    }
+}
 
-                if let PaintOrLine::Line(line) = style_layer.kind() {
-                    let mut stroke_to_fill = OutlineStrokeToFill::new(&path, StrokeStyle {
-                        line_width: line.width.as_f32(),
-                        line_cap: line.cap,
-                        line_join: line.join,
-                    });
-                    stroke_to_fill.offset();
-                    path = stroke_to_fill.into_outline();
-                }
+pub(crate) fn draw_graphic_into_scene(graphic: &GraphicLayers,
+                                      library: &SymbolLibrary,
+                                      transform: &Transform2DF,
+                                      color_transform: &ColorTransform,
+                                      scene: &mut Scene) {
+    for style_layer in graphic.layers() {
+        let mut path = style_layer_outline(style_layer);
+        let fill = match style_layer.kind() {
+            PaintOrLine::Paint(fill) => fill,
+            PaintOrLine::Line(line) => &line.fill,
+        };
 
-                scene.push_path(PathObject::new(
-                    path,
-                    paint_id,
-                    String::new()
-                ));
+        // Stroke before transforming, so that line widths scale with the shape.
+        path.transform(transform);
+        fill.draw_into_scene(&path, transform, color_transform, library, scene);
+    }
+}
+
+// Builds the outline a style layer covers, stroking it if it's a line.
+pub(crate) fn style_layer_outline(style_layer: &StyleLayer) -> Outline {
+    let mut path = Outline::new();
+
+    for shape in style_layer.shapes() {
+        let mut contour = Contour::new();
+        let Point2 { x, y } = shape.outline.first().unwrap().from.as_f32();
+        contour.push_endpoint(Vector2F::new(x, y));
+        for segment in &shape.outline {
+            let Point2 { x, y } = segment.to.as_f32();
+            match segment.ctrl {
+                Some(ctrl) => {
+                    let Point2 { x: ctrl_x, y: ctrl_y } = ctrl.as_f32();
+                    contour.push_quadratic(
+                        Vector2F::new(ctrl_x, ctrl_y),
+                        Vector2F::new(x, y)
+                    );
+                }
+                None => {
+                    contour.push_endpoint(Vector2F::new(x, y));
+                },
             }
         }
+        if shape.is_closed() {
+            // NOTE: I'm not sure if this really does anything in this context,
+            // since all our closed shapes already have coincident start and end points.
+            contour.close();
+        }
+        path.push_contour(contour);
+    }
+
+    if let PaintOrLine::Line(line) = style_layer.kind() {
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&path, StrokeStyle {
+            line_width: line.width.as_f32(),
+            line_cap: line.cap,
+            line_join: line.join,
+        });
+        stroke_to_fill.offset();
+        path = stroke_to_fill.into_outline();
     }
+    path
 }
@@ -0,0 +1,289 @@
+// pathfinder/swf/src/morph.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Morph shapes, which tween between a start shape and an end shape with the same records.
+//!
+//! The shape at a given ratio is built by interpolating each edge and style, and is then decoded
+//! like any other shape.
+
+use crate::fills::decode_morph_fill_style;
+use crate::shapes::{self, DecodedRecord, GraphicLayers, PaintOrLine, SwfLineStyle};
+use crate::{Point2, Twips};
+use pathfinder_geometry::util::lerp;
+use swf_tree::shape_records::{MorphEdge, MorphStyleChange};
+use swf_tree::{MorphShape, MorphShapeRecord, MorphShapeStyles, Vector2D};
+
+/// Decodes the morph shape as it is partway, by `t` from 0.0 to 1.0, from its start to its end.
+pub(crate) fn decode_morph_shape(shape: &MorphShape, t: f32) -> GraphicLayers {
+    let mut morph = Morph {
+        t,
+        start_position: Point2 { x: 0, y: 0 },
+        end_position: Point2 { x: 0, y: 0 },
+        position: Point2 { x: 0, y: 0 },
+    };
+    let records: Vec<_> = shape.records.iter().map(|record| morph.decode_record(record)).collect();
+    shapes::decode_records(decode_morph_styles(&shape.initial_styles, t), records)
+}
+
+// Tracks the pen of both shapes in absolute twips, along with where the interpolated pen ended
+// up after rounding. Each interpolated edge runs from that rounded position, so that contours
+// which close in both shapes still close exactly.
+struct Morph {
+    t: f32,
+    start_position: Point2<i32>,
+    end_position: Point2<i32>,
+    position: Point2<i32>,
+}
+
+impl Morph {
+    fn decode_record(&mut self, record: &MorphShapeRecord) -> DecodedRecord {
+        match record {
+            MorphShapeRecord::StyleChange(MorphStyleChange {
+                move_to,
+                morph_move_to,
+                new_styles,
+                line_style,
+                left_fill,
+                right_fill,
+            }) => {
+                // The start and end shapes only line up edge for edge, so either one can move
+                // its pen where the other doesn't. The pen that doesn't move stays put.
+                let move_to = if move_to.is_some() || morph_move_to.is_some() {
+                    self.start_position = move_to.map_or(self.start_position, to_point);
+                    self.end_position = morph_move_to.map_or(self.end_position, to_point);
+                    self.position = self.interpolate(self.start_position, self.end_position);
+                    Some(to_twips(self.position))
+                } else {
+                    None
+                };
+                DecodedRecord::StyleChange {
+                    move_to,
+                    new_styles: new_styles.as_ref().map(|new_styles| {
+                        decode_morph_styles(new_styles, self.t)
+                    }),
+                    line_style: *line_style,
+                    left_fill: *left_fill,
+                    right_fill: *right_fill,
+                }
+            }
+            MorphShapeRecord::Edge(MorphEdge {
+                delta,
+                morph_delta,
+                control_delta,
+                morph_control_delta,
+            }) => {
+                let (start_from, end_from) = (self.start_position, self.end_position);
+                self.start_position = add(start_from, to_point(*delta));
+                self.end_position = add(end_from, to_point(*morph_delta));
+
+                // A straight edge tweening to a curve acts as a curve with its control point in
+                // the middle.
+                let control = match (control_delta, morph_control_delta) {
+                    (None, None) => None,
+                    (control_delta, morph_control_delta) => {
+                        let start_control = control_point(start_from,
+                                                          self.start_position,
+                                                          *control_delta);
+                        let end_control = control_point(end_from,
+                                                        self.end_position,
+                                                        *morph_control_delta);
+                        Some(self.interpolate(start_control, end_control))
+                    }
+                };
+
+                let from = self.position;
+                self.position = self.interpolate(self.start_position, self.end_position);
+                DecodedRecord::Edge {
+                    delta: to_twips(sub(self.position, from)),
+                    control_delta: control.map(|control| to_twips(sub(control, from))),
+                }
+            }
+        }
+    }
+
+    fn interpolate(&self, start: Point2<i32>, end: Point2<i32>) -> Point2<i32> {
+        Point2 {
+            x: lerp(start.x as f32, end.x as f32, self.t).round() as i32,
+            y: lerp(start.y as f32, end.y as f32, self.t).round() as i32,
+        }
+    }
+}
+
+fn decode_morph_styles(styles: &MorphShapeStyles, t: f32) -> Vec<PaintOrLine> {
+    // Fills always come first, as in `shapes::get_new_styles()`.
+    styles.fill.iter().map(|fill_style| {
+        PaintOrLine::Paint(decode_morph_fill_style(fill_style, t))
+    }).chain(styles.line.iter().map(|line_style| {
+        let width = lerp(line_style.width as f32, line_style.morph_width as f32, t);
+        PaintOrLine::Line(SwfLineStyle {
+            width: Twips(width.round() as i32),
+            fill: decode_morph_fill_style(&line_style.fill, t),
+            join: shapes::decode_join(&line_style.join),
+            cap: shapes::decode_cap(&line_style.start_cap),
+        })
+    })).collect()
+}
+
+fn control_point(from: Point2<i32>, to: Point2<i32>, control_delta: Option<Vector2D>)
+                 -> Point2<i32> {
+    match control_delta {
+        Some(control_delta) => add(from, to_point(control_delta)),
+        None => Point2 { x: (from.x + to.x) / 2, y: (from.y + to.y) / 2 },
+    }
+}
+
+#[inline]
+fn to_point(Vector2D { x, y }: Vector2D) -> Point2<i32> {
+    Point2 { x, y }
+}
+
+#[inline]
+fn to_twips(point: Point2<i32>) -> Point2<Twips> {
+    Point2 { x: Twips(point.x), y: Twips(point.y) }
+}
+
+#[inline]
+fn add(a: Point2<i32>, b: Point2<i32>) -> Point2<i32> {
+    Point2 { x: a.x + b.x, y: a.y + b.y }
+}
+
+#[inline]
+fn sub(a: Point2<i32>, b: Point2<i32>) -> Point2<i32> {
+    Point2 { x: a.x - b.x, y: a.y - b.y }
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode_morph_shape;
+    use crate::fills::SwfFill;
+    use crate::shapes::{GraphicLayers, PaintOrLine};
+    use pathfinder_content::color::ColorU;
+    use swf_tree::shape_records::{MorphEdge, MorphStyleChange};
+    use swf_tree::{morph_fill_styles, MorphFillStyle, MorphShape, MorphShapeRecord};
+    use swf_tree::{MorphShapeStyles, StraightSRgba8, Vector2D};
+
+    const RED: StraightSRgba8 = StraightSRgba8 { r: 255, g: 0, b: 0, a: 255 };
+    const BLUE: StraightSRgba8 = StraightSRgba8 { r: 0, g: 0, b: 255, a: 255 };
+
+    fn shape(records: Vec<MorphShapeRecord>) -> MorphShape {
+        let fill = MorphFillStyle::Solid(morph_fill_styles::Solid {
+            color: RED,
+            morph_color: BLUE,
+        });
+        MorphShape {
+            initial_styles: MorphShapeStyles { fill: vec![fill], line: vec![] },
+            records,
+        }
+    }
+
+    fn move_to(start: Option<(i32, i32)>, end: Option<(i32, i32)>) -> MorphShapeRecord {
+        let vector = |(x, y)| Vector2D { x, y };
+        MorphShapeRecord::StyleChange(MorphStyleChange {
+            move_to: start.map(vector),
+            morph_move_to: end.map(vector),
+            new_styles: None,
+            line_style: None,
+            left_fill: None,
+            right_fill: Some(1),
+        })
+    }
+
+    // A square drawn clockwise from the pen, which grows from `start_size` to `end_size`.
+    fn square(start_size: i32, end_size: i32) -> Vec<MorphShapeRecord> {
+        let edge = |start: (i32, i32), end: (i32, i32)| {
+            MorphShapeRecord::Edge(MorphEdge {
+                delta: Vector2D { x: start.0, y: start.1 },
+                morph_delta: Vector2D { x: end.0, y: end.1 },
+                control_delta: None,
+                morph_control_delta: None,
+            })
+        };
+        vec![
+            edge((start_size, 0), (end_size, 0)),
+            edge((0, start_size), (0, end_size)),
+            edge((-start_size, 0), (-end_size, 0)),
+            edge((0, -start_size), (0, -end_size)),
+        ]
+    }
+
+    fn decode(shape: &MorphShape, ratio: u16) -> GraphicLayers {
+        decode_morph_shape(shape, ratio as f32 / 65535.0)
+    }
+
+    // The bounds in twips of each closed shape in the only fill, as (min x, min y, max x, max y).
+    fn bounds(graphic: &GraphicLayers) -> Vec<(i32, i32, i32, i32)> {
+        graphic.layers()[0].shapes().iter().map(|shape| {
+            assert!(shape.is_closed());
+            let points: Vec<_> = shape.outline.iter().flat_map(|segment| {
+                vec![(segment.from.x.0, segment.from.y.0), (segment.to.x.0, segment.to.y.0)]
+            }).collect();
+            (points.iter().map(|point| point.0).min().unwrap(),
+             points.iter().map(|point| point.1).min().unwrap(),
+             points.iter().map(|point| point.0).max().unwrap(),
+             points.iter().map(|point| point.1).max().unwrap())
+        }).collect()
+    }
+
+    fn color(graphic: &GraphicLayers) -> ColorU {
+        match graphic.layers()[0].kind() {
+            PaintOrLine::Paint(SwfFill::Solid(paint)) => paint.color,
+            _ => panic!("expected a solid fill"),
+        }
+    }
+
+    #[test]
+    fn test_ratios() {
+        // A 400 twip square at the origin moves right and grows to 800 twips.
+        let mut records = vec![move_to(Some((0, 0)), Some((400, 0)))];
+        records.extend(square(400, 800));
+        let shape = shape(records);
+
+        let start = decode(&shape, 0);
+        assert_eq!(bounds(&start), [(0, 0, 400, 400)]);
+        assert_eq!(color(&start), ColorU::new(255, 0, 0, 255));
+
+        let middle = decode(&shape, 32768);
+        assert_eq!(bounds(&middle), [(200, 0, 800, 600)]);
+        let ColorU { r, g, b, a } = color(&middle);
+        assert!((r as i32 - 128).abs() <= 1 && (b as i32 - 128).abs() <= 1);
+        assert_eq!((g, a), (0, 255));
+
+        let end = decode(&shape, 65535);
+        assert_eq!(bounds(&end), [(400, 0, 1200, 800)]);
+        assert_eq!(color(&end), ColorU::new(0, 0, 255, 255));
+    }
+
+    #[test]
+    fn test_moves_in_one_shape() {
+        // The start shape moves before its second square, and the end shape draws its second
+        // square from where the first one ended. Then the end shape moves where the start shape
+        // doesn't.
+        let mut records = vec![move_to(Some((0, 0)), Some((0, 0)))];
+        records.extend(square(400, 400));
+        records.push(move_to(Some((800, 0)), None));
+        records.extend(square(400, 400));
+        records.push(move_to(None, Some((0, 800))));
+        records.extend(square(400, 400));
+        let shape = shape(records);
+
+        let mut start = bounds(&decode(&shape, 0));
+        start.sort();
+        assert_eq!(start, [(0, 0, 400, 400), (800, 0, 1200, 400), (800, 0, 1200, 400)]);
+
+        let mut end = bounds(&decode(&shape, 65535));
+        end.sort();
+        assert_eq!(end, [(0, 0, 400, 400), (0, 0, 400, 400), (0, 800, 400, 1200)]);
+
+        // Halfway, each square is between where its edges start and end.
+        let mut middle = bounds(&decode(&shape, 32768));
+        middle.sort();
+        assert_eq!(middle, [(0, 0, 400, 400), (400, 0, 800, 400), (400, 400, 800, 800)]);
+    }
+}
@@ -8,16 +8,14 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use pathfinder_renderer::paint::Paint;
 use pathfinder_content::stroke::{LineJoin, LineCap};
 use crate::{Twips, Point2};
+use crate::fills::{decode_fill_style, SwfFill};
 use std::mem;
 use std::cmp::Ordering;
 use swf_tree::{
     FillStyle,
-    StraightSRgba8,
     LineStyle,
-    fill_styles,
     JoinStyle,
     CapStyle,
     join_styles,
@@ -25,7 +23,6 @@ use swf_tree::{
     shape_records,
     Vector2D
 };
-use pathfinder_content::color::ColorU;
 use swf_tree::tags::DefineShape;
 
 #[derive(Clone, Copy, Debug)]
@@ -94,12 +91,12 @@ impl Shape {
 
     #[inline]
     fn first(&self) -> LineSegment {
-        self.outline.first().unwrap()
+        *self.outline.first().unwrap()
     }
 
     #[inline]
     fn last(&self) -> LineSegment {
-        self.outline.last().unwrap()
+        *self.outline.last().unwrap()
     }
 
     #[inline]
@@ -128,14 +125,14 @@ impl Shape {
 }
 
 pub(crate) struct SwfLineStyle {
-    color: Paint,
+    pub(crate) fill: SwfFill,
     pub(crate) width: Twips,
     pub(crate) join: LineJoin,
     pub(crate) cap: LineCap,
 }
 
 pub(crate) enum PaintOrLine {
-    Paint(Paint),
+    Paint(SwfFill),
     Line(SwfLineStyle),
 }
 
@@ -158,13 +155,6 @@ impl StyleLayer {
         }
     }
 
-    pub(crate) fn fill(&self) -> Paint {
-        match &self.fill {
-            PaintOrLine::Paint(paint) => *paint,
-            PaintOrLine::Line(line) => line.color,
-        }
-    }
-
     fn push_new_shape(&mut self, direction: LineDirection) {
         if let Some(prev_shape) = self.shapes.last_mut() {
             // Check that the previous shape was actually used, otherwise reuse it.
@@ -251,31 +241,10 @@ fn get_new_styles<'a>(
 ) -> impl Iterator<Item=PaintOrLine> + 'a {
     // This enforces the order that fills and line groupings are added in.
     // Fills always come first.
-    fills.iter().filter_map(|fill_style| {
-        match fill_style {
-            FillStyle::Solid(
-                fill_styles::Solid {
-                    color: StraightSRgba8 {
-                        r,
-                        g,
-                        b,
-                        a
-                    }
-                }
-            ) =>  {
-                Some(PaintOrLine::Paint(Paint {
-                    color: ColorU {
-                        r: *r,
-                        g: *g,
-                        b: *b,
-                        a: *a
-                    }
-                }))
-            },
-            _ => unimplemented!("Unimplemented fill style")
-        }
+    fills.iter().map(|fill_style| {
+        PaintOrLine::Paint(decode_fill_style(fill_style))
     }).chain(
-        lines.iter().filter_map(|LineStyle {
+        lines.iter().map(|LineStyle {
             width,
             fill,
             join,
@@ -290,41 +259,52 @@ fn get_new_styles<'a>(
             */
             ..
         }| {
-            if let FillStyle::Solid(fill_styles::Solid {
-                color: StraightSRgba8 {
-                    r,
-                    g,
-                    b,
-                    a
-                }
-            }) = fill {
-                // NOTE: PathFinder doesn't support different cap styles for start and end of
-                // strokes, so lets assume that they're always the same for the inputs we care about.
-                // Alternately, we split a line in two with a diff cap style for each.
-                // assert_eq!(start_cap, end_cap);
-                Some(PaintOrLine::Line(SwfLineStyle {
-                    width: Twips(*width as i32),
-                    color: Paint { color: ColorU { r: *r, g: *g, b: *b, a: *a } },
-                    join: match join {
-                        JoinStyle::Bevel => LineJoin::Bevel,
-                        JoinStyle::Round => LineJoin::Round,
-                        JoinStyle::Miter(join_styles::Miter { limit }) => {
-                            LineJoin::Miter(*limit as f32)
-                        },
-                    },
-                    cap: match start_cap {
-                        CapStyle::None => LineCap::Butt,
-                        CapStyle::Square => LineCap::Square,
-                        CapStyle::Round => LineCap::Round,
-                    },
-                }))
-            } else {
-                unimplemented!("unimplemented line fill style");
-            }
+            // NOTE: PathFinder doesn't support different cap styles for start and end of
+            // strokes, so lets assume that they're always the same for the inputs we care about.
+            // Alternately, we split a line in two with a diff cap style for each.
+            // assert_eq!(start_cap, end_cap);
+            PaintOrLine::Line(SwfLineStyle {
+                width: Twips(*width as i32),
+                fill: decode_fill_style(fill),
+                join: decode_join(join),
+                cap: decode_cap(start_cap),
+            })
         })
     )
 }
 
+pub(crate) fn decode_join(join: &JoinStyle) -> LineJoin {
+    match join {
+        JoinStyle::Bevel => LineJoin::Bevel,
+        JoinStyle::Round => LineJoin::Round,
+        JoinStyle::Miter(join_styles::Miter { limit }) => LineJoin::Miter(*limit as f32),
+    }
+}
+
+pub(crate) fn decode_cap(cap: &CapStyle) -> LineCap {
+    match cap {
+        CapStyle::None => LineCap::Butt,
+        CapStyle::Square => LineCap::Square,
+        CapStyle::Round => LineCap::Round,
+    }
+}
+
+// A shape record with its styles decoded, so that shapes, morph shapes and glyphs can all be
+// turned into graphics the same way.
+pub(crate) enum DecodedRecord {
+    StyleChange {
+        move_to: Option<Point2<Twips>>,
+        new_styles: Option<Vec<PaintOrLine>>,
+        line_style: Option<usize>,
+        left_fill: Option<usize>,
+        right_fill: Option<usize>,
+    },
+    Edge {
+        delta: Point2<Twips>,
+        control_delta: Option<Point2<Twips>>,
+    },
+}
+
 pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
     let DefineShape {
         shape,
@@ -334,6 +314,43 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
         // has_scaling_strokes,
         ..
     } = shape;
+    let initial_styles = get_new_styles(&shape.initial_styles.fill, &shape.initial_styles.line);
+    decode_records(initial_styles.collect(), shape.records.iter().map(decode_record))
+}
+
+pub(crate) fn decode_record(record: &ShapeRecord) -> DecodedRecord {
+    let to_point = |Vector2D { x, y }: Vector2D| Point2 { x: Twips(x), y: Twips(y) };
+    match record {
+        ShapeRecord::StyleChange(
+            shape_records::StyleChange {
+                move_to,
+                new_styles,
+                line_style,
+                left_fill,
+                right_fill,
+            }
+        ) => {
+            DecodedRecord::StyleChange {
+                move_to: move_to.map(to_point),
+                new_styles: new_styles.as_ref().map(|new_styles| {
+                    get_new_styles(&new_styles.fill, &new_styles.line).collect()
+                }),
+                line_style: *line_style,
+                left_fill: *left_fill,
+                right_fill: *right_fill,
+            }
+        }
+        ShapeRecord::Edge(shape_records::Edge { delta, control_delta }) => {
+            DecodedRecord::Edge {
+                delta: to_point(*delta),
+                control_delta: control_delta.map(to_point),
+            }
+        }
+    }
+}
+
+pub(crate) fn decode_records<I>(initial_styles: Vec<PaintOrLine>, records: I) -> GraphicLayers
+                                where I: IntoIterator<Item = DecodedRecord> {
     let mut graphic = GraphicLayers::new();
     let mut current_line_style = None;
     let mut current_left_fill = None;
@@ -346,30 +363,28 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
     let mut both_fills_set_and_same = false;
 
     // Create style groups for initially specified fills and lines.
-    for fills_or_line in get_new_styles(&shape.initial_styles.fill, &shape.initial_styles.line) {
+    for fills_or_line in initial_styles {
         match fills_or_line {
             PaintOrLine::Paint(fill) => graphic.begin_fill_style(fill),
             PaintOrLine::Line(line) => graphic.begin_line_style(line),
         }
     }
 
-    for record in &shape.records {
+    for record in records {
         match record {
-            ShapeRecord::StyleChange(
-                shape_records::StyleChange {
-                    move_to,
-                    new_styles,
-                    line_style,
-                    left_fill,
-                    right_fill,
-                }
-            ) => {
+            DecodedRecord::StyleChange {
+                move_to,
+                new_styles,
+                line_style,
+                left_fill,
+                right_fill,
+            } => {
                 // Start a whole new style grouping.
-                if let Some(new_style) = new_styles {
+                if let Some(new_styles) = new_styles {
                     // Consolidate current style grouping and begin a new one.
                     graphic.end_style_group();
                     graphic.begin_style_group();
-                    for fills_or_line in get_new_styles(&new_style.fill, &new_style.line) {
+                    for fills_or_line in new_styles {
                         match fills_or_line {
                             PaintOrLine::Paint(fill) => graphic.begin_fill_style(fill),
                             PaintOrLine::Line(line) => graphic.begin_line_style(line),
@@ -379,24 +394,24 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
 
                 // If there's a change in right fill
                 if let Some(fill_id) = right_fill {
-                    if *fill_id == 0 {
+                    if fill_id == 0 {
                         current_right_fill = None;
                     } else {
-                        current_right_fill = Some(*fill_id);
+                        current_right_fill = Some(fill_id);
                         graphic
-                            .with_fill_style_mut(*fill_id)
+                            .with_fill_style_mut(fill_id)
                             .unwrap()
                             .push_new_shape(LineDirection::Right);
                     }
                 }
                 // If there's a change in left fill
                 if let Some(fill_id) = left_fill {
-                    if *fill_id == 0 {
+                    if fill_id == 0 {
                         current_left_fill = None;
                     } else {
-                        current_left_fill = Some(*fill_id);
+                        current_left_fill = Some(fill_id);
                         graphic
-                            .with_fill_style_mut(*fill_id)
+                            .with_fill_style_mut(fill_id)
                             .unwrap()
                             .push_new_shape(LineDirection::Left);
                     }
@@ -409,20 +424,19 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
 
                 // If there's a change in line style
                 if let Some(style_id) = line_style {
-                    if *style_id == 0 {
+                    if style_id == 0 {
                         current_line_style = None;
                     } else {
-                        current_line_style = Some(*style_id);
+                        current_line_style = Some(style_id);
                         graphic
-                            .with_line_style_mut(*style_id)
+                            .with_line_style_mut(style_id)
                             .unwrap()
                             .push_new_shape(LineDirection::Right);
                     }
                 }
 
                 // Move to, start new shape fragments with the current styles.
-                if let Some(Vector2D { x, y }) = move_to {
-                    let to: Point2<Twips> = Point2 { x: Twips(*x), y: Twips(*y) };
+                if let Some(to) = move_to {
                     prev_pos = Some(to);
 
                     // If we didn't start a new shape for the current fill due to a fill
@@ -450,27 +464,14 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                     }
                 }
             },
-            ShapeRecord::Edge(
-                shape_records::Edge {
-                    delta,
-                    control_delta,
-                }
-            ) => {
+            DecodedRecord::Edge { delta, control_delta } => {
                 let from = prev_pos.unwrap();
-                let to = Point2 {
-                    x: from.x + Twips(delta.x),
-                    y: from.y + Twips(delta.y)
-                };
+                let to = from + delta;
                 prev_pos = Some(to);
                 let new_segment = LineSegment {
                     from,
                     to,
-                    ctrl: control_delta.map(|Vector2D { x, y }| {
-                        Point2 {
-                            x: from.x + Twips(x),
-                            y: from.y + Twips(y),
-                        }
-                    }),
+                    ctrl: control_delta.map(|control_delta| from + control_delta),
                 };
                 if some_fill_set && !both_fills_same {
                     for fill_id in [
@@ -587,7 +588,7 @@ impl GraphicLayers {
         self.base_layer_offset = self.style_layers.len();
     }
 
-    fn begin_fill_style(&mut self, fill: Paint) {
+    fn begin_fill_style(&mut self, fill: SwfFill) {
         self.style_layers.push(StyleLayer { fill: PaintOrLine::Paint(fill), shapes: Vec::new() })
     }
 
@@ -623,3 +624,108 @@ impl GraphicLayers {
     }
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::{decode_records, DecodedRecord, PaintOrLine, Shape};
+    use crate::fills::SwfFill;
+    use crate::{Point2, Twips};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_renderer::paint::Paint;
+
+    fn point(x: i32, y: i32) -> Point2<Twips> {
+        Point2 { x: Twips(x), y: Twips(y) }
+    }
+
+    fn style_change(move_to: Option<(i32, i32)>,
+                    left_fill: Option<usize>,
+                    right_fill: Option<usize>)
+                    -> DecodedRecord {
+        DecodedRecord::StyleChange {
+            move_to: move_to.map(|(x, y)| point(x, y)),
+            new_styles: None,
+            line_style: None,
+            left_fill,
+            right_fill,
+        }
+    }
+
+    fn edge(x: i32, y: i32) -> DecodedRecord {
+        DecodedRecord::Edge { delta: point(x, y), control_delta: None }
+    }
+
+    fn solid(color: ColorU) -> PaintOrLine {
+        PaintOrLine::Paint(SwfFill::Solid(Paint { color }))
+    }
+
+    // Twice the signed area, which is positive for shapes that go clockwise with Y down.
+    fn signed_area(shape: &Shape) -> i32 {
+        shape.outline.iter().map(|segment| {
+            segment.from.x.0 * segment.to.y.0 - segment.to.x.0 * segment.from.y.0
+        }).sum()
+    }
+
+    fn bounds(shape: &Shape) -> (i32, i32, i32, i32) {
+        let xs = shape.outline.iter().map(|segment| segment.from.x.0);
+        let ys = shape.outline.iter().map(|segment| segment.from.y.0);
+        (xs.clone().min().unwrap(), ys.clone().min().unwrap(), xs.max().unwrap(), ys.max().unwrap())
+    }
+
+    #[test]
+    fn test_shared_edge_fills() {
+        // Two squares side by side. The edge between them is drawn once, downward, with the
+        // left square as fill 1 and the right square as fill 0. Each square is finished in
+        // pieces that only join up into closed shapes once the edges are consolidated.
+        let records = vec![
+            style_change(Some((100, 0)), Some(2), Some(1)),
+            edge(0, 100),
+            style_change(None, Some(0), None),
+            edge(-100, 0),
+            edge(0, -100),
+            edge(100, 0),
+            style_change(Some((100, 0)), None, Some(2)),
+            edge(100, 0),
+            edge(0, 100),
+            edge(-100, 0),
+        ];
+        let graphic = decode_records(vec![solid(ColorU::black()), solid(ColorU::white())],
+                                     records);
+        let layers = graphic.layers();
+        assert_eq!(layers.len(), 2);
+
+        let left = layers[0].shapes();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].is_closed());
+        assert_eq!(bounds(&left[0]), (0, 0, 100, 100));
+
+        let right = layers[1].shapes();
+        assert_eq!(right.len(), 1);
+        assert!(right[0].is_closed());
+        assert_eq!(bounds(&right[0]), (100, 0, 200, 100));
+
+        // Fill 0 edges are turned around, so both squares wind the same way.
+        assert_eq!(signed_area(&left[0]), 2 * 100 * 100);
+        assert_eq!(signed_area(&right[0]), 2 * 100 * 100);
+    }
+
+    #[test]
+    fn test_inner_edges() {
+        // An edge with the same fill on both sides is left out, so the square still closes.
+        let records = vec![
+            style_change(Some((0, 0)), None, Some(1)),
+            edge(100, 0),
+            edge(0, 100),
+            style_change(None, Some(1), None),
+            edge(-100, -100),
+            style_change(Some((100, 100)), Some(0), Some(1)),
+            edge(-100, 0),
+            edge(0, -100),
+        ];
+        let graphic = decode_records(vec![solid(ColorU::black())], records);
+        let shapes = graphic.layers()[0].shapes();
+        assert_eq!(shapes.len(), 1);
+        assert!(shapes[0].is_closed());
+        assert_eq!(shapes[0].outline.len(), 4);
+        assert_eq!(signed_area(&shapes[0]), 2 * 100 * 100);
+    }
+}
@@ -0,0 +1,219 @@
+// pathfinder/swf/src/text.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Embedded fonts and static text.
+
+use crate::fills::{to_color, SwfFill};
+use crate::shapes::{self, PaintOrLine};
+use crate::timeline::{matrix_to_transform, ColorTransform};
+use crate::{style_layer_outline, Symbol, SymbolId, SymbolLibrary};
+use pathfinder_content::color::ColorU;
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::Scene;
+use swf_tree::tags::{DefineFont, DefineGlyphFont, DefineText};
+use swf_tree::{EmSquareSize, Glyph, TextRecord};
+
+// Glyphs are drawn on an em square of this many units, or 20 times as many in newer fonts.
+const EM_SQUARE_SIZE: f32 = 1024.0;
+
+pub(crate) struct Font {
+    // Each glyph is decoded like a shape, so its coordinates are em square units divided by 20.
+    glyphs: Vec<Outline>,
+    em_square_size: f32,
+}
+
+pub(crate) fn decode_font(font: &DefineFont) -> Option<Font> {
+    // NOTE: Fonts without glyphs are device fonts, which we can't draw.
+    let glyphs = font.glyphs.as_ref()?;
+    let em_square_size = match font.em_square_size {
+        EmSquareSize::EmSquareSize1024 => EM_SQUARE_SIZE,
+        EmSquareSize::EmSquareSize20480 => EM_SQUARE_SIZE * 20.0,
+    };
+    Some(Font { glyphs: glyphs.iter().map(decode_glyph).collect(), em_square_size })
+}
+
+pub(crate) fn decode_glyph_font(font: &DefineGlyphFont) -> Font {
+    Font {
+        glyphs: font.glyphs.iter().map(decode_glyph).collect(),
+        em_square_size: EM_SQUARE_SIZE,
+    }
+}
+
+// Glyph shapes have no styles of their own, and fill with the first fill style.
+fn decode_glyph(glyph: &Glyph) -> Outline {
+    let fill = PaintOrLine::Paint(SwfFill::Solid(Paint { color: ColorU::black() }));
+    let graphic = shapes::decode_records(vec![fill],
+                                         glyph.records.iter().map(shapes::decode_record));
+    let mut outline = Outline::new();
+    for style_layer in graphic.layers() {
+        for contour in style_layer_outline(style_layer).contours() {
+            outline.push_contour(contour.clone());
+        }
+    }
+    outline
+}
+
+pub(crate) struct Text {
+    transform: Transform2DF,
+    records: Vec<TextRecord>,
+}
+
+impl Text {
+    pub(crate) fn new(text: &DefineText) -> Text {
+        Text { transform: matrix_to_transform(&text.matrix), records: text.records.clone() }
+    }
+
+    pub(crate) fn draw_into_scene(&self,
+                                  library: &SymbolLibrary,
+                                  transform: &Transform2DF,
+                                  color_transform: &ColorTransform,
+                                  scene: &mut Scene) {
+        let transform = self.transform.then(transform);
+
+        // The font, color and size carry over from one record to the next.
+        let mut font_id: Option<SymbolId> = None;
+        let mut color = ColorU::black();
+        for record in &self.records {
+            font_id = record.font_id.or(font_id);
+            if let Some(ref record_color) = record.color {
+                color = to_color(record_color);
+            }
+            let font = match font_id.and_then(|font_id| library.symbol(font_id)) {
+                Some(Symbol::Font(font)) => font,
+                _ => continue,
+            };
+
+            // NOTE: Offsets are treated as absolute positions of the pen in twips, so text
+            // that only sets one of them restarts from zero on the other axis.
+            let scale = record.font_size as f32 / font.em_square_size;
+            let mut pen = Vector2F::new(record.offset_x as f32, record.offset_y as f32)
+                .scale(1.0 / 20.0);
+            let mut outline = Outline::new();
+            for entry in &record.entries {
+                if let Some(glyph) = font.glyphs.get(entry.index) {
+                    let glyph_transform = Transform2DF::from_scale(Vector2F::splat(scale))
+                        .post_mul(&Transform2DF::from_translation(pen))
+                        .post_mul(&transform);
+                    let mut glyph = glyph.clone();
+                    glyph.transform(&glyph_transform);
+                    for contour in glyph.contours() {
+                        outline.push_contour(contour.clone());
+                    }
+                }
+                pen = pen + Vector2F::new(entry.advance as f32 / 20.0, 0.0);
+            }
+
+            let fill = SwfFill::Solid(Paint { color });
+            fill.draw_into_scene(&outline, &transform, color_transform, library, scene);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_font, Text};
+    use crate::timeline::ColorTransform;
+    use crate::{Symbol, SymbolLibrary};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2DF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::scene::Scene;
+    use std::collections::HashMap;
+    use swf_tree::shape_records::{Edge, StyleChange};
+    use swf_tree::tags::DefineFont;
+    use swf_tree::{EmSquareSize, Glyph, GlyphEntry, ShapeRecord, StraightSRgba8, TextRecord};
+    use swf_tree::Vector2D;
+
+    // A font whose only glyph fills its whole em square.
+    fn font(id: u16, em_square_size: EmSquareSize, size: i32) -> DefineFont {
+        let edge = |x, y| ShapeRecord::Edge(Edge { delta: Vector2D { x, y }, control_delta: None });
+        let records = vec![
+            ShapeRecord::StyleChange(StyleChange {
+                move_to: Some(Vector2D { x: 0, y: 0 }),
+                new_styles: None,
+                line_style: None,
+                left_fill: None,
+                right_fill: Some(1),
+            }),
+            edge(size, 0),
+            edge(0, size),
+            edge(-size, 0),
+            edge(0, -size),
+        ];
+        DefineFont {
+            id,
+            font_name: "Square".to_owned(),
+            em_square_size,
+            glyphs: Some(vec![Glyph { records }]),
+        }
+    }
+
+    fn record(font_id: Option<u16>, offset: (i16, i16), font_size: u16, advances: &[i32])
+              -> TextRecord {
+        TextRecord {
+            font_id,
+            color: if font_id == Some(1) {
+                Some(StraightSRgba8 { r: 255, g: 0, b: 0, a: 255 })
+            } else {
+                None
+            },
+            offset_x: offset.0,
+            offset_y: offset.1,
+            font_size,
+            entries: advances.iter().map(|&advance| GlyphEntry { index: 0, advance }).collect(),
+        }
+    }
+
+    fn approx_eq(a: RectF, b: RectF) -> bool {
+        (a.origin() - b.origin()).length() < 0.01 && (a.size() - b.size()).length() < 0.01
+    }
+
+    #[test]
+    fn test_glyph_positions() {
+        let mut symbols = HashMap::new();
+        let small = decode_font(&font(1, EmSquareSize::EmSquareSize1024, 1024)).unwrap();
+        let large = decode_font(&font(2, EmSquareSize::EmSquareSize20480, 20480)).unwrap();
+        symbols.insert(1, Symbol::Font(small));
+        symbols.insert(2, Symbol::Font(large));
+        let library = SymbolLibrary(symbols);
+
+        // Sizes, offsets and advances are in twips. The second record keeps the first one's
+        // color, and uses a font with the larger em square.
+        let text = Text {
+            transform: Transform2DF::default(),
+            records: vec![
+                record(Some(1), (200, 400), 400, &[500, 0]),
+                record(Some(2), (0, 1200), 200, &[0]),
+            ],
+        };
+        let mut scene = Scene::new();
+        text.draw_into_scene(&library,
+                             &Transform2DF::default(),
+                             &ColorTransform::default(),
+                             &mut scene);
+
+        let paths: Vec<(ColorU, Vec<RectF>)> = scene.paths().map(|(paint, outline, _)| {
+            (paint.color, outline.contours().iter().map(|contour| contour.bounds()).collect())
+        }).collect();
+        assert_eq!(paths.len(), 2);
+        let red = ColorU::new(255, 0, 0, 255);
+        assert_eq!((paths[0].1.len(), paths[0].0, paths[1].0), (2, red, red));
+
+        // Each glyph is as tall as its font size, and starts where the pen is.
+        let glyph = |x, y, size| RectF::new(Vector2F::new(x, y), Vector2F::splat(size));
+        assert!(approx_eq(paths[0].1[0], glyph(10.0, 20.0, 20.0)));
+        assert!(approx_eq(paths[0].1[1], glyph(35.0, 20.0, 20.0)));
+        assert!(approx_eq(paths[1].1[0], glyph(0.0, 60.0, 10.0)));
+    }
+}
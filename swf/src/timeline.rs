@@ -8,35 +8,577 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-struct PlacementInfo {
-    symbol_id: u32,
-    translate_x: Twips,
-    translate_y: Twips,
+use crate::{fills, morph};
+use crate::{draw_graphic_into_scene, Stage, Symbol, SymbolId, SymbolLibrary};
+use pathfinder_content::boolean::BooleanOp;
+use pathfinder_content::color::ColorU;
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2DF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::scene::{PathObject, Scene};
+use std::collections::BTreeMap;
+use std::mem;
+use swf_tree::tags::{PlaceObject, RemoveObject};
+use swf_tree::{ColorTransformWithAlpha, Matrix, Tag};
+
+// A change to the display list, as recorded by a `PlaceObject` or `RemoveObject` tag.
+pub(crate) enum DisplayListCommand {
+    Place(Placement),
+    Remove { depth: u16 },
+}
+
+pub(crate) struct Placement {
+    depth: u16,
+    // When set, this modifies the object already at the depth instead of placing a new one.
+    is_update: bool,
+    symbol_id: Option<SymbolId>,
+    transform: Option<Transform2DF>,
+    color_transform: Option<ColorTransform>,
+    clip_depth: Option<u16>,
+    ratio: Option<u16>,
+}
+
+impl Placement {
+    fn from_tag(place_object: &PlaceObject) -> Placement {
+        Placement {
+            depth: place_object.depth,
+            is_update: place_object.is_update,
+            symbol_id: place_object.character_id,
+            transform: place_object.matrix.as_ref().map(matrix_to_transform),
+            color_transform: place_object.color_transform.as_ref().map(ColorTransform::from_tag),
+            clip_depth: place_object.clip_depth,
+            ratio: place_object.ratio,
+        }
+    }
+}
+
+/// Multiplies and then offsets each channel of a color, alpha included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ColorTransform {
+    multiply: [f32; 4],
+    // In units of 0 to 255.
+    add: [f32; 4],
+}
+
+impl Default for ColorTransform {
+    #[inline]
+    fn default() -> ColorTransform {
+        ColorTransform { multiply: [1.0; 4], add: [0.0; 4] }
+    }
 }
 
-struct Timeline(Vec<Frame>);
+impl ColorTransform {
+    fn from_tag(color_transform: &ColorTransformWithAlpha) -> ColorTransform {
+        // The multipliers are in 8.8 fixed point.
+        let fixed = |value: i16| value as f32 / 256.0;
+        ColorTransform {
+            multiply: [
+                fixed(color_transform.red_mult.epsilons),
+                fixed(color_transform.green_mult.epsilons),
+                fixed(color_transform.blue_mult.epsilons),
+                fixed(color_transform.alpha_mult.epsilons),
+            ],
+            add: [
+                color_transform.red_add as f32,
+                color_transform.green_add as f32,
+                color_transform.blue_add as f32,
+                color_transform.alpha_add as f32,
+            ],
+        }
+    }
+
+    /// Returns a color transform that applies `self`, then `other`.
+    pub(crate) fn post_mul(&self, other: &ColorTransform) -> ColorTransform {
+        let mut result = ColorTransform::default();
+        for channel in 0..4 {
+            result.multiply[channel] = self.multiply[channel] * other.multiply[channel];
+            result.add[channel] = self.add[channel] * other.multiply[channel] + other.add[channel];
+        }
+        result
+    }
+
+    pub(crate) fn apply(&self, color: ColorU) -> ColorU {
+        let channel = |index: usize, value: u8| {
+            let value = value as f32 * self.multiply[index] + self.add[index];
+            f32::max(f32::min(value, 255.0), 0.0).round() as u8
+        };
+        ColorU {
+            r: channel(0, color.r),
+            g: channel(1, color.g),
+            b: channel(2, color.b),
+            a: channel(3, color.a),
+        }
+    }
+}
+
+// The display list changes made before a `ShowFrame` tag.
+pub(crate) struct Frame(Vec<DisplayListCommand>);
+
+pub(crate) struct Timeline(Vec<Frame>);
 
 impl Timeline {
-    fn first(&self) -> &Frame {
-        &self.0[0]
+    pub(crate) fn from_tags(tags: &[Tag]) -> Timeline {
+        let mut frames = vec![];
+        let mut commands = vec![];
+        for tag in tags {
+            match tag {
+                Tag::PlaceObject(place_object) => {
+                    commands.push(DisplayListCommand::Place(Placement::from_tag(place_object)));
+                }
+                Tag::RemoveObject(RemoveObject { depth, .. }) => {
+                    commands.push(DisplayListCommand::Remove { depth: *depth });
+                }
+                Tag::ShowFrame => frames.push(Frame(mem::replace(&mut commands, vec![]))),
+                _ => {}
+            }
+        }
+        // NOTE: Files should end with a `ShowFrame`, but if they don't, the trailing changes
+        // still make up a frame.
+        if !commands.is_empty() {
+            frames.push(Frame(commands));
+        }
+        Timeline(frames)
     }
 
-    fn last(&self) -> &Frame {
-        &self.0[self.0.len() - 1]
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
     }
 
-    fn first_mut(&mut self) -> &mut Frame {
-        &mut self.0[0]
+    #[inline]
+    pub(crate) fn frame(&self, index: usize) -> Option<&Frame> {
+        self.0.get(index)
     }
+}
+
+#[derive(Clone)]
+pub(crate) struct PlacedObject {
+    symbol_id: SymbolId,
+    transform: Transform2DF,
+    color_transform: ColorTransform,
+    // Objects with a clip depth aren't drawn. Instead, they clip the objects above them, up to and
+    // including that depth.
+    clip_depth: Option<u16>,
+    // How far a morph shape is from its start to its end, from 0 to 65535.
+    ratio: u16,
+    // The state of the timeline of a sprite, once it has started playing.
+    sprite: Option<SpriteInstance>,
+}
 
-    fn last_mut(&mut self) -> &mut Frame {
-        let last = self.0.len() - 1;
-        &mut self.0[last]
+impl PlacedObject {
+    fn new(symbol_id: SymbolId) -> PlacedObject {
+        PlacedObject {
+            symbol_id,
+            transform: Transform2DF::default(),
+            color_transform: ColorTransform::default(),
+            clip_depth: None,
+            ratio: 0,
+            sprite: None,
+        }
     }
+
+    fn draw_into_scene(&self,
+                       library: &SymbolLibrary,
+                       transform: &Transform2DF,
+                       color_transform: &ColorTransform,
+                       scene: &mut Scene) {
+        let transform = self.transform.then(transform);
+        let color_transform = self.color_transform.post_mul(color_transform);
+        match library.symbol(self.symbol_id) {
+            Some(Symbol::Graphic(graphic)) => {
+                draw_graphic_into_scene(graphic, library, &transform, &color_transform, scene)
+            }
+            Some(Symbol::Sprite(_)) => {
+                if let Some(ref sprite) = self.sprite {
+                    sprite.display_list.draw_into_scene(library,
+                                                        &transform,
+                                                        &color_transform,
+                                                        scene);
+                }
+            }
+            Some(Symbol::MorphShape(morph_shape)) => {
+                let t = self.ratio as f32 / 65535.0;
+                let graphic = morph::decode_morph_shape(morph_shape, t);
+                draw_graphic_into_scene(&graphic, library, &transform, &color_transform, scene)
+            }
+            Some(Symbol::Text(text)) => {
+                text.draw_into_scene(library, &transform, &color_transform, scene)
+            }
+            Some(Symbol::Bitmap(_)) | Some(Symbol::Font(_)) | None => {}
+        }
+    }
+}
+
+// Sprites play their own timelines, looping, one frame for each frame of their parent.
+#[derive(Clone, Default)]
+struct SpriteInstance {
+    display_list: DisplayList,
+    next_frame_index: usize,
+}
+
+// The objects on stage, ordered from back to front by depth.
+#[derive(Clone, Default)]
+pub(crate) struct DisplayList(BTreeMap<u16, PlacedObject>);
+
+impl DisplayList {
+    pub(crate) fn apply_frame(&mut self, frame: &Frame) {
+        for command in &frame.0 {
+            match command {
+                DisplayListCommand::Place(placement) => self.place(placement),
+                DisplayListCommand::Remove { depth } => {
+                    self.0.remove(depth);
+                }
+            }
+        }
+    }
+
+    fn place(&mut self, placement: &Placement) {
+        let existing = if placement.is_update { self.0.remove(&placement.depth) } else { None };
+        let mut object = match (existing, placement.symbol_id) {
+            // Replacing the character of an object keeps its other properties.
+            (Some(mut object), Some(symbol_id)) => {
+                object.symbol_id = symbol_id;
+                object.sprite = None;
+                object
+            }
+            (Some(object), None) => object,
+            (None, Some(symbol_id)) => PlacedObject::new(symbol_id),
+            (None, None) => return,
+        };
+        if let Some(transform) = placement.transform {
+            object.transform = transform;
+        }
+        if let Some(color_transform) = placement.color_transform {
+            object.color_transform = color_transform;
+        }
+        if placement.clip_depth.is_some() {
+            object.clip_depth = placement.clip_depth;
+        }
+        if let Some(ratio) = placement.ratio {
+            object.ratio = ratio;
+        }
+        self.0.insert(placement.depth, object);
+    }
+
+    // Moves each sprite on to its next frame. Sprites that were just placed show their first.
+    pub(crate) fn advance_sprites(&mut self, library: &SymbolLibrary) {
+        for object in self.0.values_mut() {
+            let timeline = match library.symbol(object.symbol_id) {
+                Some(Symbol::Sprite(timeline)) => timeline,
+                _ => continue,
+            };
+            let sprite = object.sprite.get_or_insert_with(SpriteInstance::default);
+            if sprite.next_frame_index >= timeline.len() {
+                *sprite = SpriteInstance::default();
+            }
+            if let Some(frame) = timeline.frame(sprite.next_frame_index) {
+                sprite.display_list.apply_frame(frame);
+            }
+            sprite.next_frame_index += 1;
+            sprite.display_list.advance_sprites(library);
+        }
+    }
+
+    pub(crate) fn draw_into_scene(&self,
+                                  library: &SymbolLibrary,
+                                  transform: &Transform2DF,
+                                  color_transform: &ColorTransform,
+                                  scene: &mut Scene) {
+        let objects: Vec<_> = self.0.iter().map(|(depth, object)| (*depth, object)).collect();
+        draw_objects_into_scene(&objects, library, transform, color_transform, scene);
+    }
+}
+
+fn draw_objects_into_scene(objects: &[(u16, &PlacedObject)],
+                           library: &SymbolLibrary,
+                           transform: &Transform2DF,
+                           color_transform: &ColorTransform,
+                           scene: &mut Scene) {
+    let mut index = 0;
+    while index < objects.len() {
+        let object = objects[index].1;
+        index += 1;
+        let clip_depth = match object.clip_depth {
+            None => {
+                object.draw_into_scene(library, transform, color_transform, scene);
+                continue;
+            }
+            Some(clip_depth) => clip_depth,
+        };
+
+        // The clip region is the area the clip object would cover, whatever its colors.
+        let opaque = ColorTransform { multiply: [1.0, 1.0, 1.0, 0.0], add: [0.0, 0.0, 0.0, 255.0] };
+        let mut clip_scene = Scene::new();
+        object.draw_into_scene(library, transform, &opaque, &mut clip_scene);
+        let clip = clip_scene.paths().fold(Outline::new(), |clip, (_, outline, _)| {
+            fills::combine(&clip, outline, BooleanOp::Union)
+        });
+
+        // Draw the clipped objects on their own, then keep the parts inside the region.
+        let clipped_start_index = index;
+        while index < objects.len() && objects[index].0 <= clip_depth {
+            index += 1;
+        }
+        let mut clipped_scene = Scene::new();
+        draw_objects_into_scene(&objects[clipped_start_index..index],
+                                library,
+                                transform,
+                                color_transform,
+                                &mut clipped_scene);
+        for (paint, outline, name) in clipped_scene.paths() {
+            let outline = fills::combine(outline, &clip, BooleanOp::Intersection);
+            if outline.contours().is_empty() {
+                continue;
+            }
+            let paint_id = scene.push_paint(paint);
+            scene.push_path(PathObject::new(outline, paint_id, name.to_owned()));
+        }
+    }
+}
+
+/// A lazy iterator over the frames of the main timeline, yielding one scene per frame.
+pub struct Frames<'a> {
+    library: &'a SymbolLibrary,
+    stage: &'a Stage,
+    display_list: DisplayList,
+    next_frame_index: usize,
 }
 
-struct Frame {
-    duration_frames_initial: u16,
-    duration_remaining_frames: u16,
-    placements: Vec<PlacementInfo>
+impl<'a> Frames<'a> {
+    pub(crate) fn new(library: &'a SymbolLibrary, stage: &'a Stage) -> Frames<'a> {
+        Frames { library, stage, display_list: DisplayList::default(), next_frame_index: 0 }
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Scene;
+
+    fn next(&mut self) -> Option<Scene> {
+        let frame = self.stage.timeline.frame(self.next_frame_index)?;
+        self.next_frame_index += 1;
+        self.display_list.apply_frame(frame);
+        self.display_list.advance_sprites(self.library);
+
+        let mut scene = Scene::new();
+        let size = Vector2F::new(self.stage.width() as f32, self.stage.height() as f32);
+        scene.set_view_box(RectF::new(Vector2F::default(), size));
+        self.display_list.draw_into_scene(self.library,
+                                          &Transform2DF::default(),
+                                          &ColorTransform::default(),
+                                          &mut scene);
+        Some(scene)
+    }
+}
+
+// Matrices in swf map (x, y) to (scale_x * x + rotate_skew_1 * y + translate_x,
+// rotate_skew_0 * x + scale_y * y + translate_y), with the scales in 16.16 fixed point and the
+// translation in twips.
+pub(crate) fn matrix_to_transform(matrix: &Matrix) -> Transform2DF {
+    let fixed = |value: i32| value as f32 / 65536.0;
+    Transform2DF::row_major(fixed(matrix.scale_x.epsilons),
+                            fixed(matrix.rotate_skew1.epsilons),
+                            fixed(matrix.rotate_skew0.epsilons),
+                            fixed(matrix.scale_y.epsilons),
+                            matrix.translate_x as f32 / 20.0,
+                            matrix.translate_y as f32 / 20.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ColorTransform, DisplayListCommand, Frame, Placement, Timeline};
+    use crate::fills::SwfFill;
+    use crate::shapes::{self, DecodedRecord, PaintOrLine};
+    use crate::{Point2, Stage, Symbol, SymbolId, SymbolLibrary, Twips};
+    use pathfinder_content::color::ColorU;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::Scene;
+    use std::collections::HashMap;
+    use swf_tree::tags::{PlaceObject, RemoveObject};
+    use swf_tree::{SRgb8, Tag};
+
+    // A square from the origin, filled with a color.
+    fn square(size: i32, color: ColorU) -> Symbol {
+        let point = |x, y| Point2 { x: Twips(x), y: Twips(y) };
+        let edge = |x, y| DecodedRecord::Edge { delta: point(x, y), control_delta: None };
+        let records = vec![
+            DecodedRecord::StyleChange {
+                move_to: Some(point(0, 0)),
+                new_styles: None,
+                line_style: None,
+                left_fill: None,
+                right_fill: Some(1),
+            },
+            edge(size, 0),
+            edge(0, size),
+            edge(-size, 0),
+            edge(0, -size),
+        ];
+        let fill = PaintOrLine::Paint(SwfFill::Solid(Paint { color }));
+        Symbol::Graphic(shapes::decode_records(vec![fill], records))
+    }
+
+    fn place(depth: u16, symbol_id: Option<SymbolId>, is_update: bool, clip_depth: Option<u16>)
+             -> Tag {
+        Tag::PlaceObject(PlaceObject {
+            is_update,
+            depth,
+            character_id: symbol_id,
+            class_name: None,
+            matrix: None,
+            color_transform: None,
+            ratio: None,
+            name: None,
+            clip_depth,
+            filters: None,
+            blend_mode: None,
+            bitmap_cache: None,
+            visible: None,
+            background_color: None,
+            clip_actions: None,
+        })
+    }
+
+    fn placement(depth: u16, symbol_id: SymbolId, color_transform: ColorTransform) -> Placement {
+        Placement {
+            depth,
+            is_update: false,
+            symbol_id: Some(symbol_id),
+            transform: None,
+            color_transform: Some(color_transform),
+            clip_depth: None,
+            ratio: None,
+        }
+    }
+
+    fn remove(depth: u16) -> Tag {
+        Tag::RemoveObject(RemoveObject { character_id: None, depth })
+    }
+
+    fn stage(tags: &[Tag]) -> Stage {
+        Stage {
+            timeline: Timeline::from_tags(tags),
+            background_color: SRgb8 { r: 255, g: 255, b: 255 },
+            width: 100,
+            height: 100,
+            frame_rate: 12.0,
+        }
+    }
+
+    fn colors(scene: &Scene) -> Vec<ColorU> {
+        scene.paths().map(|(paint, _, _)| paint.color).collect()
+    }
+
+    fn bounds(scene: &Scene) -> Vec<RectF> {
+        scene.paths().map(|(_, outline, _)| outline.bounds()).collect()
+    }
+
+    fn approx_eq(a: RectF, b: RectF) -> bool {
+        (a.origin() - b.origin()).length() < 0.01 && (a.size() - b.size()).length() < 0.01
+    }
+
+    #[test]
+    fn test_place_and_remove() {
+        let (red, blue) = (ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255));
+        let mut symbols = HashMap::new();
+        symbols.insert(1, square(200, red));
+        symbols.insert(2, square(400, blue));
+        let library = SymbolLibrary(symbols);
+
+        // The last frame has no `ShowFrame`, and replaces the character at depth 2.
+        let stage = stage(&[
+            place(2, Some(1), false, None),
+            Tag::ShowFrame,
+            place(1, Some(2), false, None),
+            Tag::ShowFrame,
+            remove(2),
+            Tag::ShowFrame,
+            place(1, Some(1), true, None),
+            place(3, None, true, None),
+        ]);
+        assert_eq!(stage.frame_count(), 4);
+
+        let frames: Vec<_> = stage.frames(&library).collect();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(colors(&frames[0]), [red]);
+        // Lower depths are drawn first.
+        assert_eq!(colors(&frames[1]), [blue, red]);
+        assert_eq!(colors(&frames[2]), [blue]);
+        assert_eq!(colors(&frames[3]), [red]);
+        assert_eq!(frames[3].view_box(), RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+    }
+
+    #[test]
+    fn test_clip_depth() {
+        let (red, blue) = (ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255));
+        let mut symbols = HashMap::new();
+        symbols.insert(1, square(200, ColorU::black()));
+        symbols.insert(2, square(400, red));
+        symbols.insert(3, square(400, blue));
+        let library = SymbolLibrary(symbols);
+
+        // The clip at depth 1 covers depths 2 and 3, but not 4.
+        let stage = stage(&[
+            place(1, Some(1), false, Some(3)),
+            place(2, Some(2), false, None),
+            place(3, Some(3), false, None),
+            place(4, Some(2), false, None),
+            Tag::ShowFrame,
+        ]);
+        let scene = stage.frames(&library).next().unwrap();
+        assert_eq!(colors(&scene), [red, blue, red]);
+        let (small, large) = (Vector2F::splat(10.0), Vector2F::splat(20.0));
+        let expected = [small, small, large];
+        for (bounds, size) in bounds(&scene).into_iter().zip(expected.iter()) {
+            assert!(approx_eq(bounds, RectF::new(Vector2F::default(), *size)), "{:?}", bounds);
+        }
+    }
+
+    #[test]
+    fn test_nested_sprite_color_transform() {
+        let color = ColorU::new(200, 0, 0, 255);
+        let mut symbols = HashMap::new();
+        symbols.insert(1, square(200, color));
+
+        // The sprite halves red, and shows its square every other frame.
+        let halve_red = ColorTransform { multiply: [0.5, 1.0, 1.0, 1.0], add: [0.0; 4] };
+        symbols.insert(2, Symbol::Sprite(Timeline(vec![
+            Frame(vec![DisplayListCommand::Place(placement(1, 1, halve_red))]),
+            Frame(vec![DisplayListCommand::Remove { depth: 1 }]),
+        ])));
+        let library = SymbolLibrary(symbols);
+
+        // The main timeline adds to red, after the sprite's own transform.
+        let add_red = ColorTransform { multiply: [1.0; 4], add: [100.0, 0.0, 0.0, 0.0] };
+        let mut stage = stage(&[]);
+        stage.timeline = Timeline(vec![
+            Frame(vec![DisplayListCommand::Place(placement(1, 2, add_red))]),
+            Frame(vec![]),
+            Frame(vec![]),
+        ]);
+
+        let frames: Vec<_> = stage.frames(&library).collect();
+        assert_eq!(colors(&frames[0]), [ColorU::new(200, 0, 0, 255)]);
+        assert_eq!(colors(&frames[1]), []);
+        assert_eq!(colors(&frames[2]), [ColorU::new(200, 0, 0, 255)]);
+    }
+
+    #[test]
+    fn test_color_transform_order_and_clamping() {
+        let halve = ColorTransform { multiply: [0.5; 4], add: [0.0; 4] };
+        let brighten = ColorTransform { multiply: [1.0; 4], add: [100.0, 100.0, -100.0, 0.0] };
+        let color = ColorU::new(200, 100, 50, 128);
+        assert_eq!(halve.apply(color), ColorU::new(100, 50, 25, 64));
+        assert_eq!(brighten.apply(color), ColorU::new(255, 200, 0, 128));
+
+        // `post_mul` applies `self` first.
+        let halve_then_brighten = halve.post_mul(&brighten);
+        assert_eq!(halve_then_brighten.apply(color), brighten.apply(halve.apply(color)));
+        assert_eq!(halve_then_brighten.apply(color), ColorU::new(200, 150, 0, 64));
+
+        // Only the final result is clamped, so red isn't cut off at 255 before it is halved.
+        assert_eq!(brighten.post_mul(&halve).apply(color), ColorU::new(150, 100, 0, 64));
+    }
 }